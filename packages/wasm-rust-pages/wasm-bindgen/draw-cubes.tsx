@@ -1,5 +1,5 @@
 import { createResizeObserver } from '@solid-primitives/resize-observer';
-import { createSignal, onMount } from 'solid-js';
+import { createSignal, onCleanup, onMount } from 'solid-js';
 import { App } from './wasm_bindgen/libs/from-webgl-state-diagram/draw-cubes/pkg/draw_cubes';
 import { App as App2 } from './wasm_bindgen/libs/from-webgl-state-diagram/samplers/pkg/samplers';
 
@@ -37,6 +37,25 @@ function DrawCubes() {
       }
     );
 
+    // drag to orbit, drag with the right button or shift to pan, wheel to zoom
+    canvas.addEventListener('pointerdown', (e) => canvas.setPointerCapture(e.pointerId));
+    canvas.addEventListener('pointermove', (e) => {
+      if (e.buttons === 0) return;
+      if (e.buttons & 2 || e.shiftKey) app.pan(e.movementX, e.movementY);
+      else app.rotate(e.movementX, e.movementY);
+      update();
+    });
+    canvas.addEventListener('contextmenu', (e) => e.preventDefault());
+    canvas.addEventListener(
+      'wheel',
+      (e) => {
+        e.preventDefault();
+        app.zoom(-e.deltaY);
+        update();
+      },
+      { passive: false }
+    );
+
     // const [, start] = createRAF(() => {
     //   update();
     // });
@@ -81,6 +100,32 @@ function Samplers() {
         update();
       }
     );
+
+    // click the canvas, then WASD, Space and Shift fly and dragging looks around
+    canvas.addEventListener('pointerdown', (e) => {
+      canvas.focus();
+      canvas.setPointerCapture(e.pointerId);
+    });
+    canvas.addEventListener('pointermove', (e) => {
+      if (e.buttons !== 0) app.look(e.movementX, e.movementY);
+    });
+    canvas.addEventListener('keydown', (e) => {
+      // don't scroll the page
+      if (e.code === 'Space' || e.code.startsWith('Arrow')) e.preventDefault();
+      app.key_down(e.code);
+    });
+    canvas.addEventListener('keyup', (e) => app.key_up(e.code));
+    canvas.addEventListener('blur', () => app.release_keys());
+
+    let last = performance.now();
+    const frame = (now: number) => {
+      app.update((now - last) / 1000);
+      last = now;
+      update();
+      handle = requestAnimationFrame(frame);
+    };
+    let handle = requestAnimationFrame(frame);
+    onCleanup(() => cancelAnimationFrame(handle));
   });
 
   return (
@@ -1,13 +1,28 @@
-import { createEventListener } from '@solid-primitives/event-listener';
 import { createResizeObserver } from '@solid-primitives/resize-observer';
-import { createEffect, createSignal, onMount } from 'solid-js';
-import { AppWebGL, greet } from './wasm_bindgen/pkg/wasm_bindgen_example';
+import { createEffect, createSignal, onCleanup, onMount } from 'solid-js';
+import { AppWebGL, PaintApp, greet } from './wasm_bindgen/pkg/wasm_bindgen_example';
 
 export default function WasmBindgen() {
   const canvas = (<canvas class="h-[80vh] max-w-full touch-none" tabIndex={0} />) as HTMLCanvasElement;
 
   const [App, setApp] = createSignal<AppWebGL | null>(null);
 
+  const paintCanvas = (<canvas class="h-[60vh] max-w-full touch-none bg-white" tabIndex={0} />) as HTMLCanvasElement;
+  const [Painter, setPainter] = createSignal<PaintApp | null>(null);
+
+  createEffect(() => {
+    const painter = Painter();
+    if (painter) {
+      painter.start();
+      onCleanup(() => painter.stop());
+    }
+  });
+
+  const setColor = (hex: string) => {
+    const [r, g, b] = [1, 3, 5].map((i) => parseInt(hex.slice(i, i + 2), 16) / 255);
+    Painter()?.set_color(r, g, b);
+  };
+
   createEffect(() => {
     const app = App();
     if (app) {
@@ -17,21 +32,21 @@ export default function WasmBindgen() {
       canvas.height = canvas.clientHeight;
 
       createResizeObserver(canvas as unknown as Element, ({ width, height }) => app.resize(width, height));
-      createEventListener(canvas as unknown as EventTarget, 'pointerdown', (event) => app.on_pointer_down(event as PointerEvent));
-      createEventListener(canvas as unknown as EventTarget, 'pointerenter', (event) => app.on_pointer_enter(event as PointerEvent));
-      createEventListener(canvas as unknown as EventTarget, 'pointerleave', (event) => app.on_pointer_leave(event as PointerEvent));
-      createEventListener(canvas as unknown as EventTarget, 'pointermove', (event) => app.on_pointer_move(event as PointerEvent));
-      createEventListener(canvas as unknown as EventTarget, 'pointerup', (event) => app.on_pointer_up(event as PointerEvent));
-      createEventListener(canvas as unknown as EventTarget, 'wheel', (event) => {
-        app.on_wheel(event as WheelEvent);
-        event.preventDefault();
-      });
       app.init();
+
+      // the app listens to the canvas input itself, and animates and renders once per frame
+      app.start();
+      onCleanup(() => app.stop());
     }
   });
 
   onMount(async () => {
     setApp(await AppWebGL.new(canvas));
+
+    // the painting keeps the size the canvas has now
+    paintCanvas.width = paintCanvas.clientWidth;
+    paintCanvas.height = paintCanvas.clientHeight;
+    setPainter(PaintApp.new(paintCanvas));
   });
 
   return (
@@ -41,6 +56,23 @@ export default function WasmBindgen() {
       {greet('World')}
       <span>WebGL from WASM Rust:</span>
       {canvas}
+      <span>Painting from WASM Rust:</span>
+      <div class="flex flex-wrap items-center gap-2">
+        <label>
+          Size <input type="range" min="1" max="100" value="16" onInput={(e) => Painter()?.set_brush_size(e.currentTarget.valueAsNumber)} />
+        </label>
+        <label>
+          Hardness <input type="range" min="0" max="1" step="0.01" value="0.8" onInput={(e) => Painter()?.set_hardness(e.currentTarget.valueAsNumber)} />
+        </label>
+        <label>
+          Opacity <input type="range" min="0" max="1" step="0.01" value="1" onInput={(e) => Painter()?.set_opacity(e.currentTarget.valueAsNumber)} />
+        </label>
+        <input type="color" value="#000000" onInput={(e) => setColor(e.currentTarget.value)} />
+        <button onClick={() => Painter()?.undo()}>Undo</button>
+        <button onClick={() => Painter()?.redo()}>Redo</button>
+        <button onClick={() => Painter()?.clear()}>Clear</button>
+      </div>
+      {paintCanvas}
     </div>
   );
 }
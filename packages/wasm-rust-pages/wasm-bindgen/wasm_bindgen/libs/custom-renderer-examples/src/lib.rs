@@ -1,12 +1,13 @@
 use custom_renderer::{
     buffer::Buffer,
+    gl_context::GlContext,
     renderer::Renderer,
     shader_program::ShaderProgram,
     vertex_array_object::{AttributeOptions, VertexArrayObject},
-    BufferTarget, BufferUsage, Color, DataType, DrawMode, Mask, Viewport,
+    BufferTarget, BufferUsage, Color, DataType, DrawMode, Mask, Viewport, WebGlVersion,
 };
 use wasm_bindgen::prelude::*;
-use web_sys::{console, WebGl2RenderingContext};
+use web_sys::console;
 
 #[wasm_bindgen]
 pub struct App {
@@ -16,12 +17,8 @@ pub struct App {
 #[wasm_bindgen]
 impl App {
     pub fn new(canvas: web_sys::HtmlCanvasElement) -> Self {
-        let gl = canvas
-            .get_context("webgl2")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<WebGl2RenderingContext>()
-            .unwrap();
+        // falls back to WebGL1 on browsers without WebGL2
+        let gl = GlContext::from_canvas(&canvas, &JsValue::UNDEFINED, WebGlVersion::Two).unwrap();
 
         let mut renderer = Renderer::new(gl);
 
@@ -40,9 +37,16 @@ impl App {
         //     ..RendererState::default()
         // };
 
-        let program = renderer
-            .create_shader_program(include_str!("shader.vert"), include_str!("shader.frag"))
-            .unwrap();
+        let program = match renderer.gl.version() {
+            WebGlVersion::Two => renderer
+                .create_shader_program(include_str!("shader.vert"), include_str!("shader.frag")),
+            WebGlVersion::One => renderer.create_shader_program_with_attributes(
+                include_str!("shader_webgl1.vert"),
+                include_str!("shader_webgl1.frag"),
+                &[("a_position", 1)],
+            ),
+        }
+        .unwrap();
 
         renderer.use_program(&program);
 
@@ -62,6 +66,7 @@ impl App {
                 normalized: false,
                 stride: 0,
                 offset: 0,
+                divisor: 0,
             },
         );
         vertex_array_object.unbind();
@@ -0,0 +1,7 @@
+// GLSL ES 1.00 version of shader.frag for the WebGL1 fallback.
+precision mediump float;
+
+void main(){
+    // gl_FragColor is the only output in GLSL ES 1.00
+    gl_FragColor=vec4(1,0,.5,1);
+}
\ No newline at end of file
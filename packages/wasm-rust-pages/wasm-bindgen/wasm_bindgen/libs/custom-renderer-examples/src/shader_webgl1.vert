@@ -0,0 +1,7 @@
+// GLSL ES 1.00 version of shader.vert for the WebGL1 fallback.
+// Attributes have no layout qualifier, the location is bound before linking.
+attribute vec4 a_position;
+
+void main(){
+    gl_Position=a_position;
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+#version 300 es
+
+precision highp float;
+
+in float v_t;
+
+layout(location=0)out vec4 outColor;
+
+void main(){
+    // from yellow to a dark red as the particle ages
+    outColor=vec4(mix(vec3(1,.9,.3),vec3(.4,0,0),v_t),1);
+}
@@ -0,0 +1,13 @@
+#version 300 es
+
+layout(location=0)in vec2 a_position;
+layout(location=2)in float a_age;
+layout(location=3)in float a_life;
+
+out float v_t;
+
+void main(){
+    v_t=clamp(a_age/a_life,0.,1.);
+    gl_Position=vec4(a_position,0,1);
+    gl_PointSize=mix(4.,1.,v_t);
+}
@@ -0,0 +1,157 @@
+use custom_renderer::{
+    context::{Context, WebProgramKey},
+    transform_feedback::{create_feedback_program, FeedbackAttribute, TransformFeedbackLoop},
+    DataType, DrawMode, Mask, TransformFeedbackBufferMode,
+};
+use wasm_bindgen::prelude::*;
+use web_sys::{WebGl2RenderingContext, WebGlUniformLocation};
+use webgl_common::GpuPod;
+
+/// One particle, laid out like the varyings of `update.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, GpuPod)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    age: f32,
+    life: f32,
+}
+
+const ATTRIBUTES: [FeedbackAttribute; 4] = [
+    FeedbackAttribute {
+        location: 0,
+        size: 2,
+        data_type: DataType::Float,
+    },
+    FeedbackAttribute {
+        location: 1,
+        size: 2,
+        data_type: DataType::Float,
+    },
+    FeedbackAttribute {
+        location: 2,
+        size: 1,
+        data_type: DataType::Float,
+    },
+    FeedbackAttribute {
+        location: 3,
+        size: 1,
+        data_type: DataType::Float,
+    },
+];
+
+const VARYINGS: [&str; 4] = ["v_position", "v_velocity", "v_age", "v_life"];
+
+struct UpdateUniforms {
+    time: Option<WebGlUniformLocation>,
+    time_delta: Option<WebGlUniformLocation>,
+    origin: Option<WebGlUniformLocation>,
+    gravity: Option<WebGlUniformLocation>,
+}
+
+///
+/// Particles simulated on the GPU with transform feedback:
+/// every frame `update.vert` moves the particles from one buffer into the other,
+/// then `draw.vert` draws the buffer that was just written as points.
+///
+#[wasm_bindgen]
+pub struct App {
+    canvas: web_sys::HtmlCanvasElement,
+    context: Context,
+    update_program: WebProgramKey,
+    update_uniforms: UpdateUniforms,
+    draw_program: WebProgramKey,
+    particles: TransformFeedbackLoop,
+    time: f32,
+    origin: [f32; 2],
+}
+
+#[wasm_bindgen]
+impl App {
+    pub fn new(canvas: web_sys::HtmlCanvasElement, count: u32) -> Result<App, JsValue> {
+        let gl = canvas
+            .get_context("webgl2")?
+            .ok_or("WebGL2 is not supported")?
+            .dyn_into::<WebGl2RenderingContext>()?;
+        let context = Context::new(gl)?;
+
+        let update_program = create_feedback_program(
+            &context,
+            include_str!("update.vert"),
+            include_str!("update.frag"),
+            &VARYINGS,
+            TransformFeedbackBufferMode::InterleavedAttribs,
+        )?;
+        let draw_program = create_feedback_program(
+            &context,
+            include_str!("draw.vert"),
+            include_str!("draw.frag"),
+            &[],
+            TransformFeedbackBufferMode::InterleavedAttribs,
+        )?;
+
+        let update_uniforms = UpdateUniforms {
+            time: context.get_uniform_location(update_program, "u_time"),
+            time_delta: context.get_uniform_location(update_program, "u_time_delta"),
+            origin: context.get_uniform_location(update_program, "u_origin"),
+            gravity: context.get_uniform_location(update_program, "u_gravity"),
+        };
+
+        // every particle starts dead, with a different life span, so they respawn in a steady stream
+        let particles = (0..count)
+            .map(|_| {
+                let life = 1.0 + 2.0 * js_sys::Math::random() as f32;
+                Particle {
+                    position: [0.0, 0.0],
+                    velocity: [0.0, 0.0],
+                    age: life,
+                    life,
+                }
+            })
+            .collect::<Vec<_>>();
+        let particles = TransformFeedbackLoop::new(&context, &particles, &ATTRIBUTES)?;
+
+        Ok(Self {
+            canvas,
+            context,
+            update_program,
+            update_uniforms,
+            draw_program,
+            particles,
+            time: 0.0,
+            origin: [0.0, 0.0],
+        })
+    }
+
+    /// Moves the point particles respawn at, in clip space.
+    pub fn set_origin(&mut self, x: f32, y: f32) {
+        self.origin = [x, y];
+    }
+
+    /// Advances the simulation by `time_delta` seconds and draws it. Call it from `requestAnimationFrame`.
+    pub fn frame(&mut self, time_delta: f32) {
+        let context = &self.context;
+        let gl = context.gl();
+        self.time += time_delta;
+
+        context.use_program(self.update_program);
+        let uniforms = &self.update_uniforms;
+        gl.uniform1f(uniforms.time.as_ref(), self.time);
+        gl.uniform1f(uniforms.time_delta.as_ref(), time_delta);
+        gl.uniform2f(uniforms.origin.as_ref(), self.origin[0], self.origin[1]);
+        gl.uniform2f(uniforms.gravity.as_ref(), 0.0, -0.8);
+        self.particles.step(context, self.update_program);
+
+        context.viewport(
+            0,
+            0,
+            self.canvas.width() as i32,
+            self.canvas.height() as i32,
+        );
+        context.clear_color(0.0, 0.0, 0.0, 1.0);
+        context.clear(Mask::ColorBufferBit);
+        context.use_program(self.draw_program);
+        context.bind_vertex_array(self.particles.vertex_array());
+        context.draw_arrays(DrawMode::Points, 0, self.particles.count());
+    }
+}
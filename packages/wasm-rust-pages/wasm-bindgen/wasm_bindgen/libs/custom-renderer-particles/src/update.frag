@@ -0,0 +1,7 @@
+#version 300 es
+
+precision highp float;
+
+// never runs, the update pass draws with RASTERIZER_DISCARD
+void main(){
+}
@@ -0,0 +1,40 @@
+#version 300 es
+
+precision highp float;
+
+uniform float u_time;
+uniform float u_time_delta;
+uniform vec2 u_origin;
+uniform vec2 u_gravity;
+
+layout(location=0)in vec2 a_position;
+layout(location=1)in vec2 a_velocity;
+layout(location=2)in float a_age;
+layout(location=3)in float a_life;
+
+// captured by transform feedback, in the order given to transformFeedbackVaryings
+out vec2 v_position;
+out vec2 v_velocity;
+out float v_age;
+out float v_life;
+
+float hash(float n){
+    return fract(sin(n)*43758.5453123);
+}
+
+void main(){
+    if(a_age>=a_life){
+        // respawn at the origin in a random direction
+        float seed=float(gl_VertexID)+u_time;
+        float angle=hash(seed)*6.2831853;
+        float speed=.4+hash(seed+1.)*.6;
+        v_position=u_origin;
+        v_velocity=vec2(cos(angle),sin(angle))*speed;
+        v_age=0.;
+    }else{
+        v_velocity=a_velocity+u_gravity*u_time_delta;
+        v_position=a_position+v_velocity*u_time_delta;
+        v_age=a_age+u_time_delta;
+    }
+    v_life=a_life;
+}
@@ -1,9 +1,13 @@
-use web_sys::{WebGl2RenderingContext, WebGlBuffer};
-use webgl_common::{slice_as_u8_slice, BufferTarget, BufferUsage};
+use std::ops::Range;
+
+use web_sys::WebGlBuffer;
+use webgl_common::{slice_as_u8_slice, BufferTarget, BufferUsage, DataType, GpuPod};
+
+use crate::gl_context::GlContext;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Buffer<'a> {
-    gl: &'a WebGl2RenderingContext,
+    gl: &'a GlContext,
     pub buffer: WebGlBuffer,
     is_binded: bool,
     // target: Option<BufferTarget>, // ? not sure if this is needed
@@ -11,7 +15,7 @@ pub struct Buffer<'a> {
 }
 
 impl<'a> Buffer<'a> {
-    pub fn new(gl: &'a WebGl2RenderingContext) -> Self {
+    pub fn new(gl: &'a GlContext) -> Self {
         let buffer = gl.create_buffer().expect("Failed to create buffer");
         Buffer {
             gl,
@@ -33,13 +37,20 @@ impl<'a> Buffer<'a> {
         };
     }
 
+    /// WebGL2 only, does nothing on WebGL1.
     pub fn bind_base(&mut self, target: BufferTarget, index: u32) {
-        self.gl
-            .bind_buffer_base(target.into(), index, Some(&self.buffer));
-        self.is_binded = true;
+        if let Some(gl) = self.gl.webgl2() {
+            gl.bind_buffer_base(target.into(), index, Some(&self.buffer));
+            self.is_binded = true;
+        }
+    }
+
+    /// Size in bytes of the buffer's storage, `None` before the first [Buffer::set_data].
+    pub fn size(&self) -> Option<usize> {
+        self.size
     }
 
-    pub fn set_data<T>(&mut self, target: BufferTarget, data: &[T], usage: BufferUsage) {
+    pub fn set_data<T: GpuPod>(&mut self, target: BufferTarget, data: &[T], usage: BufferUsage) {
         self.bind(target);
 
         let data = slice_as_u8_slice(data);
@@ -64,3 +75,113 @@ impl<'a> Drop for Buffer<'a> {
         self.gl.delete_buffer(Some(&self.buffer));
     }
 }
+
+/// A type an index buffer can hold. `u32` needs `OES_element_index_uint` on WebGL1.
+pub trait Index: GpuPod + Into<u32> {
+    const DATA_TYPE: DataType;
+}
+
+impl Index for u8 {
+    const DATA_TYPE: DataType = DataType::UnsignedByte;
+}
+
+impl Index for u16 {
+    const DATA_TYPE: DataType = DataType::UnsignedShort;
+}
+
+impl Index for u32 {
+    const DATA_TYPE: DataType = DataType::UnsignedInt;
+}
+
+///
+/// An `ELEMENT_ARRAY_BUFFER` that remembers the type and number of its indices,
+/// so the draw calls of the [Renderer](crate::renderer::Renderer) don't have to be told.
+///
+/// Binding it while a vertex array is bound stores it in that vertex array.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexBuffer<'a> {
+    pub buffer: Buffer<'a>,
+    index_type: DataType,
+    count: i32,
+    max_index: Option<u32>,
+}
+
+impl<'a> IndexBuffer<'a> {
+    pub fn new<T: Index>(gl: &'a GlContext, indices: &[T], usage: BufferUsage) -> Self {
+        let mut buffer = Self {
+            buffer: Buffer::new(gl),
+            index_type: T::DATA_TYPE,
+            count: 0,
+            max_index: None,
+        };
+        buffer.set_data(indices, usage);
+        buffer
+    }
+
+    /// Replaces the indices, the index type may change.
+    pub fn set_data<T: Index>(&mut self, indices: &[T], usage: BufferUsage) {
+        if self.index_type != T::DATA_TYPE {
+            // a smaller type would be written into the old, larger allocation
+            self.buffer.size = None;
+        }
+        self.buffer
+            .set_data(BufferTarget::ElementArrayBuffer, indices, usage);
+        self.index_type = T::DATA_TYPE;
+        self.count = indices.len() as i32;
+        self.max_index = indices.iter().map(|&index| index.into()).max();
+    }
+
+    pub fn bind(&self) {
+        self.buffer.bind(BufferTarget::ElementArrayBuffer);
+    }
+
+    /// `UNSIGNED_BYTE`, `UNSIGNED_SHORT` or `UNSIGNED_INT`.
+    pub fn index_type(&self) -> DataType {
+        self.index_type
+    }
+
+    pub fn count(&self) -> i32 {
+        self.count
+    }
+
+    /// The largest index, `None` without indices. The data isn't kept, so only the whole buffer's is known.
+    pub fn max_index(&self) -> Option<u32> {
+        self.max_index
+    }
+
+    /// The byte offset of the index at `first`, as `drawElements()` takes it.
+    pub fn offset(&self, first: i32) -> i32 {
+        first * self.index_type.size() as i32
+    }
+}
+
+///
+/// The counts and byte offsets of index `ranges`, as the `WEBGL_multi_draw` element draws take them.
+///
+pub fn element_ranges(index_type: DataType, ranges: &[Range<i32>]) -> (Vec<i32>, Vec<i32>) {
+    ranges
+        .iter()
+        .map(|range| {
+            (
+                range.end - range.start,
+                range.start * index_type.size() as i32,
+            )
+        })
+        .unzip()
+}
+
+#[test]
+fn index_buffer_types() {
+    assert_eq!(<u8 as Index>::DATA_TYPE, DataType::UnsignedByte);
+    assert_eq!(<u16 as Index>::DATA_TYPE, DataType::UnsignedShort);
+    assert_eq!(<u32 as Index>::DATA_TYPE, DataType::UnsignedInt);
+
+    let (counts, offsets) = element_ranges(DataType::UnsignedShort, &[0..6, 6..9, 20..32]);
+    assert_eq!(counts, vec![6, 3, 12]);
+    assert_eq!(offsets, vec![0, 12, 40]);
+
+    let (counts, offsets) = element_ranges(DataType::UnsignedInt, &[3..3, 4..10]);
+    assert_eq!(counts, vec![0, 6]);
+    assert_eq!(offsets, vec![12, 16]);
+}
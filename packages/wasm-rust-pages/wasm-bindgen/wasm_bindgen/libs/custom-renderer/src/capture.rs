@@ -0,0 +1,1582 @@
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    ExtTextureFilterAnisotropic, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlSampler,
+    WebGlTransformFeedback, WebGlVertexArrayObject,
+};
+use webgl_common::{BufferTarget, Capabilities, Capability, DataType, ShaderType};
+
+use crate::{
+    context::{
+        id, Context, WebBufferKey, WebFenceKey, WebFramebufferKey, WebProgramKey, WebQueryKey,
+        WebSamplerKey, WebShaderKey, WebTransformFeedbackKey, WebVertexArrayKey,
+    },
+    sampler::SamplerParameter,
+};
+
+///
+/// A call made through a [Context] while capturing. Resources are referred to by their capture id,
+/// enums by their GL value.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    CreateShader {
+        id: u64,
+        shader_type: u32,
+    },
+    DeleteShader {
+        shader: u64,
+    },
+    ShaderSource {
+        shader: u64,
+        source: String,
+    },
+    CompileShader {
+        shader: u64,
+    },
+    CreateProgram {
+        id: u64,
+    },
+    DeleteProgram {
+        program: u64,
+    },
+    AttachShader {
+        program: u64,
+        shader: u64,
+    },
+    LinkProgram {
+        program: u64,
+    },
+    UseProgram {
+        program: Option<u64>,
+    },
+    CreateBuffer {
+        id: u64,
+    },
+    BindBuffer {
+        target: u32,
+        buffer: Option<u64>,
+    },
+    BindBufferBase {
+        target: u32,
+        index: u32,
+        buffer: u64,
+    },
+    BufferData {
+        target: u32,
+        data: Vec<u8>,
+        usage: u32,
+    },
+    CreateVertexArray {
+        id: u64,
+    },
+    DeleteVertexArray {
+        vertex_array: u64,
+    },
+    BindVertexArray {
+        vertex_array: Option<u64>,
+    },
+    EnableVertexAttribArray {
+        index: u32,
+    },
+    VertexAttribPointer {
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    },
+    VertexAttribDivisor {
+        index: u32,
+        divisor: u32,
+    },
+    CreateFramebuffer {
+        id: u64,
+    },
+    Enable {
+        capability: u32,
+    },
+    Disable {
+        capability: u32,
+    },
+    Viewport {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    Scissor {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    ClearColor {
+        color: [f32; 4],
+    },
+    Clear {
+        mask: u32,
+    },
+    DrawArrays {
+        mode: u32,
+        first: i32,
+        count: i32,
+    },
+    DrawElements {
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+    },
+    CreateQuery {
+        id: u64,
+    },
+    DeleteQuery {
+        query: u64,
+    },
+    BeginQuery {
+        target: u32,
+        query: u64,
+    },
+    EndQuery {
+        target: u32,
+    },
+    DeleteBuffer {
+        buffer: u64,
+    },
+    /// The data read is not captured, only how much was read.
+    GetBufferSubData {
+        target: u32,
+        offset: i32,
+        length: usize,
+    },
+    FenceSync {
+        id: u64,
+    },
+    DeleteFence {
+        fence: u64,
+    },
+    /// `bufferData()` with a size instead of data.
+    BufferDataSize {
+        target: u32,
+        size: i32,
+        usage: u32,
+    },
+    /// `readPixels()` into the bound `PIXEL_PACK_BUFFER`.
+    ReadPixels {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        type_: u32,
+    },
+    CreateTransformFeedback {
+        id: u64,
+    },
+    DeleteTransformFeedback {
+        transform_feedback: u64,
+    },
+    BindTransformFeedback {
+        transform_feedback: Option<u64>,
+    },
+    TransformFeedbackVaryings {
+        program: u64,
+        varyings: Vec<String>,
+        buffer_mode: u32,
+    },
+    BeginTransformFeedback {
+        primitive_mode: u32,
+    },
+    EndTransformFeedback,
+    CreateSampler {
+        id: u64,
+        parameters: Vec<SamplerParameter>,
+    },
+    DeleteSampler {
+        sampler: u64,
+    },
+    /// `unit` is the GL value of the [TextureUnit](webgl_common::TextureUnit), e.g. `TEXTURE0`.
+    BindSampler {
+        unit: u32,
+        sampler: Option<u64>,
+    },
+}
+
+/// A resource that existed when the capture began.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Resource {
+    Shader {
+        id: u64,
+        shader_type: u32,
+        source: String,
+    },
+    Program {
+        id: u64,
+        shaders: Vec<u64>,
+        /// The transform feedback varyings it was linked with, in order.
+        #[serde(default)]
+        varyings: Vec<String>,
+        #[serde(default)]
+        buffer_mode: u32,
+    },
+    /// Replayed into the target it was first bound to, with the usage of its data.
+    Buffer {
+        id: u64,
+        #[serde(default = "default_buffer_target")]
+        target: u32,
+        #[serde(default = "default_buffer_usage")]
+        usage: u32,
+        data: Vec<u8>,
+    },
+    VertexArray {
+        id: u64,
+        attributes: Vec<CapturedAttribute>,
+        element_buffer: Option<u64>,
+    },
+    /// Results are not captured, a replayed query starts out empty.
+    Query { id: u64 },
+    /// Replayed as a new fence, signaled once the resources are rebuilt.
+    Fence { id: u64 },
+    /// The buffers bound to the `TRANSFORM_FEEDBACK_BUFFER` indices of the object.
+    TransformFeedback { id: u64, buffers: Vec<(u32, u64)> },
+    Sampler {
+        id: u64,
+        parameters: Vec<SamplerParameter>,
+    },
+}
+
+fn default_buffer_target() -> u32 {
+    BufferTarget::ArrayBuffer.into()
+}
+
+fn default_buffer_usage() -> u32 {
+    WebGl2RenderingContext::STATIC_DRAW
+}
+
+impl Resource {
+    pub fn id(&self) -> u64 {
+        match self {
+            Resource::Shader { id, .. }
+            | Resource::Program { id, .. }
+            | Resource::Buffer { id, .. }
+            | Resource::VertexArray { id, .. }
+            | Resource::Query { id }
+            | Resource::Fence { id }
+            | Resource::TransformFeedback { id, .. }
+            | Resource::Sampler { id, .. } => *id,
+        }
+    }
+}
+
+/// A `vertexAttribPointer()` of a vertex array.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedAttribute {
+    pub index: u32,
+    pub enabled: bool,
+    pub buffer: Option<u64>,
+    pub size: i32,
+    pub type_: u32,
+    pub normalized: bool,
+    pub stride: i32,
+    pub offset: i32,
+    pub divisor: u32,
+}
+
+/// The context state a replay starts from, and the state at the end of the frame.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CapturedState {
+    pub viewport: [i32; 4],
+    pub scissor_box: [i32; 4],
+    pub clear_color: [f32; 4],
+    /// GL values of the enabled [Capability]s.
+    pub enabled: Vec<u32>,
+    pub program: Option<u64>,
+    pub vertex_array: Option<u64>,
+    pub array_buffer: Option<u64>,
+}
+
+///
+/// # Frame capture
+///
+/// Everything needed to replay a frame offline: the context it was made on,
+/// the resources and state when it began, the calls made during it and the state it ended in.
+/// Saved as JSON, or as a smaller binary file, to attach to bug reports.
+///
+/// Only calls made through a [Context] are recorded. Draws issued by a [Renderer](crate::renderer::Renderer),
+/// which works on a [GlContext](crate::gl_context::GlContext), or made on [Context::gl] are out of scope.
+/// Textures, uniform values, uniform buffer binding points and framebuffer attachments are not snapshotted,
+/// a frame that depends on them replays against empty ones.
+///
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Capture {
+    pub capabilities: Capabilities,
+    pub resources: Vec<Resource>,
+    pub state: CapturedState,
+    pub commands: Vec<Command>,
+    pub end_state: CapturedState,
+}
+
+impl Capture {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        bincode::serialize(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(bytes).map_err(|e| e.to_string())
+    }
+
+    ///
+    /// The commands that rebuild the captured resources and state on a fresh context,
+    /// followed by the captured frame.
+    ///
+    pub fn replay_commands(&self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        let array_buffer = BufferTarget::ArrayBuffer.into();
+
+        // shaders before the programs they are attached to, buffers before the vertex arrays
+        let order = |resource: &Resource| match resource {
+            Resource::Shader { .. } => 0,
+            Resource::Program { .. } => 1,
+            Resource::Buffer { .. } => 2,
+            Resource::VertexArray { .. } => 3,
+            Resource::Query { .. } => 4,
+            Resource::Fence { .. } => 5,
+            Resource::TransformFeedback { .. } => 6,
+            Resource::Sampler { .. } => 7,
+        };
+        let mut resources = self.resources.iter().collect::<Vec<_>>();
+        resources.sort_by_key(|resource| order(resource));
+
+        for resource in resources {
+            match resource {
+                Resource::Shader {
+                    id,
+                    shader_type,
+                    source,
+                } => commands.extend([
+                    Command::CreateShader {
+                        id: *id,
+                        shader_type: *shader_type,
+                    },
+                    Command::ShaderSource {
+                        shader: *id,
+                        source: source.clone(),
+                    },
+                    Command::CompileShader { shader: *id },
+                ]),
+                Resource::Program {
+                    id,
+                    shaders,
+                    varyings,
+                    buffer_mode,
+                } => {
+                    commands.push(Command::CreateProgram { id: *id });
+                    commands.extend(shaders.iter().map(|shader| Command::AttachShader {
+                        program: *id,
+                        shader: *shader,
+                    }));
+                    if !varyings.is_empty() {
+                        commands.push(Command::TransformFeedbackVaryings {
+                            program: *id,
+                            varyings: varyings.clone(),
+                            buffer_mode: *buffer_mode,
+                        });
+                    }
+                    commands.push(Command::LinkProgram { program: *id });
+                }
+                Resource::Buffer {
+                    id,
+                    target,
+                    usage,
+                    data,
+                } => commands.extend([
+                    Command::CreateBuffer { id: *id },
+                    Command::BindBuffer {
+                        target: *target,
+                        buffer: Some(*id),
+                    },
+                    Command::BufferData {
+                        target: *target,
+                        data: data.clone(),
+                        usage: *usage,
+                    },
+                ]),
+                Resource::VertexArray {
+                    id,
+                    attributes,
+                    element_buffer,
+                } => {
+                    commands.push(Command::CreateVertexArray { id: *id });
+                    commands.push(Command::BindVertexArray {
+                        vertex_array: Some(*id),
+                    });
+                    for attribute in attributes {
+                        if let Some(buffer) = attribute.buffer {
+                            commands.push(Command::BindBuffer {
+                                target: array_buffer,
+                                buffer: Some(buffer),
+                            });
+                            commands.push(Command::VertexAttribPointer {
+                                index: attribute.index,
+                                size: attribute.size,
+                                type_: attribute.type_,
+                                normalized: attribute.normalized,
+                                stride: attribute.stride,
+                                offset: attribute.offset,
+                            });
+                        }
+                        if attribute.enabled {
+                            commands.push(Command::EnableVertexAttribArray {
+                                index: attribute.index,
+                            });
+                        }
+                        if attribute.divisor != 0 {
+                            commands.push(Command::VertexAttribDivisor {
+                                index: attribute.index,
+                                divisor: attribute.divisor,
+                            });
+                        }
+                    }
+                    if element_buffer.is_some() {
+                        commands.push(Command::BindBuffer {
+                            target: BufferTarget::ElementArrayBuffer.into(),
+                            buffer: *element_buffer,
+                        });
+                    }
+                    commands.push(Command::BindVertexArray { vertex_array: None });
+                }
+                Resource::Query { id } => commands.push(Command::CreateQuery { id: *id }),
+                Resource::Fence { id } => commands.push(Command::FenceSync { id: *id }),
+                Resource::TransformFeedback { id, buffers } => {
+                    commands.push(Command::CreateTransformFeedback { id: *id });
+                    commands.push(Command::BindTransformFeedback {
+                        transform_feedback: Some(*id),
+                    });
+                    commands.extend(buffers.iter().map(|(index, buffer)| {
+                        Command::BindBufferBase {
+                            target: BufferTarget::TransformFeedbackBuffer.into(),
+                            index: *index,
+                            buffer: *buffer,
+                        }
+                    }));
+                    commands.push(Command::BindTransformFeedback {
+                        transform_feedback: None,
+                    });
+                }
+                Resource::Sampler { id, parameters } => commands.push(Command::CreateSampler {
+                    id: *id,
+                    parameters: parameters.clone(),
+                }),
+            }
+        }
+
+        let state = &self.state;
+        let [x, y, width, height] = state.viewport;
+        commands.push(Command::Viewport {
+            x,
+            y,
+            width,
+            height,
+        });
+        let [x, y, width, height] = state.scissor_box;
+        commands.push(Command::Scissor {
+            x,
+            y,
+            width,
+            height,
+        });
+        commands.push(Command::ClearColor {
+            color: state.clear_color,
+        });
+        for capability in CAPABILITIES {
+            let capability = u32::from(capability);
+            commands.push(match state.enabled.contains(&capability) {
+                true => Command::Enable { capability },
+                false => Command::Disable { capability },
+            });
+        }
+        commands.push(Command::UseProgram {
+            program: state.program,
+        });
+        commands.push(Command::BindVertexArray {
+            vertex_array: state.vertex_array,
+        });
+        commands.push(Command::BindBuffer {
+            target: array_buffer,
+            buffer: state.array_buffer,
+        });
+
+        commands.extend(self.commands.iter().cloned());
+        commands
+    }
+}
+
+const SAMPLER_INT_PARAMETERS: [u32; 7] = [
+    WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+    WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+    WebGl2RenderingContext::TEXTURE_WRAP_S,
+    WebGl2RenderingContext::TEXTURE_WRAP_T,
+    WebGl2RenderingContext::TEXTURE_WRAP_R,
+    WebGl2RenderingContext::TEXTURE_COMPARE_MODE,
+    WebGl2RenderingContext::TEXTURE_COMPARE_FUNC,
+];
+
+const CAPABILITIES: [Capability; 10] = [
+    Capability::Blend,
+    Capability::CullFace,
+    Capability::DepthTest,
+    Capability::Dither,
+    Capability::PolygonOffsetFill,
+    Capability::SampleAlphaToCoverage,
+    Capability::SampleCoverage,
+    Capability::ScissorTest,
+    Capability::StencilTest,
+    Capability::RasterizerDiscard,
+];
+
+/// Something that can execute captured commands: a [ContextReplayer], or a mock in tests.
+pub trait CommandTarget {
+    fn execute(&mut self, command: &Command) -> Result<(), String>;
+}
+
+/// Rebuilds the resources and state of `capture` on `target` and replays its frame.
+pub fn replay(capture: &Capture, target: &mut impl CommandTarget) -> Result<(), String> {
+    capture
+        .replay_commands()
+        .iter()
+        .try_for_each(|command| target.execute(command))
+}
+
+///
+/// Replays commands on a [Context], mapping the ids of the capture to the keys of new resources.
+///
+pub struct ContextReplayer<'a> {
+    context: &'a Context,
+    shaders: HashMap<u64, WebShaderKey>,
+    programs: HashMap<u64, WebProgramKey>,
+    buffers: HashMap<u64, WebBufferKey>,
+    vertex_arrays: HashMap<u64, WebVertexArrayKey>,
+    framebuffers: HashMap<u64, WebFramebufferKey>,
+    queries: HashMap<u64, WebQueryKey>,
+    fences: HashMap<u64, WebFenceKey>,
+    transform_feedbacks: HashMap<u64, WebTransformFeedbackKey>,
+    samplers: HashMap<u64, WebSamplerKey>,
+}
+
+impl<'a> ContextReplayer<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        Self {
+            context,
+            shaders: HashMap::new(),
+            programs: HashMap::new(),
+            buffers: HashMap::new(),
+            vertex_arrays: HashMap::new(),
+            framebuffers: HashMap::new(),
+            queries: HashMap::new(),
+            fences: HashMap::new(),
+            transform_feedbacks: HashMap::new(),
+            samplers: HashMap::new(),
+        }
+    }
+}
+
+fn key<K: Copy>(keys: &HashMap<u64, K>, id: u64, kind: &str) -> Result<K, String> {
+    keys.get(&id)
+        .copied()
+        .ok_or_else(|| format!("unknown {} {}", kind, id))
+}
+
+fn enum_value<T: TryFrom<u32>>(value: u32, kind: &str) -> Result<T, String> {
+    T::try_from(value).map_err(|_| format!("unknown {} {:#x}", kind, value))
+}
+
+impl<'a> CommandTarget for ContextReplayer<'a> {
+    fn execute(&mut self, command: &Command) -> Result<(), String> {
+        let context = self.context;
+        let gl = context.gl();
+        match command {
+            Command::CreateShader { id, shader_type } => {
+                let shader_type: ShaderType = enum_value(*shader_type, "shader type")?;
+                let key = context.create_shader(shader_type)?;
+                self.shaders.insert(*id, key);
+            }
+            Command::DeleteShader { shader } => {
+                context.delete_shader(key(&self.shaders, *shader, "shader")?)
+            }
+            Command::ShaderSource { shader, source } => {
+                context.shader_source(key(&self.shaders, *shader, "shader")?, source)
+            }
+            Command::CompileShader { shader } => {
+                context.compile_shader(key(&self.shaders, *shader, "shader")?)
+            }
+            Command::CreateProgram { id } => {
+                let key = context.create_program()?;
+                self.programs.insert(*id, key);
+            }
+            Command::DeleteProgram { program } => {
+                context.delete_program(key(&self.programs, *program, "program")?)
+            }
+            Command::AttachShader { program, shader } => context.attach_shader(
+                key(&self.programs, *program, "program")?,
+                key(&self.shaders, *shader, "shader")?,
+            ),
+            Command::LinkProgram { program } => {
+                context.link_program(key(&self.programs, *program, "program")?)
+            }
+            Command::UseProgram {
+                program: Some(program),
+            } => context.use_program(key(&self.programs, *program, "program")?),
+            Command::UseProgram { program: None } => gl.use_program(None),
+            Command::CreateBuffer { id } => {
+                let key = context.create_buffer()?;
+                self.buffers.insert(*id, key);
+            }
+            Command::BindBuffer {
+                target,
+                buffer: Some(buffer),
+            } => context.bind_buffer(
+                enum_value(*target, "buffer target")?,
+                key(&self.buffers, *buffer, "buffer")?,
+            ),
+            Command::BindBuffer {
+                target,
+                buffer: None,
+            } => gl.bind_buffer(*target, None),
+            Command::BindBufferBase {
+                target,
+                index,
+                buffer,
+            } => context.bind_base_base(
+                enum_value(*target, "buffer target")?,
+                *index,
+                key(&self.buffers, *buffer, "buffer")?,
+            ),
+            Command::BufferData {
+                target,
+                data,
+                usage,
+            } => context.buffer_data(
+                enum_value(*target, "buffer target")?,
+                data,
+                enum_value(*usage, "buffer usage")?,
+            ),
+            Command::CreateVertexArray { id } => {
+                let key = context.create_vertex_array()?;
+                self.vertex_arrays.insert(*id, key);
+            }
+            Command::DeleteVertexArray { vertex_array } => context.delete_vertex_array(key(
+                &self.vertex_arrays,
+                *vertex_array,
+                "vertex array",
+            )?),
+            Command::BindVertexArray {
+                vertex_array: Some(vertex_array),
+            } => {
+                context.bind_vertex_array(key(&self.vertex_arrays, *vertex_array, "vertex array")?)
+            }
+            Command::BindVertexArray { vertex_array: None } => gl.bind_vertex_array(None),
+            Command::EnableVertexAttribArray { index } => {
+                context.enable_vertex_attrib_array(*index)
+            }
+            Command::VertexAttribPointer {
+                index,
+                size,
+                type_,
+                normalized,
+                stride,
+                offset,
+            } => context.vertex_attrib_pointer(
+                *index,
+                *size,
+                enum_value::<DataType>(*type_, "data type")?,
+                *normalized,
+                *stride,
+                *offset,
+            ),
+            Command::VertexAttribDivisor { index, divisor } => {
+                context.vertex_attrib_divisor(*index, *divisor)
+            }
+            Command::CreateFramebuffer { id } => {
+                let key = context.create_framebuffer()?;
+                self.framebuffers.insert(*id, key);
+            }
+            Command::Enable { capability } => gl.enable(*capability),
+            Command::Disable { capability } => gl.disable(*capability),
+            Command::Viewport {
+                x,
+                y,
+                width,
+                height,
+            } => gl.viewport(*x, *y, *width, *height),
+            Command::Scissor {
+                x,
+                y,
+                width,
+                height,
+            } => context.scissor(*x, *y, *width, *height),
+            Command::ClearColor {
+                color: [r, g, b, a],
+            } => context.clear_color(*r, *g, *b, *a),
+            Command::Clear { mask } => gl.clear(*mask),
+            Command::DrawArrays { mode, first, count } => gl.draw_arrays(*mode, *first, *count),
+            Command::DrawElements {
+                mode,
+                count,
+                type_,
+                offset,
+            } => gl.draw_elements_with_i32(*mode, *count, *type_, *offset),
+            Command::CreateQuery { id } => {
+                let key = context.create_query()?;
+                self.queries.insert(*id, key);
+            }
+            Command::DeleteQuery { query } => {
+                context.delete_query(key(&self.queries, *query, "query")?)
+            }
+            Command::BeginQuery { target, query } => {
+                context.begin_query(*target, key(&self.queries, *query, "query")?)
+            }
+            Command::EndQuery { target } => context.end_query(*target),
+            Command::DeleteBuffer { buffer } => {
+                context.delete_buffer(key(&self.buffers, *buffer, "buffer")?)
+            }
+            Command::GetBufferSubData {
+                target,
+                offset,
+                length,
+            } => context.get_buffer_sub_data(
+                enum_value(*target, "buffer target")?,
+                *offset,
+                &mut vec![0; *length],
+            ),
+            Command::FenceSync { id } => {
+                let key = context.fence_sync()?;
+                self.fences.insert(*id, key);
+            }
+            Command::DeleteFence { fence } => {
+                context.delete_fence(key(&self.fences, *fence, "fence")?)
+            }
+            Command::BufferDataSize {
+                target,
+                size,
+                usage,
+            } => gl.buffer_data_with_i32(*target, *size, *usage),
+            Command::ReadPixels {
+                x,
+                y,
+                width,
+                height,
+                format,
+                type_,
+            } => gl
+                .read_pixels_with_i32(*x, *y, *width, *height, *format, *type_, 0)
+                .map_err(|error| format!("readPixels failed: {:?}", error))?,
+            Command::CreateTransformFeedback { id } => {
+                let key = context.create_transform_feedback()?;
+                self.transform_feedbacks.insert(*id, key);
+            }
+            Command::DeleteTransformFeedback { transform_feedback } => context
+                .delete_transform_feedback(key(
+                    &self.transform_feedbacks,
+                    *transform_feedback,
+                    "transform feedback",
+                )?),
+            Command::BindTransformFeedback { transform_feedback } => {
+                let transform_feedback = match transform_feedback {
+                    Some(transform_feedback) => Some(key(
+                        &self.transform_feedbacks,
+                        *transform_feedback,
+                        "transform feedback",
+                    )?),
+                    None => None,
+                };
+                context.bind_transform_feedback(transform_feedback)
+            }
+            Command::TransformFeedbackVaryings {
+                program,
+                varyings,
+                buffer_mode,
+            } => context.transform_feedback_varyings(
+                key(&self.programs, *program, "program")?,
+                &varyings.iter().map(String::as_str).collect::<Vec<_>>(),
+                enum_value(*buffer_mode, "buffer mode")?,
+            ),
+            Command::BeginTransformFeedback { primitive_mode } => {
+                context.begin_transform_feedback(enum_value(*primitive_mode, "primitive mode")?)
+            }
+            Command::EndTransformFeedback => context.end_transform_feedback(),
+            Command::CreateSampler { id, parameters } => {
+                let key = context.create_sampler_with_parameters(parameters)?;
+                self.samplers.insert(*id, key);
+            }
+            Command::DeleteSampler { sampler } => {
+                context.delete_sampler(key(&self.samplers, *sampler, "sampler")?)
+            }
+            Command::BindSampler { unit, sampler } => {
+                let sampler = match sampler {
+                    Some(sampler) => Some(key(&self.samplers, *sampler, "sampler")?),
+                    None => None,
+                };
+                context.bind_sampler(enum_value(*unit, "texture unit")?, sampler)
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Context {
+    ///
+    /// Starts recording every call made through this context.
+    /// Snapshots the live resources, including buffer contents, and the current state,
+    /// so the capture can be replayed on a fresh context.
+    ///
+    pub fn begin_capture(&self) {
+        let capture = Capture {
+            capabilities: self.capabilities().clone(),
+            resources: self.snapshot_resources(),
+            state: self.snapshot_state(),
+            commands: Vec::new(),
+            end_state: CapturedState::default(),
+        };
+        *self.capture.borrow_mut() = Some(capture);
+    }
+
+    /// Stops recording, returning `None` if no capture was in progress.
+    pub fn end_capture(&self) -> Option<Capture> {
+        let mut capture = self.capture.borrow_mut().take()?;
+        capture.end_state = self.snapshot_state();
+        Some(capture)
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.borrow().is_some()
+    }
+
+    fn snapshot_resources(&self) -> Vec<Resource> {
+        // a headless context can't read its objects back
+        if self.is_headless() {
+            return Vec::new();
+        }
+        let gl = self.gl();
+        let mut resources = Vec::new();
+
+        let shaders = self.shaders.borrow();
+        for (key, shader) in shaders.iter() {
+            resources.push(Resource::Shader {
+                id: id(key),
+                shader_type: parameter_u32(
+                    &gl.get_shader_parameter(shader, WebGl2RenderingContext::SHADER_TYPE),
+                ),
+                source: gl.get_shader_source(shader).unwrap_or_default(),
+            });
+        }
+
+        for (key, program) in self.programs.borrow().iter() {
+            let attached = gl
+                .get_attached_shaders(program)
+                .map(|attached| attached.iter().collect::<Vec<JsValue>>())
+                .unwrap_or_default();
+            let program_parameter =
+                |pname: u32| parameter_u32(&gl.get_program_parameter(program, pname));
+            let varyings =
+                (0..program_parameter(WebGl2RenderingContext::TRANSFORM_FEEDBACK_VARYINGS))
+                    .filter_map(|index| gl.get_transform_feedback_varying(program, index))
+                    .map(|varying| varying.name())
+                    .collect();
+            resources.push(Resource::Program {
+                id: id(key),
+                shaders: shaders
+                    .iter()
+                    .filter(|(_, shader)| {
+                        attached
+                            .iter()
+                            .any(|a| a == AsRef::<JsValue>::as_ref(*shader))
+                    })
+                    .map(|(key, _)| id(key))
+                    .collect(),
+                varyings,
+                buffer_mode: program_parameter(
+                    WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER_MODE,
+                ),
+            });
+        }
+
+        let copy_read_buffer = WebGl2RenderingContext::COPY_READ_BUFFER;
+        let bound_copy_read_buffer = gl
+            .get_parameter(WebGl2RenderingContext::COPY_READ_BUFFER_BINDING)
+            .ok()
+            .and_then(|value| value.dyn_into::<WebGlBuffer>().ok());
+        let buffer_targets = self.buffer_targets.borrow();
+        for (key, buffer) in self.buffers.borrow().iter() {
+            gl.bind_buffer(copy_read_buffer, Some(buffer));
+            let buffer_parameter =
+                |pname: u32| parameter_u32(&gl.get_buffer_parameter(copy_read_buffer, pname));
+            let mut data =
+                vec![0u8; buffer_parameter(WebGl2RenderingContext::BUFFER_SIZE) as usize];
+            gl.get_buffer_sub_data_with_i32_and_u8_array(copy_read_buffer, 0, &mut data);
+            resources.push(Resource::Buffer {
+                id: id(key),
+                target: buffer_targets
+                    .get(key)
+                    .map_or_else(default_buffer_target, |target| (*target).into()),
+                usage: buffer_parameter(WebGl2RenderingContext::BUFFER_USAGE),
+                data,
+            });
+        }
+        gl.bind_buffer(copy_read_buffer, bound_copy_read_buffer.as_ref());
+
+        let bound_vertex_array = gl
+            .get_parameter(WebGl2RenderingContext::VERTEX_ARRAY_BINDING)
+            .ok()
+            .and_then(|value| value.dyn_into::<WebGlVertexArrayObject>().ok());
+        let max_vertex_attribs = self.capabilities().max_vertex_attribs;
+        for (key, vertex_array) in self.vertex_arrays.borrow().iter() {
+            gl.bind_vertex_array(Some(vertex_array));
+            let attributes = (0..max_vertex_attribs)
+                .map(|index| self.snapshot_attribute(index))
+                .filter(|attribute| attribute.enabled || attribute.buffer.is_some())
+                .collect();
+            let element_buffer = gl
+                .get_parameter(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER_BINDING)
+                .ok()
+                .and_then(|buffer| self.buffer_id(&buffer));
+            resources.push(Resource::VertexArray {
+                id: id(key),
+                attributes,
+                element_buffer,
+            });
+        }
+        gl.bind_vertex_array(bound_vertex_array.as_ref());
+
+        for (key, _) in self.queries.borrow().iter() {
+            resources.push(Resource::Query { id: id(key) });
+        }
+        for (key, _) in self.fences.borrow().iter() {
+            resources.push(Resource::Fence { id: id(key) });
+        }
+
+        let bound_transform_feedback = gl
+            .get_parameter(WebGl2RenderingContext::TRANSFORM_FEEDBACK_BINDING)
+            .ok()
+            .and_then(|value| value.dyn_into::<WebGlTransformFeedback>().ok());
+        let max_buffers = gl
+            .get_parameter(WebGl2RenderingContext::MAX_TRANSFORM_FEEDBACK_SEPARATE_ATTRIBS)
+            .map(|value| parameter_u32(&value))
+            .unwrap_or(0);
+        for (key, transform_feedback) in self.transform_feedbacks.borrow().iter() {
+            gl.bind_transform_feedback(
+                WebGl2RenderingContext::TRANSFORM_FEEDBACK,
+                Some(transform_feedback),
+            );
+            let buffers = (0..max_buffers)
+                .filter_map(|index| {
+                    let buffer = gl
+                        .get_indexed_parameter(
+                            WebGl2RenderingContext::TRANSFORM_FEEDBACK_BUFFER_BINDING,
+                            index,
+                        )
+                        .ok()?;
+                    Some((index, self.buffer_id(&buffer)?))
+                })
+                .collect();
+            resources.push(Resource::TransformFeedback {
+                id: id(key),
+                buffers,
+            });
+        }
+        gl.bind_transform_feedback(
+            WebGl2RenderingContext::TRANSFORM_FEEDBACK,
+            bound_transform_feedback.as_ref(),
+        );
+
+        for (key, sampler) in self.samplers.borrow().iter() {
+            resources.push(Resource::Sampler {
+                id: id(key),
+                parameters: self.snapshot_sampler(sampler),
+            });
+        }
+
+        resources
+    }
+
+    fn snapshot_attribute(&self, index: u32) -> CapturedAttribute {
+        let gl = self.gl();
+        let attribute = |pname: u32| gl.get_vertex_attrib(index, pname).unwrap_or(JsValue::NULL);
+
+        CapturedAttribute {
+            index,
+            enabled: attribute(WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_ENABLED)
+                .as_bool()
+                .unwrap_or(false),
+            buffer: self.buffer_id(&attribute(
+                WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING,
+            )),
+            size: parameter_u32(&attribute(WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_SIZE))
+                as i32,
+            type_: parameter_u32(&attribute(WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_TYPE)),
+            normalized: attribute(WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_NORMALIZED)
+                .as_bool()
+                .unwrap_or(false),
+            stride: parameter_u32(&attribute(
+                WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_STRIDE,
+            )) as i32,
+            offset: gl.get_vertex_attrib_offset(
+                index,
+                WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_POINTER,
+            ) as i32,
+            divisor: parameter_u32(&attribute(
+                WebGl2RenderingContext::VERTEX_ATTRIB_ARRAY_DIVISOR,
+            )),
+        }
+    }
+
+    fn snapshot_sampler(&self, sampler: &WebGlSampler) -> Vec<SamplerParameter> {
+        let gl = self.gl();
+        let mut floats = vec![
+            WebGl2RenderingContext::TEXTURE_MIN_LOD,
+            WebGl2RenderingContext::TEXTURE_MAX_LOD,
+        ];
+        if self.capabilities().max_anisotropy > 1.0 {
+            floats.push(ExtTextureFilterAnisotropic::TEXTURE_MAX_ANISOTROPY_EXT);
+        }
+
+        SAMPLER_INT_PARAMETERS
+            .into_iter()
+            .map(|name| {
+                let value = gl.get_sampler_parameter(sampler, name);
+                SamplerParameter::Int(name, parameter_u32(&value) as i32)
+            })
+            .chain(floats.into_iter().map(|name| {
+                let value = gl.get_sampler_parameter(sampler, name);
+                SamplerParameter::Float(name, value.as_f64().unwrap_or(0.0) as f32)
+            }))
+            .collect()
+    }
+
+    fn snapshot_state(&self) -> CapturedState {
+        if self.is_headless() {
+            return CapturedState::default();
+        }
+        let gl = self.gl();
+        let parameter = |pname: u32| gl.get_parameter(pname).unwrap_or(JsValue::NULL);
+        let int4 = |pname: u32| -> [i32; 4] {
+            let values = js_sys::Int32Array::new(&parameter(pname)).to_vec();
+            values.try_into().unwrap_or_default()
+        };
+
+        let program = parameter(WebGl2RenderingContext::CURRENT_PROGRAM)
+            .dyn_into::<WebGlProgram>()
+            .ok();
+        let vertex_array = parameter(WebGl2RenderingContext::VERTEX_ARRAY_BINDING)
+            .dyn_into::<WebGlVertexArrayObject>()
+            .ok();
+
+        CapturedState {
+            viewport: int4(WebGl2RenderingContext::VIEWPORT),
+            scissor_box: int4(WebGl2RenderingContext::SCISSOR_BOX),
+            clear_color: js_sys::Float32Array::new(&parameter(
+                WebGl2RenderingContext::COLOR_CLEAR_VALUE,
+            ))
+            .to_vec()
+            .try_into()
+            .unwrap_or_default(),
+            enabled: CAPABILITIES
+                .into_iter()
+                .map(u32::from)
+                .filter(|capability| gl.is_enabled(*capability))
+                .collect(),
+            program: program.and_then(|program| {
+                self.programs
+                    .borrow()
+                    .iter()
+                    .find(|(_, p)| **p == program)
+                    .map(|(key, _)| id(key))
+            }),
+            vertex_array: vertex_array.and_then(|vertex_array| {
+                self.vertex_arrays
+                    .borrow()
+                    .iter()
+                    .find(|(_, v)| **v == vertex_array)
+                    .map(|(key, _)| id(key))
+            }),
+            array_buffer: self.buffer_id(&parameter(WebGl2RenderingContext::ARRAY_BUFFER_BINDING)),
+        }
+    }
+
+    fn buffer_id(&self, buffer: &JsValue) -> Option<u64> {
+        let buffer = buffer.dyn_ref::<WebGlBuffer>()?;
+        self.buffers
+            .borrow()
+            .iter()
+            .find(|(_, b)| *b == buffer)
+            .map(|(key, _)| id(key))
+    }
+}
+
+fn parameter_u32(value: &JsValue) -> u32 {
+    value.as_f64().unwrap_or(0.0) as u32
+}
+
+/// A difference between two captures, see [diff].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureDifference {
+    /// A field of [Capture::state] or [Capture::end_state].
+    State {
+        field: String,
+        left: String,
+        right: String,
+    },
+    Resource {
+        id: u64,
+        difference: String,
+    },
+    /// The commands at `index` differ, `None` when one capture has fewer commands.
+    Command {
+        index: usize,
+        left: Option<Command>,
+        right: Option<Command>,
+    },
+}
+
+impl fmt::Display for CaptureDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureDifference::State { field, left, right } => {
+                write!(f, "{}: {} != {}", field, left, right)
+            }
+            CaptureDifference::Resource { id, difference } => {
+                write!(f, "resource {}: {}", id, difference)
+            }
+            CaptureDifference::Command { index, left, right } => {
+                write!(f, "command {}: {:?} != {:?}", index, left, right)
+            }
+        }
+    }
+}
+
+///
+/// Compares two captures of the same frame, e.g. from a working and a broken machine:
+/// the capabilities and state field by field, resources by id and commands by position.
+///
+pub fn diff(left: &Capture, right: &Capture) -> Vec<CaptureDifference> {
+    let mut differences = Vec::new();
+
+    for (name, left, right) in [
+        (
+            "capabilities",
+            serde_json::to_value(&left.capabilities),
+            serde_json::to_value(&right.capabilities),
+        ),
+        (
+            "state",
+            serde_json::to_value(&left.state),
+            serde_json::to_value(&right.state),
+        ),
+        (
+            "end_state",
+            serde_json::to_value(&left.end_state),
+            serde_json::to_value(&right.end_state),
+        ),
+    ] {
+        let (Ok(serde_json::Value::Object(left)), Ok(serde_json::Value::Object(right))) =
+            (left, right)
+        else {
+            continue;
+        };
+        for (field, value) in &left {
+            let other = right.get(field).unwrap_or(&serde_json::Value::Null);
+            if value != other {
+                differences.push(CaptureDifference::State {
+                    field: format!("{}.{}", name, field),
+                    left: value.to_string(),
+                    right: other.to_string(),
+                });
+            }
+        }
+    }
+
+    let right_resources = right
+        .resources
+        .iter()
+        .map(|resource| (resource.id(), resource))
+        .collect::<HashMap<_, _>>();
+    for resource in &left.resources {
+        let difference = match right_resources.get(&resource.id()) {
+            None => Some("only in the left capture".to_string()),
+            Some(other) => resource_difference(resource, other),
+        };
+        if let Some(difference) = difference {
+            differences.push(CaptureDifference::Resource {
+                id: resource.id(),
+                difference,
+            });
+        }
+    }
+    for resource in &right.resources {
+        if !left.resources.iter().any(|r| r.id() == resource.id()) {
+            differences.push(CaptureDifference::Resource {
+                id: resource.id(),
+                difference: "only in the right capture".to_string(),
+            });
+        }
+    }
+
+    for index in 0..left.commands.len().max(right.commands.len()) {
+        let (l, r) = (left.commands.get(index), right.commands.get(index));
+        if l != r {
+            differences.push(CaptureDifference::Command {
+                index,
+                left: l.cloned(),
+                right: r.cloned(),
+            });
+        }
+    }
+
+    differences
+}
+
+fn resource_difference(left: &Resource, right: &Resource) -> Option<String> {
+    if left == right {
+        return None;
+    }
+    Some(match (left, right) {
+        (Resource::Buffer { data: l, .. }, Resource::Buffer { data: r, .. }) => {
+            match l.iter().zip(r).position(|(l, r)| l != r) {
+                Some(byte) => format!("buffer contents differ from byte {}", byte),
+                None => format!("buffer size {} != {}", l.len(), r.len()),
+            }
+        }
+        (Resource::Shader { source: l, .. }, Resource::Shader { source: r, .. }) if l != r => {
+            match l.lines().zip(r.lines()).position(|(l, r)| l != r) {
+                Some(line) => format!("shader source differs at line {}", line + 1),
+                None => "shader source length differs".to_string(),
+            }
+        }
+        _ => format!("{:?} != {:?}", left, right),
+    })
+}
+
+#[cfg(test)]
+fn sample_capture() -> Capture {
+    use crate::profiler::TIME_ELAPSED_EXT;
+
+    Capture {
+        resources: vec![
+            Resource::VertexArray {
+                id: 7,
+                attributes: vec![CapturedAttribute {
+                    index: 0,
+                    enabled: true,
+                    buffer: Some(3),
+                    size: 2,
+                    type_: WebGl2RenderingContext::FLOAT,
+                    normalized: false,
+                    stride: 0,
+                    offset: 0,
+                    divisor: 0,
+                }],
+                element_buffer: None,
+            },
+            Resource::Buffer {
+                id: 3,
+                target: WebGl2RenderingContext::ARRAY_BUFFER,
+                usage: WebGl2RenderingContext::DYNAMIC_DRAW,
+                data: vec![0, 0, 128, 63],
+            },
+            Resource::Buffer {
+                id: 4,
+                target: WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+                usage: WebGl2RenderingContext::STATIC_DRAW,
+                data: vec![0, 0, 1, 0, 2, 0],
+            },
+            Resource::Shader {
+                id: 1,
+                shader_type: WebGl2RenderingContext::VERTEX_SHADER,
+                source: "#version 300 es\nvoid main() {}".to_string(),
+            },
+            Resource::Program {
+                id: 2,
+                shaders: vec![1],
+                varyings: vec!["v_position".to_string()],
+                buffer_mode: WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+            },
+            Resource::Query { id: 5 },
+            Resource::Sampler {
+                id: 6,
+                parameters: vec![SamplerParameter::Int(
+                    WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+                    WebGl2RenderingContext::NEAREST as i32,
+                )],
+            },
+        ],
+        state: CapturedState {
+            viewport: [0, 0, 300, 150],
+            enabled: vec![WebGl2RenderingContext::DEPTH_TEST],
+            program: Some(2),
+            ..CapturedState::default()
+        },
+        commands: vec![
+            Command::BindVertexArray {
+                vertex_array: Some(7),
+            },
+            Command::BeginQuery {
+                target: TIME_ELAPSED_EXT,
+                query: 5,
+            },
+            Command::BindSampler {
+                unit: WebGl2RenderingContext::TEXTURE0,
+                sampler: Some(6),
+            },
+            Command::DrawArrays {
+                mode: WebGl2RenderingContext::TRIANGLES,
+                first: 0,
+                count: 3,
+            },
+            Command::EndQuery {
+                target: TIME_ELAPSED_EXT,
+            },
+        ],
+        ..Capture::default()
+    }
+}
+
+#[test]
+fn capture_serialize() {
+    let capture = sample_capture();
+
+    let json = capture.to_json().unwrap();
+    assert!(json.contains("\"DrawArrays\":{\"mode\":4,\"first\":0,\"count\":3}"));
+    assert_eq!(Capture::from_json(&json).unwrap(), capture);
+
+    let bytes = capture.to_bytes().unwrap();
+    assert!(bytes.len() < json.len());
+    assert_eq!(Capture::from_bytes(&bytes).unwrap(), capture);
+    assert!(Capture::from_bytes(&bytes[..10]).is_err());
+}
+
+/// Collects the commands it is given.
+#[cfg(test)]
+#[derive(Default)]
+struct Recorder(Vec<Command>);
+
+#[cfg(test)]
+impl CommandTarget for Recorder {
+    fn execute(&mut self, command: &Command) -> Result<(), String> {
+        self.0.push(command.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn capture_replay() {
+    let mut recorder = Recorder::default();
+    replay(&sample_capture(), &mut recorder).unwrap();
+    let commands = recorder.0;
+
+    let position = |command: &Command| commands.iter().rposition(|c| c == command).unwrap();
+    // resources are created before they are used, whatever order they were captured in
+    assert!(
+        position(&Command::CreateShader {
+            id: 1,
+            shader_type: WebGl2RenderingContext::VERTEX_SHADER
+        }) < position(&Command::AttachShader {
+            program: 2,
+            shader: 1
+        })
+    );
+    assert!(
+        position(&Command::CreateBuffer { id: 3 })
+            < position(&Command::CreateVertexArray { id: 7 })
+    );
+    // buffers are filled through the target they were bound to, with their usage
+    assert!(commands.contains(&Command::BufferData {
+        target: WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+        data: vec![0, 0, 1, 0, 2, 0],
+        usage: WebGl2RenderingContext::STATIC_DRAW,
+    }));
+    assert!(commands.contains(&Command::BufferData {
+        target: WebGl2RenderingContext::ARRAY_BUFFER,
+        data: vec![0, 0, 128, 63],
+        usage: WebGl2RenderingContext::DYNAMIC_DRAW,
+    }));
+    assert!(commands.contains(&Command::EnableVertexAttribArray { index: 0 }));
+    assert!(commands.contains(&Command::Enable {
+        capability: WebGl2RenderingContext::DEPTH_TEST
+    }));
+    assert!(commands.contains(&Command::Disable {
+        capability: WebGl2RenderingContext::BLEND
+    }));
+    assert!(
+        position(&Command::UseProgram { program: Some(2) })
+            < position(&Command::BindVertexArray {
+                vertex_array: Some(7)
+            })
+    );
+    assert!(commands.contains(&Command::CreateQuery { id: 5 }));
+    assert!(commands.contains(&Command::CreateSampler {
+        id: 6,
+        parameters: vec![SamplerParameter::Int(
+            WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+            WebGl2RenderingContext::NEAREST as i32,
+        )],
+    }));
+    // a transform feedback program is linked with its varyings
+    assert!(
+        position(&Command::TransformFeedbackVaryings {
+            program: 2,
+            varyings: vec!["v_position".to_string()],
+            buffer_mode: WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+        }) < position(&Command::LinkProgram { program: 2 })
+    );
+    // the frame comes last
+    let frame = sample_capture().commands;
+    assert_eq!(&commands[commands.len() - frame.len()..], &frame[..]);
+}
+
+#[test]
+fn capture_diff() {
+    let left = sample_capture();
+    assert!(diff(&left, &left).is_empty());
+
+    let mut right = sample_capture();
+    right.state.viewport = [0, 0, 800, 600];
+    right.resources[1] = Resource::Buffer {
+        id: 3,
+        target: WebGl2RenderingContext::ARRAY_BUFFER,
+        usage: WebGl2RenderingContext::DYNAMIC_DRAW,
+        data: vec![0, 0, 0, 63],
+    };
+    right.commands.pop();
+
+    let differences = diff(&left, &right);
+    assert_eq!(
+        differences,
+        vec![
+            CaptureDifference::State {
+                field: "state.viewport".to_string(),
+                left: "[0,0,300,150]".to_string(),
+                right: "[0,0,800,600]".to_string(),
+            },
+            CaptureDifference::Resource {
+                id: 3,
+                difference: "buffer contents differ from byte 2".to_string(),
+            },
+            CaptureDifference::Command {
+                index: 4,
+                left: Some(left.commands[4].clone()),
+                right: None,
+            },
+        ]
+    );
+    assert_eq!(
+        differences[0].to_string(),
+        "state.viewport: [0,0,300,150] != [0,0,800,600]"
+    );
+}
+
+#[test]
+fn capture_records_context_calls() {
+    use webgl_common::{BufferUsage, DrawMode, ScissorBox};
+
+    let context = Context::headless(Capabilities::default());
+    context.begin_capture();
+    let buffer = context.create_buffer().unwrap();
+    let indices = context.create_buffer().unwrap();
+    context.bind_buffer(BufferTarget::ArrayBuffer, buffer);
+    context.buffer_data(
+        BufferTarget::ArrayBuffer,
+        &[1.0f32, 0.5],
+        BufferUsage::DynamicDraw,
+    );
+    let vertex_array = context.create_vertex_array().unwrap();
+    context.bind_vertex_array(vertex_array);
+    context.bind_buffer(BufferTarget::ElementArrayBuffer, indices);
+    context.enable_vertex_attrib_array(0);
+    context.vertex_attrib_pointer(0, 2, DataType::Float, false, 0, 0);
+    context.set_scissor(ScissorBox {
+        x: 0,
+        y: 0,
+        width: 10,
+        height: 20,
+    });
+    context.draw_elements(DrawMode::Triangles, 3, DataType::UnsignedShort, 0);
+    context.unbind_vertex_array();
+    context.delete_buffer(buffer);
+    let capture = context.end_capture().unwrap();
+    assert!(!context.is_capturing());
+
+    let mut recorder = Recorder::default();
+    replay(&capture, &mut recorder).unwrap();
+    let [buffer_id, indices_id, vertex_array_id] = [id(buffer), id(indices), id(vertex_array)];
+    let frame = vec![
+        Command::CreateBuffer { id: buffer_id },
+        Command::CreateBuffer { id: indices_id },
+        Command::BindBuffer {
+            target: WebGl2RenderingContext::ARRAY_BUFFER,
+            buffer: Some(buffer_id),
+        },
+        Command::BufferData {
+            target: WebGl2RenderingContext::ARRAY_BUFFER,
+            data: vec![0, 0, 128, 63, 0, 0, 0, 63],
+            usage: WebGl2RenderingContext::DYNAMIC_DRAW,
+        },
+        Command::CreateVertexArray {
+            id: vertex_array_id,
+        },
+        Command::BindVertexArray {
+            vertex_array: Some(vertex_array_id),
+        },
+        Command::BindBuffer {
+            target: WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            buffer: Some(indices_id),
+        },
+        Command::EnableVertexAttribArray { index: 0 },
+        Command::VertexAttribPointer {
+            index: 0,
+            size: 2,
+            type_: WebGl2RenderingContext::FLOAT,
+            normalized: false,
+            stride: 0,
+            offset: 0,
+        },
+        Command::Enable {
+            capability: WebGl2RenderingContext::SCISSOR_TEST,
+        },
+        Command::Scissor {
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 20,
+        },
+        Command::DrawElements {
+            mode: WebGl2RenderingContext::TRIANGLES,
+            count: 3,
+            type_: WebGl2RenderingContext::UNSIGNED_SHORT,
+            offset: 0,
+        },
+        Command::BindVertexArray { vertex_array: None },
+        Command::DeleteBuffer { buffer: buffer_id },
+    ];
+    assert_eq!(capture.commands, frame);
+    // replayed after the captured state, a headless context has no resources to rebuild
+    assert_eq!(&recorder.0[recorder.0.len() - frame.len()..], &frame[..]);
+
+    // the target the buffers are snapshotted with
+    let buffer_targets = context.buffer_targets.borrow();
+    assert_eq!(
+        buffer_targets.get(indices),
+        Some(&BufferTarget::ElementArrayBuffer)
+    );
+    assert!(!buffer_targets.contains_key(buffer));
+}
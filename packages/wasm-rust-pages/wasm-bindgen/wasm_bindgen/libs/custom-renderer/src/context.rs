@@ -1,29 +1,55 @@
-use slotmap::{new_key_type, SlotMap};
-use std::{cell::RefCell, rc::Rc};
+use slotmap::{new_key_type, Key, SecondaryMap, SlotMap};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
     WebGl2RenderingContext, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlQuery,
     WebGlRenderbuffer, WebGlSampler, WebGlShader, WebGlSync, WebGlTexture, WebGlTransformFeedback,
-    WebGlVertexArrayObject,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+use webgl_common::{
+    slice_as_u8_slice, BufferTarget, BufferUsage, Capabilities, Capability, DataType, DrawMode,
+    GpuPod, Mask, ScissorBox, ShaderType, TransformFeedbackBufferMode, Viewport,
+};
+
+use crate::{
+    capture::{Capture, Command},
+    sampler::SamplerDescriptor,
 };
-use webgl_common::{BufferTarget, Capability, ScissorBox, ShaderType, Viewport};
 
+///
+/// Owns the WebGL2 objects created through it, as slotmap keys, and records them into a
+/// [Capture] when one is in progress.
+///
+/// WebGL2 only, unlike [GlContext](crate::gl_context::GlContext): queries, fences,
+/// pixel pack buffers, transform feedback and sampler objects have no WebGL1 equivalent.
+///
 #[derive(Debug)]
 pub struct Context {
     gl: WebGl2RenderingContext,
+    capabilities: Capabilities,
+    /// Records the calls without running them, see [Context::headless].
+    headless: bool,
     // pub(super) vao: crate::context::VertexArray,
     // A cache of programs to avoid recompiling a [Program] every frame.
     // pub programs: Rc<HashMap<Vec<u8>, Program>>,
-    shaders: TrackedResource<WebShaderKey, WebGlShader>,
-    programs: TrackedResource<WebProgramKey, WebGlProgram>,
-    buffers: TrackedResource<WebBufferKey, WebGlBuffer>,
-    vertex_arrays: TrackedResource<WebVertexArrayKey, WebGlVertexArrayObject>,
+    pub(crate) shaders: TrackedResource<WebShaderKey, WebGlShader>,
+    pub(crate) programs: TrackedResource<WebProgramKey, WebGlProgram>,
+    pub(crate) buffers: TrackedResource<WebBufferKey, WebGlBuffer>,
+    /// The target each buffer was first bound to, WebGL doesn't allow binding it to another one.
+    pub(crate) buffer_targets: RefCell<SecondaryMap<WebBufferKey, BufferTarget>>,
+    pub(crate) vertex_arrays: TrackedResource<WebVertexArrayKey, WebGlVertexArrayObject>,
     textures: TrackedResource<WebTextureKey, WebGlTexture>,
-    samplers: TrackedResource<WebSamplerKey, WebGlSampler>,
-    fences: TrackedResource<WebFenceKey, WebGlSync>,
-    framebuffers: TrackedResource<WebFramebufferKey, WebGlFramebuffer>,
+    pub(crate) samplers: TrackedResource<WebSamplerKey, WebGlSampler>,
+    /// The samplers created by [Context::get_or_create_sampler].
+    pub(crate) sampler_cache: RefCell<HashMap<SamplerDescriptor, WebSamplerKey>>,
+    pub(crate) fences: TrackedResource<WebFenceKey, WebGlSync>,
+    pub(crate) framebuffers: TrackedResource<WebFramebufferKey, WebGlFramebuffer>,
     renderbuffers: TrackedResource<WebRenderbufferKey, WebGlRenderbuffer>,
-    queries: TrackedResource<WebQueryKey, WebGlQuery>,
-    transform_feedbacks: TrackedResource<WebTransformFeedbackKey, WebGlTransformFeedback>,
+    pub(crate) queries: TrackedResource<WebQueryKey, WebGlQuery>,
+    pub(crate) transform_feedbacks:
+        TrackedResource<WebTransformFeedbackKey, WebGlTransformFeedback>,
+    /// The capture in progress, see [Context::begin_capture].
+    pub(crate) capture: RefCell<Option<Capture>>,
 }
 
 impl Context {
@@ -35,28 +61,52 @@ impl Context {
         //     .create_vertex_array()
         //     .ok_or("failed creating vertex array")?;
 
-        Ok(Self {
-            gl: context,
+        Ok(Self::with_gl(Capabilities::query(&context), context, false))
+    }
+
+    ///
+    /// A context without a canvas: the calls are recorded into a [Capture] but not run, and the
+    /// created objects are placeholders. Builds captures natively, e.g. in tests.
+    /// Nothing is snapshotted, so begin the capture before creating anything.
+    /// Queries, readbacks and [Context::gl] need a real context.
+    ///
+    pub fn headless(capabilities: Capabilities) -> Self {
+        Self::with_gl(capabilities, JsValue::NULL.unchecked_into(), true)
+    }
+
+    fn with_gl(capabilities: Capabilities, gl: WebGl2RenderingContext, headless: bool) -> Self {
+        Self {
+            capabilities,
+            gl,
+            headless,
             // vao: crate::context::VertexArray::new(&context),
             // programs: Rc::new(HashMap::new()),
             shaders: tracked_resource(),
             programs: tracked_resource(),
             buffers: tracked_resource(),
+            buffer_targets: RefCell::new(SecondaryMap::new()),
             vertex_arrays: tracked_resource(),
             textures: tracked_resource(),
             samplers: tracked_resource(),
+            sampler_cache: RefCell::new(HashMap::new()),
             fences: tracked_resource(),
             framebuffers: tracked_resource(),
             renderbuffers: tracked_resource(),
             queries: tracked_resource(),
             transform_feedbacks: tracked_resource(),
-        })
+            capture: RefCell::new(None),
+        }
     }
 
+    /// The raw context, calls made on it are not recorded into a [Capture].
     pub fn gl(&self) -> &WebGl2RenderingContext {
         &self.gl
     }
 
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
     pub fn set_scissor(&self, scissor_box: ScissorBox) {
         if scissor_box.width > 0 && scissor_box.height > 0 {
             self.enable(Capability::ScissorTest);
@@ -83,15 +133,22 @@ impl Context {
 
 impl Context {
     pub fn scissor(&self, x: i32, y: i32, width: i32, height: i32) {
-        self.gl.scissor(x, y, width, height);
+        self.record(|| Command::Scissor {
+            x,
+            y,
+            width,
+            height,
+        });
+        self.run(|gl| gl.scissor(x, y, width, height));
     }
 
     pub fn create_framebuffer(&self) -> Result<WebFramebufferKey, String> {
-        let raw_framebuffer = self.gl.create_framebuffer();
+        let raw_framebuffer = self.create(|gl| gl.create_framebuffer());
 
         match raw_framebuffer {
             Some(framebuffer) => {
                 let key = self.framebuffers.borrow_mut().insert(framebuffer);
+                self.record(|| Command::CreateFramebuffer { id: id(key) });
                 Ok(key)
             }
             None => Err("Unable to create framebuffer object".to_string()),
@@ -99,11 +156,15 @@ impl Context {
     }
 
     pub fn create_shader(&self, shader_type: ShaderType) -> Result<WebShaderKey, String> {
-        let raw_shader = self.gl.create_shader(shader_type.into());
+        let raw_shader = self.create(|gl| gl.create_shader(shader_type.into()));
 
         match raw_shader {
             Some(s) => {
                 let key = self.shaders.borrow_mut().insert(s);
+                self.record(|| Command::CreateShader {
+                    id: id(key),
+                    shader_type: shader_type.into(),
+                });
                 Ok(key)
             }
             None => Err(String::from("Unable to create shader object")),
@@ -113,28 +174,35 @@ impl Context {
     pub fn delete_shader(&self, shader: WebShaderKey) {
         let mut shaders = self.shaders.borrow_mut();
         if let Some(ref s) = shaders.remove(shader) {
-            self.gl.delete_shader(Some(s))
+            self.record(|| Command::DeleteShader { shader: id(shader) });
+            self.run(|gl| gl.delete_shader(Some(s)))
         }
     }
 
     pub fn shader_source(&self, shader: WebShaderKey, source: &str) {
         let shaders = self.shaders.borrow();
         let raw_shader = unsafe { shaders.get_unchecked(shader) };
-        self.gl.shader_source(raw_shader, source);
+        self.record(|| Command::ShaderSource {
+            shader: id(shader),
+            source: source.to_string(),
+        });
+        self.run(|gl| gl.shader_source(raw_shader, source));
     }
 
     pub fn compile_shader(&self, shader: WebShaderKey) {
         let shaders = self.shaders.borrow();
         let raw_shader = unsafe { shaders.get_unchecked(shader) };
-        self.gl.compile_shader(raw_shader);
+        self.record(|| Command::CompileShader { shader: id(shader) });
+        self.run(|gl| gl.compile_shader(raw_shader));
     }
 
     pub fn create_program(&self) -> Result<WebProgramKey, String> {
-        let raw_program = self.gl.create_program();
+        let raw_program = self.create(|gl| gl.create_program());
 
         match raw_program {
             Some(p) => {
                 let key = self.programs.borrow_mut().insert(p);
+                self.record(|| Command::CreateProgram { id: id(key) });
                 Ok(key)
             }
             None => Err(String::from("Unable to create program object")),
@@ -144,7 +212,10 @@ impl Context {
     pub fn delete_program(&self, program: WebProgramKey) {
         let mut programs = self.programs.borrow_mut();
         if let Some(ref p) = programs.remove(program) {
-            self.gl.delete_program(Some(p));
+            self.record(|| Command::DeleteProgram {
+                program: id(program),
+            });
+            self.run(|gl| gl.delete_program(Some(p)));
         }
     }
 
@@ -153,22 +224,31 @@ impl Context {
         let shaders = self.shaders.borrow();
         let raw_program = unsafe { programs.get_unchecked(program) };
         let raw_shader = unsafe { shaders.get_unchecked(shader) };
-        self.gl.attach_shader(raw_program, raw_shader);
+        self.record(|| Command::AttachShader {
+            program: id(program),
+            shader: id(shader),
+        });
+        self.run(|gl| gl.attach_shader(raw_program, raw_shader));
     }
 
     pub fn link_program(&self, program: WebProgramKey) {
         let programs = self.programs.borrow();
         let raw_program = unsafe { programs.get_unchecked(program) };
-        self.gl.link_program(raw_program);
+        self.record(|| Command::LinkProgram {
+            program: id(program),
+        });
+        self.run(|gl| gl.link_program(raw_program));
     }
 
     pub fn get_program_parameter(&self, program: WebProgramKey, pname: u32) -> i32 {
         let programs = self.programs.borrow();
         let raw_program = unsafe { programs.get_unchecked(program) };
-        self.gl
-            .get_program_parameter(raw_program, pname)
+        let value = self.gl.get_program_parameter(raw_program, pname);
+        // e.g. LINK_STATUS is a boolean
+        value
             .as_f64()
             .map(|v| v as i32)
+            .or_else(|| value.as_bool().map(i32::from))
             .unwrap_or(0)
     }
 
@@ -202,18 +282,32 @@ impl Context {
             })
     }
 
+    pub fn get_uniform_location(
+        &self,
+        program: WebProgramKey,
+        name: &str,
+    ) -> Option<WebGlUniformLocation> {
+        let programs = self.programs.borrow();
+        let raw_program = unsafe { programs.get_unchecked(program) };
+        self.gl.get_uniform_location(raw_program, name)
+    }
+
     pub fn use_program(&self, program: WebProgramKey) {
         let programs = self.programs.borrow();
         let raw_program = unsafe { programs.get_unchecked(program) };
-        self.gl.use_program(Some(raw_program));
+        self.record(|| Command::UseProgram {
+            program: Some(id(program)),
+        });
+        self.run(|gl| gl.use_program(Some(raw_program)));
     }
 
     pub fn create_buffer(&self) -> Result<WebBufferKey, String> {
-        let raw_buffer = self.gl.create_buffer();
+        let raw_buffer = self.create(|gl| gl.create_buffer());
 
         match raw_buffer {
             Some(b) => {
                 let key = self.buffers.borrow_mut().insert(b);
+                self.record(|| Command::CreateBuffer { id: id(key) });
                 Ok(key)
             }
             None => Err("Unable to create buffer object".to_string()),
@@ -223,22 +317,51 @@ impl Context {
     pub fn bind_buffer(&self, target: BufferTarget, buffer: WebBufferKey) {
         let buffers = self.buffers.borrow();
         let raw_buffer = unsafe { buffers.get_unchecked(buffer) };
-        self.gl.bind_buffer(target.into(), Some(raw_buffer));
+        self.record(|| Command::BindBuffer {
+            target: target.into(),
+            buffer: Some(id(buffer)),
+        });
+        self.track_buffer_target(buffer, target);
+        self.run(|gl| gl.bind_buffer(target.into(), Some(raw_buffer)));
+    }
+
+    /// The `bufferData()` method, on the buffer bound to `target`.
+    pub fn buffer_data<T: GpuPod>(&self, target: BufferTarget, data: &[T], usage: BufferUsage) {
+        let data = slice_as_u8_slice(data);
+        self.record(|| Command::BufferData {
+            target: target.into(),
+            data: data.to_vec(),
+            usage: usage.into(),
+        });
+        self.run(|gl| gl.buffer_data_with_u8_array(target.into(), data, usage.into()));
     }
 
     pub fn bind_base_base(&self, target: BufferTarget, index: u32, buffer: WebBufferKey) {
         let buffers = self.buffers.borrow();
         let raw_buffer = unsafe { buffers.get_unchecked(buffer) };
-        self.gl
-            .bind_buffer_base(target.into(), index, Some(raw_buffer));
+        self.record(|| Command::BindBufferBase {
+            target: target.into(),
+            index,
+            buffer: id(buffer),
+        });
+        self.track_buffer_target(buffer, target);
+        self.run(|gl| gl.bind_buffer_base(target.into(), index, Some(raw_buffer)));
+    }
+
+    fn track_buffer_target(&self, buffer: WebBufferKey, target: BufferTarget) {
+        let mut buffer_targets = self.buffer_targets.borrow_mut();
+        if !buffer_targets.contains_key(buffer) {
+            buffer_targets.insert(buffer, target);
+        }
     }
 
     pub fn create_vertex_array(&self) -> Result<WebVertexArrayKey, String> {
-        let raw_vertex_array = self.gl.create_vertex_array();
+        let raw_vertex_array = self.create(|gl| gl.create_vertex_array());
 
         match raw_vertex_array {
             Some(v) => {
                 let key = self.vertex_arrays.borrow_mut().insert(v);
+                self.record(|| Command::CreateVertexArray { id: id(key) });
                 Ok(key)
             }
             None => Err("Unable to create vertex array object".to_string()),
@@ -248,31 +371,364 @@ impl Context {
     pub fn delete_vertex_array(&self, vertex_array: WebVertexArrayKey) {
         let mut vertex_arrays = self.vertex_arrays.borrow_mut();
         if let Some(ref v) = vertex_arrays.remove(vertex_array) {
-            self.gl.delete_vertex_array(Some(v));
+            self.record(|| Command::DeleteVertexArray {
+                vertex_array: id(vertex_array),
+            });
+            self.run(|gl| gl.delete_vertex_array(Some(v)));
         }
     }
 
+    pub fn unbind_buffer(&self, target: BufferTarget) {
+        self.record(|| Command::BindBuffer {
+            target: target.into(),
+            buffer: None,
+        });
+        self.run(|gl| gl.bind_buffer(target.into(), None));
+    }
+
     pub fn bind_vertex_array(&self, vertex_array: WebVertexArrayKey) {
         let vertex_arrays = self.vertex_arrays.borrow();
         let raw_vertex_array = unsafe { vertex_arrays.get_unchecked(vertex_array) };
-        self.gl.bind_vertex_array(Some(raw_vertex_array));
+        self.record(|| Command::BindVertexArray {
+            vertex_array: Some(id(vertex_array)),
+        });
+        self.run(|gl| gl.bind_vertex_array(Some(raw_vertex_array)));
+    }
+
+    pub fn unbind_vertex_array(&self) {
+        self.record(|| Command::BindVertexArray { vertex_array: None });
+        self.run(|gl| gl.bind_vertex_array(None));
+    }
+
+    pub fn enable_vertex_attrib_array(&self, index: u32) {
+        self.record(|| Command::EnableVertexAttribArray { index });
+        self.run(|gl| gl.enable_vertex_attrib_array(index));
+    }
+
+    pub fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        type_: DataType,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        self.record(|| Command::VertexAttribPointer {
+            index,
+            size,
+            type_: type_.into(),
+            normalized,
+            stride,
+            offset,
+        });
+        self.run(|gl| {
+            gl.vertex_attrib_pointer_with_i32(index, size, type_.into(), normalized, stride, offset)
+        });
+    }
+
+    pub fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.record(|| Command::VertexAttribDivisor { index, divisor });
+        self.run(|gl| gl.vertex_attrib_divisor(index, divisor));
     }
 
     pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
-        self.gl.clear_color(r, g, b, a);
+        self.record(|| Command::ClearColor {
+            color: [r, g, b, a],
+        });
+        self.run(|gl| gl.clear_color(r, g, b, a));
+    }
+
+    pub fn clear(&self, mask: Mask) {
+        self.record(|| Command::Clear { mask: mask.into() });
+        self.run(|gl| gl.clear(mask.into()));
+    }
+
+    pub fn draw_arrays(&self, mode: DrawMode, first: i32, count: i32) {
+        self.record(|| Command::DrawArrays {
+            mode: mode.into(),
+            first,
+            count,
+        });
+        self.run(|gl| gl.draw_arrays(mode.into(), first, count));
+    }
+
+    pub fn draw_elements(&self, mode: DrawMode, count: i32, type_: DataType, offset: i32) {
+        self.record(|| Command::DrawElements {
+            mode: mode.into(),
+            count,
+            type_: type_.into(),
+            offset,
+        });
+        self.run(|gl| gl.draw_elements_with_i32(mode.into(), count, type_.into(), offset));
+    }
+
+    pub fn create_query(&self) -> Result<WebQueryKey, String> {
+        match self.create(|gl| gl.create_query()) {
+            Some(query) => {
+                let key = self.queries.borrow_mut().insert(query);
+                self.record(|| Command::CreateQuery { id: id(key) });
+                Ok(key)
+            }
+            None => Err("Unable to create query object".to_string()),
+        }
+    }
+
+    pub fn delete_query(&self, query: WebQueryKey) {
+        let mut queries = self.queries.borrow_mut();
+        if let Some(ref q) = queries.remove(query) {
+            self.record(|| Command::DeleteQuery { query: id(query) });
+            self.run(|gl| gl.delete_query(Some(q)));
+        }
+    }
+
+    ///
+    /// The `beginQuery()` method, e.g. with `TIME_ELAPSED_EXT` from `EXT_disjoint_timer_query_webgl2`.
+    /// Panics when `query` was deleted.
+    ///
+    pub fn begin_query(&self, target: u32, query: WebQueryKey) {
+        let queries = self.queries.borrow();
+        let raw_query = live(&queries, query, "query");
+        self.record(|| Command::BeginQuery {
+            target,
+            query: id(query),
+        });
+        self.run(|gl| gl.begin_query(target, raw_query));
+    }
+
+    pub fn end_query(&self, target: u32) {
+        self.record(|| Command::EndQuery { target });
+        self.run(|gl| gl.end_query(target));
+    }
+
+    /// The result of `query`, or `None` while the GPU has not finished with it. Panics when `query` was deleted.
+    pub fn query_result(&self, query: WebQueryKey) -> Option<u64> {
+        let queries = self.queries.borrow();
+        let raw_query = live(&queries, query, "query");
+        let available = self
+            .gl
+            .get_query_parameter(raw_query, WebGl2RenderingContext::QUERY_RESULT_AVAILABLE)
+            .as_bool()
+            .unwrap_or(false);
+        if !available {
+            return None;
+        }
+        self.gl
+            .get_query_parameter(raw_query, WebGl2RenderingContext::QUERY_RESULT)
+            .as_f64()
+            .map(|result| result as u64)
+    }
+
+    pub fn delete_buffer(&self, buffer: WebBufferKey) {
+        let mut buffers = self.buffers.borrow_mut();
+        if let Some(ref b) = buffers.remove(buffer) {
+            self.record(|| Command::DeleteBuffer { buffer: id(buffer) });
+            self.buffer_targets.borrow_mut().remove(buffer);
+            self.run(|gl| gl.delete_buffer(Some(b)));
+        }
+    }
+
+    /// The `getBufferSubData()` method, on the buffer bound to `target`.
+    pub fn get_buffer_sub_data(&self, target: BufferTarget, offset: i32, data: &mut [u8]) {
+        self.record(|| Command::GetBufferSubData {
+            target: target.into(),
+            offset,
+            length: data.len(),
+        });
+        self.run(|gl| gl.get_buffer_sub_data_with_i32_and_u8_array(target.into(), offset, data));
+    }
+
+    /// Inserts a fence that is signaled once the GPU has executed all commands issued before it.
+    pub fn fence_sync(&self) -> Result<WebFenceKey, String> {
+        match self.create(|gl| gl.fence_sync(WebGl2RenderingContext::SYNC_GPU_COMMANDS_COMPLETE, 0))
+        {
+            Some(fence) => {
+                let key = self.fences.borrow_mut().insert(fence);
+                self.record(|| Command::FenceSync { id: id(key) });
+                Ok(key)
+            }
+            None => Err("Unable to create fence".to_string()),
+        }
+    }
+
+    pub fn delete_fence(&self, fence: WebFenceKey) {
+        let mut fences = self.fences.borrow_mut();
+        if let Some(ref f) = fences.remove(fence) {
+            self.record(|| Command::DeleteFence { fence: id(fence) });
+            self.run(|gl| gl.delete_sync(Some(f)));
+        }
+    }
+
+    ///
+    /// The `clientWaitSync()` method with a zero timeout, so it never blocks.
+    /// Returns `ALREADY_SIGNALED`, `CONDITION_SATISFIED`, `TIMEOUT_EXPIRED` or `WAIT_FAILED`,
+    /// also when `fence` was deleted.
+    ///
+    pub fn client_wait_sync(&self, fence: WebFenceKey) -> u32 {
+        let fences = self.fences.borrow();
+        let Some(raw_fence) = fences.get(fence) else {
+            return WebGl2RenderingContext::WAIT_FAILED;
+        };
+        self.gl.client_wait_sync_with_u32(
+            raw_fence,
+            WebGl2RenderingContext::SYNC_FLUSH_COMMANDS_BIT,
+            0,
+        )
+    }
+
+    pub fn create_transform_feedback(&self) -> Result<WebTransformFeedbackKey, String> {
+        match self.create(|gl| gl.create_transform_feedback()) {
+            Some(tf) => {
+                let key = self.transform_feedbacks.borrow_mut().insert(tf);
+                self.record(|| Command::CreateTransformFeedback { id: id(key) });
+                Ok(key)
+            }
+            None => Err("Unable to create transform feedback object".to_string()),
+        }
+    }
+
+    pub fn delete_transform_feedback(&self, transform_feedback: WebTransformFeedbackKey) {
+        let mut transform_feedbacks = self.transform_feedbacks.borrow_mut();
+        if let Some(ref tf) = transform_feedbacks.remove(transform_feedback) {
+            self.record(|| Command::DeleteTransformFeedback {
+                transform_feedback: id(transform_feedback),
+            });
+            self.run(|gl| gl.delete_transform_feedback(Some(tf)));
+        }
+    }
+
+    ///
+    /// Binds `transform_feedback`, or the default transform feedback object for `None`.
+    /// Panics when `transform_feedback` was deleted.
+    ///
+    pub fn bind_transform_feedback(&self, transform_feedback: Option<WebTransformFeedbackKey>) {
+        let transform_feedbacks = self.transform_feedbacks.borrow();
+        let raw_transform_feedback =
+            transform_feedback.map(|key| live(&transform_feedbacks, key, "transform feedback"));
+        self.record(|| Command::BindTransformFeedback {
+            transform_feedback: transform_feedback.map(id),
+        });
+        self.run(|gl| {
+            gl.bind_transform_feedback(
+                WebGl2RenderingContext::TRANSFORM_FEEDBACK,
+                raw_transform_feedback,
+            )
+        });
+    }
+
+    ///
+    /// The `transformFeedbackVaryings()` method. Takes effect on the next [Context::link_program].
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/transformFeedbackVaryings)
+    ///
+    pub fn transform_feedback_varyings(
+        &self,
+        program: WebProgramKey,
+        varyings: &[&str],
+        buffer_mode: TransformFeedbackBufferMode,
+    ) {
+        let programs = self.programs.borrow();
+        let raw_program = live(&programs, program, "program");
+        self.record(|| Command::TransformFeedbackVaryings {
+            program: id(program),
+            varyings: varyings.iter().map(|varying| varying.to_string()).collect(),
+            buffer_mode: buffer_mode.into(),
+        });
+        self.run(|gl| {
+            let varyings = varyings
+                .iter()
+                .map(|varying| JsValue::from_str(varying))
+                .collect::<js_sys::Array>();
+            gl.transform_feedback_varyings(raw_program, &varyings, buffer_mode.into())
+        });
+    }
+
+    /// `primitive_mode` is [DrawMode::Points], [DrawMode::Lines] or [DrawMode::Triangles].
+    pub fn begin_transform_feedback(&self, primitive_mode: DrawMode) {
+        self.record(|| Command::BeginTransformFeedback {
+            primitive_mode: primitive_mode.into(),
+        });
+        self.run(|gl| gl.begin_transform_feedback(primitive_mode.into()));
+    }
+
+    pub fn end_transform_feedback(&self) {
+        self.record(|| Command::EndTransformFeedback);
+        self.run(|gl| gl.end_transform_feedback());
+    }
+
+    /// Enables `RASTERIZER_DISCARD`, so draw calls only run the vertex shader.
+    pub fn set_rasterizer_discard(&self, discard: bool) {
+        match discard {
+            true => self.enable(Capability::RasterizerDiscard),
+            false => self.disable(Capability::RasterizerDiscard),
+        }
     }
 
     fn enable(&self, parameter: Capability) {
-        self.gl.enable(parameter.into());
+        self.record(|| Command::Enable {
+            capability: parameter.into(),
+        });
+        self.run(|gl| gl.enable(parameter.into()));
     }
 
     fn disable(&self, parameter: Capability) {
-        self.gl.disable(parameter.into());
+        self.record(|| Command::Disable {
+            capability: parameter.into(),
+        });
+        self.run(|gl| gl.disable(parameter.into()));
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(|| Command::Viewport {
+            x,
+            y,
+            width,
+            height,
+        });
+        self.run(|gl| gl.viewport(x, y, width, height));
     }
 
-    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
-        self.gl.viewport(x, y, width, height);
+    pub fn is_headless(&self) -> bool {
+        self.headless
     }
+
+    /// Runs a GL call, a headless context skips it.
+    fn run<R: Default>(&self, call: impl FnOnce(&WebGl2RenderingContext) -> R) -> R {
+        match self.headless {
+            true => R::default(),
+            false => call(&self.gl),
+        }
+    }
+
+    /// Creates a GL object, a headless context makes a placeholder.
+    fn create<T: JsCast>(
+        &self,
+        create: impl FnOnce(&WebGl2RenderingContext) -> Option<T>,
+    ) -> Option<T> {
+        match self.headless {
+            true => Some(JsValue::NULL.unchecked_into()),
+            false => create(&self.gl),
+        }
+    }
+
+    /// Appends a command to the capture in progress.
+    pub(crate) fn record(&self, command: impl FnOnce() -> Command) {
+        if let Some(capture) = self.capture.borrow_mut().as_mut() {
+            capture.commands.push(command());
+        }
+    }
+}
+
+/// The id of a resource in a [Capture].
+pub(crate) fn id(key: impl Key) -> u64 {
+    key.data().as_ffi()
+}
+
+/// The object behind `key`, panics with a message when it was deleted.
+pub(crate) fn live<'r, K: Key, V>(resources: &'r SlotMap<K, V>, key: K, kind: &str) -> &'r V {
+    resources
+        .get(key)
+        .unwrap_or_else(|| panic!("the {} {:?} was deleted", kind, key))
 }
 
 type TrackedResource<K, V> = RefCell<SlotMap<K, V>>;
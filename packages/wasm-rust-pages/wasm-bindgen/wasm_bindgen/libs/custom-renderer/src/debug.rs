@@ -0,0 +1,992 @@
+use std::fmt;
+
+use web_sys::WebGlProgram;
+use webgl_common::{get_error, DataType, DrawMode, ErrorType};
+
+use crate::{
+    buffer::IndexBuffer,
+    renderer::Renderer,
+    vertex_array_object::{AttributeOptions, VertexArrayObject},
+};
+
+/// A mistake found by [DebugRenderer].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugError {
+    /// `getError()` returned an error after the call.
+    Gl(ErrorType),
+    /// Drawing without a program in use.
+    NoProgram,
+    /// The draw reads past the end of the buffer an attribute points to.
+    AttributeOutOfBounds {
+        index: u32,
+        required: usize,
+        size: usize,
+    },
+    /// The draw reads indices past the end of the index buffer.
+    IndicesOutOfBounds { required: usize, size: usize },
+    /// Offset and stride of an attribute must be multiples of its type size.
+    Misaligned { index: u32, type_size: u32 },
+}
+
+impl fmt::Display for DebugError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugError::Gl(error) => write!(f, "GL error {:?}", error),
+            DebugError::NoProgram => write!(f, "no program is in use"),
+            DebugError::AttributeOutOfBounds {
+                index,
+                required,
+                size,
+            } => write!(
+                f,
+                "attribute {} reads {} bytes from a buffer of {} bytes",
+                index, required, size
+            ),
+            DebugError::IndicesOutOfBounds { required, size } => write!(
+                f,
+                "reads {} bytes of indices from a buffer of {} bytes",
+                required, size
+            ),
+            DebugError::Misaligned { index, type_size } => write!(
+                f,
+                "offset and stride of attribute {} are not multiples of {}",
+                index, type_size
+            ),
+        }
+    }
+}
+
+/// A failing call, with the passes it was made in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugReport {
+    /// Labels of the enclosing passes, outermost first.
+    pub passes: Vec<String>,
+    /// The call and its arguments, e.g. `draw_arrays(Triangles, 0, 3)`.
+    pub call: String,
+    pub error: DebugError,
+}
+
+impl fmt::Display for DebugReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.passes.is_empty() {
+            write!(f, "[{}] ", self.passes.join(" > "))?;
+        }
+        write!(f, "{}: {}", self.call, self.error)
+    }
+}
+
+/// An enabled attribute of a [VertexArrayObject] and the size of the [Buffer](crate::buffer::Buffer) it reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeRead {
+    pub location: u32,
+    pub options: AttributeOptions,
+    /// [Buffer::size](crate::buffer::Buffer::size), `0` before any data was uploaded.
+    pub buffer_size: usize,
+}
+
+impl AttributeRead {
+    /// The enabled attributes of `vertex_array`.
+    pub fn of(vertex_array: &VertexArrayObject) -> Vec<AttributeRead> {
+        vertex_array
+            .attribs
+            .iter()
+            .filter(|attribute| attribute.is_enabled())
+            .map(|attribute| AttributeRead {
+                location: attribute.location(),
+                options: attribute.options(),
+                buffer_size: attribute.buffer().size().unwrap_or(0),
+            })
+            .collect()
+    }
+}
+
+///
+/// Checks a draw of `vertices` vertices and `instances` instances, a non-instanced draw is one instance.
+/// Attributes with a divisor of `N` read one element per `N` instances, the others one per vertex.
+///
+pub fn validate_draw(
+    program: bool,
+    attributes: &[AttributeRead],
+    vertices: usize,
+    instances: usize,
+) -> Result<(), DebugError> {
+    if !program {
+        return Err(DebugError::NoProgram);
+    }
+
+    for attribute in attributes {
+        let AttributeOptions {
+            size,
+            data_type,
+            stride,
+            offset,
+            divisor,
+            ..
+        } = attribute.options;
+        let index = attribute.location;
+
+        let type_size = data_type.size() as i32;
+        if offset % type_size != 0 || stride % type_size != 0 {
+            return Err(DebugError::Misaligned {
+                index,
+                type_size: data_type.size(),
+            });
+        }
+
+        let elements = match divisor {
+            0 => vertices,
+            divisor => instances.div_ceil(divisor as usize),
+        };
+        if elements == 0 {
+            continue;
+        }
+
+        let element = size.max(0) as usize * data_type.size() as usize;
+        let stride = match stride {
+            0 => element,
+            stride => stride as usize,
+        };
+        let required = offset.max(0) as usize + (elements - 1) * stride + element;
+        if required > attribute.buffer_size {
+            return Err(DebugError::AttributeOutOfBounds {
+                index,
+                required,
+                size: attribute.buffer_size,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks `count` indices of `index_type` from `first` on fit in an index buffer of `size` bytes.
+pub fn validate_indices(
+    index_type: DataType,
+    size: usize,
+    first: i32,
+    count: i32,
+) -> Result<(), DebugError> {
+    let required = (first.max(0) + count.max(0)) as usize * index_type.size() as usize;
+    if required > size {
+        return Err(DebugError::IndicesOutOfBounds { required, size });
+    }
+    Ok(())
+}
+
+/// Drains the error flags of `get_error`, it returns one per call and a lost context keeps returning its flag.
+pub fn gl_errors(mut get_error_flag: impl FnMut() -> u32) -> Vec<ErrorType> {
+    let mut errors = Vec::new();
+    for _ in 0..8 {
+        match get_error(get_error_flag()) {
+            ErrorType::NoError => break,
+            error => {
+                errors.push(error);
+                if error == ErrorType::ContextLostWebgl {
+                    break;
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// What an indexed draw needs to know about its [IndexBuffer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexRead {
+    pub index_type: DataType,
+    /// The size of the buffer in bytes, `0` before any data was uploaded.
+    pub buffer_size: usize,
+    pub count: i32,
+    /// The largest of the `count` indices.
+    pub max_index: Option<u32>,
+}
+
+impl IndexRead {
+    pub fn of(indices: &IndexBuffer) -> IndexRead {
+        IndexRead {
+            index_type: indices.index_type(),
+            buffer_size: indices.buffer.size().unwrap_or(0),
+            count: indices.count(),
+            max_index: indices.max_index(),
+        }
+    }
+
+    /// The vertices read by drawing `count` indices from `first` on. Only the largest index
+    /// of all the indices is known, so a draw of part of them reads `0` vertices.
+    pub fn vertices(&self, first: i32, count: i32) -> usize {
+        match self.max_index {
+            Some(max_index) if first <= 0 && count >= self.count => max_index as usize + 1,
+            _ => 0,
+        }
+    }
+}
+
+///
+/// The calls [DebugRenderer] checks, and what it reads to validate them.
+///
+/// Implemented by [Renderer], and by mocks to test the layer without a browser.
+///
+pub trait GlBackend<'a> {
+    type Program: ?Sized + 'a;
+    type VertexArray: 'a;
+    type IndexBuffer: ?Sized;
+
+    fn has_program(&self) -> bool;
+    fn use_program(&mut self, program: &'a Self::Program);
+    fn bind_vertex_array(&mut self, vertex_array: Option<&'a Self::VertexArray>);
+    fn draw_arrays(&mut self, mode: DrawMode, first: i32, count: i32);
+    fn draw_arrays_instanced(&mut self, mode: DrawMode, first: i32, count: i32, instances: i32);
+    fn draw_elements(
+        &mut self,
+        mode: DrawMode,
+        indices: &Self::IndexBuffer,
+        first: i32,
+        count: i32,
+    );
+    fn draw_elements_instanced(
+        &mut self,
+        mode: DrawMode,
+        indices: &Self::IndexBuffer,
+        first: i32,
+        count: i32,
+        instances: i32,
+    );
+    #[allow(clippy::too_many_arguments)]
+    fn draw_range_elements(
+        &mut self,
+        mode: DrawMode,
+        start: u32,
+        end: u32,
+        indices: &Self::IndexBuffer,
+        first: i32,
+        count: i32,
+    );
+    fn get_error(&mut self) -> u32;
+
+    /// The enabled attributes of `vertex_array`.
+    fn attributes(vertex_array: &Self::VertexArray) -> Vec<AttributeRead>;
+    fn indices(indices: &Self::IndexBuffer) -> IndexRead;
+}
+
+impl<'a> GlBackend<'a> for Renderer<'a> {
+    type Program = WebGlProgram;
+    type VertexArray = VertexArrayObject<'a>;
+    type IndexBuffer = IndexBuffer<'a>;
+
+    fn has_program(&self) -> bool {
+        self.state.program.is_some()
+    }
+
+    fn use_program(&mut self, program: &'a WebGlProgram) {
+        Renderer::use_program(self, program);
+    }
+
+    fn bind_vertex_array(&mut self, vertex_array: Option<&'a VertexArrayObject<'a>>) {
+        Renderer::bind_vertex_array(self, &vertex_array);
+        self.state.vertex_array_object = vertex_array;
+    }
+
+    fn draw_arrays(&mut self, mode: DrawMode, first: i32, count: i32) {
+        Renderer::draw_arrays(self, mode, first, count);
+    }
+
+    fn draw_arrays_instanced(&mut self, mode: DrawMode, first: i32, count: i32, instances: i32) {
+        Renderer::draw_arrays_instanced(self, mode, first, count, instances);
+    }
+
+    fn draw_elements(&mut self, mode: DrawMode, indices: &IndexBuffer<'a>, first: i32, count: i32) {
+        Renderer::draw_elements(self, mode, indices, first, count);
+    }
+
+    fn draw_elements_instanced(
+        &mut self,
+        mode: DrawMode,
+        indices: &IndexBuffer<'a>,
+        first: i32,
+        count: i32,
+        instances: i32,
+    ) {
+        Renderer::draw_elements_instanced(self, mode, indices, first, count, instances);
+    }
+
+    fn draw_range_elements(
+        &mut self,
+        mode: DrawMode,
+        start: u32,
+        end: u32,
+        indices: &IndexBuffer<'a>,
+        first: i32,
+        count: i32,
+    ) {
+        Renderer::draw_range_elements(self, mode, start, end, indices, first, count);
+    }
+
+    fn get_error(&mut self) -> u32 {
+        self.gl.get_error()
+    }
+
+    fn attributes(vertex_array: &VertexArrayObject<'a>) -> Vec<AttributeRead> {
+        AttributeRead::of(vertex_array)
+    }
+
+    fn indices(indices: &IndexBuffer<'a>) -> IndexRead {
+        IndexRead::of(indices)
+    }
+}
+
+///
+/// # Debug layer
+///
+/// An opt-in wrapper around a [Renderer], or another [GlBackend], that validates draws before they reach GL
+/// and, in debug builds, checks `getError()` after every call. Failing draws are skipped and reported
+/// with their arguments and the labeled passes they were made in, see [DebugRenderer::take_reports].
+/// On wasm every report is also logged to the console.
+///
+/// Draws are checked against the program and the vertex array bound through the layer,
+/// the sizes the attribute [Buffer](crate::buffer::Buffer)s track and the divisors of the attributes.
+/// Indices are checked to fit in their [IndexBuffer]. Drawing all of them also checks the largest index
+/// stays inside the attribute buffers, [DebugRenderer::draw_range_elements] checks the vertices it is told,
+/// other draws of part of the indices don't check the vertices.
+///
+pub struct DebugRenderer<'a, B: GlBackend<'a> = Renderer<'a>> {
+    renderer: B,
+    vertex_array: Option<&'a B::VertexArray>,
+    passes: Vec<String>,
+    reports: Vec<DebugReport>,
+}
+
+impl<'a, B: GlBackend<'a>> DebugRenderer<'a, B> {
+    pub fn new(renderer: B) -> Self {
+        Self {
+            renderer,
+            vertex_array: None,
+            passes: Vec::new(),
+            reports: Vec::new(),
+        }
+    }
+
+    /// The wrapped renderer, for calls the layer does not check.
+    pub fn renderer(&self) -> &B {
+        &self.renderer
+    }
+
+    pub fn into_renderer(self) -> B {
+        self.renderer
+    }
+
+    /// Starts a labeled pass, reports of calls until the matching [DebugRenderer::pop_pass] carry the label.
+    pub fn push_pass(&mut self, label: &str) {
+        self.passes.push(label.to_string());
+    }
+
+    pub fn pop_pass(&mut self) {
+        self.passes.pop();
+    }
+
+    /// Runs `f` inside a labeled pass.
+    pub fn pass<R>(&mut self, label: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.push_pass(label);
+        let result = f(self);
+        self.pop_pass();
+        result
+    }
+
+    /// Reports since the last call, oldest first.
+    pub fn take_reports(&mut self) -> Vec<DebugReport> {
+        std::mem::take(&mut self.reports)
+    }
+
+    /// Runs an unchecked call on the renderer and checks `getError()` after it.
+    pub fn call<R>(&mut self, call: &str, f: impl FnOnce(&mut B) -> R) -> R {
+        let result = f(&mut self.renderer);
+        self.check(|| call.to_string());
+        result
+    }
+}
+
+impl<'a, B: GlBackend<'a>> DebugRenderer<'a, B> {
+    pub fn use_program(&mut self, program: &'a B::Program) {
+        self.renderer.use_program(program);
+        self.check(|| "use_program()".to_string());
+    }
+
+    /// Binds `vertex_array`, the draws are validated against its attributes.
+    pub fn bind_vertex_array(&mut self, vertex_array: Option<&'a B::VertexArray>) {
+        self.renderer.bind_vertex_array(vertex_array);
+        self.vertex_array = vertex_array;
+        self.check(|| {
+            format!(
+                "bind_vertex_array({})",
+                if vertex_array.is_some() {
+                    "Some"
+                } else {
+                    "None"
+                }
+            )
+        });
+    }
+
+    pub fn draw_arrays(&mut self, mode: DrawMode, first: i32, count: i32) {
+        let call = || format!("draw_arrays({:?}, {}, {})", mode, first, count);
+        if let Err(error) = self.validate((first + count).max(0) as usize, 1) {
+            return self.report(call(), error);
+        }
+        self.renderer.draw_arrays(mode, first, count);
+        self.check(call);
+    }
+
+    pub fn draw_arrays_instanced(
+        &mut self,
+        mode: DrawMode,
+        first: i32,
+        count: i32,
+        instances: i32,
+    ) {
+        let call = || {
+            format!(
+                "draw_arrays_instanced({:?}, {}, {}, {})",
+                mode, first, count, instances
+            )
+        };
+        let vertices = (first + count).max(0) as usize;
+        if let Err(error) = self.validate(vertices, instances.max(0) as usize) {
+            return self.report(call(), error);
+        }
+        self.renderer
+            .draw_arrays_instanced(mode, first, count, instances);
+        self.check(call);
+    }
+
+    pub fn draw_elements(
+        &mut self,
+        mode: DrawMode,
+        indices: &B::IndexBuffer,
+        first: i32,
+        count: i32,
+    ) {
+        let call = || format!("draw_elements({:?}, {}, {})", mode, first, count);
+        let index_read = B::indices(indices);
+        if let Err(error) = self
+            .validate(index_read.vertices(first, count), 1)
+            .and_then(|_| Self::validate_index_buffer(&index_read, first, count))
+        {
+            return self.report(call(), error);
+        }
+        self.renderer.draw_elements(mode, indices, first, count);
+        self.check(call);
+    }
+
+    pub fn draw_elements_instanced(
+        &mut self,
+        mode: DrawMode,
+        indices: &B::IndexBuffer,
+        first: i32,
+        count: i32,
+        instances: i32,
+    ) {
+        let call = || {
+            format!(
+                "draw_elements_instanced({:?}, {}, {}, {})",
+                mode, first, count, instances
+            )
+        };
+        let index_read = B::indices(indices);
+        if let Err(error) = self
+            .validate(index_read.vertices(first, count), instances.max(0) as usize)
+            .and_then(|_| Self::validate_index_buffer(&index_read, first, count))
+        {
+            return self.report(call(), error);
+        }
+        self.renderer
+            .draw_elements_instanced(mode, indices, first, count, instances);
+        self.check(call);
+    }
+
+    /// Also checks vertices `start..=end` stay inside the attribute buffers.
+    pub fn draw_range_elements(
+        &mut self,
+        mode: DrawMode,
+        start: u32,
+        end: u32,
+        indices: &B::IndexBuffer,
+        first: i32,
+        count: i32,
+    ) {
+        let call = || {
+            format!(
+                "draw_range_elements({:?}, {}, {}, {}, {})",
+                mode, start, end, first, count
+            )
+        };
+        if let Err(error) = self
+            .validate(end as usize + 1, 1)
+            .and_then(|_| Self::validate_index_buffer(&B::indices(indices), first, count))
+        {
+            return self.report(call(), error);
+        }
+        self.renderer
+            .draw_range_elements(mode, start, end, indices, first, count);
+        self.check(call);
+    }
+}
+
+impl<'a, B: GlBackend<'a>> DebugRenderer<'a, B> {
+    fn validate(&self, vertices: usize, instances: usize) -> Result<(), DebugError> {
+        let attributes = self.vertex_array.map(B::attributes).unwrap_or_default();
+        validate_draw(
+            self.renderer.has_program(),
+            &attributes,
+            vertices,
+            instances,
+        )
+    }
+
+    fn validate_index_buffer(
+        indices: &IndexRead,
+        first: i32,
+        count: i32,
+    ) -> Result<(), DebugError> {
+        validate_indices(indices.index_type, indices.buffer_size, first, count)
+    }
+
+    /// Reports every error flag `getError()` returns, in debug builds only.
+    fn check(&mut self, call: impl FnOnce() -> String) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let errors = gl_errors(|| self.renderer.get_error());
+        if !errors.is_empty() {
+            let call = call();
+            for error in errors {
+                self.report(call.clone(), DebugError::Gl(error));
+            }
+        }
+    }
+
+    fn report(&mut self, call: String, error: DebugError) {
+        let report = DebugReport {
+            passes: self.passes.clone(),
+            call,
+            error,
+        };
+        #[cfg(target_arch = "wasm32")]
+        web_sys::console::error_1(&report.to_string().into());
+        self.reports.push(report);
+    }
+}
+
+/// One `vec2` attribute at location 0 over 3 vertices, 24 bytes.
+#[cfg(test)]
+fn triangle() -> AttributeRead {
+    AttributeRead {
+        location: 0,
+        options: AttributeOptions {
+            size: 2,
+            data_type: DataType::Float,
+            ..Default::default()
+        },
+        buffer_size: 24,
+    }
+}
+
+#[test]
+fn debug_draw_without_program() {
+    assert_eq!(
+        validate_draw(false, &[triangle()], 3, 1),
+        Err(DebugError::NoProgram)
+    );
+
+    let report = DebugReport {
+        passes: vec!["frame".to_string(), "opaque".to_string()],
+        call: "draw_arrays(Triangles, 0, 3)".to_string(),
+        error: DebugError::NoProgram,
+    };
+    assert_eq!(
+        report.to_string(),
+        "[frame > opaque] draw_arrays(Triangles, 0, 3): no program is in use"
+    );
+}
+
+#[test]
+fn debug_attribute_out_of_bounds() {
+    assert_eq!(validate_draw(true, &[triangle()], 3, 1), Ok(()));
+    assert_eq!(
+        validate_draw(true, &[triangle()], 4, 1),
+        Err(DebugError::AttributeOutOfBounds {
+            index: 0,
+            required: 32,
+            size: 24
+        })
+    );
+
+    // no data uploaded yet
+    let empty = AttributeRead {
+        buffer_size: 0,
+        ..triangle()
+    };
+    assert_eq!(
+        validate_draw(true, &[empty], 1, 1),
+        Err(DebugError::AttributeOutOfBounds {
+            index: 0,
+            required: 8,
+            size: 0
+        })
+    );
+
+    let misaligned = AttributeRead {
+        options: AttributeOptions {
+            offset: 2,
+            ..triangle().options
+        },
+        ..triangle()
+    };
+    assert_eq!(
+        validate_draw(true, &[misaligned], 3, 1),
+        Err(DebugError::Misaligned {
+            index: 0,
+            type_size: 4
+        })
+    );
+}
+
+#[test]
+fn debug_instanced_attributes() {
+    // a vec4 per instance, 4 instances in 64 bytes
+    let offsets = AttributeRead {
+        location: 1,
+        options: AttributeOptions {
+            size: 4,
+            data_type: DataType::Float,
+            divisor: 1,
+            ..Default::default()
+        },
+        buffer_size: 64,
+    };
+    let attributes = [triangle(), offsets];
+
+    assert_eq!(validate_draw(true, &attributes, 3, 4), Ok(()));
+    assert_eq!(
+        validate_draw(true, &attributes, 3, 5),
+        Err(DebugError::AttributeOutOfBounds {
+            index: 1,
+            required: 80,
+            size: 64
+        })
+    );
+    // more instances than vertices in the buffer do not matter to per-instance attributes
+    assert_eq!(validate_draw(true, &attributes, 0, 4), Ok(()));
+
+    // one element per 2 instances
+    let halved = AttributeRead {
+        options: AttributeOptions {
+            divisor: 2,
+            ..offsets.options
+        },
+        ..offsets
+    };
+    assert_eq!(validate_draw(true, &[triangle(), halved], 3, 8), Ok(()));
+    assert_eq!(
+        validate_draw(true, &[triangle(), halved], 3, 9),
+        Err(DebugError::AttributeOutOfBounds {
+            index: 1,
+            required: 80,
+            size: 64
+        })
+    );
+}
+
+#[test]
+fn debug_indices_out_of_bounds() {
+    assert_eq!(validate_indices(DataType::UnsignedShort, 6, 0, 3), Ok(()));
+    assert_eq!(
+        validate_indices(DataType::UnsignedShort, 6, 1, 3),
+        Err(DebugError::IndicesOutOfBounds {
+            required: 8,
+            size: 6
+        })
+    );
+    assert_eq!(
+        validate_indices(DataType::UnsignedInt, 12, 0, 4),
+        Err(DebugError::IndicesOutOfBounds {
+            required: 16,
+            size: 12
+        })
+    );
+}
+
+#[test]
+fn debug_reports_gl_errors() {
+    use web_sys::WebGl2RenderingContext as Gl;
+
+    let mut flags = vec![Gl::INVALID_VALUE, Gl::INVALID_OPERATION].into_iter();
+    assert_eq!(
+        gl_errors(|| flags.next().unwrap_or(Gl::NO_ERROR)),
+        vec![ErrorType::InvalidValue, ErrorType::InvalidOperation]
+    );
+
+    // a lost context keeps returning its flag
+    assert_eq!(
+        gl_errors(|| Gl::CONTEXT_LOST_WEBGL),
+        vec![ErrorType::ContextLostWebgl]
+    );
+    assert!(gl_errors(|| Gl::NO_ERROR).is_empty());
+}
+
+/// Records the calls that reach it and returns queued error flags from `get_error`.
+#[cfg(test)]
+#[derive(Default)]
+struct MockBackend {
+    program: bool,
+    calls: Vec<String>,
+    errors: Vec<u32>,
+}
+
+#[cfg(test)]
+impl<'a> GlBackend<'a> for MockBackend {
+    type Program = str;
+    type VertexArray = Vec<AttributeRead>;
+    type IndexBuffer = IndexRead;
+
+    fn has_program(&self) -> bool {
+        self.program
+    }
+
+    fn use_program(&mut self, program: &'a str) {
+        self.program = true;
+        self.calls.push(format!("use_program({})", program));
+    }
+
+    fn bind_vertex_array(&mut self, vertex_array: Option<&'a Vec<AttributeRead>>) {
+        self.calls
+            .push(format!("bind_vertex_array({})", vertex_array.is_some()));
+    }
+
+    fn draw_arrays(&mut self, _: DrawMode, first: i32, count: i32) {
+        self.calls
+            .push(format!("draw_arrays({}, {})", first, count));
+    }
+
+    fn draw_arrays_instanced(&mut self, _: DrawMode, first: i32, count: i32, instances: i32) {
+        self.calls.push(format!(
+            "draw_arrays_instanced({}, {}, {})",
+            first, count, instances
+        ));
+    }
+
+    fn draw_elements(&mut self, _: DrawMode, _: &IndexRead, first: i32, count: i32) {
+        self.calls
+            .push(format!("draw_elements({}, {})", first, count));
+    }
+
+    fn draw_elements_instanced(
+        &mut self,
+        _: DrawMode,
+        _: &IndexRead,
+        first: i32,
+        count: i32,
+        instances: i32,
+    ) {
+        self.calls.push(format!(
+            "draw_elements_instanced({}, {}, {})",
+            first, count, instances
+        ));
+    }
+
+    fn draw_range_elements(
+        &mut self,
+        _: DrawMode,
+        start: u32,
+        end: u32,
+        _: &IndexRead,
+        first: i32,
+        count: i32,
+    ) {
+        self.calls.push(format!(
+            "draw_range_elements({}, {}, {}, {})",
+            start, end, first, count
+        ));
+    }
+
+    fn get_error(&mut self) -> u32 {
+        self.calls.push("get_error()".to_string());
+        if self.errors.is_empty() {
+            web_sys::WebGl2RenderingContext::NO_ERROR
+        } else {
+            self.errors.remove(0)
+        }
+    }
+
+    fn attributes(vertex_array: &Vec<AttributeRead>) -> Vec<AttributeRead> {
+        vertex_array.clone()
+    }
+
+    fn indices(indices: &IndexRead) -> IndexRead {
+        *indices
+    }
+}
+
+#[test]
+fn debug_renderer_reports_passes() {
+    let attributes = vec![triangle()];
+    let mut debug = DebugRenderer::new(MockBackend::default());
+
+    debug.draw_arrays(DrawMode::Triangles, 0, 3);
+    debug.pass("frame", |debug| {
+        debug.use_program("color");
+        debug.bind_vertex_array(Some(&attributes));
+        debug.pass("opaque", |debug| {
+            debug.draw_arrays(DrawMode::Triangles, 0, 4)
+        });
+        debug.draw_arrays(DrawMode::Triangles, 1, 3);
+    });
+
+    let reports = debug.take_reports();
+    assert_eq!(
+        reports
+            .iter()
+            .map(|report| report.to_string())
+            .collect::<Vec<_>>(),
+        vec![
+            "draw_arrays(Triangles, 0, 3): no program is in use",
+            "[frame > opaque] draw_arrays(Triangles, 0, 4): attribute 0 reads 32 bytes from a buffer of 24 bytes",
+            "[frame] draw_arrays(Triangles, 1, 3): attribute 0 reads 32 bytes from a buffer of 24 bytes",
+        ]
+    );
+    assert!(debug.take_reports().is_empty());
+
+    // failing draws are skipped
+    assert!(!debug
+        .renderer()
+        .calls
+        .iter()
+        .any(|call| call.starts_with("draw_arrays")));
+}
+
+#[test]
+#[cfg_attr(not(debug_assertions), ignore)]
+fn debug_renderer_checks_every_call() {
+    use web_sys::WebGl2RenderingContext as Gl;
+
+    let attributes = vec![triangle()];
+    let mut debug = DebugRenderer::new(MockBackend::default());
+    debug.use_program("color");
+    debug.bind_vertex_array(Some(&attributes));
+    debug.draw_arrays(DrawMode::Triangles, 0, 3);
+    debug.call("clear()", |backend| {
+        backend.calls.push("clear()".to_string())
+    });
+    assert_eq!(
+        debug.renderer().calls,
+        vec![
+            "use_program(color)",
+            "get_error()",
+            "bind_vertex_array(true)",
+            "get_error()",
+            "draw_arrays(0, 3)",
+            "get_error()",
+            "clear()",
+            "get_error()",
+        ]
+    );
+    assert!(debug.take_reports().is_empty());
+
+    // every flag is reported with the call and its arguments
+    debug.pass("frame", |debug| {
+        debug.renderer.errors = vec![Gl::INVALID_OPERATION, Gl::INVALID_VALUE];
+        debug.draw_arrays_instanced(DrawMode::Triangles, 0, 3, 2);
+    });
+    assert_eq!(
+        debug.take_reports(),
+        vec![
+            DebugReport {
+                passes: vec!["frame".to_string()],
+                call: "draw_arrays_instanced(Triangles, 0, 3, 2)".to_string(),
+                error: DebugError::Gl(ErrorType::InvalidOperation),
+            },
+            DebugReport {
+                passes: vec!["frame".to_string()],
+                call: "draw_arrays_instanced(Triangles, 0, 3, 2)".to_string(),
+                error: DebugError::Gl(ErrorType::InvalidValue),
+            },
+        ]
+    );
+
+    debug.renderer.errors = vec![Gl::INVALID_ENUM];
+    debug.bind_vertex_array(None);
+    assert_eq!(
+        debug.take_reports(),
+        vec![DebugReport {
+            passes: Vec::new(),
+            call: "bind_vertex_array(None)".to_string(),
+            error: DebugError::Gl(ErrorType::InvalidEnum),
+        }]
+    );
+}
+
+#[test]
+fn debug_renderer_indexed_draws() {
+    let attributes = vec![triangle()];
+    let mut debug = DebugRenderer::new(MockBackend::default());
+    debug.use_program("color");
+    debug.bind_vertex_array(Some(&attributes));
+
+    // 6 indices up to vertex 2 fit the 3 vertices
+    let indices = IndexRead {
+        index_type: DataType::UnsignedShort,
+        buffer_size: 12,
+        count: 6,
+        max_index: Some(2),
+    };
+    debug.draw_elements(DrawMode::Triangles, &indices, 0, 6);
+    debug.draw_elements_instanced(DrawMode::Triangles, &indices, 0, 6, 2);
+    assert!(debug.take_reports().is_empty());
+
+    // an index past the vertices
+    let past = IndexRead {
+        max_index: Some(3),
+        ..indices
+    };
+    debug.draw_elements(DrawMode::Triangles, &past, 0, 6);
+    assert_eq!(
+        debug.take_reports(),
+        vec![DebugReport {
+            passes: Vec::new(),
+            call: "draw_elements(Triangles, 0, 6)".to_string(),
+            error: DebugError::AttributeOutOfBounds {
+                index: 0,
+                required: 32,
+                size: 24
+            },
+        }]
+    );
+
+    // only the largest index of all of them is known, part of them only checks the indices
+    debug.draw_elements(DrawMode::Triangles, &past, 3, 3);
+    assert!(debug.take_reports().is_empty());
+    debug.draw_elements(DrawMode::Triangles, &past, 4, 3);
+    assert_eq!(
+        debug.take_reports()[0].error,
+        DebugError::IndicesOutOfBounds {
+            required: 14,
+            size: 12
+        }
+    );
+
+    // the range is checked against the vertices
+    debug.draw_range_elements(DrawMode::Triangles, 0, 3, &indices, 0, 6);
+    assert_eq!(
+        debug.take_reports()[0].call,
+        "draw_range_elements(Triangles, 0, 3, 0, 6)"
+    );
+
+    assert_eq!(indices.vertices(0, 6), 3);
+    assert_eq!(indices.vertices(0, 3), 0);
+    assert_eq!(
+        IndexRead {
+            max_index: None,
+            ..indices
+        }
+        .vertices(0, 6),
+        0
+    );
+}
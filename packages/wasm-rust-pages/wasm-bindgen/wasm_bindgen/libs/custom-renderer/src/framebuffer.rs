@@ -1,25 +1,27 @@
 use wasm_bindgen::JsValue;
-use web_sys::{WebGl2RenderingContext, WebGlFramebuffer};
+use web_sys::WebGlFramebuffer;
 use webgl_common::FramebufferTarget;
 
+use crate::gl_context::GlContext;
+
 #[derive(Debug, Clone)]
 pub struct Framebuffer {
     framebuffer: WebGlFramebuffer,
 }
 
 impl Framebuffer {
-    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
+    pub fn new(gl: &GlContext) -> Result<Self, JsValue> {
         let framebuffer = gl
             .create_framebuffer()
             .ok_or("Failed to create framebuffer")?;
         Ok(Self { framebuffer })
     }
 
-    pub fn unbind(gl: &WebGl2RenderingContext, target: FramebufferTarget) {
+    pub fn unbind(gl: &GlContext, target: FramebufferTarget) {
         gl.bind_framebuffer(target.into(), None);
     }
 
-    pub fn bind(&self, gl: &WebGl2RenderingContext) {
+    pub fn bind(&self, gl: &GlContext) {
         gl.bind_framebuffer(
             FramebufferTarget::Framebuffer.into(),
             Some(&self.framebuffer),
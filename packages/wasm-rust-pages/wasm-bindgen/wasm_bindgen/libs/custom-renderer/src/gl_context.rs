@@ -0,0 +1,267 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    AngleInstancedArrays, HtmlCanvasElement, OesVertexArrayObject, WebGl2RenderingContext,
+    WebGlActiveInfo, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderingContext,
+    WebGlShader, WebGlUniformLocation, WebGlVertexArrayObject,
+};
+use webgl_common::{Capabilities, Extension, WebGlVersion};
+
+///
+/// The context every renderer object draws with.
+///
+/// A WebGL2 context, or a WebGL1 context for browsers without WebGL2.
+/// On WebGL1 vertex array objects and instancing come from extensions,
+/// and uniform buffers are emulated with plain uniforms (see [UniformBlock](crate::uniform_block::UniformBlock)).
+///
+/// Only the [Renderer](crate::renderer::Renderer), buffers, vertex array objects, framebuffers,
+/// shader programs and uniform blocks draw with a `GlContext`. [Context](crate::context::Context)
+/// and what is built on it (capture and replay, timer queries, pixel readback, fences,
+/// transform feedback and sampler objects) need WebGL2.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlContext {
+    WebGl1(WebGl1Context),
+    WebGl2(WebGl2RenderingContext),
+}
+
+/// A WebGL1 context with the extensions the renderer can not work without.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebGl1Context {
+    pub gl: WebGlRenderingContext,
+    pub vertex_array_object: OesVertexArrayObject,
+    pub instanced_arrays: AngleInstancedArrays,
+}
+
+/// Forwards methods with the same signature on both context types.
+macro_rules! forward {
+    ($(pub fn $name:ident(&self $(, $arg:ident: $ty:ty)*) $(-> $ret:ty)?;)*) => {
+        $(
+            pub fn $name(&self $(, $arg: $ty)*) $(-> $ret)? {
+                match self {
+                    GlContext::WebGl1(context) => context.gl.$name($($arg),*),
+                    GlContext::WebGl2(gl) => gl.$name($($arg),*),
+                }
+            }
+        )*
+    };
+}
+
+impl GlContext {
+    ///
+    /// Creates a context of the requested `version` on `canvas`.
+    /// When WebGL2 is requested but not available, falls back to WebGL1.
+    ///
+    /// `options` are the [context attributes](https://developer.mozilla.org/en-US/docs/Web/API/HTMLCanvasElement/getContext#contextattributes).
+    ///
+    pub fn from_canvas(
+        canvas: &HtmlCanvasElement,
+        options: &JsValue,
+        version: WebGlVersion,
+    ) -> Result<Self, String> {
+        if version == WebGlVersion::Two {
+            if let Ok(Some(context)) = canvas.get_context_with_context_options("webgl2", options) {
+                let gl = context
+                    .dyn_into::<WebGl2RenderingContext>()
+                    .map_err(|_| "webgl2 context is not a WebGL2RenderingContext")?;
+                return Ok(GlContext::WebGl2(gl));
+            }
+        }
+
+        let gl = canvas
+            .get_context_with_context_options("webgl", options)
+            .ok()
+            .flatten()
+            .ok_or("WebGL is not supported")?
+            .dyn_into::<WebGlRenderingContext>()
+            .map_err(|_| "webgl context is not a WebGLRenderingContext")?;
+        Self::webgl1(gl)
+    }
+
+    /// Wraps a WebGL1 context, failing when it lacks `OES_vertex_array_object` or `ANGLE_instanced_arrays`.
+    pub fn webgl1(gl: WebGlRenderingContext) -> Result<Self, String> {
+        let extension = |extension: Extension| {
+            gl.get_extension(extension.name())
+                .ok()
+                .flatten()
+                .ok_or_else(|| format!("{} is not supported", extension.name()))
+        };
+
+        let vertex_array_object = extension(Extension::VertexArrayObject)?.unchecked_into();
+        let instanced_arrays = extension(Extension::InstancedArrays)?.unchecked_into();
+        // optional, without it only `u8` and `u16` indices can be drawn
+        let _ = extension(Extension::ElementIndexUint);
+
+        Ok(GlContext::WebGl1(WebGl1Context {
+            gl,
+            vertex_array_object,
+            instanced_arrays,
+        }))
+    }
+
+    pub fn version(&self) -> WebGlVersion {
+        match self {
+            GlContext::WebGl1(_) => WebGlVersion::One,
+            GlContext::WebGl2(_) => WebGlVersion::Two,
+        }
+    }
+
+    /// The WebGL2 context, for features that can not be emulated on WebGL1.
+    pub fn webgl2(&self) -> Option<&WebGl2RenderingContext> {
+        match self {
+            GlContext::WebGl1(_) => None,
+            GlContext::WebGl2(gl) => Some(gl),
+        }
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        match self {
+            GlContext::WebGl1(context) => Capabilities::query_webgl1(&context.gl),
+            GlContext::WebGl2(gl) => Capabilities::query(gl),
+        }
+    }
+
+    forward! {
+        pub fn create_buffer(&self) -> Option<WebGlBuffer>;
+        pub fn delete_buffer(&self, buffer: Option<&WebGlBuffer>);
+        pub fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>);
+        pub fn buffer_data_with_u8_array(&self, target: u32, data: &[u8], usage: u32);
+        pub fn buffer_sub_data_with_i32_and_u8_array(&self, target: u32, offset: i32, data: &[u8]);
+
+        pub fn enable_vertex_attrib_array(&self, index: u32);
+        pub fn disable_vertex_attrib_array(&self, index: u32);
+        pub fn vertex_attrib_pointer_with_i32(
+            &self,
+            index: u32,
+            size: i32,
+            type_: u32,
+            normalized: bool,
+            stride: i32,
+            offset: i32
+        );
+
+        pub fn create_shader(&self, type_: u32) -> Option<WebGlShader>;
+        pub fn shader_source(&self, shader: &WebGlShader, source: &str);
+        pub fn compile_shader(&self, shader: &WebGlShader);
+        pub fn get_shader_parameter(&self, shader: &WebGlShader, pname: u32) -> JsValue;
+        pub fn get_shader_info_log(&self, shader: &WebGlShader) -> Option<String>;
+        pub fn create_program(&self) -> Option<WebGlProgram>;
+        pub fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader);
+        pub fn bind_attrib_location(&self, program: &WebGlProgram, index: u32, name: &str);
+        pub fn link_program(&self, program: &WebGlProgram);
+        pub fn get_program_parameter(&self, program: &WebGlProgram, pname: u32) -> JsValue;
+        pub fn get_program_info_log(&self, program: &WebGlProgram) -> Option<String>;
+        pub fn use_program(&self, program: Option<&WebGlProgram>);
+
+        pub fn get_active_uniform(&self, program: &WebGlProgram, index: u32) -> Option<WebGlActiveInfo>;
+        pub fn get_uniform_location(&self, program: &WebGlProgram, name: &str) -> Option<WebGlUniformLocation>;
+        pub fn uniform1fv_with_f32_array(&self, location: Option<&WebGlUniformLocation>, data: &[f32]);
+        pub fn uniform2fv_with_f32_array(&self, location: Option<&WebGlUniformLocation>, data: &[f32]);
+        pub fn uniform3fv_with_f32_array(&self, location: Option<&WebGlUniformLocation>, data: &[f32]);
+        pub fn uniform4fv_with_f32_array(&self, location: Option<&WebGlUniformLocation>, data: &[f32]);
+        pub fn uniform1iv_with_i32_array(&self, location: Option<&WebGlUniformLocation>, data: &[i32]);
+        pub fn uniform2iv_with_i32_array(&self, location: Option<&WebGlUniformLocation>, data: &[i32]);
+        pub fn uniform3iv_with_i32_array(&self, location: Option<&WebGlUniformLocation>, data: &[i32]);
+        pub fn uniform4iv_with_i32_array(&self, location: Option<&WebGlUniformLocation>, data: &[i32]);
+        pub fn uniform_matrix2fv_with_f32_array(
+            &self,
+            location: Option<&WebGlUniformLocation>,
+            transpose: bool,
+            data: &[f32]
+        );
+        pub fn uniform_matrix3fv_with_f32_array(
+            &self,
+            location: Option<&WebGlUniformLocation>,
+            transpose: bool,
+            data: &[f32]
+        );
+        pub fn uniform_matrix4fv_with_f32_array(
+            &self,
+            location: Option<&WebGlUniformLocation>,
+            transpose: bool,
+            data: &[f32]
+        );
+
+        pub fn create_framebuffer(&self) -> Option<WebGlFramebuffer>;
+        pub fn bind_framebuffer(&self, target: u32, framebuffer: Option<&WebGlFramebuffer>);
+
+        pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+        pub fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32);
+        pub fn clear(&self, mask: u32);
+        pub fn draw_arrays(&self, mode: u32, first: i32, count: i32);
+        pub fn draw_elements_with_i32(&self, mode: u32, count: i32, type_: u32, offset: i32);
+        pub fn get_error(&self) -> u32;
+        pub fn get_extension(&self, name: &str) -> Result<Option<js_sys::Object>, JsValue>;
+    }
+
+    /// `createVertexArray()`, or `OES_vertex_array_object` on WebGL1.
+    pub fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        match self {
+            GlContext::WebGl1(context) => context.vertex_array_object.create_vertex_array_oes(),
+            GlContext::WebGl2(gl) => gl.create_vertex_array(),
+        }
+    }
+
+    pub fn bind_vertex_array(&self, vertex_array: Option<&WebGlVertexArrayObject>) {
+        match self {
+            GlContext::WebGl1(context) => context
+                .vertex_array_object
+                .bind_vertex_array_oes(vertex_array),
+            GlContext::WebGl2(gl) => gl.bind_vertex_array(vertex_array),
+        }
+    }
+
+    pub fn delete_vertex_array(&self, vertex_array: Option<&WebGlVertexArrayObject>) {
+        match self {
+            GlContext::WebGl1(context) => context
+                .vertex_array_object
+                .delete_vertex_array_oes(vertex_array),
+            GlContext::WebGl2(gl) => gl.delete_vertex_array(vertex_array),
+        }
+    }
+
+    /// `vertexAttribDivisor()`, or `ANGLE_instanced_arrays` on WebGL1.
+    pub fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        match self {
+            GlContext::WebGl1(context) => context
+                .instanced_arrays
+                .vertex_attrib_divisor_angle(index, divisor),
+            GlContext::WebGl2(gl) => gl.vertex_attrib_divisor(index, divisor),
+        }
+    }
+
+    pub fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        match self {
+            GlContext::WebGl1(context) => context.instanced_arrays.draw_arrays_instanced_angle(
+                mode,
+                first,
+                count,
+                instance_count,
+            ),
+            GlContext::WebGl2(gl) => gl.draw_arrays_instanced(mode, first, count, instance_count),
+        }
+    }
+
+    pub fn draw_elements_instanced_with_i32(
+        &self,
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        match self {
+            GlContext::WebGl1(context) => context
+                .instanced_arrays
+                .draw_elements_instanced_angle_with_i32(mode, count, type_, offset, instance_count),
+            GlContext::WebGl2(gl) => {
+                gl.draw_elements_instanced_with_i32(mode, count, type_, offset, instance_count)
+            }
+        }
+    }
+}
+
+impl From<WebGl2RenderingContext> for GlContext {
+    fn from(gl: WebGl2RenderingContext) -> Self {
+        GlContext::WebGl2(gl)
+    }
+}
@@ -1,10 +1,18 @@
 pub mod buffer;
+pub mod capture;
 pub mod context;
+pub mod debug;
 pub mod framebuffer;
+pub mod gl_context;
+pub mod profiler;
+pub mod readback;
 pub mod renderer;
 pub mod renderer_state;
+pub mod sampler;
 pub mod shader_program;
 pub mod texture;
+pub mod transform_feedback;
+pub mod uniform_block;
 pub mod vertex_array_object;
 
 // re-export webgl_common
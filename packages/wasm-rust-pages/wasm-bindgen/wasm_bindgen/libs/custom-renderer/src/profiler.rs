@@ -0,0 +1,705 @@
+use std::{collections::VecDeque, fmt};
+
+use webgl_common::Extension;
+
+use crate::context::{Context, WebQueryKey};
+
+/// `TIME_ELAPSED_EXT` of `EXT_disjoint_timer_query_webgl2`.
+pub const TIME_ELAPSED_EXT: u32 = 0x88BF;
+/// `GPU_DISJOINT_EXT` of `EXT_disjoint_timer_query_webgl2`.
+pub const GPU_DISJOINT_EXT: u32 = 0x8FBB;
+
+/// Frames whose GPU timings are still waiting for results. Older frames are resolved without them.
+const MAX_PENDING_FRAMES: usize = 8;
+
+///
+/// The timer queries and the CPU clock a [Profiler] measures with.
+/// Implemented for [ContextTimer], and by a mock in tests.
+///
+pub trait TimerBackend {
+    type Query;
+
+    /// A new timer query, `None` when GPU timing is not supported.
+    fn create_query(&mut self) -> Option<Self::Query>;
+    fn delete_query(&mut self, query: Self::Query);
+    fn begin_query(&mut self, query: &Self::Query);
+    fn end_query(&mut self);
+    /// Elapsed nanoseconds, `None` while the GPU has not finished with the query.
+    fn query_result(&mut self, query: &Self::Query) -> Option<u64>;
+    /// Whether a disjoint operation (e.g. a clock change) made the running queries unreliable.
+    fn disjoint(&mut self) -> bool;
+    /// CPU time in milliseconds.
+    fn now(&self) -> f64;
+}
+
+///
+/// Times with `EXT_disjoint_timer_query_webgl2` on a [Context] and with `performance.now()`.
+/// Without the extension only CPU times are measured.
+///
+pub struct ContextTimer<'a> {
+    context: &'a Context,
+    supported: bool,
+}
+
+impl<'a> ContextTimer<'a> {
+    pub fn new(context: &'a Context) -> Self {
+        let supported = context
+            .gl()
+            .get_extension(Extension::DisjointTimerQuery.name())
+            .ok()
+            .flatten()
+            .is_some();
+        Self { context, supported }
+    }
+
+    pub fn supported(&self) -> bool {
+        self.supported
+    }
+}
+
+impl<'a> TimerBackend for ContextTimer<'a> {
+    type Query = WebQueryKey;
+
+    fn create_query(&mut self) -> Option<WebQueryKey> {
+        match self.supported {
+            true => self.context.create_query().ok(),
+            false => None,
+        }
+    }
+
+    fn delete_query(&mut self, query: WebQueryKey) {
+        self.context.delete_query(query);
+    }
+
+    fn begin_query(&mut self, query: &WebQueryKey) {
+        self.context.begin_query(TIME_ELAPSED_EXT, *query);
+    }
+
+    fn end_query(&mut self) {
+        self.context.end_query(TIME_ELAPSED_EXT);
+    }
+
+    fn query_result(&mut self, query: &WebQueryKey) -> Option<u64> {
+        self.context.query_result(*query)
+    }
+
+    fn disjoint(&mut self) -> bool {
+        self.supported
+            && self
+                .context
+                .gl()
+                .get_parameter(GPU_DISJOINT_EXT)
+                .ok()
+                .and_then(|disjoint| disjoint.as_bool())
+                .unwrap_or(false)
+    }
+
+    fn now(&self) -> f64 {
+        web_sys::window()
+            .and_then(|window| window.performance())
+            .map(|performance| performance.now())
+            .unwrap_or(0.0)
+    }
+}
+
+///
+/// A fixed-capacity queue that drops its oldest item when full.
+///
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "ring buffer capacity must not be zero");
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds `item`, returning the oldest item when it had to make room.
+    pub fn push(&mut self, item: T) -> Option<T> {
+        let evicted = match self.items.len() == self.capacity {
+            true => self.items.pop_front(),
+            false => None,
+        };
+        self.items.push_back(item);
+        evicted
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn latest(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    /// Oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.items.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+/// A named scope of a [FrameProfile] and the scopes nested in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileScope {
+    pub name: String,
+    pub cpu_ms: f64,
+    /// `None` without timer queries, or when the GPU reported a disjoint operation.
+    pub gpu_ms: Option<f64>,
+    pub children: Vec<ProfileScope>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameProfile {
+    pub frame: u64,
+    pub cpu_ms: f64,
+    /// The GPU time of all top level scopes.
+    pub gpu_ms: Option<f64>,
+    pub scopes: Vec<ProfileScope>,
+}
+
+impl FrameProfile {
+    /// The scope at `path`, e.g. `["main", "opaque"]`.
+    pub fn scope(&self, path: &[&str]) -> Option<&ProfileScope> {
+        let (first, rest) = path.split_first()?;
+        let mut scope = self.scopes.iter().find(|scope| scope.name == *first)?;
+        for name in rest {
+            scope = scope.children.iter().find(|child| child.name == *name)?;
+        }
+        Some(scope)
+    }
+}
+
+impl fmt::Display for FrameProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn timing(f: &mut fmt::Formatter<'_>, cpu_ms: f64, gpu_ms: Option<f64>) -> fmt::Result {
+            write!(f, "cpu {:.2} ms", cpu_ms)?;
+            match gpu_ms {
+                Some(gpu_ms) => writeln!(f, ", gpu {:.2} ms", gpu_ms),
+                None => writeln!(f),
+            }
+        }
+
+        fn tree(f: &mut fmt::Formatter<'_>, scopes: &[ProfileScope], depth: usize) -> fmt::Result {
+            for scope in scopes {
+                write!(f, "{:indent$}{}: ", "", scope.name, indent = depth * 2)?;
+                timing(f, scope.cpu_ms, scope.gpu_ms)?;
+                tree(f, &scope.children, depth + 1)?;
+            }
+            Ok(())
+        }
+
+        write!(f, "frame {}: ", self.frame)?;
+        timing(f, self.cpu_ms, self.gpu_ms)?;
+        tree(f, &self.scopes, 1)
+    }
+}
+
+/// Timings of one scope over the frames in a [Profiler]'s history.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScopeStats {
+    /// Frames the scope appeared in.
+    pub frames: usize,
+    pub cpu_ms: f64,
+    pub max_cpu_ms: f64,
+    /// Average over the frames with a GPU time.
+    pub gpu_ms: Option<f64>,
+    pub max_gpu_ms: Option<f64>,
+}
+
+struct Node {
+    name: String,
+    parent: Option<usize>,
+    start: f64,
+    cpu_ms: f64,
+}
+
+/// A timer query that ran while the scopes in `open` were open.
+struct Segment<Q> {
+    query: Q,
+    open: Vec<usize>,
+}
+
+struct PendingFrame<Q> {
+    frame: u64,
+    cpu_ms: f64,
+    nodes: Vec<Node>,
+    segments: Vec<Segment<Q>>,
+    disjoint: bool,
+}
+
+impl<Q> PendingFrame<Q> {
+    /// `gpu` holds the nanoseconds of each segment.
+    fn resolve(&self, gpu: Option<&[u64]>) -> FrameProfile {
+        let scopes = self.scopes(None, gpu);
+        FrameProfile {
+            frame: self.frame,
+            cpu_ms: self.cpu_ms,
+            gpu_ms: gpu.and_then(|_| scopes.iter().map(|scope| scope.gpu_ms).sum()),
+            scopes,
+        }
+    }
+
+    fn scopes(&self, parent: Option<usize>, gpu: Option<&[u64]>) -> Vec<ProfileScope> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent == parent)
+            .map(|(index, node)| ProfileScope {
+                name: node.name.clone(),
+                cpu_ms: node.cpu_ms,
+                gpu_ms: self.gpu_ms(index, gpu),
+                children: self.scopes(Some(index), gpu),
+            })
+            .collect()
+    }
+
+    /// The sum of the segments that ran while the scope was open, including those of nested scopes.
+    fn gpu_ms(&self, node: usize, gpu: Option<&[u64]>) -> Option<f64> {
+        let mut segments = self
+            .segments
+            .iter()
+            .zip(gpu?)
+            .filter(|(segment, _)| segment.open.contains(&node))
+            .peekable();
+        segments.peek()?;
+        Some(segments.map(|(_, ns)| *ns as f64 / 1_000_000.0).sum())
+    }
+}
+
+///
+/// # Frame profiler
+///
+/// Measures named, nested scopes on the CPU and, with timer queries, on the GPU.
+///
+/// WebGL allows only one `TIME_ELAPSED_EXT` query at a time, so every scope boundary ends the running
+/// query and starts a new one; a scope's GPU time is the sum of the queries that ran while it was open.
+/// Query results arrive a few frames later, [Profiler::end_frame] polls them
+/// and adds the finished frames to a ring buffer of recent [FrameProfile]s.
+///
+/// ```ignore
+/// let mut profiler = Profiler::new(ContextTimer::new(&context), 120);
+/// profiler.begin_frame();
+/// profiler.scope("shadows", |_| draw_shadows());
+/// profiler.scope("main", |profiler| {
+///     profiler.scope("opaque", |_| draw_opaque());
+///     profiler.scope("transparent", |_| draw_transparent());
+/// });
+/// profiler.end_frame();
+/// ```
+///
+pub struct Profiler<B: TimerBackend> {
+    backend: B,
+    frame: u64,
+    frame_start: f64,
+    nodes: Vec<Node>,
+    open: Vec<usize>,
+    segments: Vec<Segment<B::Query>>,
+    active: Option<B::Query>,
+    free: Vec<B::Query>,
+    pending: VecDeque<PendingFrame<B::Query>>,
+    history: RingBuffer<FrameProfile>,
+}
+
+impl<B: TimerBackend> Profiler<B> {
+    /// Keeps the profiles of the last `history` frames.
+    pub fn new(backend: B, history: usize) -> Self {
+        Self {
+            frame_start: backend.now(),
+            backend,
+            frame: 0,
+            nodes: Vec::new(),
+            open: Vec::new(),
+            segments: Vec::new(),
+            active: None,
+            free: Vec::new(),
+            pending: VecDeque::new(),
+            history: RingBuffer::new(history),
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// The number of the frame being recorded.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = self.backend.now();
+        self.nodes.clear();
+        self.open.clear();
+    }
+
+    pub fn begin_scope(&mut self, name: &str) {
+        self.end_segment();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: self.open.last().copied(),
+            start: self.backend.now(),
+            cpu_ms: 0.0,
+        });
+        self.open.push(self.nodes.len() - 1);
+        self.begin_segment();
+    }
+
+    /// Ends the innermost open scope.
+    pub fn end_scope(&mut self) {
+        self.end_segment();
+        if let Some(index) = self.open.pop() {
+            let node = &mut self.nodes[index];
+            node.cpu_ms = self.backend.now() - node.start;
+        }
+        self.begin_segment();
+    }
+
+    /// Runs `f` inside a scope called `name`.
+    pub fn scope<R>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.begin_scope(name);
+        let result = f(self);
+        self.end_scope();
+        result
+    }
+
+    /// Ends the scopes left open, queues the frame for its GPU timings and polls the queued frames.
+    pub fn end_frame(&mut self) {
+        while !self.open.is_empty() {
+            self.end_scope();
+        }
+        self.pending.push_back(PendingFrame {
+            frame: self.frame,
+            cpu_ms: self.backend.now() - self.frame_start,
+            nodes: std::mem::take(&mut self.nodes),
+            segments: std::mem::take(&mut self.segments),
+            disjoint: false,
+        });
+        self.frame += 1;
+        self.poll();
+    }
+
+    /// Moves the frames whose timer queries have finished into the history, oldest first.
+    pub fn poll(&mut self) {
+        if self.backend.disjoint() {
+            for frame in &mut self.pending {
+                frame.disjoint = true;
+            }
+        }
+
+        while let Some(frame) = self.pending.front() {
+            let gpu = frame
+                .segments
+                .iter()
+                .map(|segment| self.backend.query_result(&segment.query))
+                .collect::<Option<Vec<u64>>>()
+                .filter(|_| !frame.disjoint);
+            if gpu.is_none() && !frame.disjoint && self.pending.len() <= MAX_PENDING_FRAMES {
+                break;
+            }
+
+            let frame = self.pending.pop_front().unwrap();
+            self.history.push(frame.resolve(gpu.as_deref()));
+            self.free
+                .extend(frame.segments.into_iter().map(|segment| segment.query));
+        }
+    }
+
+    pub fn history(&self) -> &RingBuffer<FrameProfile> {
+        &self.history
+    }
+
+    /// The most recent frame with resolved timings.
+    pub fn latest(&self) -> Option<&FrameProfile> {
+        self.history.latest()
+    }
+
+    /// Average and worst timings of the scope at `path` over the history.
+    pub fn stats(&self, path: &[&str]) -> Option<ScopeStats> {
+        let scopes = self
+            .history
+            .iter()
+            .filter_map(|frame| frame.scope(path))
+            .collect::<Vec<_>>();
+        if scopes.is_empty() {
+            return None;
+        }
+
+        let cpu = scopes.iter().map(|scope| scope.cpu_ms);
+        let gpu = scopes
+            .iter()
+            .filter_map(|scope| scope.gpu_ms)
+            .collect::<Vec<_>>();
+        Some(ScopeStats {
+            frames: scopes.len(),
+            cpu_ms: cpu.clone().sum::<f64>() / scopes.len() as f64,
+            max_cpu_ms: cpu.fold(0.0, f64::max),
+            gpu_ms: (!gpu.is_empty()).then(|| gpu.iter().sum::<f64>() / gpu.len() as f64),
+            max_gpu_ms: (!gpu.is_empty()).then(|| gpu.iter().copied().fold(0.0, f64::max)),
+        })
+    }
+
+    fn begin_segment(&mut self) {
+        if self.open.is_empty() {
+            return;
+        }
+        let query = self.free.pop().or_else(|| self.backend.create_query());
+        if let Some(query) = query {
+            self.backend.begin_query(&query);
+            self.active = Some(query);
+        }
+    }
+
+    fn end_segment(&mut self) {
+        if let Some(query) = self.active.take() {
+            self.backend.end_query();
+            self.segments.push(Segment {
+                query,
+                open: self.open.clone(),
+            });
+        }
+    }
+}
+
+impl<B: TimerBackend> Drop for Profiler<B> {
+    fn drop(&mut self) {
+        if self.active.is_some() {
+            self.backend.end_query();
+        }
+        let pending = self
+            .pending
+            .drain(..)
+            .flat_map(|frame| frame.segments)
+            .chain(self.segments.drain(..))
+            .map(|segment| segment.query);
+        let queries = pending
+            .chain(self.active.take())
+            .chain(self.free.drain(..))
+            .collect::<Vec<_>>();
+        for query in queries {
+            self.backend.delete_query(query);
+        }
+    }
+}
+
+/// Timer queries that finish when the test calls `flush()`.
+#[cfg(test)]
+#[derive(Default)]
+struct MockTimer {
+    unsupported: bool,
+    cpu_ms: f64,
+    gpu_ns: u64,
+    next_query: u32,
+    live_queries: usize,
+    active: Option<(u32, u64)>,
+    finished: Vec<(u32, u64)>,
+    available: std::collections::HashMap<u32, u64>,
+    disjoint: bool,
+}
+
+#[cfg(test)]
+impl MockTimer {
+    fn advance(&mut self, cpu_ms: f64, gpu_ns: u64) {
+        self.cpu_ms += cpu_ms;
+        self.gpu_ns += gpu_ns;
+    }
+
+    fn flush(&mut self) {
+        self.available.extend(self.finished.drain(..));
+    }
+}
+
+#[cfg(test)]
+impl TimerBackend for MockTimer {
+    type Query = u32;
+
+    fn create_query(&mut self) -> Option<u32> {
+        if self.unsupported {
+            return None;
+        }
+        self.next_query += 1;
+        self.live_queries += 1;
+        Some(self.next_query)
+    }
+
+    fn delete_query(&mut self, _query: u32) {
+        self.live_queries -= 1;
+    }
+
+    fn begin_query(&mut self, query: &u32) {
+        assert!(self.active.is_none(), "nested timer queries");
+        self.available.remove(query);
+        self.active = Some((*query, self.gpu_ns));
+    }
+
+    fn end_query(&mut self) {
+        let (query, start) = self.active.take().unwrap();
+        self.finished.push((query, self.gpu_ns - start));
+    }
+
+    fn query_result(&mut self, query: &u32) -> Option<u64> {
+        self.available.get(query).copied()
+    }
+
+    fn disjoint(&mut self) -> bool {
+        std::mem::take(&mut self.disjoint)
+    }
+
+    fn now(&self) -> f64 {
+        self.cpu_ms
+    }
+}
+
+#[cfg(test)]
+fn profile_frame(profiler: &mut Profiler<MockTimer>) {
+    profiler.begin_frame();
+    profiler.scope("shadows", |profiler| {
+        profiler.backend_mut().advance(1.0, 2_000_000)
+    });
+    profiler.scope("main", |profiler| {
+        profiler.backend_mut().advance(0.5, 1_000_000);
+        profiler.scope("opaque", |profiler| {
+            profiler.backend_mut().advance(2.0, 3_000_000)
+        });
+        profiler.scope("transparent", |profiler| {
+            profiler.backend_mut().advance(1.0, 500_000)
+        });
+    });
+    profiler.end_frame();
+}
+
+#[test]
+fn ring_buffer() {
+    let mut ring = RingBuffer::new(3);
+    assert!(ring.is_empty());
+    assert_eq!(ring.push(1), None);
+    assert_eq!(ring.push(2), None);
+    assert_eq!(ring.push(3), None);
+    assert_eq!(ring.push(4), Some(1));
+    assert_eq!(ring.push(5), Some(2));
+    assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![3, 4, 5]);
+    assert_eq!(ring.latest(), Some(&5));
+    assert_eq!((ring.len(), ring.capacity()), (3, 3));
+    ring.clear();
+    assert_eq!(ring.latest(), None);
+}
+
+#[test]
+fn profiler_nested_scopes() {
+    let mut profiler = Profiler::new(MockTimer::default(), 4);
+    profile_frame(&mut profiler);
+
+    // the queries have not finished yet
+    assert_eq!(profiler.latest(), None);
+    profiler.backend_mut().flush();
+    profiler.poll();
+
+    let frame = profiler.latest().unwrap();
+    assert_eq!(frame.frame, 0);
+    assert_eq!(frame.cpu_ms, 4.5);
+    assert_eq!(frame.gpu_ms, Some(6.5));
+
+    let timing = |path: &[&str]| {
+        let scope = frame.scope(path).unwrap();
+        (scope.cpu_ms, scope.gpu_ms)
+    };
+    assert_eq!(timing(&["shadows"]), (1.0, Some(2.0)));
+    assert_eq!(timing(&["main"]), (3.5, Some(4.5)));
+    assert_eq!(timing(&["main", "opaque"]), (2.0, Some(3.0)));
+    assert_eq!(timing(&["main", "transparent"]), (1.0, Some(0.5)));
+    assert_eq!(frame.scope(&["opaque"]), None);
+
+    assert_eq!(
+        frame.to_string(),
+        "frame 0: cpu 4.50 ms, gpu 6.50 ms\n  shadows: cpu 1.00 ms, gpu 2.00 ms\n  main: cpu 3.50 ms, gpu 4.50 ms\n    opaque: cpu 2.00 ms, gpu 3.00 ms\n    transparent: cpu 1.00 ms, gpu 0.50 ms\n"
+    );
+}
+
+#[test]
+fn profiler_resolves_frames_later() {
+    let mut profiler = Profiler::new(MockTimer::default(), 4);
+    let mut queries_per_frame = 0;
+    for frame in 0..10 {
+        profile_frame(&mut profiler);
+        let backend = profiler.backend_mut();
+        if frame == 0 {
+            queries_per_frame = backend.finished.len();
+        }
+        // results arrive two frames late
+        let ready = backend.finished.len().saturating_sub(2 * queries_per_frame);
+        let finished = backend.finished.drain(..ready).collect::<Vec<_>>();
+        backend.available.extend(finished);
+    }
+    profiler.poll();
+
+    let frames = profiler
+        .history()
+        .iter()
+        .map(|frame| frame.frame)
+        .collect::<Vec<_>>();
+    assert!(frames.windows(2).all(|pair| pair[0] + 1 == pair[1]));
+    assert_eq!(profiler.history().len(), 4);
+    // queries are reused instead of created every frame
+    assert!(profiler.backend().live_queries < 30);
+
+    let stats = profiler.stats(&["main", "opaque"]).unwrap();
+    assert_eq!(stats.frames, 4);
+    assert_eq!((stats.cpu_ms, stats.max_cpu_ms), (2.0, 2.0));
+    assert_eq!((stats.gpu_ms, stats.max_gpu_ms), (Some(3.0), Some(3.0)));
+    assert_eq!(profiler.stats(&["missing"]), None);
+}
+
+#[test]
+fn profiler_without_gpu_timings() {
+    // disjoint operations invalidate the GPU timings of the frames in flight
+    let mut profiler = Profiler::new(MockTimer::default(), 4);
+    profile_frame(&mut profiler);
+    profiler.backend_mut().disjoint = true;
+    profiler.poll();
+    let frame = profiler.latest().unwrap();
+    assert_eq!(frame.cpu_ms, 4.5);
+    assert_eq!(frame.gpu_ms, None);
+    assert_eq!(frame.scope(&["main"]).unwrap().gpu_ms, None);
+
+    // no timer queries: frames resolve immediately with CPU times only
+    let mut profiler = Profiler::new(
+        MockTimer {
+            unsupported: true,
+            ..MockTimer::default()
+        },
+        4,
+    );
+    profile_frame(&mut profiler);
+    let frame = profiler.latest().unwrap();
+    assert_eq!(frame.scope(&["main", "opaque"]).unwrap().cpu_ms, 2.0);
+    assert_eq!(frame.gpu_ms, None);
+
+    // results that never arrive are given up on
+    let mut profiler = Profiler::new(MockTimer::default(), 4);
+    for _ in 0..MAX_PENDING_FRAMES + 2 {
+        profile_frame(&mut profiler);
+    }
+    assert_eq!(profiler.history().len(), 2);
+    assert_eq!(profiler.latest().unwrap().gpu_ms, None);
+}
@@ -0,0 +1,318 @@
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+use webgl_common::{BufferTarget, CastError, GpuPod, TextureDataType, TextureFormat};
+
+use crate::{
+    capture::Command,
+    context::{Context, WebBufferKey, WebFenceKey},
+};
+
+///
+/// The bytes per pixel `readPixels()` writes for `format` and `type_`,
+/// `None` for combinations it can not read.
+///
+pub fn bytes_per_pixel(format: TextureFormat, type_: TextureDataType) -> Option<usize> {
+    let components = match format {
+        TextureFormat::Red | TextureFormat::RedInteger | TextureFormat::Luminance => 1,
+        TextureFormat::Rg | TextureFormat::RgInteger | TextureFormat::LuminanceAlpha => 2,
+        TextureFormat::Rgb | TextureFormat::RgbInteger => 3,
+        TextureFormat::Rgba | TextureFormat::RgbaInteger => 4,
+        TextureFormat::DepthComponent | TextureFormat::DepthStencil => return None,
+    };
+
+    match type_ {
+        TextureDataType::UnsignedByte | TextureDataType::Byte => Some(components),
+        TextureDataType::Short | TextureDataType::UnsignedShort | TextureDataType::HalfFloat => {
+            Some(components * 2)
+        }
+        TextureDataType::Int | TextureDataType::UnsignedInt | TextureDataType::Float => {
+            Some(components * 4)
+        }
+        TextureDataType::UnsignedShort4444 | TextureDataType::UnsignedShort5551 => {
+            (format == TextureFormat::Rgba).then_some(2)
+        }
+        TextureDataType::UnsignedShort565 => (format == TextureFormat::Rgb).then_some(2),
+    }
+}
+
+///
+/// Flips rows of `row_bytes` bytes in place.
+/// `readPixels()` returns the bottom row first, images are stored top row first.
+///
+pub fn flip_rows(data: &mut [u8], row_bytes: usize) {
+    if row_bytes == 0 {
+        return;
+    }
+    let rows = data.len() / row_bytes;
+    for row in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - row - 1) * row_bytes);
+        top[row * row_bytes..(row + 1) * row_bytes].swap_with_slice(&mut bottom[..row_bytes]);
+    }
+}
+
+/// Whether a `clientWaitSync()` status means the fence was signaled.
+fn fence_signaled(status: u32) -> Result<bool, String> {
+    match status {
+        WebGl2RenderingContext::ALREADY_SIGNALED | WebGl2RenderingContext::CONDITION_SATISFIED => {
+            Ok(true)
+        }
+        WebGl2RenderingContext::TIMEOUT_EXPIRED => Ok(false),
+        WebGl2RenderingContext::WAIT_FAILED => Err("clientWaitSync failed".to_string()),
+        status => Err(format!("unknown clientWaitSync status {:#x}", status)),
+    }
+}
+
+///
+/// Pixels on their way from the framebuffer into a pixel pack buffer, see [Context::read_pixels_to_buffer].
+///
+/// Poll [PendingReadback::is_ready] once per frame and [PendingReadback::read] the pixels when it is,
+/// or call [PendingReadback::cancel] to free the buffer and fence.
+///
+#[derive(Debug)]
+pub struct PendingReadback {
+    buffer: WebBufferKey,
+    fence: WebFenceKey,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row, rows are tightly packed (`PACK_ALIGNMENT` is 1).
+    pub row_bytes: usize,
+}
+
+impl PendingReadback {
+    pub fn size(&self) -> usize {
+        self.row_bytes * self.height as usize
+    }
+
+    /// Whether the GPU has written the pixels. Never blocks.
+    pub fn is_ready(&self, context: &Context) -> Result<bool, String> {
+        fence_signaled(context.client_wait_sync(self.fence))
+    }
+
+    /// Resolves once the GPU has written the pixels, checking once per animation frame.
+    pub async fn wait(&self, context: &Context) -> Result<(), String> {
+        while !self.is_ready(context)? {
+            next_frame().await?;
+        }
+        Ok(())
+    }
+
+    /// The pixels, bottom row first. Stalls when the readback is not [ready](PendingReadback::is_ready) yet.
+    pub fn read(self, context: &Context) -> Vec<u8> {
+        let mut data = vec![0; self.size()];
+        self.read_into(context, &mut data);
+        data
+    }
+
+    /// The pixels as `T`, e.g. `f32` for `FLOAT` or `[u8; 4]` for `RGBA` / `UNSIGNED_BYTE`.
+    pub fn read_as<T: GpuPod>(self, context: &Context) -> Result<Vec<T>, CastError> {
+        let size = std::mem::size_of::<T>();
+        if size == 0 || !self.size().is_multiple_of(size) {
+            let len = self.size();
+            self.cancel(context);
+            return Err(CastError::SizeMismatch { len, size });
+        }
+
+        let bytes = self.read(context);
+        Ok(bytes
+            .chunks_exact(size)
+            // SAFETY: the chunk is `size_of::<T>()` bytes and any bit pattern is a valid `T: GpuPod`
+            .map(|chunk| unsafe { chunk.as_ptr().cast::<T>().read_unaligned() })
+            .collect())
+    }
+
+    pub fn cancel(self, context: &Context) {
+        context.delete_fence(self.fence);
+        context.delete_buffer(self.buffer);
+    }
+
+    fn read_into(self, context: &Context, data: &mut [u8]) {
+        context.bind_buffer(BufferTarget::PixelPackBuffer, self.buffer);
+        context.get_buffer_sub_data(BufferTarget::PixelPackBuffer, 0, data);
+        context.unbind_buffer(BufferTarget::PixelPackBuffer);
+        self.cancel(context);
+    }
+}
+
+/// Resolves on the next `requestAnimationFrame()`, or right away without a window.
+async fn next_frame() -> Result<(), String> {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let requested = web_sys::window()
+            .map(|window| window.request_animation_frame(&resolve).is_ok())
+            .unwrap_or(false);
+        if !requested {
+            let _ = resolve.call0(&JsValue::NULL);
+        }
+    });
+    wasm_bindgen_futures::JsFuture::from(promise)
+        .await
+        .map(|_| ())
+        .map_err(|error| format!("{:?}", error))
+}
+
+impl Context {
+    ///
+    /// Starts reading a rectangle of the read framebuffer into a pixel pack buffer, without waiting for the GPU.
+    /// A fence after the `readPixels()` tells when the pixels are in the buffer.
+    ///
+    pub fn read_pixels_to_buffer(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        type_: TextureDataType,
+    ) -> Result<PendingReadback, String> {
+        let bytes_per_pixel = bytes_per_pixel(format, type_)
+            .ok_or_else(|| format!("can not read {:?} pixels as {:?}", format, type_))?;
+        let row_bytes = width as usize * bytes_per_pixel;
+        let size = row_bytes * height as usize;
+
+        let gl = self.gl();
+        let buffer = self.create_buffer()?;
+        self.bind_buffer(BufferTarget::PixelPackBuffer, buffer);
+        self.record(|| Command::BufferDataSize {
+            target: WebGl2RenderingContext::PIXEL_PACK_BUFFER,
+            size: size as i32,
+            usage: WebGl2RenderingContext::STREAM_READ,
+        });
+        gl.buffer_data_with_i32(
+            WebGl2RenderingContext::PIXEL_PACK_BUFFER,
+            size as i32,
+            WebGl2RenderingContext::STREAM_READ,
+        );
+        self.record(|| Command::ReadPixels {
+            x,
+            y,
+            width: width as i32,
+            height: height as i32,
+            format: format.into(),
+            type_: type_.into(),
+        });
+        let read = gl.read_pixels_with_i32(
+            x,
+            y,
+            width as i32,
+            height as i32,
+            format.into(),
+            type_.into(),
+            0,
+        );
+        self.unbind_buffer(BufferTarget::PixelPackBuffer);
+
+        let fence = read
+            .map_err(|error| format!("readPixels failed: {:?}", error))
+            .and_then(|_| self.fence_sync());
+        let fence = match fence {
+            Ok(fence) => fence,
+            Err(error) => {
+                self.delete_buffer(buffer);
+                return Err(error);
+            }
+        };
+        // make sure the fence gets to the GPU, or it may never be signaled
+        gl.flush();
+
+        Ok(PendingReadback {
+            buffer,
+            fence,
+            width,
+            height,
+            row_bytes,
+        })
+    }
+
+    ///
+    /// Reads a rectangle of the read framebuffer without stalling the pipeline, e.g. for picking or screenshots.
+    /// The pixels are bottom row first, see [flip_rows].
+    ///
+    pub async fn read_pixels_async(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        type_: TextureDataType,
+    ) -> Result<Vec<u8>, String> {
+        let readback = self.read_pixels_to_buffer(x, y, width, height, format, type_)?;
+        if let Err(error) = readback.wait(self).await {
+            readback.cancel(self);
+            return Err(error);
+        }
+        Ok(readback.read(self))
+    }
+
+    /// [Context::read_pixels_async] as `T`, see [PendingReadback::read_as].
+    pub async fn read_pixels_async_as<T: GpuPod>(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        type_: TextureDataType,
+    ) -> Result<Vec<T>, String> {
+        let readback = self.read_pixels_to_buffer(x, y, width, height, format, type_)?;
+        if let Err(error) = readback.wait(self).await {
+            readback.cancel(self);
+            return Err(error);
+        }
+        readback
+            .read_as(self)
+            .map_err(|error| format!("{:?}", error))
+    }
+}
+
+#[test]
+fn readback_pixel_sizes() {
+    assert_eq!(
+        bytes_per_pixel(TextureFormat::Rgba, TextureDataType::UnsignedByte),
+        Some(4)
+    );
+    assert_eq!(
+        bytes_per_pixel(TextureFormat::Rgba, TextureDataType::Float),
+        Some(16)
+    );
+    assert_eq!(
+        bytes_per_pixel(TextureFormat::RgInteger, TextureDataType::UnsignedInt),
+        Some(8)
+    );
+    assert_eq!(
+        bytes_per_pixel(TextureFormat::Rgb, TextureDataType::UnsignedShort565),
+        Some(2)
+    );
+    assert_eq!(
+        bytes_per_pixel(TextureFormat::Rgba, TextureDataType::UnsignedShort565),
+        None
+    );
+    assert_eq!(
+        bytes_per_pixel(TextureFormat::DepthComponent, TextureDataType::Float),
+        None
+    );
+
+    assert_eq!(
+        fence_signaled(WebGl2RenderingContext::ALREADY_SIGNALED),
+        Ok(true)
+    );
+    assert_eq!(
+        fence_signaled(WebGl2RenderingContext::CONDITION_SATISFIED),
+        Ok(true)
+    );
+    assert_eq!(
+        fence_signaled(WebGl2RenderingContext::TIMEOUT_EXPIRED),
+        Ok(false)
+    );
+    assert!(fence_signaled(WebGl2RenderingContext::WAIT_FAILED).is_err());
+}
+
+#[test]
+fn readback_flip_rows() {
+    let mut pixels = vec![1, 1, 2, 2, 3, 3];
+    flip_rows(&mut pixels, 2);
+    assert_eq!(pixels, vec![3, 3, 2, 2, 1, 1]);
+
+    let mut pixels = vec![1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4];
+    flip_rows(&mut pixels, 3);
+    assert_eq!(pixels, vec![4, 4, 4, 3, 3, 3, 2, 2, 2, 1, 1, 1]);
+}
@@ -1,27 +1,41 @@
-use std::{collections::HashMap, mem::swap};
+use std::{collections::HashMap, mem::swap, ops::Range};
 
 use crate::{
-    buffer::Buffer,
-    renderer_state::RendererState,
+    buffer::{element_ranges, Buffer, IndexBuffer},
+    gl_context::GlContext,
+    renderer_state::{RasterizerDiscard, RendererState},
+    sampler::texture_unit_index,
     shader_program::{create_shader_program, ShaderProgram},
     vertex_array_object::VertexArrayObject,
 };
-use web_sys::{console, WebGl2RenderingContext, WebGlProgram};
+use wasm_bindgen::JsCast;
+use web_sys::{console, WebGl2RenderingContext, WebGlProgram, WebGlSampler, WebglMultiDraw};
 use webgl_common::{
-    get_error, slice_as_u8_slice, BufferTarget, BufferUsage, Color, DataType, DrawMode, ErrorType,
-    Mask, Viewport,
+    get_error, slice_as_u8_slice, BufferTarget, BufferUsage, Capabilities, Color, DataType,
+    DrawMode, ErrorType, Extension, GpuPod, Mask, TextureUnit, Viewport,
 };
 
-/// The WebGL2 renderer.
+/// The renderer, on WebGL2 or the WebGL1 fallback.
 #[derive(Debug, Clone)]
 pub struct Renderer<'a> {
-    pub gl: web_sys::WebGl2RenderingContext,
+    pub gl: GlContext,
     pub state: RendererState<'a>,
+    pub capabilities: Capabilities,
+    /// `WEBGL_multi_draw`, when the context supports it.
+    multi_draw: Option<WebglMultiDraw>,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(gl: WebGl2RenderingContext) -> Self {
+    pub fn new(gl: impl Into<GlContext>) -> Self {
+        let gl = gl.into();
+        let multi_draw = gl
+            .get_extension(Extension::MultiDraw.name())
+            .ok()
+            .flatten()
+            .map(|extension| extension.unchecked_into());
         Self {
+            capabilities: gl.capabilities(),
+            multi_draw,
             gl,
             state: RendererState::default(),
         }
@@ -41,7 +55,17 @@ impl<'a> Renderer<'a> {
         vert_src: &str,
         frag_src: &str,
     ) -> Result<WebGlProgram, String> {
-        create_shader_program(&self.gl, vert_src, frag_src)
+        create_shader_program(&self.gl, vert_src, frag_src, &[])
+    }
+
+    /// Like [Renderer::create_shader_program], with attribute locations for shaders without `layout(location = N)`.
+    pub fn create_shader_program_with_attributes(
+        &self,
+        vert_src: &str,
+        frag_src: &str,
+        attributes: &[(&str, u32)],
+    ) -> Result<WebGlProgram, String> {
+        create_shader_program(&self.gl, vert_src, frag_src, attributes)
     }
 
     /// The `useProgram()` method.
@@ -59,7 +83,7 @@ impl<'a> Renderer<'a> {
         buffer.bind(BufferTarget::ArrayBuffer);
     }
 
-    pub fn set_array_buffer_data<T>(
+    pub fn set_array_buffer_data<T: GpuPod>(
         &mut self,
         data: &[T],
         mut buffer: Buffer<'a>,
@@ -83,9 +107,12 @@ impl<'a> Renderer<'a> {
     /// The `bindBufferBase()` method.
     ///
     /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bindBufferBase)
+    ///
+    /// WebGL2 only, use a [UniformBlock](crate::uniform_block::UniformBlock) to fall back to plain uniforms on WebGL1.
     pub fn bind_buffer_base(&self, target: BufferTarget, index: u32, buffer: &Option<Buffer>) {
-        self.gl
-            .bind_buffer_base(target.into(), index, buffer.as_ref().map(|v| &v.buffer));
+        if let Some(gl) = self.gl.webgl2() {
+            gl.bind_buffer_base(target.into(), index, buffer.as_ref().map(|v| &v.buffer));
+        }
     }
 
     /// The `bindVertexArray()` method.
@@ -99,7 +126,7 @@ impl<'a> Renderer<'a> {
     /// The `bufferData()` method.
     ///
     /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bufferData)
-    pub fn buffer_data<T>(&self, target: BufferTarget, data: &[T], usage: BufferUsage) {
+    pub fn buffer_data<T: GpuPod>(&self, target: BufferTarget, data: &[T], usage: BufferUsage) {
         self.gl
             .buffer_data_with_u8_array(target.into(), slice_as_u8_slice(data), usage.into());
     }
@@ -163,6 +190,197 @@ impl<'a> Renderer<'a> {
         self.gl.draw_arrays(mode.into(), first, count);
     }
 
+    /// The `drawArraysInstanced()` method.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/drawArraysInstanced)
+    pub fn draw_arrays_instanced(&self, mode: DrawMode, first: i32, count: i32, instances: i32) {
+        self.gl
+            .draw_arrays_instanced(mode.into(), first, count, instances);
+    }
+
+    /// The `drawElements()` method, drawing `count` indices from `first` on.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/drawElements)
+    pub fn draw_elements(&self, mode: DrawMode, indices: &IndexBuffer, first: i32, count: i32) {
+        indices.bind();
+        self.gl.draw_elements_with_i32(
+            mode.into(),
+            count,
+            indices.index_type().into(),
+            indices.offset(first),
+        );
+    }
+
+    /// The `drawElementsInstanced()` method.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/drawElementsInstanced)
+    pub fn draw_elements_instanced(
+        &self,
+        mode: DrawMode,
+        indices: &IndexBuffer,
+        first: i32,
+        count: i32,
+        instances: i32,
+    ) {
+        indices.bind();
+        self.gl.draw_elements_instanced_with_i32(
+            mode.into(),
+            count,
+            indices.index_type().into(),
+            indices.offset(first),
+            instances,
+        );
+    }
+
+    /// The `drawRangeElements()` method, the indices only refer to vertices `start..=end`.
+    /// Falls back to `drawElements()` on WebGL1.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/drawRangeElements)
+    pub fn draw_range_elements(
+        &self,
+        mode: DrawMode,
+        start: u32,
+        end: u32,
+        indices: &IndexBuffer,
+        first: i32,
+        count: i32,
+    ) {
+        match self.gl.webgl2() {
+            Some(gl) => {
+                indices.bind();
+                gl.draw_range_elements_with_i32(
+                    mode.into(),
+                    start,
+                    end,
+                    count,
+                    indices.index_type().into(),
+                    indices.offset(first),
+                );
+            }
+            None => self.draw_elements(mode, indices, first, count),
+        }
+    }
+
+    /// Draws every `firsts[i]`, `counts[i]` range in one call with `WEBGL_multi_draw`, one by one without it.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_multi_draw/multiDrawArraysWEBGL)
+    pub fn multi_draw_arrays(&self, mode: DrawMode, firsts: &[i32], counts: &[i32]) {
+        let draws = firsts.len().min(counts.len());
+        match &self.multi_draw {
+            Some(multi_draw) => multi_draw.multi_draw_arrays_webgl_with_i32_array_and_i32_array(
+                mode.into(),
+                &mut firsts[..draws].to_vec(),
+                0,
+                &mut counts[..draws].to_vec(),
+                0,
+                draws as i32,
+            ),
+            None => {
+                for (first, count) in firsts.iter().zip(counts) {
+                    self.draw_arrays(mode, *first, *count);
+                }
+            }
+        }
+    }
+
+    /// Draws every index range in one call with `WEBGL_multi_draw`, one by one without it.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_multi_draw/multiDrawElementsWEBGL)
+    pub fn multi_draw_elements(
+        &self,
+        mode: DrawMode,
+        indices: &IndexBuffer,
+        ranges: &[Range<i32>],
+    ) {
+        match &self.multi_draw {
+            Some(multi_draw) => {
+                let (mut counts, mut offsets) = element_ranges(indices.index_type(), ranges);
+                indices.bind();
+                multi_draw.multi_draw_elements_webgl_with_i32_array_and_i32_array(
+                    mode.into(),
+                    &mut counts,
+                    0,
+                    indices.index_type().into(),
+                    &mut offsets,
+                    0,
+                    ranges.len() as i32,
+                );
+            }
+            None => {
+                for range in ranges {
+                    self.draw_elements(mode, indices, range.start, range.end - range.start);
+                }
+            }
+        }
+    }
+
+    /// [Renderer::multi_draw_elements] with `instances[i]` instances of every range.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_multi_draw/multiDrawElementsInstancedWEBGL)
+    pub fn multi_draw_elements_instanced(
+        &self,
+        mode: DrawMode,
+        indices: &IndexBuffer,
+        ranges: &[Range<i32>],
+        instances: &[i32],
+    ) {
+        let draws = ranges.len().min(instances.len());
+        match &self.multi_draw {
+            Some(multi_draw) => {
+                let (mut counts, mut offsets) =
+                    element_ranges(indices.index_type(), &ranges[..draws]);
+                indices.bind();
+                multi_draw
+                    .multi_draw_elements_instanced_webgl_with_i32_array_and_i32_array_and_i32_array(
+                        mode.into(),
+                        &mut counts,
+                        0,
+                        indices.index_type().into(),
+                        &mut offsets,
+                        0,
+                        &mut instances[..draws].to_vec(),
+                        0,
+                        draws as i32,
+                    );
+            }
+            None => {
+                for (range, instances) in ranges.iter().zip(instances) {
+                    self.draw_elements_instanced(
+                        mode,
+                        indices,
+                        range.start,
+                        range.end - range.start,
+                        *instances,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Enables or disables `RASTERIZER_DISCARD`. WebGL2 only, does nothing on WebGL1.
+    pub fn set_rasterizer_discard(&mut self, discard: bool) {
+        if let Some(gl) = self.gl.webgl2() {
+            match discard {
+                true => gl.enable(WebGl2RenderingContext::RASTERIZER_DISCARD),
+                false => gl.disable(WebGl2RenderingContext::RASTERIZER_DISCARD),
+            }
+            self.state.rasterizer_discard = discard.then_some(RasterizerDiscard {});
+        }
+    }
+
+    ///
+    /// Binds `sampler` to `unit`, or unbinds it for `None`, and keeps track of it in [RendererState::texture_units].
+    /// WebGL2 only, does nothing on WebGL1.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bindSampler)
+    pub fn bind_sampler(&mut self, unit: TextureUnit, sampler: Option<&WebGlSampler>) {
+        let (Some(gl), Some(index)) = (self.gl.webgl2(), texture_unit_index(unit)) else {
+            return;
+        };
+        gl.bind_sampler(index, sampler);
+        self.state.texture_units.entry(index).or_default().sampler = sampler.cloned();
+    }
+
     pub fn get_error(&self) -> Option<ErrorType> {
         match get_error(self.gl.get_error()) {
             ErrorType::NoError => None,
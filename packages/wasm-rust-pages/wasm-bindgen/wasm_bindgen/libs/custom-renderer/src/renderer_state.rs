@@ -5,7 +5,7 @@ use crate::{
     vertex_array_object::VertexArrayObject,
 };
 
-use web_sys::WebGlProgram;
+use web_sys::{WebGlProgram, WebGlSampler};
 use webgl_common::{
     BlendEquation, BlendFactor, Color, CullFaceMode, DepthFunction, FrontFaceDirection, HintMode,
     StencilOp, TextureTarget, TextureUnit, Viewport,
@@ -30,7 +30,7 @@ pub struct RendererState<'a> {
 
     // 4. Texture Units and Bindings
     pub active_texture_unit: TextureUnit,
-    pub texture_units: HashMap<u32, (Texture, TextureTarget)>, // (Texture, Target)
+    pub texture_units: HashMap<u32, TextureUnitState>,
 
     // 5. Framebuffer Bindings
     pub draw_framebuffer: Option<Framebuffer>,
@@ -123,6 +123,16 @@ impl<'a> RendererState<'a> {
     }
 }
 
+///
+/// What is bound to one texture unit. The sampler, when there is one,
+/// overrides the parameters of the texture, see [crate::sampler::SamplerDescriptor].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TextureUnitState {
+    pub texture: Option<(Texture, TextureTarget)>,
+    pub sampler: Option<WebGlSampler>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BlendState {
     func: (BlendFactor, BlendFactor),
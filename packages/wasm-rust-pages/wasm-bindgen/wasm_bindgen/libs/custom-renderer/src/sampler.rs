@@ -0,0 +1,399 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use web_sys::{ExtTextureFilterAnisotropic, WebGl2RenderingContext, WebGlSampler};
+use webgl_common::{DepthFunction, TextureFilter, TextureUnit, TextureWrap};
+
+use crate::{
+    capture::Command,
+    context::{id, live, Context, WebSamplerKey},
+};
+
+///
+/// # Sampler descriptor
+///
+/// How a texture is sampled, independent of the texture itself.
+/// A sampler bound to a texture unit overrides the parameters of the texture bound to that unit,
+/// so the same texture can be read with different filters at the same time.
+///
+/// Descriptors with the same parameters compare equal, which is what [Context::get_or_create_sampler]
+/// uses to share one sampler object between them.
+///
+/// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGLSampler)
+///
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerDescriptor {
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    pub wrap_r: TextureWrap,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    /// Compares against the reference value for depth textures (`COMPARE_REF_TO_TEXTURE`), e.g. for shadow maps.
+    pub compare: Option<DepthFunction>,
+    /// Clamped to [webgl_common::Capabilities::max_anisotropy], `1.0` disables anisotropic filtering.
+    pub max_anisotropy: f32,
+}
+
+/// The WebGL2 defaults.
+impl Default for SamplerDescriptor {
+    fn default() -> Self {
+        Self {
+            min_filter: TextureFilter::NearestMipmapLinear,
+            mag_filter: TextureFilter::Linear,
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            wrap_r: TextureWrap::Repeat,
+            min_lod: -1000.0,
+            max_lod: 1000.0,
+            compare: None,
+            max_anisotropy: 1.0,
+        }
+    }
+}
+
+/// A parameter passed to `samplerParameteri()` or `samplerParameterf()`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SamplerParameter {
+    Int(u32, i32),
+    Float(u32, f32),
+}
+
+impl SamplerParameter {
+    pub fn apply(&self, gl: &WebGl2RenderingContext, sampler: &WebGlSampler) {
+        match *self {
+            SamplerParameter::Int(name, value) => gl.sampler_parameteri(sampler, name, value),
+            SamplerParameter::Float(name, value) => gl.sampler_parameterf(sampler, name, value),
+        }
+    }
+}
+
+impl SamplerDescriptor {
+    /// Linear filtering without mipmaps, clamped to the edge.
+    pub fn linear() -> Self {
+        Self {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            ..Self::clamped()
+        }
+    }
+
+    /// Nearest filtering without mipmaps, clamped to the edge, e.g. for pixel art.
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            ..Self::clamped()
+        }
+    }
+
+    /// Trilinear filtering, for textures with mipmaps.
+    pub fn trilinear() -> Self {
+        Self {
+            min_filter: TextureFilter::LinearMipmapLinear,
+            mag_filter: TextureFilter::Linear,
+            ..Self::default()
+        }
+    }
+
+    /// Linear filtering that compares depth values with `func`, for `sampler2DShadow`.
+    pub fn shadow(func: DepthFunction) -> Self {
+        Self {
+            compare: Some(func),
+            ..Self::linear()
+        }
+    }
+
+    pub fn with_wrap(self, wrap: TextureWrap) -> Self {
+        Self {
+            wrap_s: wrap,
+            wrap_t: wrap,
+            wrap_r: wrap,
+            ..self
+        }
+    }
+
+    pub fn with_lod(self, min_lod: f32, max_lod: f32) -> Self {
+        Self {
+            min_lod,
+            max_lod,
+            ..self
+        }
+    }
+
+    pub fn with_anisotropy(self, max_anisotropy: f32) -> Self {
+        Self {
+            max_anisotropy,
+            ..self
+        }
+    }
+
+    fn clamped() -> Self {
+        Self::default().with_wrap(TextureWrap::ClampToEdge)
+    }
+
+    ///
+    /// The parameters to set on a new sampler object.
+    /// `max_anisotropy` is the limit of the device, the anisotropy is left out when it is `1.0`.
+    ///
+    pub fn parameters(&self, max_anisotropy: f32) -> Vec<SamplerParameter> {
+        let mut parameters = vec![
+            SamplerParameter::Int(
+                WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+                u32::from(self.min_filter) as i32,
+            ),
+            SamplerParameter::Int(
+                WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+                u32::from(self.mag_filter) as i32,
+            ),
+            SamplerParameter::Int(
+                WebGl2RenderingContext::TEXTURE_WRAP_S,
+                u32::from(self.wrap_s) as i32,
+            ),
+            SamplerParameter::Int(
+                WebGl2RenderingContext::TEXTURE_WRAP_T,
+                u32::from(self.wrap_t) as i32,
+            ),
+            SamplerParameter::Int(
+                WebGl2RenderingContext::TEXTURE_WRAP_R,
+                u32::from(self.wrap_r) as i32,
+            ),
+            SamplerParameter::Float(WebGl2RenderingContext::TEXTURE_MIN_LOD, self.min_lod),
+            SamplerParameter::Float(WebGl2RenderingContext::TEXTURE_MAX_LOD, self.max_lod),
+        ];
+
+        match self.compare {
+            Some(func) => {
+                parameters.push(SamplerParameter::Int(
+                    WebGl2RenderingContext::TEXTURE_COMPARE_MODE,
+                    WebGl2RenderingContext::COMPARE_REF_TO_TEXTURE as i32,
+                ));
+                parameters.push(SamplerParameter::Int(
+                    WebGl2RenderingContext::TEXTURE_COMPARE_FUNC,
+                    u32::from(func) as i32,
+                ));
+            }
+            None => parameters.push(SamplerParameter::Int(
+                WebGl2RenderingContext::TEXTURE_COMPARE_MODE,
+                WebGl2RenderingContext::NONE as i32,
+            )),
+        }
+
+        let anisotropy = self.max_anisotropy.clamp(1.0, max_anisotropy.max(1.0));
+        if anisotropy > 1.0 {
+            parameters.push(SamplerParameter::Float(
+                ExtTextureFilterAnisotropic::TEXTURE_MAX_ANISOTROPY_EXT,
+                anisotropy,
+            ));
+        }
+        parameters
+    }
+
+    /// Creates a sampler object with these parameters.
+    pub fn create(
+        &self,
+        gl: &WebGl2RenderingContext,
+        max_anisotropy: f32,
+    ) -> Result<WebGlSampler, String> {
+        let sampler = gl
+            .create_sampler()
+            .ok_or("Unable to create sampler object")?;
+        for parameter in self.parameters(max_anisotropy) {
+            parameter.apply(gl, &sampler);
+        }
+        Ok(sampler)
+    }
+
+    /// The fields compared and hashed, `-0.0` and `0.0` are the same level of detail.
+    fn key(&self) -> impl Eq + Hash {
+        let bits = |value: f32| (value + 0.0).to_bits();
+        (
+            (self.min_filter, self.mag_filter),
+            (self.wrap_s, self.wrap_t, self.wrap_r),
+            (bits(self.min_lod), bits(self.max_lod)),
+            self.compare,
+            bits(self.max_anisotropy),
+        )
+    }
+}
+
+impl PartialEq for SamplerDescriptor {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerDescriptor {}
+
+impl Hash for SamplerDescriptor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// The index `bindSampler()` takes, `None` for [TextureUnit::ActiveTexture].
+pub fn texture_unit_index(unit: TextureUnit) -> Option<u32> {
+    match unit {
+        TextureUnit::ActiveTexture => None,
+        unit => Some(u32::from(unit) - WebGl2RenderingContext::TEXTURE0),
+    }
+}
+
+impl Context {
+    /// Creates a new sampler object, even when one with the same parameters exists.
+    pub fn create_sampler(&self, descriptor: &SamplerDescriptor) -> Result<WebSamplerKey, String> {
+        self.create_sampler_with_parameters(
+            &descriptor.parameters(self.capabilities().max_anisotropy),
+        )
+    }
+
+    /// Creates a new sampler object with raw `parameters`, e.g. to replay a [Capture](crate::capture::Capture).
+    pub fn create_sampler_with_parameters(
+        &self,
+        parameters: &[SamplerParameter],
+    ) -> Result<WebSamplerKey, String> {
+        let gl = self.gl();
+        let sampler = gl
+            .create_sampler()
+            .ok_or("Unable to create sampler object")?;
+        for parameter in parameters {
+            parameter.apply(gl, &sampler);
+        }
+        let key = self.samplers.borrow_mut().insert(sampler);
+        self.record(|| Command::CreateSampler {
+            id: id(key),
+            parameters: parameters.to_vec(),
+        });
+        Ok(key)
+    }
+
+    ///
+    /// The sampler object for `descriptor`, created on first use.
+    /// Every call with an equal descriptor returns the same key.
+    ///
+    pub fn get_or_create_sampler(
+        &self,
+        descriptor: &SamplerDescriptor,
+    ) -> Result<WebSamplerKey, String> {
+        if let Some(sampler) = self.sampler_cache.borrow().get(descriptor) {
+            return Ok(*sampler);
+        }
+        let sampler = self.create_sampler(descriptor)?;
+        self.sampler_cache.borrow_mut().insert(*descriptor, sampler);
+        Ok(sampler)
+    }
+
+    /// Deletes `sampler`, also when it was created by [Context::get_or_create_sampler].
+    pub fn delete_sampler(&self, sampler: WebSamplerKey) {
+        self.sampler_cache
+            .borrow_mut()
+            .retain(|_, cached| *cached != sampler);
+        if let Some(ref s) = self.samplers.borrow_mut().remove(sampler) {
+            self.record(|| Command::DeleteSampler {
+                sampler: id(sampler),
+            });
+            self.gl().delete_sampler(Some(s));
+        }
+    }
+
+    ///
+    /// The `bindSampler()` method. Binds `sampler` to `unit`, or unbinds it for `None`,
+    /// so the unit samples with the parameters of its texture again. Panics when `sampler` was deleted.
+    ///
+    /// [MDN Documentation](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/bindSampler)
+    ///
+    pub fn bind_sampler(&self, unit: TextureUnit, sampler: Option<WebSamplerKey>) {
+        let Some(index) = texture_unit_index(unit) else {
+            return;
+        };
+        let samplers = self.samplers.borrow();
+        let raw_sampler = sampler.map(|key| live(&samplers, key, "sampler"));
+        self.record(|| Command::BindSampler {
+            unit: unit.into(),
+            sampler: sampler.map(id),
+        });
+        self.gl().bind_sampler(index, raw_sampler);
+    }
+}
+
+#[test]
+fn sampler_descriptor_dedup() {
+    use std::collections::HashSet;
+
+    assert_eq!(SamplerDescriptor::linear(), SamplerDescriptor::linear());
+    assert_ne!(SamplerDescriptor::linear(), SamplerDescriptor::nearest());
+    assert_eq!(
+        SamplerDescriptor::default().with_lod(0.0, 4.0),
+        SamplerDescriptor::default().with_lod(-0.0, 4.0)
+    );
+    assert_ne!(
+        SamplerDescriptor::trilinear(),
+        SamplerDescriptor::trilinear().with_anisotropy(8.0)
+    );
+
+    let descriptors = [
+        SamplerDescriptor::linear(),
+        SamplerDescriptor::nearest(),
+        SamplerDescriptor::linear(),
+        SamplerDescriptor::shadow(DepthFunction::Lequal),
+        SamplerDescriptor::shadow(DepthFunction::Lequal),
+        SamplerDescriptor::nearest().with_wrap(TextureWrap::Repeat),
+    ];
+    let unique = descriptors.iter().collect::<HashSet<_>>();
+    assert_eq!(unique.len(), 4);
+}
+
+#[test]
+fn sampler_descriptor_parameters() {
+    let parameters = SamplerDescriptor::nearest().parameters(16.0);
+    assert!(parameters.contains(&SamplerParameter::Int(
+        WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+        WebGl2RenderingContext::NEAREST as i32
+    )));
+    assert!(parameters.contains(&SamplerParameter::Int(
+        WebGl2RenderingContext::TEXTURE_WRAP_S,
+        WebGl2RenderingContext::CLAMP_TO_EDGE as i32
+    )));
+    assert!(parameters.contains(&SamplerParameter::Int(
+        WebGl2RenderingContext::TEXTURE_COMPARE_MODE,
+        WebGl2RenderingContext::NONE as i32
+    )));
+
+    let anisotropy = |descriptor: SamplerDescriptor, max: f32| {
+        descriptor
+            .parameters(max)
+            .into_iter()
+            .find_map(|parameter| match parameter {
+                SamplerParameter::Float(
+                    ExtTextureFilterAnisotropic::TEXTURE_MAX_ANISOTROPY_EXT,
+                    value,
+                ) => Some(value),
+                _ => None,
+            })
+    };
+    assert_eq!(anisotropy(SamplerDescriptor::trilinear(), 16.0), None);
+    assert_eq!(
+        anisotropy(SamplerDescriptor::trilinear().with_anisotropy(8.0), 16.0),
+        Some(8.0)
+    );
+    assert_eq!(
+        anisotropy(SamplerDescriptor::trilinear().with_anisotropy(32.0), 16.0),
+        Some(16.0)
+    );
+    // without the extension the limit is 1.0
+    assert_eq!(
+        anisotropy(SamplerDescriptor::trilinear().with_anisotropy(8.0), 1.0),
+        None
+    );
+
+    let shadow = SamplerDescriptor::shadow(DepthFunction::Lequal).parameters(1.0);
+    assert!(shadow.contains(&SamplerParameter::Int(
+        WebGl2RenderingContext::TEXTURE_COMPARE_FUNC,
+        WebGl2RenderingContext::LEQUAL as i32
+    )));
+
+    assert_eq!(texture_unit_index(TextureUnit::Texture0), Some(0));
+    assert_eq!(texture_unit_index(TextureUnit::Texture5), Some(5));
+    assert_eq!(texture_unit_index(TextureUnit::ActiveTexture), None);
+}
@@ -1,6 +1,8 @@
 use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
 use webgl_common::ShaderType;
 
+use crate::gl_context::GlContext;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ShaderProgram {}
 
@@ -10,18 +12,23 @@ impl ShaderProgram {
     }
 }
 
+///
+/// Compiles and links a program, binding every `(name, location)` in `attributes` before linking.
+/// GLSL ES 1.00 has no `layout(location = N)`, so this is how WebGL1 programs get fixed locations.
+///
 pub(crate) fn create_shader_program(
-    gl: &WebGl2RenderingContext,
+    gl: &GlContext,
     vert_src: &str,
     frag_src: &str,
+    attributes: &[(&str, u32)],
 ) -> Result<WebGlProgram, String> {
     let vert_shader = compile_shader(gl, ShaderType::VertexShader, vert_src)?;
     let frag_shader = compile_shader(gl, ShaderType::FragmentShader, frag_src)?;
-    link_program(gl, &vert_shader, &frag_shader)
+    link_program(gl, &vert_shader, &frag_shader, attributes)
 }
 
 fn compile_shader(
-    gl: &WebGl2RenderingContext,
+    gl: &GlContext,
     shader_type: ShaderType,
     source: &str,
 ) -> Result<WebGlShader, String> {
@@ -45,15 +52,19 @@ fn compile_shader(
 }
 
 fn link_program(
-    gl: &WebGl2RenderingContext,
+    gl: &GlContext,
     vert_shader: &WebGlShader,
     frag_shader: &WebGlShader,
+    attributes: &[(&str, u32)],
 ) -> Result<WebGlProgram, String> {
     let program = gl
         .create_program()
         .ok_or("Unable to create shader object")?;
     gl.attach_shader(&program, vert_shader);
     gl.attach_shader(&program, frag_shader);
+    for (name, location) in attributes {
+        gl.bind_attrib_location(&program, *location, name);
+    }
     gl.link_program(&program);
 
     if gl
@@ -0,0 +1,258 @@
+use web_sys::WebGl2RenderingContext;
+use webgl_common::{
+    slice_as_u8_slice, BufferTarget, BufferUsage, DataType, DrawMode, GpuPod, ShaderType,
+    TransformFeedbackBufferMode,
+};
+
+use crate::context::{
+    Context, WebBufferKey, WebProgramKey, WebTransformFeedbackKey, WebVertexArrayKey,
+};
+
+///
+/// Two values that swap roles every step,
+/// e.g. the buffer a simulation reads from and the one it writes to.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingPong<T> {
+    items: [T; 2],
+    read: usize,
+}
+
+impl<T> PingPong<T> {
+    pub fn new(read: T, write: T) -> Self {
+        Self {
+            items: [read, write],
+            read: 0,
+        }
+    }
+
+    pub fn read(&self) -> &T {
+        &self.items[self.read]
+    }
+
+    pub fn write(&self) -> &T {
+        &self.items[1 - self.read]
+    }
+
+    pub fn swap(&mut self) {
+        self.read = 1 - self.read;
+    }
+
+    pub fn into_inner(self) -> [T; 2] {
+        self.items
+    }
+}
+
+/// An attribute of the interleaved vertices of a [TransformFeedbackLoop].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedbackAttribute {
+    pub location: u32,
+    pub size: i32,
+    pub data_type: DataType,
+}
+
+/// The offsets of tightly packed interleaved `attributes`, and the stride of one vertex.
+fn interleaved_layout(attributes: &[FeedbackAttribute]) -> (Vec<i32>, i32) {
+    let mut offsets = Vec::with_capacity(attributes.len());
+    let mut stride = 0;
+    for attribute in attributes {
+        offsets.push(stride);
+        stride += attribute.size * attribute.data_type.size() as i32;
+    }
+    (offsets, stride)
+}
+
+///
+/// Compiles and links a program whose vertex shader outputs `varyings` are captured by transform feedback.
+/// The varyings have to be declared before linking, in the order they are written to the buffer.
+/// Without `varyings` this links a plain program.
+///
+pub fn create_feedback_program(
+    context: &Context,
+    vert_src: &str,
+    frag_src: &str,
+    varyings: &[&str],
+    buffer_mode: TransformFeedbackBufferMode,
+) -> Result<WebProgramKey, String> {
+    let program = context.create_program()?;
+    let mut shaders = Vec::new();
+    for (shader_type, source) in [
+        (ShaderType::VertexShader, vert_src),
+        (ShaderType::FragmentShader, frag_src),
+    ] {
+        let shader = context.create_shader(shader_type)?;
+        context.shader_source(shader, source);
+        context.compile_shader(shader);
+        context.attach_shader(program, shader);
+        shaders.push(shader);
+    }
+
+    context.transform_feedback_varyings(program, varyings, buffer_mode);
+    context.link_program(program);
+    for shader in shaders {
+        context.delete_shader(shader);
+    }
+
+    if context.get_program_parameter(program, WebGl2RenderingContext::LINK_STATUS) == 0 {
+        let log = context.get_program_info_log(program);
+        context.delete_program(program);
+        return Err(log);
+    }
+    Ok(program)
+}
+
+///
+/// # Transform feedback loop
+///
+/// Runs a vertex shader over interleaved vertices and captures its outputs as the input of the next step,
+/// e.g. to simulate particles on the GPU.
+///
+/// Each side has a buffer, a vertex array reading that buffer and a transform feedback object
+/// writing into the other buffer. [TransformFeedbackLoop::step] runs with rasterizer discard and swaps the sides,
+/// so [TransformFeedbackLoop::vertex_array] always reads the latest output and can be drawn directly.
+///
+/// The update program must write its varyings interleaved, in the order of the `attributes`.
+///
+pub struct TransformFeedbackLoop {
+    buffers: PingPong<WebBufferKey>,
+    vertex_arrays: PingPong<WebVertexArrayKey>,
+    transform_feedbacks: PingPong<WebTransformFeedbackKey>,
+    count: i32,
+}
+
+impl TransformFeedbackLoop {
+    pub fn new<T: GpuPod>(
+        context: &Context,
+        vertices: &[T],
+        attributes: &[FeedbackAttribute],
+    ) -> Result<Self, String> {
+        let (offsets, stride) = interleaved_layout(attributes);
+        if stride as usize != std::mem::size_of::<T>() {
+            return Err(format!(
+                "attributes are {} bytes, but a vertex is {} bytes",
+                stride,
+                std::mem::size_of::<T>()
+            ));
+        }
+
+        let bytes = slice_as_u8_slice(vertices);
+        let mut buffers = Vec::with_capacity(2);
+        let mut vertex_arrays = Vec::with_capacity(2);
+        for _ in 0..2 {
+            let buffer = context.create_buffer()?;
+            context.bind_buffer(BufferTarget::ArrayBuffer, buffer);
+            context.buffer_data(BufferTarget::ArrayBuffer, bytes, BufferUsage::StreamCopy);
+
+            let vertex_array = context.create_vertex_array()?;
+            context.bind_vertex_array(vertex_array);
+            for (attribute, offset) in attributes.iter().zip(&offsets) {
+                context.enable_vertex_attrib_array(attribute.location);
+                context.vertex_attrib_pointer(
+                    attribute.location,
+                    attribute.size,
+                    attribute.data_type,
+                    false,
+                    stride,
+                    *offset,
+                );
+            }
+            context.unbind_vertex_array();
+
+            buffers.push(buffer);
+            vertex_arrays.push(vertex_array);
+        }
+
+        // each side writes into the buffer of the other side
+        let mut transform_feedbacks = Vec::with_capacity(2);
+        for buffer in buffers.iter().rev() {
+            let transform_feedback = context.create_transform_feedback()?;
+            context.bind_transform_feedback(Some(transform_feedback));
+            context.bind_base_base(BufferTarget::TransformFeedbackBuffer, 0, *buffer);
+            transform_feedbacks.push(transform_feedback);
+        }
+        context.bind_transform_feedback(None);
+        context.unbind_buffer(BufferTarget::TransformFeedbackBuffer);
+        context.unbind_buffer(BufferTarget::ArrayBuffer);
+
+        Ok(Self {
+            buffers: PingPong::new(buffers[0], buffers[1]),
+            vertex_arrays: PingPong::new(vertex_arrays[0], vertex_arrays[1]),
+            transform_feedbacks: PingPong::new(transform_feedbacks[0], transform_feedbacks[1]),
+            count: (bytes.len() / stride as usize) as i32,
+        })
+    }
+
+    /// Runs `program` over every vertex, capturing the outputs without rasterizing anything.
+    pub fn step(&mut self, context: &Context, program: WebProgramKey) {
+        context.use_program(program);
+        context.bind_vertex_array(*self.vertex_arrays.read());
+        context.bind_transform_feedback(Some(*self.transform_feedbacks.read()));
+        context.set_rasterizer_discard(true);
+
+        context.begin_transform_feedback(DrawMode::Points);
+        context.draw_arrays(DrawMode::Points, 0, self.count);
+        context.end_transform_feedback();
+
+        context.set_rasterizer_discard(false);
+        context.bind_transform_feedback(None);
+        context.unbind_vertex_array();
+
+        self.buffers.swap();
+        self.vertex_arrays.swap();
+        self.transform_feedbacks.swap();
+    }
+
+    /// Reads the output of the last step.
+    pub fn vertex_array(&self) -> WebVertexArrayKey {
+        *self.vertex_arrays.read()
+    }
+
+    /// The buffer with the output of the last step.
+    pub fn buffer(&self) -> WebBufferKey {
+        *self.buffers.read()
+    }
+
+    pub fn count(&self) -> i32 {
+        self.count
+    }
+
+    pub fn delete(self, context: &Context) {
+        for transform_feedback in self.transform_feedbacks.into_inner() {
+            context.delete_transform_feedback(transform_feedback);
+        }
+        for vertex_array in self.vertex_arrays.into_inner() {
+            context.delete_vertex_array(vertex_array);
+        }
+        for buffer in self.buffers.into_inner() {
+            context.delete_buffer(buffer);
+        }
+    }
+}
+
+#[test]
+fn ping_pong_swap() {
+    let mut ping_pong = PingPong::new("a", "b");
+    assert_eq!((*ping_pong.read(), *ping_pong.write()), ("a", "b"));
+    ping_pong.swap();
+    assert_eq!((*ping_pong.read(), *ping_pong.write()), ("b", "a"));
+    ping_pong.swap();
+    assert_eq!(*ping_pong.read(), "a");
+    assert_eq!(ping_pong.into_inner(), ["a", "b"]);
+}
+
+#[test]
+fn feedback_interleaved_layout() {
+    let attribute = |location, size, data_type| FeedbackAttribute {
+        location,
+        size,
+        data_type,
+    };
+    let (offsets, stride) = interleaved_layout(&[
+        attribute(0, 2, DataType::Float),
+        attribute(1, 2, DataType::Float),
+        attribute(2, 1, DataType::Float),
+        attribute(3, 4, DataType::UnsignedByte),
+    ]);
+    assert_eq!(offsets, vec![0, 8, 16, 20]);
+    assert_eq!(stride, 24);
+}
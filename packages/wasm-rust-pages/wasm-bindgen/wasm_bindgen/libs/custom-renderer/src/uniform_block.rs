@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
+use webgl_common::{value_as_u8_slice, BufferTarget, BufferUsage, GpuPod};
+
+use crate::{buffer::Buffer, gl_context::GlContext};
+
+/// `(GLSL name, std140 byte offset)` of a plain uniform that stands in for a block member
+/// in GLSL ES 1.00, as listed by `make_naga_module!` in `<name>_uniform_members`.
+pub type UniformMember = (&'static str, u32);
+
+///
+/// A uniform block that works on both WebGL versions.
+///
+/// On WebGL2 the data goes into a uniform buffer bound to `binding`.
+/// On WebGL1 the same std140 bytes are split up and uploaded to the plain uniforms in `members`.
+///
+pub struct UniformBlock<'a> {
+    gl: &'a GlContext,
+    binding: u32,
+    storage: Storage<'a>,
+}
+
+enum Storage<'a> {
+    Buffer(Buffer<'a>),
+    Uniforms {
+        program: WebGlProgram,
+        members: Vec<EmulatedUniform>,
+    },
+}
+
+struct EmulatedUniform {
+    location: WebGlUniformLocation,
+    offset: usize,
+    type_: u32,
+}
+
+impl<'a> UniformBlock<'a> {
+    pub fn new(
+        gl: &'a GlContext,
+        program: &WebGlProgram,
+        block_name: &str,
+        binding: u32,
+        members: &[UniformMember],
+    ) -> Self {
+        let storage = match gl.webgl2() {
+            Some(webgl2) => {
+                let index = webgl2.get_uniform_block_index(program, block_name);
+                webgl2.uniform_block_binding(program, index, binding);
+                Storage::Buffer(Buffer::new(gl))
+            }
+            None => {
+                let types = active_uniform_types(gl, program);
+                let members = members
+                    .iter()
+                    .filter_map(|&(name, offset)| {
+                        // members the compiler optimized out have no location
+                        let location = gl.get_uniform_location(program, name)?;
+                        let type_ = uniform_type(&types, name)?;
+                        Some(EmulatedUniform {
+                            location,
+                            offset: offset as usize,
+                            type_,
+                        })
+                    })
+                    .collect();
+                Storage::Uniforms {
+                    program: program.clone(),
+                    members,
+                }
+            }
+        };
+
+        Self {
+            gl,
+            binding,
+            storage,
+        }
+    }
+
+    /// Uploads `data`, laid out with std140 rules. On WebGL1 this makes the block's program current.
+    pub fn set_data<T: GpuPod>(&mut self, data: &T) {
+        match &mut self.storage {
+            Storage::Buffer(buffer) => {
+                buffer.set_data(
+                    BufferTarget::UniformBuffer,
+                    std::slice::from_ref(data),
+                    BufferUsage::DynamicDraw,
+                );
+                self.bind();
+            }
+            Storage::Uniforms { program, members } => {
+                let bytes = value_as_u8_slice(data);
+                self.gl.use_program(Some(program));
+                for member in members.iter() {
+                    upload(self.gl, member, bytes);
+                }
+            }
+        }
+    }
+
+    /// Binds the buffer to the block's binding point. Nothing to do on WebGL1.
+    pub fn bind(&self) {
+        if let (Storage::Buffer(buffer), Some(webgl2)) = (&self.storage, self.gl.webgl2()) {
+            webgl2.bind_buffer_base(
+                WebGl2RenderingContext::UNIFORM_BUFFER,
+                self.binding,
+                Some(&buffer.buffer),
+            );
+        }
+    }
+}
+
+fn active_uniform_types(gl: &GlContext, program: &WebGlProgram) -> HashMap<String, u32> {
+    let count = gl
+        .get_program_parameter(program, WebGl2RenderingContext::ACTIVE_UNIFORMS)
+        .as_f64()
+        .unwrap_or(0.0) as u32;
+
+    (0..count)
+        .filter_map(|index| gl.get_active_uniform(program, index))
+        .map(|info| (info.name(), info.type_()))
+        .collect()
+}
+
+/// Arrays are reported once as `name[0]`, so `name[3]` takes the type of `name[0]`.
+fn uniform_type(types: &HashMap<String, u32>, name: &str) -> Option<u32> {
+    if let Some(type_) = types.get(name) {
+        return Some(*type_);
+    }
+    let (array, _) = name.strip_suffix(']')?.rsplit_once('[')?;
+    types.get(&format!("{}[0]", array)).copied()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum UniformValues {
+    Float(Vec<f32>),
+    Int(Vec<i32>),
+    Matrix(usize, Vec<f32>),
+}
+
+///
+/// Reads a uniform of GL type `type_` at `offset` from std140 bytes.
+/// Matrix columns are padded to 16 bytes in std140, but uploaded tightly packed.
+///
+fn read_uniform(bytes: &[u8], offset: usize, type_: u32) -> Option<UniformValues> {
+    let word = |index: usize| -> Option<[u8; 4]> {
+        let start = offset + index * 4;
+        bytes.get(start..start + 4)?.try_into().ok()
+    };
+    let floats = |count: usize| -> Option<Vec<f32>> {
+        (0..count)
+            .map(|i| word(i).map(f32::from_ne_bytes))
+            .collect()
+    };
+    let ints = |count: usize| -> Option<Vec<i32>> {
+        (0..count)
+            .map(|i| word(i).map(i32::from_ne_bytes))
+            .collect()
+    };
+    let matrix = |size: usize| -> Option<UniformValues> {
+        let values = (0..size)
+            .flat_map(|column| (0..size).map(move |row| column * 4 + row))
+            .map(|i| word(i).map(f32::from_ne_bytes))
+            .collect::<Option<Vec<_>>>()?;
+        Some(UniformValues::Matrix(size, values))
+    };
+
+    match type_ {
+        WebGl2RenderingContext::FLOAT => floats(1).map(UniformValues::Float),
+        WebGl2RenderingContext::FLOAT_VEC2 => floats(2).map(UniformValues::Float),
+        WebGl2RenderingContext::FLOAT_VEC3 => floats(3).map(UniformValues::Float),
+        WebGl2RenderingContext::FLOAT_VEC4 => floats(4).map(UniformValues::Float),
+        WebGl2RenderingContext::INT | WebGl2RenderingContext::BOOL => {
+            ints(1).map(UniformValues::Int)
+        }
+        WebGl2RenderingContext::INT_VEC2 | WebGl2RenderingContext::BOOL_VEC2 => {
+            ints(2).map(UniformValues::Int)
+        }
+        WebGl2RenderingContext::INT_VEC3 | WebGl2RenderingContext::BOOL_VEC3 => {
+            ints(3).map(UniformValues::Int)
+        }
+        WebGl2RenderingContext::INT_VEC4 | WebGl2RenderingContext::BOOL_VEC4 => {
+            ints(4).map(UniformValues::Int)
+        }
+        WebGl2RenderingContext::FLOAT_MAT2 => matrix(2),
+        WebGl2RenderingContext::FLOAT_MAT3 => matrix(3),
+        WebGl2RenderingContext::FLOAT_MAT4 => matrix(4),
+        _ => None,
+    }
+}
+
+fn upload(gl: &GlContext, uniform: &EmulatedUniform, bytes: &[u8]) {
+    let location = Some(&uniform.location);
+    match read_uniform(bytes, uniform.offset, uniform.type_) {
+        Some(UniformValues::Float(values)) => match values.len() {
+            1 => gl.uniform1fv_with_f32_array(location, &values),
+            2 => gl.uniform2fv_with_f32_array(location, &values),
+            3 => gl.uniform3fv_with_f32_array(location, &values),
+            _ => gl.uniform4fv_with_f32_array(location, &values),
+        },
+        Some(UniformValues::Int(values)) => match values.len() {
+            1 => gl.uniform1iv_with_i32_array(location, &values),
+            2 => gl.uniform2iv_with_i32_array(location, &values),
+            3 => gl.uniform3iv_with_i32_array(location, &values),
+            _ => gl.uniform4iv_with_i32_array(location, &values),
+        },
+        Some(UniformValues::Matrix(size, values)) => match size {
+            2 => gl.uniform_matrix2fv_with_f32_array(location, false, &values),
+            3 => gl.uniform_matrix3fv_with_f32_array(location, false, &values),
+            _ => gl.uniform_matrix4fv_with_f32_array(location, false, &values),
+        },
+        None => {}
+    }
+}
+
+#[test]
+fn read_std140_uniforms() {
+    #[repr(C)]
+    #[derive(Clone, Copy, GpuPod)]
+    struct Block {
+        color: [f32; 4],
+        normal_matrix: [[f32; 4]; 3],
+        count: i32,
+    }
+
+    let block = Block {
+        color: [0.1, 0.2, 0.3, 1.0],
+        normal_matrix: [
+            [1.0, 2.0, 3.0, 0.0],
+            [4.0, 5.0, 6.0, 0.0],
+            [7.0, 8.0, 9.0, 0.0],
+        ],
+        count: 7,
+    };
+    let bytes = value_as_u8_slice(&block);
+
+    assert_eq!(
+        read_uniform(bytes, 0, WebGl2RenderingContext::FLOAT_VEC4),
+        Some(UniformValues::Float(vec![0.1, 0.2, 0.3, 1.0]))
+    );
+    assert_eq!(
+        read_uniform(bytes, 16, WebGl2RenderingContext::FLOAT_MAT3),
+        Some(UniformValues::Matrix(
+            3,
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+        ))
+    );
+    assert_eq!(
+        read_uniform(bytes, 64, WebGl2RenderingContext::INT),
+        Some(UniformValues::Int(vec![7]))
+    );
+    // out of range
+    assert_eq!(
+        read_uniform(bytes, 64, WebGl2RenderingContext::FLOAT_VEC2),
+        None
+    );
+
+    let types = HashMap::from([("lights[0]".to_string(), WebGl2RenderingContext::FLOAT_VEC3)]);
+    assert_eq!(
+        uniform_type(&types, "lights[2]"),
+        Some(WebGl2RenderingContext::FLOAT_VEC3)
+    );
+    assert_eq!(uniform_type(&types, "other"), None);
+}
@@ -1,18 +1,18 @@
-use web_sys::{WebGl2RenderingContext, WebGlVertexArrayObject};
+use web_sys::WebGlVertexArrayObject;
 
 use webgl_common::DataType;
 
-use crate::buffer::Buffer;
+use crate::{buffer::Buffer, gl_context::GlContext};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VertexArrayObject<'a> {
-    gl: &'a WebGl2RenderingContext,
+    gl: &'a GlContext,
     pub id: WebGlVertexArrayObject,
     pub attribs: Vec<VertexAttrib<'a>>,
 }
 
 impl<'a> VertexArrayObject<'a> {
-    pub fn new(gl: &'a WebGl2RenderingContext) -> Self {
+    pub fn new(gl: &'a GlContext) -> Self {
         let id = gl
             .create_vertex_array()
             .expect("Unable to create vertex array object");
@@ -40,9 +40,20 @@ impl<'a> VertexArrayObject<'a> {
                 options.normalized,
                 options.stride,
                 options.offset,
-            );
+            )
+            .divisor(options.divisor);
         self.attribs.push(attribute);
     }
+
+    /// Changes how often the attribute at `location` advances, the vertex array has to be bound.
+    pub fn set_divisor(&mut self, location: u32, divisor: u32) {
+        self.gl.vertex_attrib_divisor(location, divisor);
+        for attribute in self.attribs.iter_mut() {
+            if attribute.location == location {
+                attribute.options.divisor = divisor;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
@@ -52,36 +63,68 @@ pub struct AttributeOptions {
     pub normalized: bool,
     pub stride: i32,
     pub offset: i32,
+    /// `0` advances once per vertex, `N` once per `N` instances, see [VertexAttrib::divisor].
+    pub divisor: u32,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct VertexAttrib<'a> {
-    gl: &'a WebGl2RenderingContext,
+    gl: &'a GlContext,
     buffer: &'a Buffer<'a>,
     location: u32,
+    options: AttributeOptions,
+    enabled: bool,
 }
 
 impl<'a> VertexAttrib<'a> {
-    pub fn new(gl: &'a WebGl2RenderingContext, location: u32, buffer: &'a Buffer) -> Self {
+    pub fn new(gl: &'a GlContext, location: u32, buffer: &'a Buffer) -> Self {
         Self {
             gl,
             location,
             buffer,
+            options: AttributeOptions::default(),
+            enabled: false,
         }
     }
 
-    pub fn enable(self) -> Self {
+    pub fn location(&self) -> u32 {
+        self.location
+    }
+
+    pub fn buffer(&self) -> &'a Buffer<'a> {
+        self.buffer
+    }
+
+    /// The options of the last [VertexAttrib::pointer] and [VertexAttrib::divisor] calls.
+    pub fn options(&self) -> AttributeOptions {
+        self.options
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn enable(mut self) -> Self {
         self.gl.enable_vertex_attrib_array(self.location);
+        self.enabled = true;
         self
     }
 
-    pub fn disable(self) -> Self {
+    pub fn disable(mut self) -> Self {
         self.gl.disable_vertex_attrib_array(self.location);
+        self.enabled = false;
+        self
+    }
+
+    /// Advances the attribute once per `divisor` instances instead of once per vertex, `0` resets it.
+    pub fn divisor(mut self, divisor: u32) -> Self {
+        self.gl.vertex_attrib_divisor(self.location, divisor);
+        self.options.divisor = divisor;
         self
     }
 
     pub fn pointer(
-        self,
+        mut self,
         size: i32,
         data_type: DataType,
         normalized: bool,
@@ -96,6 +139,14 @@ impl<'a> VertexAttrib<'a> {
             stride,
             offset,
         );
+        self.options = AttributeOptions {
+            size,
+            data_type,
+            normalized,
+            stride,
+            offset,
+            divisor: self.options.divisor,
+        };
         self
     }
 }
@@ -1,7 +1,7 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, Data, DeriveInput};
 
 #[proc_macro_derive(IntoU32)]
 pub fn derive_into_u32(input: TokenStream) -> TokenStream {
@@ -18,3 +18,113 @@ pub fn derive_into_u32(input: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+///
+/// Implements `TryFrom<u32>` for a fieldless enum, the inverse of [IntoU32].
+/// Values that match no variant are returned as the error.
+///
+#[proc_macro_derive(TryFromU32)]
+pub fn derive_try_from_u32(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+
+    let Data::Enum(data) = input.data else {
+        return syn::Error::new_spanned(name, "TryFromU32 can only be derived for enums")
+            .to_compile_error()
+            .into();
+    };
+    let variants = data.variants.iter().map(|variant| &variant.ident);
+
+    let expanded = quote! {
+        impl TryFrom<u32> for #name {
+            type Error = u32;
+
+            fn try_from(value: u32) -> Result<Self, Self::Error> {
+                #(
+                    if value == #name::#variants as u32 {
+                        return Ok(#name::#variants);
+                    }
+                )*
+                Err(value)
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+///
+/// Implements `webgl_common::GpuPod` for a `#[repr(C)]` struct.
+///
+/// Fails to compile when the struct is not `#[repr(C)]` or `#[repr(transparent)]`,
+/// has generics, contains a field that is not `GpuPod` (e.g. a pointer or reference),
+/// or when its size is larger than the sum of its fields, i.e. it has padding.
+///
+#[proc_macro_derive(GpuPod)]
+pub fn derive_gpu_pod(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match gpu_pod(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => TokenStream::from(error.to_compile_error()),
+    }
+}
+
+fn gpu_pod(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "GpuPod can only be derived for structs",
+            ))
+        }
+    };
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "GpuPod can not be derived for generic structs",
+        ));
+    }
+
+    let mut repr_c = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                repr_c = true;
+            }
+            Ok(())
+        })?;
+    }
+    if !repr_c {
+        return Err(syn::Error::new_spanned(
+            name,
+            "GpuPod requires #[repr(C)] or #[repr(transparent)]",
+        ));
+    }
+
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let padding_message = format!("`{}` has padding bytes and can not be GpuPod", name);
+
+    Ok(quote! {
+        const _: () = {
+            fn assert_gpu_pod<T: ::webgl_common::GpuPod>() {}
+            #[allow(dead_code)]
+            fn assert_fields() {
+                #(assert_gpu_pod::<#types>();)*
+            }
+            assert!(
+                ::core::mem::size_of::<#name>() == 0 #(+ ::core::mem::size_of::<#types>())*,
+                #padding_message
+            );
+        };
+
+        unsafe impl ::webgl_common::GpuPod for #name {}
+    })
+}
@@ -2,12 +2,13 @@ use core::str;
 use std::{f32::consts::PI, rc::Rc};
 
 use fundamentals_common::{
+    camera::{Camera3D, OrbitController, Projection},
     create_checker_texture, create_decal_texture,
     cube_data::{get_vertex_data, Attributes},
     set_element_array_buffer,
     uniform::{SetUniform, Uniform},
 };
-use nalgebra::{Matrix4, Perspective3, Vector3};
+use nalgebra::{Matrix4, Point3, Vector3};
 use wasm_bindgen::prelude::*;
 use web_sys::{
     HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram, WebGlTexture, WebGlVertexArrayObject,
@@ -24,6 +25,9 @@ pub struct App {
     program: WebGlProgram,
     checker_texture: WebGlTexture,
     decal_texture: WebGlTexture,
+    camera: Camera3D,
+    /// Circles around the center cube, it starts where the camera of the original demo is.
+    orbit: OrbitController,
 }
 
 struct Uniforms {
@@ -58,6 +62,16 @@ impl App {
 
         let attributes = Attributes::new(Rc::clone(&gl), &prg, cube_vertex, cube_vertex_indices);
 
+        let mut camera = Camera3D::new(canvas.client_width() as f32, canvas.client_height() as f32);
+        camera.projection = Projection::Perspective {
+            fov_y: PI / 4.0,
+            near: 0.1,
+            far: 20.0,
+        };
+        let mut orbit = OrbitController::new(Point3::new(0.0, 0.0, -5.0), 5.0);
+        orbit.min_distance = 2.0;
+        orbit.max_distance = 12.0;
+
         Self {
             uniforms: Uniforms {
                 projection: Uniform::new(
@@ -87,6 +101,8 @@ impl App {
             },
             checker_texture: create_checker_texture(&gl),
             decal_texture: create_decal_texture(&gl),
+            camera,
+            orbit,
             canvas,
             attributes,
             program: prg,
@@ -94,8 +110,23 @@ impl App {
         }
     }
 
+    /// Rotates around the center cube by a pointer that moved `dx`, `dy` pixels.
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.orbit.rotate(dx, dy);
+    }
+
+    /// Moves what the camera circles around by a pointer that moved `dx`, `dy` pixels.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.orbit.pan(dx, dy);
+    }
+
+    /// Moves closer for positive deltas, e.g. the negated `WheelEvent.deltaY`.
+    pub fn zoom(&mut self, delta: f32) {
+        self.orbit.zoom(delta);
+    }
+
     /// is rendering code.
-    pub fn render(&self) {
+    pub fn render(&mut self) {
         {
             // Set up WebGL state before rendering
             // and cleanup the canvas
@@ -149,19 +180,22 @@ impl App {
 
             {
                 // set up projection matrix
-                let projection = Perspective3::new(
-                    self.canvas.client_width() as f32 / self.canvas.client_height() as f32,
-                    PI / 4.0,
-                    0.1,
-                    10.0,
+                self.camera.resize(
+                    self.canvas.client_width() as f32,
+                    self.canvas.client_height() as f32,
                 );
+                self.orbit.apply(&mut self.camera);
 
-                self.uniforms.projection.set(projection.as_matrix());
+                self.uniforms
+                    .projection
+                    .set(&self.camera.projection_matrix());
             }
+            let view = self.camera.view_matrix();
 
             {
                 // draw center cube
-                let model_view = Matrix4::new_translation(&Vector3::new(0.0, 0.0, -5.0))
+                let model_view = view
+                    * Matrix4::new_translation(&Vector3::new(0.0, 0.0, -5.0))
                     * Matrix4::from_euler_angles(0.5, 0.0, 0.0)
                     * Matrix4::from_euler_angles(0.0, 0.5, 0.0);
 
@@ -179,7 +213,8 @@ impl App {
             {
                 // draw left cube
 
-                let model_view = Matrix4::new_translation(&Vector3::new(-3.0, 0.0, -4.0))
+                let model_view = view
+                    * Matrix4::new_translation(&Vector3::new(-3.0, 0.0, -4.0))
                     * Matrix4::from_euler_angles(0.5, 0.0, 0.0)
                     * Matrix4::from_euler_angles(0.0, 0.8, 0.0);
 
@@ -197,7 +232,8 @@ impl App {
             {
                 // draw right cube
 
-                let model_view = Matrix4::new_translation(&Vector3::new(3.0, 0.0, -4.0))
+                let model_view = view
+                    * Matrix4::new_translation(&Vector3::new(3.0, 0.0, -4.0))
                     * Matrix4::from_euler_angles(0.5, 0.0, 0.0)
                     * Matrix4::from_euler_angles(0.0, -0.6, 0.0);
 
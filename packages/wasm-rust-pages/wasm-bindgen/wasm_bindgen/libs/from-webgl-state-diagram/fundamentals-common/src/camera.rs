@@ -0,0 +1,513 @@
+use std::{collections::HashSet, f32::consts::FRAC_PI_2};
+
+use nalgebra::{Matrix4, Orthographic3, Perspective3, Point3, Vector3};
+use webgl_common::{value_as_u8_slice, DepthFunction, GpuPod};
+
+/// How a [Camera3D] maps view space to clip space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    /// `height` is the world units visible from the bottom to the top of the screen.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective {
+            fov_y: 60f32.to_radians(),
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+///
+/// # 3D camera
+///
+/// Looks from `position` at `target`, right handed with y up.
+/// The `projection` and `modelView` uniforms of the cube demos are [Camera3D::projection_matrix]
+/// and [Camera3D::view_matrix] times the model matrix, [Camera3D::transforms] fills the `Transforms`
+/// uniform block of `start_webgl.wgsl`.
+///
+/// With `reversed_z` the near plane maps to depth `1` and the far plane to `0`,
+/// draw with [Camera3D::depth_function] and clear the depth to [Camera3D::clear_depth].
+/// WebGL stores `(z + 1) / 2` in the depth buffer, so the reversed range uses its upper half.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera3D {
+    pub position: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+    /// Width divided by height of the screen.
+    pub aspect: f32,
+    pub reversed_z: bool,
+}
+
+/// The `Transforms` uniform block of `start_webgl.wgsl`, it goes straight into the webgl buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, GpuPod)]
+pub struct Transforms {
+    pub projection_matrix: [f32; 16],
+    pub view_matrix: [f32; 16],
+}
+
+impl Transforms {
+    pub fn as_bytes(&self) -> &[u8] {
+        value_as_u8_slice(self)
+    }
+}
+
+impl Camera3D {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            position: Point3::new(0.0, 0.0, 5.0),
+            target: Point3::origin(),
+            up: Vector3::y(),
+            projection: Projection::default(),
+            aspect: width / height,
+            reversed_z: false,
+        }
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.aspect = width / height;
+    }
+
+    /// The normalized direction the camera looks in.
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target - self.position).normalize()
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(&self.position, &self.target, &self.up)
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        let (mut projection, near, far) = match self.projection {
+            Projection::Perspective { fov_y, near, far } => (
+                Perspective3::new(self.aspect, fov_y, near, far).to_homogeneous(),
+                near,
+                far,
+            ),
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                let projection = Orthographic3::new(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+                .to_homogeneous();
+                (projection, near, far)
+            }
+        };
+
+        if self.reversed_z {
+            // clip z = a * view z + b * view w, with near at z = w and far at z = 0
+            let (a, b) = match self.projection {
+                Projection::Perspective { .. } => (near / (far - near), near * far / (far - near)),
+                Projection::Orthographic { .. } => (1.0 / (far - near), far / (far - near)),
+            };
+            projection[(2, 2)] = a;
+            projection[(2, 3)] = b;
+        }
+        projection
+    }
+
+    pub fn view_projection_matrix(&self) -> Matrix4<f32> {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// The matrices for the `Transforms` uniform block, column major like GLSL expects them.
+    pub fn transforms(&self) -> Transforms {
+        let mut transforms = Transforms {
+            projection_matrix: [0.0; 16],
+            view_matrix: [0.0; 16],
+        };
+        transforms
+            .projection_matrix
+            .copy_from_slice(self.projection_matrix().as_slice());
+        transforms
+            .view_matrix
+            .copy_from_slice(self.view_matrix().as_slice());
+        transforms
+    }
+
+    /// The depth test that keeps the nearest fragments.
+    pub fn depth_function(&self) -> DepthFunction {
+        match self.reversed_z {
+            true => DepthFunction::Gequal,
+            false => DepthFunction::Lequal,
+        }
+    }
+
+    /// The depth buffer value of nothing drawn, the farthest depth.
+    pub fn clear_depth(&self) -> f32 {
+        match self.reversed_z {
+            true => 0.0,
+            false => 1.0,
+        }
+    }
+
+    /// The world point in normalized device coordinates.
+    pub fn project(&self, world: &Point3<f32>) -> Point3<f32> {
+        self.view_projection_matrix().transform_point(world)
+    }
+
+    /// The world point at normalized device coordinates, `None` when the matrices can't be inverted.
+    pub fn unproject(&self, ndc: &Point3<f32>) -> Option<Point3<f32>> {
+        self.view_projection_matrix()
+            .try_inverse()
+            .map(|inverse| inverse.transform_point(ndc))
+    }
+}
+
+/// The direction of `yaw` and `pitch` in radians, `-z` for both `0`, `yaw` turns towards `+x`.
+pub fn direction(yaw: f32, pitch: f32) -> Vector3<f32> {
+    Vector3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        -pitch.cos() * yaw.cos(),
+    )
+}
+
+/// Keeps the pitch a bit away from straight up and down, where `look_at` has no up direction.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+///
+/// # Orbit controller
+///
+/// Circles around `target` at `distance`: dragging rotates, the wheel moves closer or farther,
+/// [OrbitController::pan] moves the target along the screen.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f32,
+    pub zoom_speed: f32,
+    /// World units per pixel dragged, times the distance.
+    pub pan_speed: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            rotate_speed: 0.005,
+            zoom_speed: 0.001,
+            pan_speed: 0.001,
+        }
+    }
+
+    /// Rotates by a pointer that moved `dx`, `dy` pixels.
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.yaw -= dx * self.rotate_speed;
+        self.pitch = (self.pitch + dy * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves closer for positive deltas, by the same factor at every distance.
+    pub fn zoom(&mut self, delta: f32) {
+        self.distance = (self.distance * (-delta * self.zoom_speed).exp())
+            .clamp(self.min_distance, self.max_distance);
+    }
+
+    /// Moves the target with a pointer that moved `dx`, `dy` pixels.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = direction(self.yaw, self.pitch);
+        let right = forward.cross(&Vector3::y()).normalize();
+        let up = right.cross(&forward);
+        let scale = self.distance * self.pan_speed;
+        self.target += (-right * dx + up * dy) * scale;
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        self.target - direction(self.yaw, self.pitch) * self.distance
+    }
+
+    pub fn apply(&self, camera: &mut Camera3D) {
+        camera.position = self.position();
+        camera.target = self.target;
+        camera.up = Vector3::y();
+    }
+}
+
+///
+/// # Fly controller
+///
+/// Moves with `WASD`, `Space` and `Shift` held down (the `KeyboardEvent.code`s),
+/// the pointer movement turns the camera.
+/// [FlyController::first_person] walks: it moves in the horizontal plane wherever it looks,
+/// and `Space` and `Shift` do nothing.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyController {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// World units per second.
+    pub speed: f32,
+    /// Radians per pixel moved.
+    pub look_speed: f32,
+    pub walk: bool,
+    keys: HashSet<String>,
+}
+
+impl FlyController {
+    pub fn fly(position: Point3<f32>) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 5.0,
+            look_speed: 0.003,
+            walk: false,
+            keys: HashSet::new(),
+        }
+    }
+
+    pub fn first_person(position: Point3<f32>) -> Self {
+        Self {
+            walk: true,
+            ..Self::fly(position)
+        }
+    }
+
+    pub fn key_down(&mut self, code: &str) {
+        self.keys.insert(code.to_string());
+    }
+
+    pub fn key_up(&mut self, code: &str) {
+        self.keys.remove(code);
+    }
+
+    /// Forgets the keys held down, e.g. when the canvas loses focus and their `keyup` never comes.
+    pub fn release_keys(&mut self) {
+        self.keys.clear();
+    }
+
+    /// Turns by a pointer that moved `dx`, `dy` pixels, e.g. `movementX` and `movementY` under pointer lock.
+    pub fn look(&mut self, dx: f32, dy: f32) {
+        self.yaw += dx * self.look_speed;
+        self.pitch = (self.pitch - dy * self.look_speed).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves by the keys held down for `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        let held = |codes: &[&str]| f32::from(codes.iter().any(|code| self.keys.contains(*code)));
+        let forward = match self.walk {
+            true => direction(self.yaw, 0.0),
+            false => direction(self.yaw, self.pitch),
+        };
+        let right = direction(self.yaw, 0.0).cross(&Vector3::y()).normalize();
+
+        let mut movement = forward * (held(&["KeyW", "ArrowUp"]) - held(&["KeyS", "ArrowDown"]))
+            + right * (held(&["KeyD", "ArrowRight"]) - held(&["KeyA", "ArrowLeft"]));
+        if !self.walk {
+            movement += Vector3::y() * (held(&["Space"]) - held(&["ShiftLeft", "ShiftRight"]));
+        }
+
+        if let Some(movement) = movement.try_normalize(f32::EPSILON) {
+            self.position += movement * self.speed * dt;
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera3D) {
+        camera.position = self.position;
+        camera.target = self.position + direction(self.yaw, self.pitch);
+        camera.up = Vector3::y();
+    }
+}
+
+#[cfg(test)]
+fn assert_near(a: Point3<f32>, b: Point3<f32>) {
+    assert!((a - b).norm() < 1e-3, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn camera_3d_perspective() {
+    let mut camera = Camera3D::new(800.0, 600.0);
+    camera.projection = Projection::Perspective {
+        fov_y: 90f32.to_radians(),
+        near: 1.0,
+        far: 100.0,
+    };
+
+    // the camera looks at the origin from +z
+    let center = camera.project(&Point3::origin());
+    assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+    assert_near(Point3::from(camera.forward()), Point3::new(0.0, 0.0, -1.0));
+    let near = camera.project(&Point3::new(0.0, 0.0, 4.0));
+    let far = camera.project(&Point3::new(0.0, 0.0, -95.0));
+    assert!((near.z + 1.0).abs() < 1e-4 && (far.z - 1.0).abs() < 1e-4);
+
+    // 90 degrees vertically: at distance 5 the top of the screen is 5 units up
+    let top = camera.project(&Point3::new(0.0, 5.0, 0.0));
+    assert!((top.y - 1.0).abs() < 1e-4);
+    let right = camera.project(&Point3::new(5.0 * 800.0 / 600.0, 0.0, 0.0));
+    assert!((right.x - 1.0).abs() < 1e-4);
+
+    for world in [Point3::new(1.0, 2.0, -3.0), Point3::new(-0.5, 0.25, 2.0)] {
+        let ndc = camera.project(&world);
+        assert_near(camera.unproject(&ndc).unwrap(), world);
+    }
+}
+
+#[test]
+fn camera_3d_orthographic() {
+    let mut camera = Camera3D::new(400.0, 200.0);
+    camera.projection = Projection::Orthographic {
+        height: 10.0,
+        near: 0.1,
+        far: 50.0,
+    };
+    let corner = camera.project(&Point3::new(10.0, 5.0, 0.0));
+    assert!((corner.x - 1.0).abs() < 1e-5 && (corner.y - 1.0).abs() < 1e-5);
+    // no perspective, distance doesn't move points on screen
+    let behind = camera.project(&Point3::new(10.0, 5.0, -20.0));
+    assert!((behind.x - 1.0).abs() < 1e-5 && behind.z > corner.z);
+}
+
+#[test]
+fn camera_3d_orbit() {
+    let mut orbit = OrbitController::new(Point3::new(1.0, 0.0, 0.0), 10.0);
+    let mut camera = Camera3D::new(800.0, 600.0);
+    orbit.apply(&mut camera);
+    assert_near(camera.position, Point3::new(1.0, 0.0, 10.0));
+
+    orbit.rotate(-FRAC_PI_2 / orbit.rotate_speed, 0.0);
+    orbit.apply(&mut camera);
+    assert_near(camera.position, Point3::new(-9.0, 0.0, 0.0));
+    // the target stays in the middle of the screen
+    let center = camera.project(&orbit.target);
+    assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+
+    orbit.rotate(0.0, 10_000.0);
+    assert!(orbit.pitch < FRAC_PI_2);
+    assert!(((orbit.position() - orbit.target).norm() - 10.0).abs() < 1e-4);
+
+    orbit.zoom(1e6);
+    assert_eq!(orbit.distance, orbit.min_distance);
+    orbit.zoom(-1e6);
+    assert_eq!(orbit.distance, orbit.max_distance);
+
+    let mut orbit = OrbitController::new(Point3::origin(), 1.0);
+    orbit.pan(-1.0 / orbit.pan_speed, 0.0);
+    assert_near(orbit.target, Point3::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn camera_3d_fly_and_walk() {
+    let mut fly = FlyController::fly(Point3::origin());
+    fly.key_down("KeyW");
+    fly.update(1.0);
+    assert_near(fly.position, Point3::new(0.0, 0.0, -5.0));
+
+    // diagonal moves aren't faster
+    fly.key_down("KeyD");
+    fly.update(1.0);
+    assert!(((fly.position - Point3::new(0.0, 0.0, -5.0)).norm() - 5.0).abs() < 1e-4);
+    fly.release_keys();
+
+    // looking up flies up, walking stays on the ground
+    fly.look(0.0, -FRAC_PI_2 / 2.0 / fly.look_speed);
+    let mut walk = FlyController::first_person(Point3::origin());
+    walk.pitch = fly.pitch;
+    fly.key_down("KeyW");
+    walk.key_down("KeyW");
+    walk.key_down("Space");
+    fly.update(1.0);
+    walk.update(1.0);
+    assert!(fly.position.y > 0.0);
+    assert_near(walk.position, Point3::new(0.0, 0.0, -5.0));
+
+    // turning right looks towards +x
+    let mut camera = Camera3D::new(800.0, 600.0);
+    walk.pitch = 0.0;
+    walk.look(FRAC_PI_2 / walk.look_speed, 0.0);
+    walk.apply(&mut camera);
+    assert_near(Point3::from(camera.forward()), Point3::new(1.0, 0.0, 0.0));
+    walk.key_up("KeyW");
+    walk.update(1.0);
+    assert_near(walk.position, Point3::new(0.0, 0.0, -5.0));
+}
+
+#[test]
+fn camera_3d_reversed_z() {
+    let mut camera = Camera3D::new(800.0, 600.0);
+    camera.projection = Projection::Perspective {
+        fov_y: 90f32.to_radians(),
+        near: 1.0,
+        far: 100.0,
+    };
+    camera.reversed_z = true;
+    assert_eq!(camera.depth_function(), DepthFunction::Gequal);
+    assert_eq!(camera.clear_depth(), 0.0);
+
+    // the camera is at z = 5
+    let near = camera.project(&Point3::new(0.0, 0.0, 4.0));
+    let middle = camera.project(&Point3::new(0.0, 0.0, -45.0));
+    let far = camera.project(&Point3::new(0.0, 0.0, -95.0));
+    assert!((near.z - 1.0).abs() < 1e-5 && far.z.abs() < 1e-5);
+    assert!(near.z > middle.z && middle.z > far.z);
+    // x and y don't change
+    let top = camera.project(&Point3::new(0.0, 5.0, 0.0));
+    assert!((top.y - 1.0).abs() < 1e-4);
+    let world = Point3::new(1.0, 2.0, -3.0);
+    assert_near(camera.unproject(&camera.project(&world)).unwrap(), world);
+
+    camera.projection = Projection::Orthographic {
+        height: 10.0,
+        near: 1.0,
+        far: 11.0,
+    };
+    let near = camera.project(&Point3::new(0.0, 0.0, 4.0));
+    let middle = camera.project(&Point3::new(0.0, 0.0, -1.0));
+    let far = camera.project(&Point3::new(0.0, 0.0, -6.0));
+    assert!((near.z - 1.0).abs() < 1e-5 && (middle.z - 0.5).abs() < 1e-5 && far.z.abs() < 1e-5);
+}
+
+#[test]
+fn camera_3d_transforms_layout() {
+    use memoffset::offset_of;
+
+    // two mat4 in a std140 block
+    assert_eq!(std::mem::size_of::<Transforms>(), 128);
+    assert_eq!(offset_of!(Transforms, projection_matrix), 0);
+    assert_eq!(offset_of!(Transforms, view_matrix), 64);
+
+    let mut camera = Camera3D::new(800.0, 600.0);
+    camera.position = Point3::new(3.0, 4.0, 5.0);
+    let transforms = camera.transforms();
+    assert_eq!(transforms.as_bytes().len(), 128);
+    assert_eq!(
+        transforms.as_bytes()[64..68],
+        camera.view_matrix()[(0, 0)].to_ne_bytes()
+    );
+
+    // what the shader computes: projection_matrix * view_matrix * position
+    let matrix = |values: &[f32; 16]| Matrix4::from_column_slice(values);
+    let world = Point3::new(0.5, -1.0, 2.0);
+    let clip = matrix(&transforms.projection_matrix)
+        * matrix(&transforms.view_matrix)
+        * world.to_homogeneous();
+    assert_near(
+        Point3::from_homogeneous(clip).unwrap(),
+        camera.project(&world),
+    );
+}
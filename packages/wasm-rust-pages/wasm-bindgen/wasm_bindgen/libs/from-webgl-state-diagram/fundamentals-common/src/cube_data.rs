@@ -4,11 +4,12 @@ use web_sys::{
     HtmlCanvasElement, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlTexture,
     WebGlVertexArrayObject,
 };
-use webgl_common::{slice_as_u8_slice, DataType};
+use webgl_common::{slice_as_u8_slice, DataType, GpuPod};
 
 use crate::set_element_array_buffer;
 
 #[repr(C)]
+#[derive(Clone, Copy, GpuPod)]
 pub struct Vertex {
     position: [f32; 3],
     normal: [f32; 3],
@@ -2,13 +2,14 @@ use wasm_bindgen::JsCast;
 use web_sys::{
     CanvasRenderingContext2d, HtmlCanvasElement, WebGl2RenderingContext, WebGlBuffer, WebGlTexture,
 };
-use webgl_common::slice_as_u8_slice;
+use webgl_common::{slice_as_u8_slice, GpuPod};
 
+pub mod camera;
 pub mod cube_data;
 pub mod cube_data_old;
 pub mod uniform;
 
-pub fn set_array_buffer<T>(
+pub fn set_array_buffer<T: GpuPod>(
     gl: &WebGl2RenderingContext,
     data: &[T],
     location: u32,
@@ -38,7 +39,7 @@ pub fn set_array_buffer<T>(
     buffer
 }
 
-pub fn set_element_array_buffer<T>(
+pub fn set_element_array_buffer<T: GpuPod>(
     gl: &WebGl2RenderingContext,
     data: &[T],
     usage: u32,
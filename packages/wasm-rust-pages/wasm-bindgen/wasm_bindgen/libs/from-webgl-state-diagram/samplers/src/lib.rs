@@ -1,11 +1,12 @@
 use std::{f32::consts::PI, rc::Rc};
 
 use fundamentals_common::{
+    camera::{Camera3D, FlyController, Projection},
     create_checker_texture,
     cube_data_old::get_cube_data,
     uniform::{SetUniform, Uniform},
 };
-use nalgebra::{Matrix4, Perspective3, Vector3};
+use nalgebra::{Matrix4, Point3, Vector3};
 use wasm_bindgen::prelude::*;
 use web_sys::{
     console, WebGl2RenderingContext, WebGlProgram, WebGlSampler, WebGlTexture,
@@ -23,6 +24,9 @@ pub struct App {
     checker_texture: WebGlTexture,
     linear_sampler: WebGlSampler,
     nearest_sampler: WebGlSampler,
+    camera: Camera3D,
+    /// Starts where the camera of the original demo is.
+    fly: FlyController,
 }
 
 struct Uniforms {
@@ -170,6 +174,14 @@ impl App {
             nearest_sampler
         };
 
+        let mut camera = Camera3D::new(canvas.client_width() as f32, canvas.client_height() as f32);
+        camera.projection = Projection::Perspective {
+            fov_y: PI / 4.0,
+            near: 0.1,
+            far: 20.0,
+        };
+        let fly = FlyController::fly(Point3::origin());
+
         Self {
             uniforms: Uniforms {
                 projection: Uniform::new(
@@ -194,6 +206,8 @@ impl App {
                 ),
             },
             checker_texture: create_checker_texture(&gl),
+            camera,
+            fly,
             canvas,
             vao: cube_vertex_array,
             program,
@@ -203,8 +217,32 @@ impl App {
         }
     }
 
+    /// `code` is the `KeyboardEvent.code`.
+    pub fn key_down(&mut self, code: &str) {
+        self.fly.key_down(code);
+    }
+
+    pub fn key_up(&mut self, code: &str) {
+        self.fly.key_up(code);
+    }
+
+    /// Call it when the canvas loses the focus, its `keyup`s won't come.
+    pub fn release_keys(&mut self) {
+        self.fly.release_keys();
+    }
+
+    /// Turns by a pointer that moved `dx`, `dy` pixels.
+    pub fn look(&mut self, dx: f32, dy: f32) {
+        self.fly.look(dx, dy);
+    }
+
+    /// Moves by the keys held down for `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        self.fly.update(dt);
+    }
+
     /// is rendering code.
-    pub fn render(&self) {
+    pub fn render(&mut self) {
         {
             // Set up WebGL state before rendering
             // and cleanup the canvas
@@ -248,19 +286,22 @@ impl App {
             }
             {
                 // set up projection matrix
-                let projection = Perspective3::new(
-                    self.canvas.client_width() as f32 / self.canvas.client_height() as f32,
-                    PI / 4.0,
-                    0.1,
-                    10.0,
+                self.camera.resize(
+                    self.canvas.client_width() as f32,
+                    self.canvas.client_height() as f32,
                 );
+                self.fly.apply(&mut self.camera);
 
-                self.uniforms.projection.set(projection.as_matrix());
+                self.uniforms
+                    .projection
+                    .set(&self.camera.projection_matrix());
             }
+            let view = self.camera.view_matrix();
             {
                 // draw left cube
 
-                let model_view = Matrix4::new_translation(&Vector3::new(-2.0, 0.0, -4.0))
+                let model_view = view
+                    * Matrix4::new_translation(&Vector3::new(-2.0, 0.0, -4.0))
                     * Matrix4::from_euler_angles(0.5, 0.0, 0.0)
                     * Matrix4::from_euler_angles(0.0, 0.5, 0.0);
 
@@ -280,7 +321,8 @@ impl App {
             {
                 // draw right cube
 
-                let model_view = Matrix4::new_translation(&Vector3::new(2.0, 0.0, -4.0))
+                let model_view = view
+                    * Matrix4::new_translation(&Vector3::new(2.0, 0.0, -4.0))
                     * Matrix4::from_euler_angles(0.5, 0.0, 0.0)
                     * Matrix4::from_euler_angles(0.0, 0.8, 0.0);
 
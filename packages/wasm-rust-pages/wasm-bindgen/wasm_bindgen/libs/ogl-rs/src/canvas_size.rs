@@ -0,0 +1,237 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+
+///
+/// Size of the canvas in CSS pixels together with the device pixel ratio it is rendered at.
+/// The drawing buffer is `logical * dpr` physical pixels, so the output stays sharp on HiDPI displays.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasSize {
+    /// Width in CSS pixels.
+    pub width: f64,
+    /// Height in CSS pixels.
+    pub height: f64,
+    /// Physical pixels per CSS pixel, e.g. `1.5` or `2.0`.
+    pub dpr: f64,
+}
+
+impl CanvasSize {
+    pub fn new(width: f64, height: f64, dpr: f64) -> Self {
+        Self { width, height, dpr }
+    }
+
+    pub fn with_dpr(self, dpr: f64) -> Self {
+        Self { dpr, ..self }
+    }
+
+    /// Size in CSS pixels.
+    pub fn logical(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+
+    /// Size of the drawing buffer. Never smaller than one pixel in either direction.
+    pub fn physical(&self) -> (u32, u32) {
+        (
+            to_physical_length(self.width, self.dpr),
+            to_physical_length(self.height, self.dpr),
+        )
+    }
+
+    /// Converts a position in CSS pixels to drawing-buffer pixels.
+    pub fn to_physical(self, x: f64, y: f64) -> (f64, f64) {
+        (x * self.dpr, y * self.dpr)
+    }
+
+    /// Converts a position in drawing-buffer pixels to CSS pixels.
+    pub fn to_logical(self, x: f64, y: f64) -> (f64, f64) {
+        (x / self.dpr, y / self.dpr)
+    }
+}
+
+impl Default for CanvasSize {
+    fn default() -> Self {
+        Self {
+            width: 300.0,
+            height: 150.0,
+            dpr: 1.0,
+        }
+    }
+}
+
+fn to_physical_length(logical: f64, dpr: f64) -> u32 {
+    (logical * dpr).round().max(1.0) as u32
+}
+
+///
+/// Calls back with the new `devicePixelRatio` whenever it changes,
+/// e.g. when the window is moved to another monitor or the page is zoomed.
+///
+/// A `(resolution: Xdppx)` media query only fires once, when it stops matching,
+/// so the listener moves to a query for the new ratio every time it fires.
+/// The listener is removed when the watcher is dropped.
+///
+pub struct DprWatcher {
+    inner: Rc<RefCell<DprWatcherInner>>,
+    _closure: Closure<dyn FnMut()>,
+}
+
+struct DprWatcherInner {
+    window: web_sys::Window,
+    media_query_list: Option<web_sys::MediaQueryList>,
+    listener: Option<js_sys::Function>,
+}
+
+impl DprWatcher {
+    pub fn new(callback: impl FnMut(f64) + 'static) -> Result<Self, JsValue> {
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("no global `window`"))?;
+
+        let inner = Rc::new(RefCell::new(DprWatcherInner {
+            window,
+            media_query_list: None,
+            listener: None,
+        }));
+        let callback = RefCell::new(callback);
+
+        let closure = Closure::<dyn FnMut()>::new({
+            let inner = inner.clone();
+            move || {
+                let dpr = {
+                    let mut inner = inner.borrow_mut();
+                    if let Err(error) = inner.watch() {
+                        web_sys::console::error_2(
+                            &"failed to watch devicePixelRatio".into(),
+                            &error,
+                        );
+                    }
+                    inner.window.device_pixel_ratio()
+                };
+                // released, so the callback can read or drop the watcher
+                (callback.borrow_mut())(dpr);
+            }
+        });
+
+        {
+            let mut inner = inner.borrow_mut();
+            inner.listener = Some(closure.as_ref().unchecked_ref::<js_sys::Function>().clone());
+            inner.watch()?;
+        }
+
+        Ok(Self {
+            inner,
+            _closure: closure,
+        })
+    }
+
+    pub fn dpr(&self) -> f64 {
+        self.inner.borrow().window.device_pixel_ratio()
+    }
+}
+
+impl DprWatcherInner {
+    fn watch(&mut self) -> Result<(), JsValue> {
+        self.unwatch()?;
+
+        let Some(listener) = &self.listener else {
+            return Ok(());
+        };
+        let query = format!("(resolution: {}dppx)", self.window.device_pixel_ratio());
+        let media_query_list = self
+            .window
+            .match_media(&query)?
+            .ok_or_else(|| JsValue::from_str("matchMedia is not supported"))?;
+        media_query_list.add_event_listener_with_callback("change", listener)?;
+        self.media_query_list = Some(media_query_list);
+        Ok(())
+    }
+
+    fn unwatch(&mut self) -> Result<(), JsValue> {
+        if let (Some(media_query_list), Some(listener)) =
+            (self.media_query_list.take(), &self.listener)
+        {
+            media_query_list.remove_event_listener_with_callback("change", listener)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DprWatcher {
+    fn drop(&mut self) {
+        if let Err(error) = self.inner.borrow_mut().unwatch() {
+            web_sys::console::error_2(&"failed to remove devicePixelRatio listener".into(), &error);
+        }
+    }
+}
+
+///
+/// Calls back with the CSS size of an element whenever it changes, through a
+/// [ResizeObserver](https://developer.mozilla.org/en-US/docs/Web/API/ResizeObserver).
+///
+/// Observe the element the canvas fills rather than the canvas,
+/// [Renderer::set_size](crate::renderer::Renderer::set_size) sets the size of the canvas itself.
+/// The observer is disconnected when the watcher is dropped.
+///
+pub struct ResizeWatcher {
+    observer: web_sys::ResizeObserver,
+    _closure: Closure<dyn FnMut(js_sys::Array)>,
+}
+
+impl ResizeWatcher {
+    /// `callback` gets the width and height of the content box, also once with the current size.
+    pub fn new(
+        element: &web_sys::Element,
+        mut callback: impl FnMut(f64, f64) + 'static,
+    ) -> Result<Self, JsValue> {
+        let closure = Closure::<dyn FnMut(js_sys::Array)>::new(move |entries: js_sys::Array| {
+            // only the latest size matters
+            if let Some(entry) = entries
+                .iter()
+                .last()
+                .and_then(|entry| entry.dyn_into::<web_sys::ResizeObserverEntry>().ok())
+            {
+                let rect = entry.content_rect();
+                callback(rect.width(), rect.height());
+            }
+        });
+
+        let observer = web_sys::ResizeObserver::new(closure.as_ref().unchecked_ref())?;
+        observer.observe(element);
+
+        Ok(Self {
+            observer,
+            _closure: closure,
+        })
+    }
+}
+
+impl Drop for ResizeWatcher {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+#[test]
+fn canvas_size_physical() {
+    let size = CanvasSize::new(300.0, 150.0, 1.0);
+    assert_eq!(size.physical(), (300, 150));
+    assert_eq!(size.logical(), (300.0, 150.0));
+
+    let size = size.with_dpr(2.0);
+    assert_eq!(size.physical(), (600, 300));
+    assert_eq!(size.logical(), (300.0, 150.0));
+
+    // fractional ratios round to the nearest pixel
+    let size = CanvasSize::new(101.0, 33.0, 1.25);
+    assert_eq!(size.physical(), (126, 41));
+
+    // the drawing buffer never collapses to zero
+    let size = CanvasSize::new(0.0, 0.2, 1.5);
+    assert_eq!(size.physical(), (1, 1));
+}
+
+#[test]
+fn canvas_size_conversions() {
+    let size = CanvasSize::new(800.0, 600.0, 1.5);
+    assert_eq!(size.to_physical(10.0, 20.0), (15.0, 30.0));
+    assert_eq!(size.to_logical(15.0, 30.0), (10.0, 20.0));
+}
@@ -1,6 +1,11 @@
-mod geometry;
-mod program;
-mod renderer;
+pub mod canvas_size;
+pub mod geometry;
+pub mod pipeline_state;
+pub mod post;
+pub mod program;
+pub mod program_cache;
+pub mod render_target;
+pub mod renderer;
 
 use webgl_common::{BlendEquation, BlendFactor};
 
@@ -12,8 +17,36 @@ pub struct BlendFuncState {
     dst_alpha: Option<BlendFactor>,
 }
 
+impl BlendFuncState {
+    /// The "over" operator for colors that are, or are not, premultiplied by alpha.
+    pub fn transparent(premultiplied_alpha: bool) -> Self {
+        let src = if premultiplied_alpha {
+            BlendFactor::One
+        } else {
+            BlendFactor::SrcAlpha
+        };
+        Self {
+            src,
+            dst: BlendFactor::OneMinusSrcAlpha,
+            src_alpha: Some(BlendFactor::One),
+            dst_alpha: Some(BlendFactor::OneMinusSrcAlpha),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BlendEquationState {
     mode_rgb: BlendEquation,
     mode_alpha: Option<BlendEquation>,
 }
+
+#[test]
+fn transparent_blend_func() {
+    let straight = BlendFuncState::transparent(false);
+    assert_eq!(straight.src, BlendFactor::SrcAlpha);
+    assert_eq!(straight.dst, BlendFactor::OneMinusSrcAlpha);
+
+    let premultiplied = BlendFuncState::transparent(true);
+    assert_eq!(premultiplied.src, BlendFactor::One);
+    assert_eq!(premultiplied.dst, BlendFactor::OneMinusSrcAlpha);
+}
@@ -0,0 +1,145 @@
+use webgl_common::{CullFaceMode, DepthFunction, FrontFaceDirection, ScissorBox, StencilOp};
+
+use crate::{BlendEquationState, BlendFuncState};
+
+///
+/// All fixed-function state a [Program](crate::program::Program) wants applied before drawing.
+/// The `Renderer` diffs it against the current GL state and only issues the calls that changed.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PipelineState {
+    /// `None` disables `CULL_FACE`.
+    pub cull_face: Option<CullFaceMode>,
+    pub front_face: FrontFaceDirection,
+
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_func: DepthFunction,
+
+    /// `None` disables `BLEND`.
+    pub blend_func: Option<BlendFuncState>,
+    pub blend_equation: Option<BlendEquationState>,
+
+    /// `None` disables `STENCIL_TEST`.
+    pub stencil: Option<StencilState>,
+
+    /// `None` disables `POLYGON_OFFSET_FILL`.
+    pub polygon_offset: Option<PolygonOffsetState>,
+
+    /// Which of the red, green, blue and alpha channels are written.
+    pub color_mask: [bool; 4],
+
+    /// `None` disables `SCISSOR_TEST`.
+    pub scissor: Option<ScissorBox>,
+
+    /// `None` disables `SAMPLE_COVERAGE`.
+    pub sample_coverage: Option<SampleCoverageState>,
+    pub alpha_to_coverage: bool,
+}
+
+impl Default for PipelineState {
+    fn default() -> Self {
+        Self {
+            cull_face: Some(CullFaceMode::Back),
+            front_face: FrontFaceDirection::Ccw,
+            depth_test: true,
+            depth_write: true,
+            depth_func: DepthFunction::Less,
+            blend_func: None,
+            blend_equation: None,
+            stencil: None,
+            polygon_offset: None,
+            color_mask: [true, true, true, true],
+            scissor: None,
+            sample_coverage: None,
+            alpha_to_coverage: false,
+        }
+    }
+}
+
+/// [stencilFuncSeparate](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilFuncSeparate)
+/// and [stencilOpSeparate](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilOpSeparate)
+/// state for the front and back faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StencilState {
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+}
+
+impl StencilState {
+    /// Uses the same stencil state for front and back faces.
+    pub fn both(face: StencilFaceState) -> Self {
+        Self {
+            front: face,
+            back: face,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilFaceState {
+    /// Comparison between `reference & read_mask` and `stored & read_mask`.
+    pub func: DepthFunction,
+    pub reference: i32,
+    pub read_mask: u32,
+    /// [stencilMaskSeparate](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilMaskSeparate)
+    pub write_mask: u32,
+    /// Stencil test fails.
+    pub fail: StencilOp,
+    /// Stencil test passes, depth test fails.
+    pub depth_fail: StencilOp,
+    /// Both stencil and depth tests pass.
+    pub pass: StencilOp,
+}
+
+impl Default for StencilFaceState {
+    fn default() -> Self {
+        Self {
+            func: DepthFunction::Always,
+            reference: 0,
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// [polygonOffset](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/polygonOffset)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PolygonOffsetState {
+    pub factor: f32,
+    pub units: f32,
+}
+
+/// [sampleCoverage](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/sampleCoverage)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleCoverageState {
+    pub value: f32,
+    pub invert: bool,
+}
+
+impl Default for SampleCoverageState {
+    fn default() -> Self {
+        Self {
+            value: 1.0,
+            invert: false,
+        }
+    }
+}
+
+#[test]
+fn stencil_state_both() {
+    let face = StencilFaceState {
+        func: DepthFunction::Equal,
+        reference: 1,
+        pass: StencilOp::Replace,
+        ..StencilFaceState::default()
+    };
+    let stencil = StencilState::both(face);
+
+    assert_eq!(stencil.front, stencil.back);
+    assert_eq!(stencil.front.read_mask, 0xFF);
+    assert_ne!(stencil, StencilState::default());
+}
@@ -0,0 +1,218 @@
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext, WebGlVertexArrayObject};
+use webgl_common::{FramebufferTarget, TextureUnit};
+
+use crate::{
+    program::{Program, UniformValue},
+    render_target::{RenderTarget, RenderTargetOptions},
+    renderer::Renderer,
+};
+
+/// Vertex shader for every pass: one triangle covering the screen, without any vertex buffer.
+/// Fragment shaders get the `v_uv` varying, the previous result in `t_map` and its size in `u_resolution`.
+const FULLSCREEN_VERTEX: &str = r#"#version 300 es
+out vec2 v_uv;
+
+void main() {
+    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    v_uv = position;
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+/// A full-screen fragment shader applied to the output of the previous pass.
+pub struct Pass<'a> {
+    pub program: Program<'a>,
+    pub enabled: bool,
+}
+
+impl<'a> Pass<'a> {
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        self.program.set_uniform(name, value);
+    }
+}
+
+///
+/// A chain of [Pass]es that ping-pong between two [RenderTarget]s.
+/// The scene is drawn into the first target, every pass reads the previous result,
+/// and the last enabled pass writes to the final target (or the canvas).
+///
+pub struct Post<'a> {
+    gl: &'a WebGl2RenderingContext,
+    targets: [RenderTarget<'a>; 2],
+    read: usize,
+    vertex_array: WebGlVertexArrayObject,
+    pub passes: Vec<Pass<'a>>,
+}
+
+impl<'a> Post<'a> {
+    pub fn new(renderer: &mut Renderer<'a>, options: RenderTargetOptions) -> Result<Self, JsValue> {
+        let gl = renderer.gl();
+        let targets = [
+            RenderTarget::new(renderer, options)?,
+            RenderTarget::new(renderer, options)?,
+        ];
+        let vertex_array = gl
+            .create_vertex_array()
+            .ok_or("Unable to create vertex array object")?;
+
+        Ok(Self {
+            gl,
+            targets,
+            read: 0,
+            vertex_array,
+            passes: Vec::new(),
+        })
+    }
+
+    /// Adds a pass running `fragment` and returns its index in [Post::passes].
+    pub fn add_pass(&mut self, fragment: &str) -> Result<usize, JsValue> {
+        let mut program = Program::new(self.gl, FULLSCREEN_VERTEX, fragment)?;
+        program.state.cull_face = None;
+        program.state.depth_test = false;
+        program.state.depth_write = false;
+
+        self.passes.push(Pass {
+            program,
+            enabled: true,
+        });
+        Ok(self.passes.len() - 1)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        for target in &mut self.targets {
+            target.resize(width, height);
+        }
+    }
+
+    /// The target the scene is drawn into, also the input of the first pass.
+    pub fn scene_target(&self) -> &RenderTarget<'a> {
+        &self.targets[self.read]
+    }
+
+    ///
+    /// Draws the scene with `draw_scene` into the scene target, then runs every enabled pass.
+    /// The last pass renders into `target`, or the canvas when it is `None`.
+    ///
+    pub fn render(
+        &mut self,
+        renderer: &mut Renderer<'a>,
+        draw_scene: impl FnOnce(&mut Renderer<'a>),
+        target: Option<&RenderTarget<'a>>,
+    ) {
+        let enabled = self.passes.iter().filter(|pass| pass.enabled).count();
+        if enabled == 0 {
+            bind_output(renderer, target);
+            draw_scene(renderer);
+            return;
+        }
+
+        self.targets[self.read].bind(renderer);
+        self.gl.clear(
+            WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
+        );
+        draw_scene(renderer);
+
+        let steps = plan_passes(self.read, enabled);
+        let passes = self.passes.iter_mut().filter(|pass| pass.enabled);
+
+        self.gl.bind_vertex_array(Some(&self.vertex_array));
+        for (pass, step) in passes.zip(steps) {
+            let (width, height) = match step.write {
+                Some(write) => {
+                    self.targets[write].bind(renderer);
+                    (self.targets[write].width(), self.targets[write].height())
+                }
+                None => bind_output(renderer, target),
+            };
+
+            self.targets[step.read].bind_texture(renderer, 0, TextureUnit::Texture0);
+            pass.set_uniform("t_map", UniformValue::Int(0));
+            pass.set_uniform(
+                "u_resolution",
+                UniformValue::Vec2([width as f32, height as f32]),
+            );
+
+            renderer.use_program(&pass.program);
+            self.gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+
+            if let Some(write) = step.write {
+                self.read = write;
+            }
+        }
+        self.gl.bind_vertex_array(None);
+    }
+}
+
+impl Drop for Post<'_> {
+    fn drop(&mut self) {
+        self.gl.delete_vertex_array(Some(&self.vertex_array));
+    }
+}
+
+/// Binds `target`, or the canvas when it is `None`, and returns its size.
+fn bind_output(renderer: &mut Renderer, target: Option<&RenderTarget>) -> (u32, u32) {
+    match target {
+        Some(target) => {
+            target.bind(renderer);
+            (target.width(), target.height())
+        }
+        None => {
+            renderer.bind_framebuffer(FramebufferTarget::Framebuffer, None);
+            let (width, height) = renderer.physical_size();
+            renderer.set_viewport(0, 0, width as i32, height as i32);
+            (width, height)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PassStep {
+    /// Index of the target to sample.
+    read: usize,
+    /// Index of the target to render into, `None` for the final output.
+    write: Option<usize>,
+}
+
+/// Which of the two targets every pass reads from and writes to, starting with the scene in `read`.
+fn plan_passes(read: usize, count: usize) -> impl Iterator<Item = PassStep> {
+    (0..count).map(move |i| {
+        let read = (read + i) % 2;
+        let write = if i + 1 == count { None } else { Some(1 - read) };
+        PassStep { read, write }
+    })
+}
+
+#[test]
+fn ping_pong_passes() {
+    let steps = plan_passes(0, 3).collect::<Vec<_>>();
+    assert_eq!(
+        steps,
+        vec![
+            PassStep {
+                read: 0,
+                write: Some(1)
+            },
+            PassStep {
+                read: 1,
+                write: Some(0)
+            },
+            PassStep {
+                read: 0,
+                write: None
+            },
+        ]
+    );
+
+    // a single pass goes straight to the output
+    let steps = plan_passes(1, 1).collect::<Vec<_>>();
+    assert_eq!(
+        steps,
+        vec![PassStep {
+            read: 1,
+            write: None
+        }]
+    );
+
+    assert_eq!(plan_passes(0, 0).count(), 0);
+}
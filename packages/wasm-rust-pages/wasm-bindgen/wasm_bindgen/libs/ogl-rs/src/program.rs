@@ -1,11 +1,19 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
 use wasm_bindgen::prelude::*;
 use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
-use webgl_common::{CullFaceMode, DepthFunction, FrontFaceDirection};
 
-use crate::{BlendEquationState, BlendFuncState};
+use crate::{pipeline_state::PipelineState, BlendEquationState, BlendFuncState};
 
-static mut ID: u32 = 1;
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+fn next_id() -> u32 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct UniformInfo {
@@ -17,61 +25,40 @@ pub struct UniformInfo {
     pub struct_property: String,
 }
 
-pub struct Program<'a> {
+///
+/// A linked GL program with its uniform and attribute locations.
+/// Shared between every [Program] created from the same sources, see [ProgramCache](crate::program_cache::ProgramCache).
+/// The GL program is deleted when the last handle is dropped.
+///
+pub struct CompiledProgram<'a> {
     gl: &'a WebGl2RenderingContext,
-    uniforms: HashMap<String, JsValue>,
     id: u32,
 
-    /// this 7 variables are the state of the program
-    pub cull_face: Option<CullFaceMode>,
-    pub depth_test: bool,
-    pub blend_func: Option<BlendFuncState>,
-    pub front_face: FrontFaceDirection,
-    pub depth_write: bool,
-    pub depth_func: DepthFunction,
-    pub blend_equation: Option<BlendEquationState>,
-
-    transparent: bool,
-
     pub program: WebGlProgram,
     uniform_locations: HashMap<UniformInfo, WebGlUniformLocation>,
     attribute_locations: HashMap<String, i32>,
     attribute_order: String,
+    /// The values on the GL program, shared by every [Program] using it.
+    uploaded_uniforms: RefCell<UploadedUniforms>,
 }
 
-impl<'a> Program<'a> {
-    pub fn new(
+impl<'a> CompiledProgram<'a> {
+    pub fn compile(
         gl: &'a WebGl2RenderingContext,
-        vertex: &String,
-        fragment: &String,
+        vertex: &str,
+        fragment: &str,
     ) -> Result<Self, JsValue> {
-        let uniforms = HashMap::new();
-        let transparent = false;
-        let cull_face = Some(CullFaceMode::Back);
-        let front_face = FrontFaceDirection::Ccw;
-
-        let depth_test = true;
-        let stencil_test = false;
-        let depth_write = true;
-        let depth_func = DepthFunction::Less;
-
         if gl.canvas().is_none() {
             return Err(JsValue::from_str(
                 "gl not passed as first argument to Program",
             ));
         }
 
-        let id = unsafe {
-            let current_id = ID;
-            ID += 1;
-            current_id
-        };
-
         if vertex.is_empty() {
-            web_sys::console::warn_1(&JsValue::from_str("vertex shader not supplied"));
+            warn("vertex shader not supplied");
         }
         if fragment.is_empty() {
-            web_sys::console::warn_1(&JsValue::from_str("fragment shader not supplied"));
+            warn("fragment shader not supplied");
         }
 
         // Compile vertex shader and log errors
@@ -83,6 +70,7 @@ impl<'a> Program<'a> {
             gl.compile_shader(&vertex_shader);
             if let Some(log) = gl.get_shader_info_log(&vertex_shader) {
                 if !log.is_empty() {
+                    gl.delete_shader(Some(&vertex_shader));
                     return Err(JsValue::from_str(&format!(
                         "{}\nVertex Shader\n{}",
                         log,
@@ -102,6 +90,8 @@ impl<'a> Program<'a> {
             gl.compile_shader(&fragment_shader);
             if let Some(log) = gl.get_shader_info_log(&fragment_shader) {
                 if !log.is_empty() {
+                    gl.delete_shader(Some(&vertex_shader));
+                    gl.delete_shader(Some(&fragment_shader));
                     return Err(JsValue::from_str(&format!(
                         "{}\nFragment Shader\n{}",
                         log,
@@ -123,9 +113,11 @@ impl<'a> Program<'a> {
                 .as_bool()
                 .unwrap_or(false)
             {
-                return Err(JsValue::from_str(
-                    &gl.get_program_info_log(&program).unwrap_or_default(),
-                ));
+                let log = gl.get_program_info_log(&program).unwrap_or_default();
+                gl.delete_program(Some(&program));
+                gl.delete_shader(Some(&vertex_shader));
+                gl.delete_shader(Some(&fragment_shader));
+                return Err(JsValue::from_str(&log));
             }
             program
         };
@@ -182,57 +174,34 @@ impl<'a> Program<'a> {
 
         Ok(Self {
             gl,
-            uniforms,
-            id,
-            transparent,
-            cull_face,
-            front_face,
-            depth_test,
-            depth_write,
-            depth_func,
-            blend_func: None,
-            blend_equation: None,
+            id: next_id(),
             program,
             uniform_locations,
             attribute_locations,
             attribute_order,
+            uploaded_uniforms: RefCell::default(),
         })
     }
 
-    // pub fn set_transparent(&mut self, transparent: bool) {
-    //     self.transparent = transparent;
-
-    //     if self.transparent && self.blend_func.is_none() {
-    //         if self.gl.renderer.premultiplied_alpha.unwrap_or(false)
-    //         {
-    //             self.set_blend_func(BlendFuncState {
-    //                 src: BlendFunc::One,
-    //                 dst: BlendFunc::OneMinusSrcAlpha,
-    //                 src_alpha: None,
-    //                 dst_alpha: None,
-    //             });
-    //         } else {
-    //             self.set_blend_func(BlendFuncState {
-    //                 src: BlendFunc::SrcAlpha,
-    //                 dst: BlendFunc::OneMinusSrcAlpha,
-    //                 src_alpha: None,
-    //                 dst_alpha: None,
-    //             });
-    //         }
-    //     } else if !self.transparent {
-    //         self.blend_func = None;
-    //     }
-    // }
+    pub fn id(&self) -> u32 {
+        self.id
+    }
 
-    pub fn set_blend_func(&mut self, blend_func: BlendFuncState) {
-        self.blend_func = Some(blend_func);
-        if blend_func.src as u32 != 0 {
-            self.transparent = true;
-        }
+    pub fn attribute_location(&self, name: &str) -> Option<i32> {
+        self.attribute_locations.get(name).copied()
     }
 
-    pub fn set_blend_equation(&mut self, blend_equation: BlendEquationState) {
-        self.blend_equation = Some(blend_equation);
+    /// The active attribute names in location order, joined: programs with the same order
+    /// can share a vertex array object.
+    pub fn attribute_order(&self) -> &str {
+        &self.attribute_order
+    }
+
+    pub fn uniform_location(&self, name: &str) -> Option<&WebGlUniformLocation> {
+        self.uniform_locations
+            .iter()
+            .find(|(info, _)| info.uniform_name == name)
+            .map(|(_, location)| location)
     }
 
     pub fn remove(&self) {
@@ -240,95 +209,145 @@ impl<'a> Program<'a> {
     }
 }
 
-impl Drop for Program<'_> {
+impl Drop for CompiledProgram<'_> {
     fn drop(&mut self) {
         self.remove();
     }
 }
 
-fn set_uniform(
-    gl: &WebGl2RenderingContext,
-    type_: u32,
-    location: &WebGlUniformLocation,
-    value: impl Into<JsValue>,
-) {
-    match type_ {
-        WebGl2RenderingContext::FLOAT => {
-            if let Some(value) = value.into().as_f64() {
-                gl.uniform1f(Some(location), value as f32);
-            }
-        }
-        WebGl2RenderingContext::FLOAT_VEC2 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform2fv_with_f32_array(Some(location), &[values as f32]);
-            }
-        }
-        WebGl2RenderingContext::FLOAT_VEC3 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform3fv_with_f32_array(Some(location), &[values as f32]);
-            }
-        }
-        WebGl2RenderingContext::FLOAT_VEC4 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform4fv_with_f32_array(Some(location), &[values as f32]);
-            }
-        }
-        WebGl2RenderingContext::INT => {
-            if let Some(value) = value.into().as_f64() {
-                gl.uniform1i(Some(location), value as i32);
-            }
-        }
-        WebGl2RenderingContext::INT_VEC2 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform2iv_with_i32_array(Some(location), &[values as i32]);
-            }
-        }
-        WebGl2RenderingContext::INT_VEC3 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform3iv_with_i32_array(Some(location), &[values as i32]);
-            }
-        }
-        WebGl2RenderingContext::INT_VEC4 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform4iv_with_i32_array(Some(location), &[values as i32]);
-            }
-        }
-        WebGl2RenderingContext::BOOL => {
-            if let Some(value) = value.into().as_f64() {
-                gl.uniform1i(Some(location), value as i32);
+/// Value of a regular (non-block) uniform, uploaded when its [Program] is used and the GL program has another value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    /// Also used for samplers, as the texture unit index.
+    Int(i32),
+    Mat3([f32; 9]),
+    Mat4([f32; 16]),
+}
+
+impl UniformValue {
+    pub fn upload(&self, gl: &WebGl2RenderingContext, location: &WebGlUniformLocation) {
+        let location = Some(location);
+        match self {
+            UniformValue::Float(value) => gl.uniform1f(location, *value),
+            UniformValue::Vec2(values) => gl.uniform2fv_with_f32_array(location, values),
+            UniformValue::Vec3(values) => gl.uniform3fv_with_f32_array(location, values),
+            UniformValue::Vec4(values) => gl.uniform4fv_with_f32_array(location, values),
+            UniformValue::Int(value) => gl.uniform1i(location, *value),
+            UniformValue::Mat3(values) => {
+                gl.uniform_matrix3fv_with_f32_array(location, false, values)
             }
-        }
-        WebGl2RenderingContext::BOOL_VEC2 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform2iv_with_i32_array(Some(location), &[values as i32]);
+            UniformValue::Mat4(values) => {
+                gl.uniform_matrix4fv_with_f32_array(location, false, values)
             }
         }
-        WebGl2RenderingContext::BOOL_VEC3 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform3iv_with_i32_array(Some(location), &[values as i32]);
-            }
+    }
+}
+
+pub struct Program<'a> {
+    uniforms: HashMap<String, UniformValue>,
+
+    /// fixed-function state applied by the renderer before drawing with this program
+    pub state: PipelineState,
+
+    transparent: bool,
+
+    pub compiled: Rc<CompiledProgram<'a>>,
+}
+
+impl<'a> Program<'a> {
+    /// Compiles `vertex` and `fragment` without going through a [ProgramCache](crate::program_cache::ProgramCache).
+    pub fn new(
+        gl: &'a WebGl2RenderingContext,
+        vertex: &str,
+        fragment: &str,
+    ) -> Result<Self, JsValue> {
+        let compiled = CompiledProgram::compile(gl, vertex, fragment)?;
+        Ok(Self::with_compiled(Rc::new(compiled)))
+    }
+
+    /// Creates a program with its own state on top of an already linked, possibly shared, GL program.
+    pub fn with_compiled(compiled: Rc<CompiledProgram<'a>>) -> Self {
+        Self {
+            uniforms: HashMap::new(),
+            state: PipelineState::default(),
+            transparent: false,
+            compiled,
         }
-        WebGl2RenderingContext::BOOL_VEC4 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform4iv_with_i32_array(Some(location), &[values as i32]);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.compiled.id
+    }
+
+    pub fn program(&self) -> &WebGlProgram {
+        &self.compiled.program
+    }
+
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        self.uniforms.insert(name.to_string(), value);
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&UniformValue> {
+        self.uniforms.get(name)
+    }
+
+    /// Uploads the uniform values that have an active location in the linked program
+    /// and differ from the last ones uploaded to it, by this or another [Program] sharing it.
+    pub fn upload_uniforms(&self, gl: &WebGl2RenderingContext) {
+        let mut uploaded = self.compiled.uploaded_uniforms.borrow_mut();
+        for (name, value) in &self.uniforms {
+            if let Some(location) = self.compiled.uniform_location(name) {
+                if uploaded.changed(name, value) {
+                    value.upload(gl, location);
+                }
             }
         }
-        WebGl2RenderingContext::FLOAT_MAT2 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform_matrix2fv_with_f32_array(Some(location), false, &[values as f32]);
-            }
+    }
+
+    /// Transparent programs without an explicit blend func get one picked by the renderer,
+    /// depending on whether its context uses premultiplied alpha.
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+
+        if !self.transparent {
+            self.state.blend_func = None;
         }
-        WebGl2RenderingContext::FLOAT_MAT3 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform_matrix3fv_with_f32_array(Some(location), false, &[values as f32]);
-            }
+    }
+
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+
+    pub fn set_blend_func(&mut self, blend_func: BlendFuncState) {
+        self.state.blend_func = Some(blend_func);
+        if blend_func.src as u32 != 0 {
+            self.transparent = true;
         }
-        WebGl2RenderingContext::FLOAT_MAT4 => {
-            if let Some(values) = value.into().as_f64() {
-                gl.uniform_matrix4fv_with_f32_array(Some(location), false, &[values as f32]);
-            }
+    }
+
+    pub fn set_blend_equation(&mut self, blend_equation: BlendEquationState) {
+        self.state.blend_equation = Some(blend_equation);
+    }
+}
+
+/// The last value uploaded to each uniform of a GL program.
+#[derive(Debug, Default)]
+struct UploadedUniforms {
+    values: HashMap<String, UniformValue>,
+}
+
+impl UploadedUniforms {
+    /// Whether `value` needs uploading, it is then remembered as uploaded.
+    fn changed(&mut self, name: &str, value: &UniformValue) -> bool {
+        if self.values.get(name) == Some(value) {
+            return false;
         }
-        _ => {}
+        self.values.insert(name.to_string(), *value);
+        true
     }
 }
 
@@ -344,3 +363,14 @@ fn add_line_numbers(string: &str) -> String {
 fn warn(message: &str) {
     web_sys::console::warn_1(&JsValue::from_str(message));
 }
+
+#[test]
+fn uploaded_uniforms_changed() {
+    let mut uploaded = UploadedUniforms::default();
+    assert!(uploaded.changed("u_time", &UniformValue::Float(1.0)));
+    assert!(!uploaded.changed("u_time", &UniformValue::Float(1.0)));
+    assert!(uploaded.changed("u_color", &UniformValue::Float(1.0)));
+    assert!(uploaded.changed("u_time", &UniformValue::Float(2.0)));
+    assert!(uploaded.changed("u_time", &UniformValue::Int(2)));
+    assert!(!uploaded.changed("u_color", &UniformValue::Float(1.0)));
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::{Rc, Weak},
+};
+
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+use crate::program::{CompiledProgram, Program};
+
+/// Identifies a linked program: identical sources and defines always produce the same GL program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProgramKey {
+    pub vertex: String,
+    pub fragment: String,
+    /// `#define NAME VALUE` lines injected into both shaders, sorted by name.
+    pub defines: BTreeMap<String, String>,
+}
+
+impl ProgramKey {
+    pub fn new(vertex: &str, fragment: &str) -> Self {
+        Self {
+            vertex: vertex.to_string(),
+            fragment: fragment.to_string(),
+            defines: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn vertex_source(&self) -> String {
+        apply_defines(&self.vertex, &self.defines)
+    }
+
+    pub fn fragment_source(&self) -> String {
+        apply_defines(&self.fragment, &self.defines)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ProgramCacheStats {
+    /// Lookups that returned an already linked program.
+    pub hits: u32,
+    /// Lookups that had to compile and link a new program.
+    pub misses: u32,
+    /// Total time spent compiling and linking, in milliseconds.
+    pub compile_time_ms: f64,
+}
+
+///
+/// Deduplicates programs by [ProgramKey], so creating many meshes with the same material compiles once.
+/// The cache only keeps weak references: a program is deleted once every [Program] using it is dropped.
+///
+pub struct ProgramCache<T> {
+    entries: HashMap<ProgramKey, Weak<T>>,
+    stats: ProgramCacheStats,
+}
+
+impl<T> Default for ProgramCache<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            stats: ProgramCacheStats::default(),
+        }
+    }
+}
+
+impl<T> ProgramCache<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> ProgramCacheStats {
+        self.stats
+    }
+
+    /// Number of programs that are still alive.
+    pub fn len(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.strong_count() > 0)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the shared program for `key`, calling `compile` only when there is none alive.
+    pub fn get_or_insert_with<E>(
+        &mut self,
+        key: &ProgramKey,
+        compile: impl FnOnce(&ProgramKey) -> Result<T, E>,
+    ) -> Result<Rc<T>, E> {
+        if let Some(program) = self.entries.get(key).and_then(Weak::upgrade) {
+            self.stats.hits += 1;
+            return Ok(program);
+        }
+
+        self.stats.misses += 1;
+        let program = Rc::new(compile(key)?);
+        self.entries.retain(|_, entry| entry.strong_count() > 0);
+        self.entries.insert(key.clone(), Rc::downgrade(&program));
+        Ok(program)
+    }
+}
+
+impl<'a> ProgramCache<CompiledProgram<'a>> {
+    pub fn get_or_compile(
+        &mut self,
+        gl: &'a WebGl2RenderingContext,
+        key: &ProgramKey,
+    ) -> Result<Rc<CompiledProgram<'a>>, JsValue> {
+        let mut compile_time_ms = 0.0;
+        let program = self.get_or_insert_with(key, |key| {
+            let start = now();
+            let program =
+                CompiledProgram::compile(gl, &key.vertex_source(), &key.fragment_source());
+            compile_time_ms = now() - start;
+            program
+        })?;
+        self.stats.compile_time_ms += compile_time_ms;
+        Ok(program)
+    }
+
+    /// Creates a [Program] with default state that shares the GL program for `key`.
+    pub fn create_program(
+        &mut self,
+        gl: &'a WebGl2RenderingContext,
+        key: &ProgramKey,
+    ) -> Result<Program<'a>, JsValue> {
+        Ok(Program::with_compiled(self.get_or_compile(gl, key)?))
+    }
+}
+
+/// `performance.now()`, `Date.now()` only has millisecond resolution and most compiles take less.
+fn now() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map(|performance| performance.now())
+        .unwrap_or_else(js_sys::Date::now)
+}
+
+/// Inserts a `#define` line per entry right after the `#version` directive, which has to stay first.
+fn apply_defines(source: &str, defines: &BTreeMap<String, String>) -> String {
+    if defines.is_empty() {
+        return source.to_string();
+    }
+
+    let defines = defines
+        .iter()
+        .map(|(name, value)| format!("#define {} {}\n", name, value))
+        .collect::<String>();
+
+    let trimmed = source.trim_start();
+    if trimmed.starts_with("#version") {
+        let (version, rest) = trimmed.split_once('\n').unwrap_or((trimmed, ""));
+        format!("{}\n{}{}", version, defines, rest)
+    } else {
+        format!("{}{}", defines, source)
+    }
+}
+
+#[test]
+fn defines_after_version() {
+    let key = ProgramKey::new("#version 300 es\nvoid main() {}", "void main() {}")
+        .with_define("USE_FOG", "1")
+        .with_define("COUNT", "4");
+
+    assert_eq!(
+        key.vertex_source(),
+        "#version 300 es\n#define COUNT 4\n#define USE_FOG 1\nvoid main() {}"
+    );
+    assert_eq!(
+        key.fragment_source(),
+        "#define COUNT 4\n#define USE_FOG 1\nvoid main() {}"
+    );
+}
+
+#[test]
+fn cache_hits_and_misses() {
+    let mut cache: ProgramCache<String> = ProgramCache::new();
+    let key = ProgramKey::new("vertex", "fragment");
+    let other = key.clone().with_define("FLAT", "1");
+    let compile = |key: &ProgramKey| Ok::<_, ()>(key.vertex_source());
+
+    let a = cache.get_or_insert_with(&key, compile).unwrap();
+    let b = cache.get_or_insert_with(&key, compile).unwrap();
+    let c = cache.get_or_insert_with(&other, compile).unwrap();
+
+    assert!(Rc::ptr_eq(&a, &b));
+    assert!(!Rc::ptr_eq(&a, &c));
+    assert_eq!(cache.stats().hits, 1);
+    assert_eq!(cache.stats().misses, 2);
+    assert_eq!(cache.len(), 2);
+
+    // once every handle is dropped the program is compiled again
+    drop((a, b));
+    assert_eq!(cache.len(), 1);
+    cache.get_or_insert_with(&key, compile).unwrap();
+    assert_eq!(cache.stats().misses, 3);
+}
+
+#[test]
+fn cache_does_not_store_failures() {
+    let mut cache: ProgramCache<String> = ProgramCache::new();
+    let key = ProgramKey::new("vertex", "fragment");
+
+    assert!(cache
+        .get_or_insert_with(&key, |_| Err::<String, _>("link error"))
+        .is_err());
+    assert!(cache.is_empty());
+}
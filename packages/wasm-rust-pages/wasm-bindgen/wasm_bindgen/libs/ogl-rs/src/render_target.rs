@@ -0,0 +1,235 @@
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext, WebGlFramebuffer, WebGlRenderbuffer, WebGlTexture};
+use webgl_common::{
+    FramebufferTarget, TextureDataType, TextureFilter, TextureFormat, TextureInternalFormat,
+    TextureUnit, TextureWrap,
+};
+
+use crate::renderer::Renderer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderTargetOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Number of color attachments, more than one renders to multiple targets via `drawBuffers`.
+    pub color: u32,
+    pub depth: bool,
+    pub stencil: bool,
+    pub internal_format: TextureInternalFormat,
+    pub format: TextureFormat,
+    pub data_type: TextureDataType,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    pub wrap: TextureWrap,
+}
+
+impl Default for RenderTargetOptions {
+    fn default() -> Self {
+        Self {
+            width: 300,
+            height: 150,
+            color: 1,
+            depth: true,
+            stencil: false,
+            internal_format: TextureInternalFormat::Rgba8,
+            format: TextureFormat::Rgba,
+            data_type: TextureDataType::UnsignedByte,
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            wrap: TextureWrap::ClampToEdge,
+        }
+    }
+}
+
+///
+/// A framebuffer with color textures that can be sampled after rendering,
+/// and an optional depth (and stencil) renderbuffer.
+///
+pub struct RenderTarget<'a> {
+    gl: &'a WebGl2RenderingContext,
+    options: RenderTargetOptions,
+
+    pub framebuffer: WebGlFramebuffer,
+    pub textures: Vec<WebGlTexture>,
+    pub depth_buffer: Option<WebGlRenderbuffer>,
+}
+
+impl<'a> RenderTarget<'a> {
+    /// Binds the new framebuffer through `renderer`, so its cached binding stays right,
+    /// and binds the previous one again once the attachments are set up.
+    pub fn new(renderer: &mut Renderer<'a>, options: RenderTargetOptions) -> Result<Self, JsValue> {
+        let gl = renderer.gl();
+        let framebuffer = gl
+            .create_framebuffer()
+            .ok_or("Failed to create framebuffer")?;
+        let previous = renderer.framebuffer().cloned();
+        renderer.bind_framebuffer(FramebufferTarget::Framebuffer, Some(&framebuffer));
+
+        let mut textures = Vec::with_capacity(options.color as usize);
+        for i in 0..options.color {
+            let texture = gl.create_texture().ok_or("Failed to create texture")?;
+            gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+            for (name, value) in [
+                (
+                    WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+                    options.min_filter as u32,
+                ),
+                (
+                    WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+                    options.mag_filter as u32,
+                ),
+                (WebGl2RenderingContext::TEXTURE_WRAP_S, options.wrap as u32),
+                (WebGl2RenderingContext::TEXTURE_WRAP_T, options.wrap as u32),
+            ] {
+                gl.tex_parameteri(WebGl2RenderingContext::TEXTURE_2D, name, value as i32);
+            }
+            gl.framebuffer_texture_2d(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                WebGl2RenderingContext::COLOR_ATTACHMENT0 + i,
+                WebGl2RenderingContext::TEXTURE_2D,
+                Some(&texture),
+                0,
+            );
+            textures.push(texture);
+        }
+
+        if options.color > 1 {
+            let draw_buffers = (0..options.color)
+                .map(|i| JsValue::from(WebGl2RenderingContext::COLOR_ATTACHMENT0 + i))
+                .collect::<js_sys::Array>();
+            gl.draw_buffers(&draw_buffers);
+        }
+
+        let depth_buffer = if options.depth || options.stencil {
+            let depth_buffer = gl
+                .create_renderbuffer()
+                .ok_or("Failed to create renderbuffer")?;
+            gl.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, Some(&depth_buffer));
+            let attachment = if options.stencil {
+                WebGl2RenderingContext::DEPTH_STENCIL_ATTACHMENT
+            } else {
+                WebGl2RenderingContext::DEPTH_ATTACHMENT
+            };
+            gl.framebuffer_renderbuffer(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                attachment,
+                WebGl2RenderingContext::RENDERBUFFER,
+                Some(&depth_buffer),
+            );
+            Some(depth_buffer)
+        } else {
+            None
+        };
+
+        let mut target = Self {
+            gl,
+            options,
+            framebuffer,
+            textures,
+            depth_buffer,
+        };
+        target.allocate();
+
+        let status = gl.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER);
+        renderer.bind_framebuffer(FramebufferTarget::Framebuffer, previous.as_ref());
+        if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+            return Err(JsValue::from_str(&format!(
+                "Render target framebuffer is incomplete: 0x{:x}",
+                status
+            )));
+        }
+
+        Ok(target)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.options.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.options.height
+    }
+
+    /// The first color attachment.
+    pub fn texture(&self) -> &WebGlTexture {
+        &self.textures[0]
+    }
+
+    /// Reallocates the storage of every attachment, the previous contents are lost.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if self.options.width == width && self.options.height == height {
+            return;
+        }
+        self.options.width = width;
+        self.options.height = height;
+        self.allocate();
+    }
+
+    /// Binds the framebuffer and sets the viewport to cover it.
+    pub fn bind(&self, renderer: &mut Renderer) {
+        renderer.bind_framebuffer(FramebufferTarget::Framebuffer, Some(&self.framebuffer));
+        renderer.set_viewport(0, 0, self.width() as i32, self.height() as i32);
+    }
+
+    /// Binds the color attachment `index` to `unit` for sampling.
+    pub fn bind_texture(&self, renderer: &mut Renderer, index: usize, unit: TextureUnit) {
+        renderer.active_texture(unit);
+        self.gl.bind_texture(
+            WebGl2RenderingContext::TEXTURE_2D,
+            Some(&self.textures[index]),
+        );
+    }
+
+    fn allocate(&mut self) {
+        let options = &self.options;
+        for texture in &self.textures {
+            self.gl
+                .bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(texture));
+            self.gl
+                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    WebGl2RenderingContext::TEXTURE_2D,
+                    0,
+                    options.internal_format as i32,
+                    options.width as i32,
+                    options.height as i32,
+                    0,
+                    options.format as u32,
+                    options.data_type as u32,
+                    None,
+                )
+                .unwrap();
+        }
+        self.gl
+            .bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+
+        if let Some(depth_buffer) = &self.depth_buffer {
+            let internal_format = if options.stencil {
+                WebGl2RenderingContext::DEPTH24_STENCIL8
+            } else {
+                WebGl2RenderingContext::DEPTH_COMPONENT24
+            };
+            self.gl
+                .bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, Some(depth_buffer));
+            self.gl.renderbuffer_storage(
+                WebGl2RenderingContext::RENDERBUFFER,
+                internal_format,
+                options.width as i32,
+                options.height as i32,
+            );
+            self.gl
+                .bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, None);
+        }
+    }
+}
+
+impl Drop for RenderTarget<'_> {
+    fn drop(&mut self) {
+        for texture in &self.textures {
+            self.gl.delete_texture(Some(texture));
+        }
+        if let Some(depth_buffer) = &self.depth_buffer {
+            self.gl.delete_renderbuffer(Some(depth_buffer));
+        }
+        self.gl.delete_framebuffer(Some(&self.framebuffer));
+    }
+}
@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 
-use wasm_bindgen::JsCast;
+use wasm_bindgen::{JsCast, JsValue};
 
-use crate::{program::Program, BlendEquationState, BlendFuncState};
+use crate::{
+    canvas_size::CanvasSize,
+    pipeline_state::{PipelineState, PolygonOffsetState, SampleCoverageState, StencilFaceState},
+    program::Program,
+    BlendEquationState, BlendFuncState,
+};
 
 use webgl_common::{
     BlendEquation, BlendFactor, Capability, CullFaceMode, DepthFunction, FramebufferTarget,
-    FrontFaceDirection, TextureUnit,
+    FrontFaceDirection, ScissorBox, TextureUnit,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,7 +22,7 @@ struct Viewport {
     height: i32,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 struct RenderState {
     blend_func: BlendFuncState,
     blend_equation: BlendEquationState,
@@ -27,139 +32,261 @@ struct RenderState {
     front_face: Option<FrontFaceDirection>,
     depth_mask: Option<bool>,
     depth_func: Option<DepthFunction>,
+    stencil_front: Option<StencilFaceState>,
+    stencil_back: Option<StencilFaceState>,
+    polygon_offset: Option<PolygonOffsetState>,
+    color_mask: Option<[bool; 4]>,
+    scissor: Option<ScissorBox>,
+    sample_coverage: Option<SampleCoverageState>,
     active_texture_unit: Option<TextureUnit>,
     framebuffer: Option<web_sys::WebGlFramebuffer>,
+    program: Option<u32>,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            blend_func: BlendFuncState {
+                src: BlendFactor::One,
+                dst: BlendFactor::Zero,
+                src_alpha: None,
+                dst_alpha: None,
+            },
+            blend_equation: BlendEquationState {
+                mode_rgb: BlendEquation::FuncAdd,
+                mode_alpha: None,
+            },
+            viewport: Viewport {
+                x: 0,
+                y: 0,
+                width: 300,
+                height: 150,
+            },
+            capabilities: HashMap::new(),
+            cull_face: None,
+            front_face: None,
+            depth_mask: None,
+            depth_func: None,
+            stencil_front: None,
+            stencil_back: None,
+            polygon_offset: None,
+            color_mask: None,
+            scissor: None,
+            sample_coverage: None,
+            active_texture_unit: None,
+            framebuffer: None,
+            program: None,
+        }
+    }
+}
+
+/// Options for creating the WebGL2 context and the [Renderer] that draws into it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RendererOptions {
+    /// Device pixel ratio, usually `window.devicePixelRatio`.
+    pub dpr: f64,
+    /// Whether the canvas has an alpha channel that is composited with the page.
+    pub alpha: bool,
+    pub depth: bool,
+    pub stencil: bool,
+    pub antialias: bool,
+    /// Whether the page compositor treats the canvas colors as premultiplied by alpha.
+    pub premultiplied_alpha: bool,
+    pub preserve_drawing_buffer: bool,
+    pub auto_clear: bool,
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        Self {
+            dpr: 1.0,
+            alpha: false,
+            depth: true,
+            stencil: false,
+            antialias: false,
+            premultiplied_alpha: false,
+            preserve_drawing_buffer: false,
+            auto_clear: true,
+        }
+    }
+}
+
+impl RendererOptions {
+    /// Creates a `"webgl2"` context on `canvas` with these options as context attributes.
+    pub fn get_context(
+        &self,
+        canvas: &web_sys::HtmlCanvasElement,
+    ) -> Result<web_sys::WebGl2RenderingContext, JsValue> {
+        let context_options = js_sys::Object::new();
+        for (name, value) in [
+            ("alpha", self.alpha),
+            ("depth", self.depth),
+            ("stencil", self.stencil),
+            ("antialias", self.antialias),
+            ("premultipliedAlpha", self.premultiplied_alpha),
+            ("preserveDrawingBuffer", self.preserve_drawing_buffer),
+        ] {
+            js_sys::Reflect::set(
+                &context_options,
+                &JsValue::from(name),
+                &JsValue::from_bool(value),
+            )?;
+        }
+
+        canvas
+            .get_context_with_context_options("webgl2", &context_options)?
+            .ok_or_else(|| JsValue::from_str("unable to create webgl2 context"))?
+            .dyn_into::<web_sys::WebGl2RenderingContext>()
+            .map_err(JsValue::from)
+    }
 }
 
 /// The WebGL2 renderer.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Renderer<'a> {
     gl: &'a web_sys::WebGl2RenderingContext,
 
-    dpr: u32,
     alpha: bool,
     color: bool,
     depth: bool,
     stencil: bool,
+    antialias: bool,
     premultiplied_alpha: bool,
+    preserve_drawing_buffer: bool,
     auto_clear: bool,
 
     state: RenderState,
 
-    width: i32,
-    height: i32,
+    size: CanvasSize,
 }
 
 impl<'a> Renderer<'a> {
     pub fn new(gl: &'a web_sys::WebGl2RenderingContext) -> Self {
+        Self::with_options(gl, RendererOptions::default())
+    }
+
+    /// `gl` should be created with [RendererOptions::get_context] from the same `options`,
+    /// so that blending matches how the canvas is composited.
+    pub fn with_options(gl: &'a web_sys::WebGl2RenderingContext, options: RendererOptions) -> Self {
         Self {
-            dpr: 1,
-            alpha: false,
+            alpha: options.alpha,
             color: true,
-            depth: true,
-            stencil: false,
-            premultiplied_alpha: false,
-            auto_clear: true,
-            // antialias: false,
+            depth: options.depth,
+            stencil: options.stencil,
+            antialias: options.antialias,
+            premultiplied_alpha: options.premultiplied_alpha,
+            preserve_drawing_buffer: options.preserve_drawing_buffer,
+            auto_clear: options.auto_clear,
             gl,
-            state: RenderState {
-                blend_func: BlendFuncState {
-                    src: BlendFactor::One,
-                    dst: BlendFactor::Zero,
-                    src_alpha: None,
-                    dst_alpha: None,
-                },
-                blend_equation: BlendEquationState {
-                    mode_rgb: BlendEquation::FuncAdd,
-                    mode_alpha: None,
-                },
-                viewport: Viewport {
-                    x: 0,
-                    y: 0,
-                    width: 300,
-                    height: 150,
-                },
-                capabilities: HashMap::new(),
-                cull_face: None,
-                front_face: None,
-                depth_mask: None,
-                depth_func: None,
-                active_texture_unit: None,
-                framebuffer: None,
-            },
-            // preserve_drawing_buffer = false,
-            width: 300,
-            height: 150,
+            state: RenderState::default(),
+            size: CanvasSize::default().with_dpr(options.dpr),
         }
     }
 
-    pub fn set_size(&mut self, width: i32, height: i32) {
-        self.width = width;
-        self.height = height;
+    pub fn gl(&self) -> &'a web_sys::WebGl2RenderingContext {
+        self.gl
+    }
+
+    pub fn premultiplied_alpha(&self) -> bool {
+        self.premultiplied_alpha
+    }
+
+    /// Resizes the canvas to `width` x `height` CSS pixels.
+    /// The drawing buffer and viewport are scaled by the device pixel ratio.
+    /// Call when the container changes size, e.g. from a [ResizeWatcher](crate::canvas_size::ResizeWatcher).
+    pub fn set_size(&mut self, width: f64, height: f64) {
+        self.size = CanvasSize::new(width, height, self.size.dpr);
+        self.apply_size();
+    }
+
+    /// Call when `window.devicePixelRatio` changes, e.g. from a [DprWatcher](crate::canvas_size::DprWatcher).
+    pub fn set_dpr(&mut self, dpr: f64) {
+        if self.size.dpr == dpr {
+            return;
+        }
+        self.size = self.size.with_dpr(dpr);
+        self.apply_size();
+    }
+
+    pub fn dpr(&self) -> f64 {
+        self.size.dpr
+    }
+
+    /// Size in CSS pixels.
+    pub fn logical_size(&self) -> (f64, f64) {
+        self.size.logical()
+    }
+
+    /// Size of the drawing buffer in physical pixels.
+    pub fn physical_size(&self) -> (u32, u32) {
+        self.size.physical()
+    }
+
+    fn apply_size(&mut self) {
+        let (logical_width, logical_height) = self.size.logical();
+        let (physical_width, physical_height) = self.size.physical();
 
         let canvas: web_sys::HtmlCanvasElement = self.gl.canvas().unwrap().dyn_into().unwrap();
 
-        canvas.set_width(width as u32);
-        canvas.set_height(height as u32);
+        canvas.set_width(physical_width);
+        canvas.set_height(physical_height);
 
         let canvas: &web_sys::HtmlElement = canvas.as_ref();
 
         let style = canvas.style();
         style
-            .set_property("width", &format!("{}px", width))
+            .set_property("width", &format!("{}px", logical_width))
             .unwrap();
         style
-            .set_property("height", &format!("{}px", height))
+            .set_property("height", &format!("{}px", logical_height))
             .unwrap();
+
+        self.set_viewport(0, 0, physical_width as i32, physical_height as i32);
     }
 
     pub fn set_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
-        if self.state.viewport.width == width && self.state.viewport.height == height {
+        let viewport = Viewport {
+            x,
+            y,
+            width,
+            height,
+        };
+        if self.state.viewport == viewport {
             return;
         }
-        self.state.viewport.width = width;
-        self.state.viewport.height = height;
-        self.state.viewport.x = x;
-        self.state.viewport.y = y;
+        self.state.viewport = viewport;
         self.gl.viewport(x, y, width, height);
     }
 
     /// [scissor](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/scissor)
     pub fn set_scissor(&mut self, x: i32, y: i32, width: i32, height: i32) {
-        self.gl.scissor(x, y, width, height);
+        self.set_scissor_box(ScissorBox {
+            x,
+            y,
+            width: width.max(0) as u32,
+            height: height.max(0) as u32,
+        });
+    }
+
+    pub fn set_scissor_box(&mut self, scissor_box: ScissorBox) {
+        let changes = self.state.set_scissor(scissor_box);
+        self.issue(changes);
     }
 
     /// [enable](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/enable)
     pub fn enable(&mut self, capability: Capability) {
-        if let Some(&true) = self.state.capabilities.get(&capability) {
-            return;
-        }
-        self.gl.enable(capability as u32);
-        self.state.capabilities.insert(capability, true);
+        let changes = self.state.set_capability(capability, true);
+        self.issue(changes);
     }
 
     pub fn disable(&mut self, capability: Capability) {
-        if let Some(&false) = self.state.capabilities.get(&capability) {
-            return;
-        }
-        self.gl.disable(capability as u32);
-        self.state.capabilities.insert(capability, false);
+        let changes = self.state.set_capability(capability, false);
+        self.issue(changes);
     }
 
     pub fn set_blend_func(&mut self, blend_func: BlendFuncState) {
-        if self.state.blend_func == blend_func {
-            return;
-        }
-
-        let (src, dst) = (blend_func.src, blend_func.dst);
-        if let (Some(src_alpha), Some(dst_alpha)) = (blend_func.src_alpha, blend_func.dst_alpha) {
-            self.state.blend_func = blend_func;
-            self.gl
-                .blend_func_separate(src as u32, dst as u32, src_alpha as u32, dst_alpha as u32);
-        } else {
-            self.state.blend_func = blend_func;
-            self.gl.blend_func(src as u32, dst as u32);
-        }
+        let changes = self.state.set_blend_func(blend_func);
+        self.issue(changes);
     }
 
     pub fn set_blend_equation(
@@ -167,51 +294,57 @@ impl<'a> Renderer<'a> {
         mode_rgb: BlendEquation,
         mode_alpha: Option<BlendEquation>,
     ) {
-        if self.state.blend_equation.mode_rgb == mode_rgb
-            && self.state.blend_equation.mode_alpha == mode_alpha
-        {
-            return;
-        }
-        self.state.blend_equation.mode_rgb = mode_rgb;
-        self.state.blend_equation.mode_alpha = mode_alpha;
-        if let Some(mode_alpha) = mode_alpha {
-            self.gl
-                .blend_equation_separate(mode_rgb as u32, mode_alpha as u32);
-        } else {
-            self.gl.blend_equation(mode_rgb as u32);
-        }
+        let changes = self.state.set_blend_equation(BlendEquationState {
+            mode_rgb,
+            mode_alpha,
+        });
+        self.issue(changes);
     }
 
     pub fn set_cull_face(&mut self, value: CullFaceMode) {
-        if self.state.cull_face == Some(value) {
-            return;
-        }
-        self.state.cull_face = Some(value);
-        self.gl.cull_face(value as u32);
+        let changes = self.state.set_cull_face(value);
+        self.issue(changes);
     }
 
     pub fn set_front_face(&mut self, value: FrontFaceDirection) {
-        if self.state.front_face == Some(value) {
-            return;
-        }
-        self.state.front_face = Some(value);
-        self.gl.front_face(value as u32);
+        let changes = self.state.set_front_face(value);
+        self.issue(changes);
     }
 
     pub fn set_depth_mask(&mut self, value: bool) {
-        if self.state.depth_mask == Some(value) {
-            return;
-        }
-        self.state.depth_mask = Some(value);
-        self.gl.depth_mask(value);
+        let changes = self.state.set_depth_mask(value);
+        self.issue(changes);
     }
 
     pub fn set_depth_func(&mut self, value: DepthFunction) {
-        if self.state.depth_func == Some(value) {
-            return;
-        }
-        self.state.depth_func = Some(value);
-        self.gl.depth_func(value as u32);
+        let changes = self.state.set_depth_func(value);
+        self.issue(changes);
+    }
+
+    /// [stencilFuncSeparate](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilFuncSeparate),
+    /// [stencilOpSeparate](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilOpSeparate)
+    /// and [stencilMaskSeparate](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/stencilMaskSeparate)
+    pub fn set_stencil_face(&mut self, face: CullFaceMode, value: StencilFaceState) {
+        let changes = self.state.set_stencil_face(face, value);
+        self.issue(changes);
+    }
+
+    /// [polygonOffset](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/polygonOffset)
+    pub fn set_polygon_offset(&mut self, value: PolygonOffsetState) {
+        let changes = self.state.set_polygon_offset(value);
+        self.issue(changes);
+    }
+
+    /// [colorMask](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/colorMask)
+    pub fn set_color_mask(&mut self, value: [bool; 4]) {
+        let changes = self.state.set_color_mask(value);
+        self.issue(changes);
+    }
+
+    /// [sampleCoverage](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/sampleCoverage)
+    pub fn set_sample_coverage(&mut self, value: SampleCoverageState) {
+        let changes = self.state.set_sample_coverage(value);
+        self.issue(changes);
     }
 
     pub fn active_texture(&mut self, texture_unit: TextureUnit) {
@@ -222,6 +355,11 @@ impl<'a> Renderer<'a> {
         self.gl.active_texture(texture_unit as u32);
     }
 
+    /// The framebuffer bound by [Renderer::bind_framebuffer], `None` for the canvas.
+    pub fn framebuffer(&self) -> Option<&web_sys::WebGlFramebuffer> {
+        self.state.framebuffer.as_ref()
+    }
+
     pub fn bind_framebuffer(
         &mut self,
         target: FramebufferTarget,
@@ -259,42 +397,379 @@ impl<'a> Renderer<'a> {
     }
 
     pub fn apply_state(&mut self, program: &Program) {
-        if program.depth_test {
-            self.enable(Capability::DepthTest);
-        } else {
-            self.disable(Capability::DepthTest);
+        let mut state = program.state;
+        if program.transparent() && state.blend_func.is_none() {
+            state.blend_func = Some(BlendFuncState::transparent(self.premultiplied_alpha));
         }
+        self.apply_pipeline_state(&state);
+    }
 
-        if let Some(cull_face) = program.cull_face {
-            self.enable(Capability::CullFace);
-            self.set_cull_face(cull_face);
-        } else {
-            self.disable(Capability::CullFace);
+    pub fn apply_pipeline_state(&mut self, state: &PipelineState) {
+        let changes = self.state.apply_pipeline_state(state);
+        self.issue(changes);
+    }
+
+    fn issue(&self, changes: Vec<StateChange>) {
+        for change in changes {
+            match change {
+                StateChange::Enable(capability) => self.gl.enable(capability as u32),
+                StateChange::Disable(capability) => self.gl.disable(capability as u32),
+                StateChange::BlendFunc(blend_func) => {
+                    let (src, dst) = (blend_func.src, blend_func.dst);
+                    if let (Some(src_alpha), Some(dst_alpha)) =
+                        (blend_func.src_alpha, blend_func.dst_alpha)
+                    {
+                        self.gl.blend_func_separate(
+                            src as u32,
+                            dst as u32,
+                            src_alpha as u32,
+                            dst_alpha as u32,
+                        );
+                    } else {
+                        self.gl.blend_func(src as u32, dst as u32);
+                    }
+                }
+                StateChange::BlendEquation(blend_equation) => {
+                    let mode_rgb = blend_equation.mode_rgb as u32;
+                    if let Some(mode_alpha) = blend_equation.mode_alpha {
+                        self.gl.blend_equation_separate(mode_rgb, mode_alpha as u32);
+                    } else {
+                        self.gl.blend_equation(mode_rgb);
+                    }
+                }
+                StateChange::CullFace(value) => self.gl.cull_face(value as u32),
+                StateChange::FrontFace(value) => self.gl.front_face(value as u32),
+                StateChange::DepthMask(value) => self.gl.depth_mask(value),
+                StateChange::DepthFunc(value) => self.gl.depth_func(value as u32),
+                StateChange::StencilFunc(face, value) => self.gl.stencil_func_separate(
+                    face as u32,
+                    value.func as u32,
+                    value.reference,
+                    value.read_mask,
+                ),
+                StateChange::StencilOp(face, value) => self.gl.stencil_op_separate(
+                    face as u32,
+                    value.fail as u32,
+                    value.depth_fail as u32,
+                    value.pass as u32,
+                ),
+                StateChange::StencilMask(face, value) => {
+                    self.gl.stencil_mask_separate(face as u32, value.write_mask)
+                }
+                StateChange::PolygonOffset(value) => {
+                    self.gl.polygon_offset(value.factor, value.units)
+                }
+                StateChange::ColorMask([r, g, b, a]) => self.gl.color_mask(r, g, b, a),
+                StateChange::Scissor(scissor_box) => self.gl.scissor(
+                    scissor_box.x,
+                    scissor_box.y,
+                    scissor_box.width as i32,
+                    scissor_box.height as i32,
+                ),
+                StateChange::SampleCoverage(value) => {
+                    self.gl.sample_coverage(value.value, value.invert)
+                }
+            }
         }
+    }
+
+    pub fn use_program(&mut self, program: &Program) {
+        if self.state.program != Some(program.id()) {
+            self.state.program = Some(program.id());
+            self.gl.use_program(Some(program.program()));
+        }
+
+        program.upload_uniforms(self.gl);
+
+        // TODO set uniform buffer objects
+
+        self.apply_state(program);
+    }
+}
 
-        if let Some(blend_func) = program.blend_func {
-            self.enable(Capability::Blend);
-            self.set_blend_func(blend_func);
+/// A GL call [RenderState] found necessary to reach the requested state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StateChange {
+    Enable(Capability),
+    Disable(Capability),
+    BlendFunc(BlendFuncState),
+    BlendEquation(BlendEquationState),
+    CullFace(CullFaceMode),
+    FrontFace(FrontFaceDirection),
+    DepthMask(bool),
+    DepthFunc(DepthFunction),
+    /// The func, reference and read mask of the face.
+    StencilFunc(CullFaceMode, StencilFaceState),
+    /// The fail, depth fail and pass ops of the face.
+    StencilOp(CullFaceMode, StencilFaceState),
+    /// The write mask of the face.
+    StencilMask(CullFaceMode, StencilFaceState),
+    PolygonOffset(PolygonOffsetState),
+    ColorMask([bool; 4]),
+    Scissor(ScissorBox),
+    SampleCoverage(SampleCoverageState),
+}
+
+/// Each setter records the new value and returns the calls needed, none when it was already set.
+impl RenderState {
+    fn set_capability(&mut self, capability: Capability, enabled: bool) -> Vec<StateChange> {
+        if self.capabilities.get(&capability) == Some(&enabled) {
+            return vec![];
+        }
+        self.capabilities.insert(capability, enabled);
+        if enabled {
+            vec![StateChange::Enable(capability)]
         } else {
-            self.disable(Capability::Blend);
+            vec![StateChange::Disable(capability)]
+        }
+    }
+
+    fn set_blend_func(&mut self, value: BlendFuncState) -> Vec<StateChange> {
+        if self.blend_func == value {
+            return vec![];
         }
+        self.blend_func = value;
+        vec![StateChange::BlendFunc(value)]
+    }
 
-        self.set_front_face(program.front_face);
-        self.set_depth_mask(program.depth_write);
-        self.set_depth_func(program.depth_func);
+    fn set_blend_equation(&mut self, value: BlendEquationState) -> Vec<StateChange> {
+        if self.blend_equation == value {
+            return vec![];
+        }
+        self.blend_equation = value;
+        vec![StateChange::BlendEquation(value)]
+    }
 
-        if let Some(blend_equation) = program.blend_equation {
-            self.set_blend_equation(blend_equation.mode_rgb, blend_equation.mode_alpha);
+    fn set_cull_face(&mut self, value: CullFaceMode) -> Vec<StateChange> {
+        if self.cull_face == Some(value) {
+            return vec![];
         }
+        self.cull_face = Some(value);
+        vec![StateChange::CullFace(value)]
     }
 
-    pub fn use_program(&mut self, program: &Program) {
-        // TODO check if program is already in use
+    fn set_front_face(&mut self, value: FrontFaceDirection) -> Vec<StateChange> {
+        if self.front_face == Some(value) {
+            return vec![];
+        }
+        self.front_face = Some(value);
+        vec![StateChange::FrontFace(value)]
+    }
+
+    fn set_depth_mask(&mut self, value: bool) -> Vec<StateChange> {
+        if self.depth_mask == Some(value) {
+            return vec![];
+        }
+        self.depth_mask = Some(value);
+        vec![StateChange::DepthMask(value)]
+    }
 
-        self.gl.use_program(Some(&program.program));
+    fn set_depth_func(&mut self, value: DepthFunction) -> Vec<StateChange> {
+        if self.depth_func == Some(value) {
+            return vec![];
+        }
+        self.depth_func = Some(value);
+        vec![StateChange::DepthFunc(value)]
+    }
 
-        // TODO set uniform buffer objects (we will not use regular uniforms)
+    /// Only the func, op and mask calls whose values changed.
+    fn set_stencil_face(
+        &mut self,
+        face: CullFaceMode,
+        value: StencilFaceState,
+    ) -> Vec<StateChange> {
+        let current = match face {
+            CullFaceMode::Front => self.stencil_front,
+            CullFaceMode::Back => self.stencil_back,
+            CullFaceMode::FrontAndBack if self.stencil_front == self.stencil_back => {
+                self.stencil_front
+            }
+            CullFaceMode::FrontAndBack => None,
+        };
+        if current == Some(value) {
+            return vec![];
+        }
 
-        self.apply_state(program);
+        let mut changes = vec![];
+        if current.map(|c| (c.func, c.reference, c.read_mask))
+            != Some((value.func, value.reference, value.read_mask))
+        {
+            changes.push(StateChange::StencilFunc(face, value));
+        }
+        if current.map(|c| (c.fail, c.depth_fail, c.pass))
+            != Some((value.fail, value.depth_fail, value.pass))
+        {
+            changes.push(StateChange::StencilOp(face, value));
+        }
+        if current.map(|c| c.write_mask) != Some(value.write_mask) {
+            changes.push(StateChange::StencilMask(face, value));
+        }
+
+        if face != CullFaceMode::Back {
+            self.stencil_front = Some(value);
+        }
+        if face != CullFaceMode::Front {
+            self.stencil_back = Some(value);
+        }
+        changes
+    }
+
+    fn set_polygon_offset(&mut self, value: PolygonOffsetState) -> Vec<StateChange> {
+        if self.polygon_offset == Some(value) {
+            return vec![];
+        }
+        self.polygon_offset = Some(value);
+        vec![StateChange::PolygonOffset(value)]
     }
+
+    fn set_color_mask(&mut self, value: [bool; 4]) -> Vec<StateChange> {
+        if self.color_mask == Some(value) {
+            return vec![];
+        }
+        self.color_mask = Some(value);
+        vec![StateChange::ColorMask(value)]
+    }
+
+    fn set_scissor(&mut self, value: ScissorBox) -> Vec<StateChange> {
+        if self.scissor == Some(value) {
+            return vec![];
+        }
+        self.scissor = Some(value);
+        vec![StateChange::Scissor(value)]
+    }
+
+    fn set_sample_coverage(&mut self, value: SampleCoverageState) -> Vec<StateChange> {
+        if self.sample_coverage == Some(value) {
+            return vec![];
+        }
+        self.sample_coverage = Some(value);
+        vec![StateChange::SampleCoverage(value)]
+    }
+
+    /// Disabled features keep their last values, re-enabling them with the same values
+    /// only calls `enable`.
+    fn apply_pipeline_state(&mut self, state: &PipelineState) -> Vec<StateChange> {
+        let mut changes = self.set_capability(Capability::DepthTest, state.depth_test);
+
+        changes.extend(self.set_capability(Capability::CullFace, state.cull_face.is_some()));
+        if let Some(cull_face) = state.cull_face {
+            changes.extend(self.set_cull_face(cull_face));
+        }
+
+        changes.extend(self.set_capability(Capability::Blend, state.blend_func.is_some()));
+        if let Some(blend_func) = state.blend_func {
+            changes.extend(self.set_blend_func(blend_func));
+        }
+
+        changes.extend(self.set_front_face(state.front_face));
+        changes.extend(self.set_depth_mask(state.depth_write));
+        changes.extend(self.set_depth_func(state.depth_func));
+
+        if let Some(blend_equation) = state.blend_equation {
+            changes.extend(self.set_blend_equation(blend_equation));
+        }
+
+        changes.extend(self.set_capability(Capability::StencilTest, state.stencil.is_some()));
+        if let Some(stencil) = state.stencil {
+            if stencil.front == stencil.back {
+                changes.extend(self.set_stencil_face(CullFaceMode::FrontAndBack, stencil.front));
+            } else {
+                changes.extend(self.set_stencil_face(CullFaceMode::Front, stencil.front));
+                changes.extend(self.set_stencil_face(CullFaceMode::Back, stencil.back));
+            }
+        }
+
+        changes.extend(self.set_capability(
+            Capability::PolygonOffsetFill,
+            state.polygon_offset.is_some(),
+        ));
+        if let Some(polygon_offset) = state.polygon_offset {
+            changes.extend(self.set_polygon_offset(polygon_offset));
+        }
+
+        changes.extend(self.set_capability(Capability::ScissorTest, state.scissor.is_some()));
+        if let Some(scissor) = state.scissor {
+            changes.extend(self.set_scissor(scissor));
+        }
+
+        changes.extend(
+            self.set_capability(Capability::SampleCoverage, state.sample_coverage.is_some()),
+        );
+        if let Some(sample_coverage) = state.sample_coverage {
+            changes.extend(self.set_sample_coverage(sample_coverage));
+        }
+
+        changes.extend(
+            self.set_capability(Capability::SampleAlphaToCoverage, state.alpha_to_coverage),
+        );
+
+        changes.extend(self.set_color_mask(state.color_mask));
+        changes
+    }
+}
+
+#[test]
+fn pipeline_state_diff() {
+    use crate::pipeline_state::StencilState;
+    use webgl_common::StencilOp;
+
+    let mut state = RenderState::default();
+    let opaque = PipelineState::default();
+    let changes = state.apply_pipeline_state(&opaque);
+    assert!(changes.contains(&StateChange::Enable(Capability::DepthTest)));
+    assert!(changes.contains(&StateChange::CullFace(CullFaceMode::Back)));
+    assert!(changes.contains(&StateChange::Disable(Capability::Blend)));
+    assert!(changes.contains(&StateChange::DepthFunc(DepthFunction::Less)));
+
+    // nothing changed, nothing to call
+    assert_eq!(state.apply_pipeline_state(&opaque), []);
+
+    let face = StencilFaceState {
+        func: DepthFunction::Equal,
+        reference: 1,
+        ..StencilFaceState::default()
+    };
+    let masked = PipelineState {
+        depth_write: false,
+        stencil: Some(StencilState::both(face)),
+        ..opaque
+    };
+    let both = CullFaceMode::FrontAndBack;
+    assert_eq!(
+        state.apply_pipeline_state(&masked),
+        [
+            StateChange::DepthMask(false),
+            StateChange::Enable(Capability::StencilTest),
+            StateChange::StencilFunc(both, face),
+            StateChange::StencilOp(both, face),
+            StateChange::StencilMask(both, face),
+        ]
+    );
+
+    // only the changed stencil op, the func and the mask are the same
+    let replace = StencilFaceState {
+        pass: StencilOp::Replace,
+        ..face
+    };
+    let replacing = PipelineState {
+        stencil: Some(StencilState::both(replace)),
+        ..masked
+    };
+    assert_eq!(
+        state.apply_pipeline_state(&replacing),
+        [StateChange::StencilOp(both, replace)]
+    );
+
+    // disabling keeps the values, enabling again only enables
+    assert_eq!(
+        state.apply_pipeline_state(&PipelineState {
+            stencil: None,
+            ..replacing
+        }),
+        [StateChange::Disable(Capability::StencilTest)]
+    );
+    assert_eq!(
+        state.apply_pipeline_state(&replacing),
+        [StateChange::Enable(Capability::StencilTest)]
+    );
 }
@@ -1,8 +1,11 @@
 use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{HtmlCanvasElement, WebGl2RenderingContext, WebglDebugRendererInfo};
+use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
+use webgl_common::Capabilities;
 
 pub struct WebGlRenderBackend {
     gl: WebGl2RenderingContext,
+    capabilities: Capabilities,
+    msaa_sample_count: u32,
 }
 
 impl WebGlRenderBackend {
@@ -31,30 +34,23 @@ impl WebGlRenderBackend {
             .unwrap()
             .dyn_into::<WebGl2RenderingContext>()?;
 
-        let msaa_sample_count = {
-            let mut msaa_sample_count = 4;
-            if let Ok(max_samples) = gl.get_parameter(WebGl2RenderingContext::MAX_SAMPLES) {
-                msaa_sample_count = std::cmp::min(
-                    msaa_sample_count,
-                    max_samples.as_f64().unwrap_or(0.0) as u32,
-                );
-            }
-            msaa_sample_count
-        };
+        let capabilities = Capabilities::query(&gl);
+        let msaa_sample_count = capabilities.msaa_samples(4);
 
-        {
-            // Get WebGL driver info.
-            if log::log_enabled!(log::Level::Info) {
-                let driver_info = gl
-                    .get_extension("WEBGL_debug_renderer_info")
-                    .and_then(|_| gl.get_parameter(WebglDebugRendererInfo::UNMASKED_RENDERER_WEBGL))
-                    .ok()
-                    .and_then(|val| val.as_string())
-                    .unwrap_or_else(|| "<unknown>".to_string());
-                log::info!("WebGL graphics driver: {}", driver_info);
-            }
-        }
+        log::info!("WebGL graphics driver: {}", capabilities.renderer);
+
+        Ok(WebGlRenderBackend {
+            gl,
+            capabilities,
+            msaa_sample_count,
+        })
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
 
-        Ok(WebGlRenderBackend { gl })
+    pub fn msaa_sample_count(&self) -> u32 {
+        self.msaa_sample_count
     }
 }
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use web_sys::{
+    ExtTextureFilterAnisotropic, WebGl2RenderingContext, WebGlRenderingContext,
+    WebglDebugRendererInfo,
+};
+
+use crate::WebGlVersion;
+
+/// Optional WebGL extensions the renderers know how to use.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Extension {
+    /// Render to `RGBA16F`/`RGBA32F` color attachments.
+    ColorBufferFloat,
+    /// Linear filtering of `FLOAT` textures.
+    TextureFloatLinear,
+    TextureFilterAnisotropic,
+    /// `multiDrawArraysWEBGL` and friends.
+    MultiDraw,
+    /// GPU timer queries.
+    DisjointTimerQuery,
+    /// Unmasked vendor and renderer strings.
+    DebugRendererInfo,
+    /// BC1-BC3 (DXT) compressed textures.
+    CompressedTextureS3tc,
+    CompressedTextureEtc,
+    CompressedTextureAstc,
+    /// `WEBGL_lose_context`, for testing context loss.
+    LoseContext,
+    /// Vertex array objects on WebGL1, core in WebGL2.
+    VertexArrayObject,
+    /// Instanced drawing on WebGL1, core in WebGL2.
+    InstancedArrays,
+    /// `UNSIGNED_INT` indices on WebGL1, core in WebGL2.
+    ElementIndexUint,
+}
+
+impl Extension {
+    /// The name passed to `getExtension()`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Extension::ColorBufferFloat => "EXT_color_buffer_float",
+            Extension::TextureFloatLinear => "OES_texture_float_linear",
+            Extension::TextureFilterAnisotropic => "EXT_texture_filter_anisotropic",
+            Extension::MultiDraw => "WEBGL_multi_draw",
+            Extension::DisjointTimerQuery => "EXT_disjoint_timer_query_webgl2",
+            Extension::DebugRendererInfo => "WEBGL_debug_renderer_info",
+            Extension::CompressedTextureS3tc => "WEBGL_compressed_texture_s3tc",
+            Extension::CompressedTextureEtc => "WEBGL_compressed_texture_etc",
+            Extension::CompressedTextureAstc => "WEBGL_compressed_texture_astc",
+            Extension::LoseContext => "WEBGL_lose_context",
+            Extension::VertexArrayObject => "OES_vertex_array_object",
+            Extension::InstancedArrays => "ANGLE_instanced_arrays",
+            Extension::ElementIndexUint => "OES_element_index_uint",
+        }
+    }
+}
+
+///
+/// Limits and extensions of a context, queried once after it is created.
+/// Renderers check it to pick code paths instead of calling `getParameter()` on their own.
+/// Serializes to JSON so it can be attached to bug reports.
+///
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub version: WebGlVersion,
+    /// `UNMASKED_VENDOR_WEBGL` when available, otherwise `VENDOR`.
+    pub vendor: String,
+    /// `UNMASKED_RENDERER_WEBGL` when available, otherwise `RENDERER`.
+    pub renderer: String,
+
+    pub max_texture_size: u32,
+    pub max_cube_map_texture_size: u32,
+    pub max_texture_image_units: u32,
+    pub max_vertex_attribs: u32,
+    pub max_uniform_block_size: u32,
+    pub max_uniform_buffer_bindings: u32,
+    pub max_draw_buffers: u32,
+    pub max_samples: u32,
+    /// `MAX_TEXTURE_MAX_ANISOTROPY_EXT`, `1.0` without `EXT_texture_filter_anisotropic`.
+    pub max_anisotropy: f32,
+
+    /// Every name returned by `getSupportedExtensions()`.
+    pub extensions: Vec<String>,
+    /// The extensions [Capabilities::query] turned on with `getExtension()`,
+    /// a supported extension does nothing until it is enabled.
+    #[serde(default)]
+    pub enabled: Vec<Extension>,
+}
+
+///
+/// Queries everything both WebGL versions share. A macro because `WebGlRenderingContext`
+/// and `WebGl2RenderingContext` have the same methods but no common trait.
+///
+macro_rules! query_common {
+    ($gl:expr, $version:expr) => {{
+        let gl = $gl;
+        let extensions = gl
+            .get_supported_extensions()
+            .map(|extensions| {
+                extensions
+                    .iter()
+                    .filter_map(|extension| extension.as_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let parameter = |name: u32| -> u32 {
+            gl.get_parameter(name)
+                .ok()
+                .and_then(|value| value.as_f64())
+                .unwrap_or(0.0) as u32
+        };
+        let string = |name: u32| -> String {
+            gl.get_parameter(name)
+                .ok()
+                .and_then(|value| value.as_string())
+                .unwrap_or_default()
+        };
+        let mut enabled = Vec::new();
+        let mut enable = |extension: Extension| -> bool {
+            let on = extensions.iter().any(|name| name == extension.name())
+                && matches!(gl.get_extension(extension.name()), Ok(Some(_)));
+            if on {
+                enabled.push(extension);
+            }
+            on
+        };
+
+        let mut capabilities = Capabilities {
+            version: $version,
+            vendor: string(WebGl2RenderingContext::VENDOR),
+            renderer: string(WebGl2RenderingContext::RENDERER),
+            max_texture_size: parameter(WebGl2RenderingContext::MAX_TEXTURE_SIZE),
+            max_cube_map_texture_size: parameter(WebGl2RenderingContext::MAX_CUBE_MAP_TEXTURE_SIZE),
+            max_texture_image_units: parameter(WebGl2RenderingContext::MAX_TEXTURE_IMAGE_UNITS),
+            max_vertex_attribs: parameter(WebGl2RenderingContext::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: 0,
+            max_uniform_buffer_bindings: 0,
+            max_draw_buffers: 1,
+            max_samples: 0,
+            max_anisotropy: 1.0,
+            extensions: Vec::new(),
+            enabled: Vec::new(),
+        };
+
+        if enable(Extension::DebugRendererInfo) {
+            capabilities.vendor = string(WebglDebugRendererInfo::UNMASKED_VENDOR_WEBGL);
+            capabilities.renderer = string(WebglDebugRendererInfo::UNMASKED_RENDERER_WEBGL);
+        }
+
+        if enable(Extension::TextureFilterAnisotropic) {
+            capabilities.max_anisotropy = gl
+                .get_parameter(ExtTextureFilterAnisotropic::MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+                .ok()
+                .and_then(|value| value.as_f64())
+                .unwrap_or(1.0) as f32;
+        }
+
+        // float render targets are incomplete until these are enabled
+        enable(Extension::ColorBufferFloat);
+        enable(Extension::TextureFloatLinear);
+
+        capabilities.extensions = extensions;
+        capabilities.enabled = enabled;
+        (capabilities, parameter)
+    }};
+}
+
+impl Capabilities {
+    pub fn query(gl: &WebGl2RenderingContext) -> Self {
+        let (mut capabilities, parameter) = query_common!(gl, WebGlVersion::Two);
+        capabilities.max_uniform_block_size =
+            parameter(WebGl2RenderingContext::MAX_UNIFORM_BLOCK_SIZE);
+        capabilities.max_uniform_buffer_bindings =
+            parameter(WebGl2RenderingContext::MAX_UNIFORM_BUFFER_BINDINGS);
+        capabilities.max_draw_buffers = parameter(WebGl2RenderingContext::MAX_DRAW_BUFFERS);
+        capabilities.max_samples = parameter(WebGl2RenderingContext::MAX_SAMPLES);
+        capabilities
+    }
+
+    /// WebGL1 has no uniform buffers, multiple render targets or multisampled renderbuffers,
+    /// so those limits stay at zero (one draw buffer).
+    pub fn query_webgl1(gl: &WebGlRenderingContext) -> Self {
+        let (capabilities, _) = query_common!(gl, WebGlVersion::One);
+        capabilities
+    }
+
+    /// Whether the context lists the extension, it may still have to be enabled.
+    pub fn supports(&self, extension: Extension) -> bool {
+        self.extensions
+            .iter()
+            .any(|supported| supported == extension.name())
+    }
+
+    /// Whether [Capabilities::query] enabled the extension, so its formats and parameters work.
+    pub fn is_enabled(&self, extension: Extension) -> bool {
+        self.enabled.contains(&extension)
+    }
+
+    /// The largest MSAA sample count not above `requested` the context can render with.
+    pub fn msaa_samples(&self, requested: u32) -> u32 {
+        requested.min(self.max_samples)
+    }
+
+    /// Float color attachments can be rendered to and sampled with linear filtering.
+    pub fn float_render_targets(&self) -> bool {
+        self.is_enabled(Extension::ColorBufferFloat)
+            && self.is_enabled(Extension::TextureFloatLinear)
+    }
+}
+
+#[test]
+fn capabilities_supports() {
+    let capabilities = Capabilities {
+        max_samples: 4,
+        extensions: vec![
+            "EXT_color_buffer_float".to_string(),
+            "WEBGL_multi_draw".to_string(),
+        ],
+        ..Capabilities::default()
+    };
+
+    assert!(capabilities.supports(Extension::MultiDraw));
+    assert!(!capabilities.supports(Extension::TextureFloatLinear));
+    assert!(!capabilities.float_render_targets());
+    assert_eq!(capabilities.msaa_samples(8), 4);
+    assert_eq!(capabilities.msaa_samples(2), 2);
+}
+
+#[test]
+fn capabilities_supported_is_not_enabled() {
+    let float_extensions = vec![
+        "EXT_color_buffer_float".to_string(),
+        "OES_texture_float_linear".to_string(),
+    ];
+    let supported = Capabilities {
+        extensions: float_extensions.clone(),
+        ..Capabilities::default()
+    };
+    assert!(supported.supports(Extension::ColorBufferFloat));
+    assert!(!supported.is_enabled(Extension::ColorBufferFloat));
+    assert!(!supported.float_render_targets());
+
+    let enabled = Capabilities {
+        extensions: float_extensions,
+        enabled: vec![Extension::ColorBufferFloat, Extension::TextureFloatLinear],
+        ..Capabilities::default()
+    };
+    assert!(enabled.float_render_targets());
+}
+
+#[test]
+fn capabilities_serialize() {
+    let capabilities = Capabilities {
+        renderer: "ANGLE (Apple M1)".to_string(),
+        max_texture_size: 16384,
+        extensions: vec!["EXT_color_buffer_float".to_string()],
+        ..Capabilities::default()
+    };
+
+    let json = serde_json::to_string(&capabilities).unwrap();
+    assert!(json.contains("\"renderer\":\"ANGLE (Apple M1)\""));
+    assert_eq!(
+        serde_json::from_str::<Capabilities>(&json).unwrap(),
+        capabilities
+    );
+}
@@ -1,8 +1,8 @@
-use crate::value_as_u8_slice;
+use crate::{value_as_u8_slice, GpuPod};
 
 /// RGBA color.
 #[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, GpuPod)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
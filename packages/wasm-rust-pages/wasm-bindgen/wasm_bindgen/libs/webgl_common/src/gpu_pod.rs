@@ -0,0 +1,147 @@
+///
+/// Plain data that can be uploaded to the GPU as raw bytes.
+///
+/// Implementors are `Copy`, contain no pointers or references and have no padding bytes,
+/// so every byte of a value is initialized and any bit pattern is a valid value.
+/// Use `#[derive(GpuPod)]` on `#[repr(C)]` structs instead of implementing it by hand:
+/// the derive checks at compile time that every field is `GpuPod` and that there is no padding.
+///
+/// ```
+/// use webgl_common::GpuPod;
+///
+/// #[derive(Clone, Copy, GpuPod)]
+/// #[repr(C)]
+/// struct Vertex {
+///     position: [f32; 3],
+///     color: [u8; 4],
+/// }
+/// ```
+///
+/// A field followed by padding doesn't compile:
+///
+/// ```compile_fail
+/// use webgl_common::GpuPod;
+///
+/// #[derive(Clone, Copy, GpuPod)]
+/// #[repr(C)]
+/// struct Padded {
+///     flag: u8,
+///     value: u32,
+/// }
+/// ```
+///
+/// Neither do references and pointers:
+///
+/// ```compile_fail
+/// use webgl_common::GpuPod;
+///
+/// #[derive(Clone, Copy, GpuPod)]
+/// #[repr(C)]
+/// struct WithReference {
+///     value: &'static u32,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use webgl_common::GpuPod;
+///
+/// #[derive(Clone, Copy, GpuPod)]
+/// #[repr(C)]
+/// struct WithPointer {
+///     value: *const u32,
+/// }
+/// ```
+///
+/// Nor a struct whose layout Rust is free to change:
+///
+/// ```compile_fail
+/// use webgl_common::GpuPod;
+///
+/// #[derive(Clone, Copy, GpuPod)]
+/// struct NotReprC {
+///     position: [f32; 3],
+/// }
+/// ```
+///
+/// # Safety
+///
+/// Implementing this for a type with padding, pointers or invalid bit patterns is undefined behavior.
+///
+pub unsafe trait GpuPod: Copy + 'static {}
+
+macro_rules! impl_gpu_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl GpuPod for $ty {})*
+    };
+}
+
+impl_gpu_pod!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+unsafe impl<T: GpuPod, const N: usize> GpuPod for [T; N] {}
+
+/// Converts a single value to a byte slice.
+pub fn value_as_u8_slice<T: GpuPod>(p: &T) -> &[u8] {
+    slice_as_u8_slice(core::slice::from_ref(p))
+}
+
+/// Converts a slice of values to a byte slice.
+pub fn slice_as_u8_slice<T: GpuPod>(vec: &[T]) -> &[u8] {
+    // SAFETY: `T: GpuPod` has no padding, so every byte of the slice is initialized.
+    unsafe { core::slice::from_raw_parts(vec.as_ptr() as *const u8, core::mem::size_of_val(vec)) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastError {
+    /// The bytes do not start at a multiple of the alignment of the target type.
+    Misaligned { align: usize },
+    /// The length is not a multiple of the size of the target type.
+    SizeMismatch { len: usize, size: usize },
+}
+
+///
+/// Reinterprets bytes as a slice of `T`, e.g. data read back from a buffer.
+/// Fails instead of producing a misaligned or truncated slice.
+///
+pub fn u8_slice_as_slice<T: GpuPod>(bytes: &[u8]) -> Result<&[T], CastError> {
+    let size = core::mem::size_of::<T>();
+    let align = core::mem::align_of::<T>();
+
+    if size == 0 || !bytes.len().is_multiple_of(size) {
+        return Err(CastError::SizeMismatch {
+            len: bytes.len(),
+            size,
+        });
+    }
+    if !(bytes.as_ptr() as usize).is_multiple_of(align) {
+        return Err(CastError::Misaligned { align });
+    }
+
+    // SAFETY: size and alignment are checked above and any bit pattern is a valid `T`.
+    Ok(unsafe { core::slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / size) })
+}
+
+#[test]
+fn gpu_pod_round_trip() {
+    let indices: [u16; 3] = [1, 2, 0x0304];
+    let bytes = slice_as_u8_slice(&indices);
+    assert_eq!(bytes.len(), 6);
+    assert_eq!(u8_slice_as_slice::<u16>(bytes), Ok(&indices[..]));
+
+    let matrix = [[1.0f32; 4]; 4];
+    assert_eq!(value_as_u8_slice(&matrix).len(), 64);
+}
+
+#[test]
+fn gpu_pod_cast_errors() {
+    let words = [0u32; 2];
+    let bytes = slice_as_u8_slice(&words);
+
+    assert_eq!(
+        u8_slice_as_slice::<u32>(&bytes[..6]),
+        Err(CastError::SizeMismatch { len: 6, size: 4 })
+    );
+    assert_eq!(
+        u8_slice_as_slice::<u16>(&bytes[1..7]),
+        Err(CastError::Misaligned { align: 2 })
+    );
+}
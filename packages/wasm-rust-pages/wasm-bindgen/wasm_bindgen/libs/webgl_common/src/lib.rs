@@ -4,17 +4,18 @@ pub use static_variables::*;
 mod common_structs;
 pub use common_structs::*;
 
-use web_sys::{console, WebGl2RenderingContext, WebGlProgram};
+mod capabilities;
+pub use capabilities::*;
 
-/// Converts a single value to a byte slice.
-pub fn value_as_u8_slice<T>(p: &T) -> &[u8] {
-    unsafe { core::slice::from_raw_parts((p as *const T) as *const u8, core::mem::size_of_val(p)) }
-}
+mod gpu_pod;
+/// `#[derive(GpuPod)]`, shares the name of the trait like `serde`'s derives.
+pub use derive_into::GpuPod;
+pub use gpu_pod::*;
 
-/// Converts a slice of values to a byte slice.
-pub fn slice_as_u8_slice<T>(vec: &[T]) -> &[u8] {
-    unsafe { core::slice::from_raw_parts(vec.as_ptr() as *const u8, core::mem::size_of_val(vec)) }
-}
+// Lets `#[derive(GpuPod)]` refer to `::webgl_common` from inside this crate.
+extern crate self as webgl_common;
+
+use web_sys::{console, WebGl2RenderingContext, WebGlProgram};
 
 pub fn create_shader_program(
     gl: &WebGl2RenderingContext,
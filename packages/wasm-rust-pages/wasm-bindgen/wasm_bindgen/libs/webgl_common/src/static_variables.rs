@@ -1,16 +1,17 @@
 use std::default;
 
-use derive_into::IntoU32;
+use derive_into::{IntoU32, TryFromU32};
+use serde::{Deserialize, Serialize};
 use web_sys::WebGl2RenderingContext;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, Default)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, Default, Serialize, Deserialize)]
 pub enum WebGlVersion {
     One,
     #[default]
     Two,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, Default)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, TryFromU32, Default)]
 #[repr(u32)]
 pub enum DataType {
     /// i8
@@ -32,7 +33,18 @@ pub enum DataType {
     HalfFloat = WebGl2RenderingContext::HALF_FLOAT,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32)]
+impl DataType {
+    /// Size of one component in bytes.
+    pub fn size(&self) -> u32 {
+        match self {
+            DataType::Byte | DataType::UnsignedByte => 1,
+            DataType::Short | DataType::UnsignedShort | DataType::HalfFloat => 2,
+            DataType::Int | DataType::UnsignedInt | DataType::Float => 4,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, TryFromU32)]
 #[repr(u32)]
 pub enum BufferTarget {
     /// Buffer containing vertex attributes, such as vertex coordinates, texture coordinate data, or vertex color data.
@@ -53,7 +65,7 @@ pub enum BufferTarget {
     PixelUnpackBuffer = WebGl2RenderingContext::PIXEL_UNPACK_BUFFER,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, TryFromU32)]
 #[repr(u32)]
 pub enum BufferUsage {
     StreamDraw = WebGl2RenderingContext::STREAM_DRAW,
@@ -139,7 +151,7 @@ pub enum TextureInternalFormat {
     Depth32fStencil8 = WebGl2RenderingContext::DEPTH32F_STENCIL8,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32)]
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, IntoU32)]
 #[repr(u32)]
 pub enum TextureWrap {
     Repeat = WebGl2RenderingContext::REPEAT,
@@ -147,7 +159,7 @@ pub enum TextureWrap {
     MirroredRepeat = WebGl2RenderingContext::MIRRORED_REPEAT,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32)]
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, IntoU32)]
 #[repr(u32)]
 pub enum TextureFilter {
     Nearest = WebGl2RenderingContext::NEAREST,
@@ -219,7 +231,7 @@ pub enum TextureCubeMapFaceDirection {
 }
 
 /// WebGLRenderingContext: create_shader()
-#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, IntoU32)]
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, IntoU32, TryFromU32)]
 #[repr(u32)]
 pub enum ShaderType {
     /// vertex shader    
@@ -243,6 +255,20 @@ pub enum Capability {
     SampleCoverage = WebGl2RenderingContext::SAMPLE_COVERAGE,
     ScissorTest = WebGl2RenderingContext::SCISSOR_TEST,
     StencilTest = WebGl2RenderingContext::STENCIL_TEST,
+    /// Primitives are discarded before rasterization, e.g. while only capturing transform feedback.
+    RasterizerDiscard = WebGl2RenderingContext::RASTERIZER_DISCARD,
+}
+
+/// WebGL2RenderingContext: transformFeedbackVaryings()
+/// [MDN Reference](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/transformFeedbackVaryings)
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, IntoU32, TryFromU32, Default)]
+#[repr(u32)]
+pub enum TransformFeedbackBufferMode {
+    /// All varyings are written to one buffer, one vertex after another.
+    #[default]
+    InterleavedAttribs = WebGl2RenderingContext::INTERLEAVED_ATTRIBS,
+    /// Each varying is written to its own buffer binding.
+    SeparateAttribs = WebGl2RenderingContext::SEPARATE_ATTRIBS,
 }
 
 /// WebGLRenderingContext: blendFunc() blendFuncSeparate()
@@ -329,7 +355,7 @@ pub enum StencilOp {
 /// ```rust
 /// gl.active_texture(gl.TEXTURE1);
 /// ```
-#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, IntoU32, Default)]
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, IntoU32, TryFromU32, Default)]
 #[repr(u32)]
 pub enum TextureUnit {
     #[default]
@@ -403,7 +429,7 @@ pub enum Mask {
     ColorBufferBit = WebGl2RenderingContext::COLOR_BUFFER_BIT,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, IntoU32, TryFromU32)]
 #[repr(u32)]
 pub enum DrawMode {
     /// Draws a single dot.
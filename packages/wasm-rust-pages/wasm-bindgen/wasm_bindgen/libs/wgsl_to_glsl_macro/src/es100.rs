@@ -0,0 +1,684 @@
+use std::collections::HashMap;
+
+use naga::ShaderStage;
+
+/// A GLSL ES 1.00 shader for WebGL1.
+#[derive(Clone, Debug)]
+pub struct Es100Shader {
+    pub source: String,
+    /// `(attribute name, location)` to pass to `bindAttribLocation()`,
+    /// ES 1.00 has no `layout(location = N)`.
+    pub attribute_bindings: Vec<(String, u32)>,
+}
+
+/// The most times a loop runs in a [downlevel]ed shader, ES 1.00 only has loops with a constant bound.
+pub const MAX_LOOP_ITERATIONS: u32 = 1024;
+
+/// Identifiers naga can write that have no GLSL ES 1.00 (or WebGL1) equivalent.
+const UNSUPPORTED: &[&str] = &[
+    "uint",
+    "uvec2",
+    "uvec3",
+    "uvec4",
+    "mat2x3",
+    "mat2x4",
+    "mat3x2",
+    "mat3x4",
+    "mat4x2",
+    "mat4x3",
+    "sampler3D",
+    "sampler2DArray",
+    "sampler2DShadow",
+    "isampler2D",
+    "usampler2D",
+    "gl_VertexID",
+    "gl_InstanceID",
+    "gl_FragDepth",
+    "switch",
+    "while",
+    "do",
+    "flat",
+    "texelFetch",
+    "textureLod",
+    "textureGrad",
+    "textureSize",
+    "textureOffset",
+    "trunc",
+    "round",
+    "roundEven",
+    "isnan",
+    "isinf",
+    "sinh",
+    "cosh",
+    "tanh",
+    "asinh",
+    "acosh",
+    "atanh",
+    "determinant",
+    "inverse",
+    "transpose",
+    "outerProduct",
+    "floatBitsToInt",
+    "floatBitsToUint",
+    "intBitsToFloat",
+    "uintBitsToFloat",
+];
+
+///
+/// Rewrites the GLSL ES 3.00 that naga writes for WebGL2 into GLSL ES 1.00.
+///
+/// naga can not write ES 1.00 itself, so this works on its (very regular) output:
+/// - `in`/`out` become `attribute`/`varying`, the fragment output becomes `gl_FragColor`
+/// - uniform blocks become plain uniforms, named `_group_<g>_binding_<b>` in both stages
+///   so the vertex and fragment shader share them
+/// - `matNxN` becomes `matN`, `texture()` becomes `texture2D()`/`textureCube()`
+/// - the `while(true)` naga writes for every WGSL loop becomes a `for` loop bounded by
+///   [MAX_LOOP_ITERATIONS], the loop still ends at its `break`, but a loop that needs more
+///   iterations stops early
+///
+/// Fails when the shader uses something ES 1.00 can not express,
+/// e.g. unsigned integers, bit operations, `switch` or `vertex_index`.
+///
+pub fn downlevel(source: &str, stage: ShaderStage) -> Result<Es100Shader, String> {
+    let mut attribute_bindings = Vec::new();
+    let mut fragment_output = None;
+    let mut samplers = HashMap::new();
+    let mut lines = Vec::new();
+    let mut loops = 0;
+
+    for line in source.lines() {
+        if line == "#version 300 es" {
+            lines.push("#version 100".to_string());
+        } else if stage == ShaderStage::Fragment && line.starts_with("precision highp ") {
+            // highp is optional in ES 1.00 fragment shaders
+            let type_ = line["precision highp ".len()..].trim_end_matches(';');
+            lines.push("#ifdef GL_FRAGMENT_PRECISION_HIGH".to_string());
+            lines.push(line.to_string());
+            lines.push("#else".to_string());
+            lines.push(format!("precision mediump {};", type_));
+            lines.push("#endif".to_string());
+        } else if let Some(block) = line.strip_prefix("uniform ").filter(|l| l.contains('{')) {
+            // uniform Name_block_0Vertex { Type _group_0_binding_0_vs; };
+            let member = block
+                .split_once('{')
+                .and_then(|(_, member)| member.split_once('}'))
+                .map(|(member, _)| member.trim())
+                .ok_or_else(|| format!("unexpected uniform block `{}`", line))?;
+            lines.push(format!("uniform {}", member));
+        } else if let Some(rest) = line.strip_prefix("layout(location = ") {
+            let (location, declaration) = rest
+                .split_once(')')
+                .ok_or_else(|| format!("unexpected layout `{}`", line))?;
+            let location = location
+                .parse::<u32>()
+                .map_err(|_| format!("unexpected location `{}`", line))?;
+            let declaration = declaration.trim();
+
+            match stage {
+                ShaderStage::Vertex => {
+                    let declaration = declaration
+                        .strip_prefix("in ")
+                        .ok_or_else(|| format!("unexpected vertex input `{}`", line))?;
+                    attribute_bindings.push((declaration_name(declaration)?, location));
+                    lines.push(format!("attribute {}", declaration));
+                }
+                _ => {
+                    let declaration = declaration
+                        .strip_prefix("out ")
+                        .ok_or_else(|| format!("unexpected fragment output `{}`", line))?;
+                    if location != 0 || !declaration.starts_with("vec4 ") {
+                        return Err(format!(
+                            "GLSL ES 1.00 only has one vec4 fragment output, found `{}`",
+                            line
+                        ));
+                    }
+                    fragment_output = Some(declaration_name(declaration)?);
+                }
+            }
+        } else if let Some(varying) = varying(line, stage) {
+            lines.push(format!("varying {}", varying));
+        } else if let Some(sampler) = line.strip_prefix("uniform highp sampler") {
+            // uniform highp sampler2D _group_0_binding_1_fs;
+            let name = declaration_name(sampler)?;
+            samplers.insert(strip_stage(&name).to_string(), sampler.starts_with("Cube"));
+            lines.push(format!("uniform sampler{}", sampler));
+        } else if line.trim_start() == "while(true) {" {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            lines.push(format!(
+                "{}for (int _loop_{loops} = 0; _loop_{loops} < {}; _loop_{loops}++) {{",
+                indent, MAX_LOOP_ITERATIONS
+            ));
+            loops += 1;
+        } else if is_unused_builtin(line, source) {
+            // `uint vertex_index = uint(gl_VertexID);` is written for every declared builtin
+            continue;
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+
+    let source = lines
+        .iter()
+        .map(|line| {
+            let line = map_identifiers(line, |identifier| match identifier {
+                "mat2x2" => Some("mat2".to_string()),
+                "mat3x3" => Some("mat3".to_string()),
+                "mat4x4" => Some("mat4".to_string()),
+                _ if Some(identifier) == fragment_output.as_deref() => {
+                    Some("gl_FragColor".to_string())
+                }
+                _ => Some(strip_stage(identifier).to_string()),
+            });
+            texture_calls(&line, &samplers)
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .join("\n");
+
+    check_supported(&source)?;
+
+    Ok(Es100Shader {
+        source,
+        attribute_bindings,
+    })
+}
+
+///
+/// The plain uniforms that replace the uniform block at `group`/`binding` in a [downlevel]ed shader,
+/// as `(GLSL name, std140 byte offset)`, e.g. `("_group_0_binding_1.view_matrix", 64)`.
+/// Arrays are listed element by element.
+///
+pub fn uniform_members(
+    source: &str,
+    group: u32,
+    binding: u32,
+) -> Result<Vec<(String, u32)>, String> {
+    let structs = parse_structs(source)?;
+    let name = format!("_group_{}_binding_{}", group, binding);
+
+    let declaration = source
+        .lines()
+        .filter_map(|line| line.strip_prefix("uniform "))
+        .map(|declaration| declaration.trim_end_matches(';'))
+        .find(|declaration| {
+            declaration
+                .split_whitespace()
+                .nth(1)
+                .map(|member| member.split('[').next() == Some(name.as_str()))
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| format!("no uniform `{}`", name))?;
+
+    let member = parse_member(declaration)?;
+    let mut members = Vec::new();
+    flatten(&name, &member, 0, &structs, &mut members)?;
+    Ok(members)
+}
+
+#[derive(Clone, Debug)]
+struct Member {
+    type_: String,
+    name: String,
+    array: Option<u32>,
+}
+
+/// `Type name` or `Type name[N]`
+fn parse_member(declaration: &str) -> Result<Member, String> {
+    let mut words = declaration.trim().trim_end_matches(';').split_whitespace();
+    let (type_, name) = match (words.next(), words.next()) {
+        (Some(type_), Some(name)) => (type_, name),
+        _ => return Err(format!("unexpected declaration `{}`", declaration)),
+    };
+
+    match name.strip_suffix(']').and_then(|name| name.split_once('[')) {
+        Some((name, length)) => Ok(Member {
+            type_: type_.to_string(),
+            name: name.to_string(),
+            array: Some(
+                length
+                    .parse()
+                    .map_err(|_| format!("unexpected array length `{}`", declaration))?,
+            ),
+        }),
+        None => Ok(Member {
+            type_: type_.to_string(),
+            name: name.to_string(),
+            array: None,
+        }),
+    }
+}
+
+fn parse_structs(source: &str) -> Result<HashMap<String, Vec<Member>>, String> {
+    let mut structs = HashMap::new();
+    let mut current: Option<(String, Vec<Member>)> = None;
+
+    for line in source.lines() {
+        if let Some(name) = line
+            .strip_prefix("struct ")
+            .and_then(|line| line.strip_suffix(" {"))
+        {
+            current = Some((name.to_string(), Vec::new()));
+        } else if line == "};" {
+            if let Some((name, members)) = current.take() {
+                structs.insert(name, members);
+            }
+        } else if let Some((_, members)) = current.as_mut() {
+            members.push(parse_member(line)?);
+        }
+    }
+
+    Ok(structs)
+}
+
+/// `(size, alignment)` in bytes with std140 rules.
+fn layout(type_: &str, structs: &HashMap<String, Vec<Member>>) -> Result<(u32, u32), String> {
+    Ok(match type_ {
+        "float" | "int" | "bool" => (4, 4),
+        "vec2" | "ivec2" | "bvec2" => (8, 8),
+        "vec3" | "ivec3" | "bvec3" => (12, 16),
+        "vec4" | "ivec4" | "bvec4" => (16, 16),
+        // columns are vec4 aligned
+        "mat2" => (32, 16),
+        "mat3" => (48, 16),
+        "mat4" => (64, 16),
+        _ => {
+            let members = structs
+                .get(type_)
+                .ok_or_else(|| format!("unsupported uniform type `{}`", type_))?;
+            let mut size = 0u32;
+            for member in members {
+                let (member_size, align) = member_layout(member, structs)?;
+                size = size.next_multiple_of(align) + member_size;
+            }
+            (size.next_multiple_of(16), 16)
+        }
+    })
+}
+
+fn member_layout(
+    member: &Member,
+    structs: &HashMap<String, Vec<Member>>,
+) -> Result<(u32, u32), String> {
+    let (size, align) = layout(&member.type_, structs)?;
+    Ok(match member.array {
+        Some(length) => (size.next_multiple_of(16) * length, 16),
+        None => (size, align),
+    })
+}
+
+fn flatten(
+    name: &str,
+    member: &Member,
+    offset: u32,
+    structs: &HashMap<String, Vec<Member>>,
+    out: &mut Vec<(String, u32)>,
+) -> Result<(), String> {
+    let element = |name: &str, offset: u32, out: &mut Vec<(String, u32)>| -> Result<(), String> {
+        match structs.get(&member.type_) {
+            Some(fields) => {
+                let mut field_offset = offset;
+                for field in fields {
+                    let (size, align) = member_layout(field, structs)?;
+                    field_offset = field_offset.next_multiple_of(align);
+                    flatten(
+                        &format!("{}.{}", name, field.name),
+                        field,
+                        field_offset,
+                        structs,
+                        out,
+                    )?;
+                    field_offset += size;
+                }
+            }
+            None => out.push((name.to_string(), offset)),
+        }
+        Ok(())
+    };
+
+    match member.array {
+        Some(length) => {
+            let stride = layout(&member.type_, structs)?.0.next_multiple_of(16);
+            for index in 0..length {
+                element(
+                    &format!("{}[{}]", name, index),
+                    offset + index * stride,
+                    out,
+                )?;
+            }
+            Ok(())
+        }
+        None => element(name, offset, out),
+    }
+}
+
+/// The declared name of `Type name;`
+fn declaration_name(declaration: &str) -> Result<String, String> {
+    parse_member(declaration).map(|member| member.name)
+}
+
+/// `out`/`in` declarations between the stages.
+fn varying(line: &str, stage: ShaderStage) -> Option<&str> {
+    let line = line.strip_prefix("smooth ").unwrap_or(line);
+    match stage {
+        ShaderStage::Vertex => line.strip_prefix("out "),
+        _ => line.strip_prefix("in "),
+    }
+}
+
+/// `_group_0_binding_1_vs` and `_group_0_binding_1_fs` become `_group_0_binding_1`.
+fn strip_stage(identifier: &str) -> &str {
+    if identifier.starts_with("_group_") {
+        if let Some(name) = identifier
+            .strip_suffix("_vs")
+            .or_else(|| identifier.strip_suffix("_fs"))
+        {
+            return name;
+        }
+    }
+    identifier
+}
+
+/// A `Type name = type(gl_VertexID);` line whose variable is never read.
+fn is_unused_builtin(line: &str, source: &str) -> bool {
+    if !line.contains("gl_VertexID") && !line.contains("gl_InstanceID") {
+        return false;
+    }
+    let name = match line.split_whitespace().nth(1) {
+        Some(name) => name,
+        None => return false,
+    };
+    let mut uses = 0;
+    for source_line in source.lines() {
+        map_identifiers(source_line, |identifier| {
+            if identifier == name {
+                uses += 1;
+            }
+            None
+        });
+    }
+    uses == 1
+}
+
+/// Calls `map` on every identifier in `line`, replacing it when `map` returns `Some`.
+fn map_identifiers(line: &str, mut map: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let identifier = &rest[..end];
+        // the suffix of a number like `1e5` or `0u`
+        let is_number = out.ends_with(|c: char| c.is_ascii_digit());
+        match map(identifier).filter(|_| !is_number) {
+            Some(replacement) => out.push_str(&replacement),
+            None => out.push_str(identifier),
+        }
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// `texture(sampler, uv)` becomes `texture2D(...)` or `textureCube(...)`.
+fn texture_calls(line: &str, samplers: &HashMap<String, bool>) -> Result<String, String> {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find("texture(") {
+        let is_call = rest[..start]
+            .chars()
+            .last()
+            .map(|c| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(true);
+        out.push_str(&rest[..start]);
+        rest = &rest[start + "texture(".len()..];
+
+        if !is_call {
+            out.push_str("texture(");
+            continue;
+        }
+
+        let sampler = rest.split([',', ')']).next().unwrap_or("").trim();
+        let function = match samplers.get(sampler) {
+            Some(true) => "textureCube(",
+            Some(false) => "texture2D(",
+            None => return Err(format!("unknown sampler `{}` in `{}`", sampler, line)),
+        };
+        out.push_str(function);
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn check_supported(source: &str) -> Result<(), String> {
+    for line in source.lines().filter(|line| !line.starts_with('#')) {
+        let mut unsupported = None;
+        map_identifiers(line, |identifier| {
+            if unsupported.is_none() && UNSUPPORTED.contains(&identifier) {
+                unsupported = Some(identifier.to_string());
+            }
+            None
+        });
+        if let Some(identifier) = unsupported {
+            return Err(format!(
+                "`{}` is not supported in GLSL ES 1.00: `{}`",
+                identifier,
+                line.trim()
+            ));
+        }
+
+        let operator = ["%", "<<", ">>", "~", "^", "](", "[]"]
+            .into_iter()
+            .find(|operator| line.contains(operator))
+            .or_else(|| {
+                let single = |c: char| {
+                    line.match_indices(c).any(|(index, _)| {
+                        !line[index + 1..].starts_with(c) && !line[..index].ends_with(c)
+                    })
+                };
+                if single('&') {
+                    Some("&")
+                } else if single('|') {
+                    Some("|")
+                } else {
+                    None
+                }
+            });
+        if let Some(operator) = operator {
+            return Err(format!(
+                "`{}` is not supported in GLSL ES 1.00: `{}`",
+                operator,
+                line.trim()
+            ));
+        }
+
+        // unsigned literals like `0u`
+        let unsigned_literal = line.as_bytes().windows(2).enumerate().any(|(index, pair)| {
+            pair[0].is_ascii_digit()
+                && pair[1] == b'u'
+                && !line.as_bytes()[..index]
+                    .last()
+                    .map(|c| c.is_ascii_alphabetic() || *c == b'_')
+                    .unwrap_or(false)
+        });
+        if unsigned_literal {
+            return Err(format!(
+                "unsigned integers are not supported in GLSL ES 1.00: `{}`",
+                line.trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn es300(wgsl: &str) -> Vec<crate::composer::Shader> {
+    let module = naga::front::wgsl::parse_str(wgsl).unwrap();
+    crate::composer::create_shader(&module).unwrap()
+}
+
+#[test]
+fn downlevel_to_es100() {
+    let shaders = es300(
+        r##"
+    struct Light {
+        color: vec3f,
+        intensity: f32,
+    };
+    struct Transforms {
+        projection_matrix: mat4x4f,
+        normal_matrix: mat3x3f,
+        lights: array<Light, 2>,
+    };
+    @binding(0) @group(0) var<uniform> transforms : Transforms;
+    @binding(1) @group(0) var<uniform> color : vec4f;
+    @binding(2) @group(0) var diffuse : texture_2d<f32>;
+    @binding(3) @group(0) var diffuse_sampler : sampler;
+
+    struct VertexOutput {
+        @builtin(position) position: vec4f,
+        @location(0) uv: vec2f,
+    };
+
+    @vertex
+    fn vs_main(
+        @location(0) position: vec4f,
+        @location(1) uv: vec2f,
+        @builtin(vertex_index) vertex_index: u32,
+    ) -> VertexOutput {
+        return VertexOutput(transforms.projection_matrix * position * color.x, uv);
+    }
+
+    @fragment
+    fn fs_main(input: VertexOutput) -> @location(0) vec4f {
+        return textureSample(diffuse, diffuse_sampler, input.uv) * color;
+    }
+    "##,
+    );
+
+    let vertex = downlevel(&shaders[0].source, ShaderStage::Vertex).unwrap();
+    let fragment = downlevel(&shaders[1].source, ShaderStage::Fragment).unwrap();
+
+    assert!(vertex.source.starts_with("#version 100\n"));
+    assert!(vertex.source.contains("attribute vec4 _p2vs_location0;"));
+    assert!(vertex.source.contains("varying vec2 _vs2fs_location0;"));
+    assert!(vertex
+        .source
+        .contains("uniform Transforms _group_0_binding_0;"));
+    assert!(vertex.source.contains("mat4 projection_matrix;"));
+    assert!(!vertex.source.contains("gl_VertexID"));
+    assert_eq!(
+        vertex.attribute_bindings,
+        vec![
+            ("_p2vs_location0".to_string(), 0),
+            ("_p2vs_location1".to_string(), 1)
+        ]
+    );
+
+    assert!(fragment
+        .source
+        .contains("#ifdef GL_FRAGMENT_PRECISION_HIGH"));
+    assert!(fragment.source.contains("varying vec2 _vs2fs_location0;"));
+    assert!(fragment.source.contains("uniform vec4 _group_0_binding_1;"));
+    assert!(fragment.source.contains("texture2D(_group_0_binding_2"));
+    assert!(fragment.source.contains("gl_FragColor = "));
+    assert!(fragment.attribute_bindings.is_empty());
+
+    assert_eq!(
+        uniform_members(&vertex.source, 0, 0).unwrap(),
+        vec![
+            ("_group_0_binding_0.projection_matrix".to_string(), 0),
+            ("_group_0_binding_0.normal_matrix".to_string(), 64),
+            ("_group_0_binding_0.lights[0].color".to_string(), 112),
+            ("_group_0_binding_0.lights[0].intensity".to_string(), 124),
+            ("_group_0_binding_0.lights[1].color".to_string(), 128),
+            ("_group_0_binding_0.lights[1].intensity".to_string(), 140),
+        ]
+    );
+    assert_eq!(
+        uniform_members(&fragment.source, 0, 1).unwrap(),
+        vec![("_group_0_binding_1".to_string(), 0)]
+    );
+}
+
+#[test]
+fn downlevel_unsupported() {
+    let shaders = es300(
+        r##"
+    @binding(0) @group(0) var<uniform> frame : u32;
+
+    @vertex
+    fn vs_main(@builtin(vertex_index) vertex_index : u32) -> @builtin(position) vec4f {
+        return vec4f(f32(vertex_index), 0, 0, 1);
+    }
+
+    @fragment
+    fn fs_main() -> @location(0) vec4f {
+        return vec4(1, sin(f32(frame % 128u)), 0, 1);
+    }
+    "##,
+    );
+
+    let vertex = downlevel(&shaders[0].source, ShaderStage::Vertex).unwrap_err();
+    assert!(vertex.contains("gl_VertexID"), "{}", vertex);
+    let fragment = downlevel(&shaders[1].source, ShaderStage::Fragment).unwrap_err();
+    assert!(fragment.contains("uint"), "{}", fragment);
+}
+
+#[test]
+fn downlevel_loops() {
+    let shaders = es300(
+        r##"
+    @binding(0) @group(0) var<uniform> color : vec4f;
+
+    @vertex
+    fn vs_main(@location(0) position: vec4f) -> @builtin(position) vec4f {
+        var p = position;
+        for (var i = 0; i < 4; i++) {
+            p.x += 1.0;
+        }
+        return p;
+    }
+
+    @fragment
+    fn fs_main() -> @location(0) vec4f {
+        var c = color;
+        var j = 0;
+        loop {
+            if j >= 2 { break; }
+            for (var i = 0; i < 2; i++) {
+                c.x += 0.5;
+            }
+            j += 1;
+        }
+        return c;
+    }
+    "##,
+    );
+
+    let vertex = downlevel(&shaders[0].source, ShaderStage::Vertex).unwrap();
+    assert!(!vertex.source.contains("while"));
+    assert!(vertex
+        .source
+        .contains("for (int _loop_0 = 0; _loop_0 < 1024; _loop_0++) {"));
+
+    // nested loops get their own counter
+    let fragment = downlevel(&shaders[1].source, ShaderStage::Fragment).unwrap();
+    assert!(fragment.source.contains("_loop_0 < 1024"));
+    assert!(fragment.source.contains("_loop_1 < 1024"));
+    assert!(fragment.source.contains("break;"));
+
+    // a `while` with a condition has no constant bound
+    let error = downlevel(
+        "#version 300 es\nvoid main() {\n    while(i < 4) {\n    }\n}",
+        ShaderStage::Vertex,
+    )
+    .unwrap_err();
+    assert!(error.contains("while"), "{}", error);
+}
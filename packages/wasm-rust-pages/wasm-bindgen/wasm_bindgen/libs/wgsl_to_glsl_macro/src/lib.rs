@@ -5,9 +5,13 @@ use naga_oil::compose::{ComposableModuleDescriptor, Composer, NagaModuleDescript
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use std::sync::{LazyLock, Mutex};
-use syn::{parse_macro_input, LitInt, LitStr};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, LitInt, LitStr, Token,
+};
 
 mod composer;
+mod es100;
 mod wgsl_to_glsl;
 
 #[proc_macro]
@@ -132,10 +136,47 @@ pub fn add_composable_module(input: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// `"path/to/shader.wgsl"` with an optional GLSL ES version, `make_naga_module!("shader.wgsl", 100)`
+struct MakeNagaModuleInput {
+    path: LitStr,
+    version: Option<LitInt>,
+}
+
+impl Parse for MakeNagaModuleInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let version = if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let version: LitInt = input.parse()?;
+            if version.base10_parse::<u32>()? != 100 {
+                return Err(syn::Error::new(
+                    version.span(),
+                    "only GLSL ES 1.00 (`100`) can be requested, 3.00 is always emitted",
+                ));
+            }
+            Some(version)
+        } else {
+            None
+        };
+        Ok(Self { path, version })
+    }
+}
+
+///
+/// Composes a WGSL module and emits GLSL ES 3.00 for WebGL2:
+/// `vertex`/`fragment` sources, `<name>_attribute_location` and
+/// `<name>_uniform_block_name`/`_group`/`_binding` for every uniform.
+///
+/// `make_naga_module!("shader.wgsl", 100)` also emits GLSL ES 1.00 for the WebGL1 fallback:
+/// `vertex_es100`/`fragment_es100` sources, `attribute_bindings` to bind before linking
+/// and `<name>_uniform_members`, the plain uniforms that replace each uniform block.
+/// Loops run at most `es100::MAX_LOOP_ITERATIONS` times there, and a shader ES 1.00
+/// can not express (unsigned integers, bit operations, `switch`...) fails to compile.
+///
 #[proc_macro]
 pub fn make_naga_module(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as LitStr);
-    let file_path = input.value();
+    let input = parse_macro_input!(input as MakeNagaModuleInput);
+    let file_path = input.path.value();
+    let es100 = input.version.is_some();
     let source = std::fs::read_to_string(&file_path).expect("Failed to read WGSL file");
 
     let mut composer = COMPOSER.lock().unwrap();
@@ -163,6 +204,42 @@ pub fn make_naga_module(input: TokenStream) -> TokenStream {
             textures,
         }) in shaders
         {
+            let es100 = if es100 {
+                let naga_stage = match stage.as_str() {
+                    "vertex" => naga::ShaderStage::Vertex,
+                    "fragment" => naga::ShaderStage::Fragment,
+                    _ => naga::ShaderStage::Compute,
+                };
+                let shader = es100::downlevel(&source, naga_stage)
+                    .map_err(|e| format!("{} shader: {}", stage, e))
+                    .unwrap();
+
+                let field_ident = format_ident!("{}_es100", stage);
+                struct_fields.push(quote! {
+                    pub #field_ident: &'static str,
+                });
+                let value_lit = LitStr::new(&shader.source, proc_macro2::Span::call_site());
+                struct_inits.push(quote! {
+                    #field_ident: #value_lit,
+                });
+
+                if naga_stage == naga::ShaderStage::Vertex {
+                    let bindings = shader
+                        .attribute_bindings
+                        .iter()
+                        .map(|(name, location)| quote! { (#name, #location) });
+                    struct_fields.push(quote! {
+                        pub attribute_bindings: &'static [(&'static str, u32)],
+                    });
+                    struct_inits.push(quote! {
+                        attribute_bindings: &[#(#bindings),*],
+                    });
+                }
+
+                Some(shader)
+            } else {
+                None
+            };
             {
                 let field_ident = format_ident!("{}", stage);
                 struct_fields.push(quote! {
@@ -221,6 +298,20 @@ pub fn make_naga_module(input: TokenStream) -> TokenStream {
                     struct_inits.push(quote! {
                         #field_ident: #value_lit,
                     });
+
+                    if let Some(shader) = &es100 {
+                        let members = es100::uniform_members(&shader.source, group, block_binding)
+                            .unwrap()
+                            .into_iter()
+                            .map(|(name, offset)| quote! { (#name, #offset) });
+                        let field_ident = format_ident!("{}_uniform_members", name);
+                        struct_fields.push(quote! {
+                            pub #field_ident: &'static [(&'static str, u32)],
+                        });
+                        struct_inits.push(quote! {
+                            #field_ident: &[#(#members),*],
+                        });
+                    }
                 }
             }
         }
@@ -12,4 +12,24 @@ mod tests {
         let shader = make_naga_module!("libs/wgsl_to_glsl_macro_tests/src/simple/top.wgsl");
         println!("🫠 {:#?}", shader);
     }
+
+    #[test]
+    fn test_naga_macros_es100() {
+        add_composable_module!("libs/wgsl_to_glsl_macro_tests/src/simple/inc.wgsl");
+        let shader = make_naga_module!("libs/wgsl_to_glsl_macro_tests/src/simple/top.wgsl", 100);
+
+        assert!(shader.vertex_es100.starts_with("#version 100"));
+        assert!(shader.fragment_es100.contains("gl_FragColor"));
+        assert_eq!(
+            shader.attribute_bindings,
+            &[("_p2vs_location0", 0), ("_p2vs_location1", 1)]
+        );
+        assert_eq!(
+            shader.transforms_uniform_members,
+            &[
+                ("_group_0_binding_0.projection_matrix", 0),
+                ("_group_0_binding_0.view_matrix", 64)
+            ]
+        );
+    }
 }
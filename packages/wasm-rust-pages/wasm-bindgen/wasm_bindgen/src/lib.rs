@@ -1,9 +1,13 @@
 use wasm_bindgen::prelude::*;
 use web_sys::Element;
 
+#[cfg(test)]
 mod test_lyon;
 
-#[cfg(target_arch = "wasm32")]
+// only the wasm demo uses the vector graphics, natively just the tests do
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod vector;
+
 mod web;
 
 #[wasm_bindgen]
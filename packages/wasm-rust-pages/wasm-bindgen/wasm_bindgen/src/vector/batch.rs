@@ -0,0 +1,504 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use lyon::{
+    math::Transform,
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+        StrokeVertex, VertexBuffers,
+    },
+};
+use webgl_common::{Color, GpuPod};
+
+use super::path::{Style, VectorPath};
+
+/// The vertex layout of the batch buffers: `position` at offset 0, `color` at offset 8.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, GpuPod)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub color: Color,
+}
+
+/// `u32` indices, a path can have more than 65536 vertices.
+pub type Mesh = VertexBuffers<Vertex, u32>;
+
+/// Default tessellation tolerance, in path units.
+pub const DEFAULT_TOLERANCE: f32 = 0.1;
+
+///
+/// Tessellates the fill and then the stroke of the path into `output`, in path space,
+/// the transform isn't applied.
+///
+pub fn tessellate(path: &VectorPath, tolerance: f32, output: &mut Mesh) -> Result<(), String> {
+    if let Some(fill) = &path.style.fill {
+        let options = FillOptions::tolerance(tolerance).with_fill_rule(fill.rule);
+        FillTessellator::new()
+            .tessellate_path(
+                &path.path,
+                &options,
+                &mut BuffersBuilder::new(output, |vertex: FillVertex| Vertex {
+                    position: vertex.position().to_array(),
+                    color: fill.color,
+                }),
+            )
+            .map_err(|e| format!("Failed to fill path: {:?}", e))?;
+    }
+
+    if let Some(stroke) = &path.style.stroke {
+        let options = StrokeOptions::tolerance(tolerance)
+            .with_line_width(stroke.width)
+            .with_line_join(stroke.line_join)
+            .with_start_cap(stroke.start_cap)
+            .with_end_cap(stroke.end_cap)
+            .with_miter_limit(stroke.miter_limit.max(StrokeOptions::MINIMUM_MITER_LIMIT));
+        StrokeTessellator::new()
+            .tessellate_path(
+                &path.path,
+                &options,
+                &mut BuffersBuilder::new(output, |vertex: StrokeVertex| Vertex {
+                    position: vertex.position().to_array(),
+                    color: stroke.color,
+                }),
+            )
+            .map_err(|e| format!("Failed to stroke path: {:?}", e))?;
+    }
+
+    Ok(())
+}
+
+/// How much the transform scales lengths, its largest axis scale.
+fn transform_scale(transform: &Transform) -> f32 {
+    let x = transform.m11.hypot(transform.m12);
+    let y = transform.m21.hypot(transform.m22);
+    x.max(y)
+}
+
+/// Identifies a path in a [PathBatch]. Ids aren't reused, the id of a cleared path finds nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathId(u32);
+
+impl From<PathId> for u32 {
+    fn from(id: PathId) -> u32 {
+        id.0
+    }
+}
+
+impl From<u32> for PathId {
+    fn from(id: u32) -> PathId {
+        PathId(id)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: VectorPath,
+    /// The tessellation in path space, `None` when the path or its style changed.
+    mesh: Option<Mesh>,
+    /// The path space tolerance of `mesh`.
+    mesh_tolerance: f32,
+    /// Why the path couldn't be tessellated, it is left out of the buffers.
+    error: Option<String>,
+    vertices: Range<usize>,
+    indices: Range<usize>,
+}
+
+///
+/// # Path batch
+///
+/// Many [VectorPath]s in one vertex and one index buffer, drawn with a single draw call
+/// in the order they were added.
+///
+/// A path is tessellated again only when its path or style changes, or when its transform scales
+/// it differently, a transform that only moves or rotates it is applied to the cached tessellation.
+/// [PathBatch::rebuild] brings the buffers up to date and tells whether they have to be uploaded again.
+///
+#[derive(Debug)]
+pub struct PathBatch {
+    entries: BTreeMap<PathId, Entry>,
+    next_id: u32,
+    /// In world units, divided by the scale of each transform.
+    tolerance: f32,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    changed: bool,
+    tessellations: usize,
+}
+
+impl Default for PathBatch {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOLERANCE)
+    }
+}
+
+impl PathBatch {
+    pub fn new(tolerance: f32) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            next_id: 0,
+            tolerance,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            changed: false,
+            tessellations: 0,
+        }
+    }
+
+    pub fn add(&mut self, path: VectorPath) -> PathId {
+        let id = PathId(self.next_id);
+        self.next_id += 1;
+        self.entries.insert(
+            id,
+            Entry {
+                path,
+                mesh: None,
+                mesh_tolerance: 0.0,
+                error: None,
+                vertices: 0..0,
+                indices: 0..0,
+            },
+        );
+        self.changed = true;
+        id
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.changed = true;
+    }
+
+    pub fn get(&self, id: PathId) -> Option<&VectorPath> {
+        self.entries.get(&id).map(|entry| &entry.path)
+    }
+
+    /// Every path is tessellated again by the next [PathBatch::rebuild].
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        if self.tolerance != tolerance {
+            self.tolerance = tolerance;
+            self.changed = true;
+        }
+    }
+
+    /// Replaces the path, it is tessellated again.
+    pub fn set(&mut self, id: PathId, path: VectorPath) -> bool {
+        let Some(entry) = self.entries.get_mut(&id) else {
+            return false;
+        };
+        entry.path = path;
+        entry.mesh = None;
+        entry.error = None;
+        self.changed = true;
+        true
+    }
+
+    /// Tessellates the path again when the style is different.
+    pub fn set_style(&mut self, id: PathId, style: Style) -> bool {
+        let Some(entry) = self.entries.get_mut(&id) else {
+            return false;
+        };
+        if entry.path.style != style {
+            entry.path.style = style;
+            entry.mesh = None;
+            entry.error = None;
+            self.changed = true;
+        }
+        true
+    }
+
+    /// Only moves the cached tessellation, unless the new transform scales the path differently.
+    pub fn set_transform(&mut self, id: PathId, transform: Transform) -> bool {
+        let Some(entry) = self.entries.get_mut(&id) else {
+            return false;
+        };
+        if entry.path.transform != transform {
+            entry.path.transform = transform;
+            self.changed = true;
+        }
+        true
+    }
+
+    /// The vertices and the indices of the path in the buffers, after [PathBatch::rebuild].
+    #[cfg(test)]
+    pub fn ranges(&self, id: PathId) -> Option<(Range<usize>, Range<usize>)> {
+        self.entries
+            .get(&id)
+            .map(|entry| (entry.vertices.clone(), entry.indices.clone()))
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// The paths that couldn't be tessellated by the last [PathBatch::rebuild], with the reason.
+    /// They are left out of the buffers until they change.
+    pub fn errors(&self) -> impl Iterator<Item = (PathId, &str)> + '_ {
+        self.entries
+            .iter()
+            .filter_map(|(id, entry)| Some((*id, entry.error.as_deref()?)))
+    }
+
+    /// How many times a path was tessellated since the batch was created.
+    pub fn tessellations(&self) -> usize {
+        self.tessellations
+    }
+
+    /// Tessellates the changed paths and rebuilds the buffers.
+    /// Returns whether the buffers changed since the last rebuild.
+    ///
+    /// A path that can't be tessellated is left out, see [PathBatch::errors].
+    pub fn rebuild(&mut self) -> bool {
+        if !self.changed {
+            return false;
+        }
+
+        self.vertices.clear();
+        self.indices.clear();
+        for entry in self.entries.values_mut() {
+            let tolerance = self.tolerance / transform_scale(&entry.path.transform);
+            if entry.mesh.is_none() || entry.mesh_tolerance != tolerance {
+                let mut mesh = Mesh::new();
+                entry.error = tessellate(&entry.path, tolerance, &mut mesh).err();
+                if entry.error.is_some() {
+                    // the tessellators can fail halfway
+                    mesh = Mesh::new();
+                }
+                entry.mesh = Some(mesh);
+                entry.mesh_tolerance = tolerance;
+                self.tessellations += 1;
+            }
+            let Some(mesh) = &entry.mesh else {
+                continue;
+            };
+
+            let base_vertex = self.vertices.len();
+            let base_index = self.indices.len();
+            let transform = &entry.path.transform;
+            self.vertices
+                .extend(mesh.vertices.iter().map(|vertex| Vertex {
+                    position: transform.transform_point(vertex.position.into()).to_array(),
+                    ..*vertex
+                }));
+            self.indices
+                .extend(mesh.indices.iter().map(|index| index + base_vertex as u32));
+            entry.vertices = base_vertex..self.vertices.len();
+            entry.indices = base_index..self.indices.len();
+        }
+
+        self.changed = false;
+        true
+    }
+}
+
+#[cfg(test)]
+fn area(mesh: &[Vertex], indices: &[u32]) -> f32 {
+    indices
+        .chunks(3)
+        .map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh[triangle[i] as usize].position);
+            ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+        })
+        .sum()
+}
+
+#[test]
+fn tessellate_fill_and_stroke() {
+    use super::path::{FillStyle, StrokeStyle};
+    use lyon::path::LineCap;
+
+    let square = VectorPath::rect([0.0, 0.0], [10.0, 10.0]);
+    let mut mesh = Mesh::new();
+    tessellate(&square, DEFAULT_TOLERANCE, &mut mesh).unwrap();
+    assert!(mesh.vertices.is_empty(), "no style, nothing to draw");
+
+    let mut mesh = Mesh::new();
+    tessellate(
+        &square.clone().with_fill(FillStyle::new(Color::RED)),
+        DEFAULT_TOLERANCE,
+        &mut mesh,
+    )
+    .unwrap();
+    assert!((area(&mesh.vertices, &mesh.indices) - 100.0).abs() < 1e-3);
+    assert!(mesh
+        .vertices
+        .iter()
+        .all(|vertex| vertex.color == Color::RED));
+
+    let line = VectorPath::line([0.0, 0.0], [10.0, 0.0]);
+    let mut mesh = Mesh::new();
+    tessellate(
+        &line.clone().with_stroke(StrokeStyle::new(Color::BLUE, 2.0)),
+        DEFAULT_TOLERANCE,
+        &mut mesh,
+    )
+    .unwrap();
+    assert!((area(&mesh.vertices, &mesh.indices) - 20.0).abs() < 1e-3);
+
+    let mut mesh = Mesh::new();
+    tessellate(
+        &line.with_stroke(StrokeStyle::new(Color::BLUE, 2.0).with_cap(LineCap::Square)),
+        DEFAULT_TOLERANCE,
+        &mut mesh,
+    )
+    .unwrap();
+    assert!((area(&mesh.vertices, &mesh.indices) - 24.0).abs() < 1e-3);
+
+    // the stroke is drawn over the fill
+    let mut mesh = Mesh::new();
+    tessellate(
+        &square
+            .with_fill(FillStyle::new(Color::RED))
+            .with_stroke(StrokeStyle::new(Color::BLUE, 1.0)),
+        DEFAULT_TOLERANCE,
+        &mut mesh,
+    )
+    .unwrap();
+    let first_stroke = mesh
+        .indices
+        .iter()
+        .position(|i| mesh.vertices[*i as usize].color == Color::BLUE);
+    assert!(first_stroke.unwrap() > 0);
+    assert!(mesh.indices[first_stroke.unwrap()..]
+        .iter()
+        .all(|i| mesh.vertices[*i as usize].color == Color::BLUE));
+}
+
+#[test]
+fn batch_rebuilds_only_changes() {
+    use super::path::FillStyle;
+
+    let mut batch = PathBatch::default();
+    let square =
+        batch.add(VectorPath::rect([0.0, 0.0], [10.0, 10.0]).with_fill(FillStyle::new(Color::RED)));
+    let circle =
+        batch.add(VectorPath::circle([0.0, 0.0], 5.0).with_fill(FillStyle::new(Color::GREEN)));
+    assert!(batch.rebuild());
+    assert!(!batch.rebuild());
+    assert_eq!(batch.tessellations(), 2);
+
+    let (square_vertices, square_indices) = batch.ranges(square).unwrap();
+    let (circle_vertices, circle_indices) = batch.ranges(circle).unwrap();
+    assert_eq!(square_vertices.end, circle_vertices.start);
+    assert_eq!(square_indices.end, circle_indices.start);
+    assert_eq!(circle_indices.end, batch.indices().len());
+    // the indices of the circle point at its own vertices
+    assert!(batch.indices()[circle_indices]
+        .iter()
+        .all(|i| circle_vertices.contains(&(*i as usize))));
+    let indices = batch.indices().len();
+    let area_before = area(batch.vertices(), batch.indices());
+
+    // moving a path doesn't tessellate it again
+    batch.set_transform(square, Transform::translation(100.0, 0.0));
+    assert!(batch.rebuild());
+    assert_eq!(batch.tessellations(), 2);
+    assert_eq!(batch.indices().len(), indices);
+    assert!(batch.vertices()[square_vertices.clone()]
+        .iter()
+        .all(|vertex| vertex.position[0] >= 100.0));
+    assert!((area(batch.vertices(), batch.indices()) - area_before).abs() < 1e-3);
+
+    // a new style does
+    batch.set_style(
+        circle,
+        Style {
+            fill: Some(FillStyle::new(Color::GREEN)),
+            stroke: None,
+        },
+    );
+    assert!(!batch.rebuild());
+    batch.set_style(
+        circle,
+        Style {
+            fill: Some(FillStyle::new(Color::BLUE)),
+            stroke: None,
+        },
+    );
+    assert!(batch.rebuild());
+    assert_eq!(batch.tessellations(), 3);
+    assert!(batch.vertices()[circle_vertices]
+        .iter()
+        .all(|vertex| vertex.color == Color::BLUE));
+}
+
+#[test]
+fn batch_tolerance_follows_scale() {
+    use super::path::FillStyle;
+
+    let mut batch = PathBatch::default();
+    let circle =
+        batch.add(VectorPath::circle([0.0, 0.0], 1.0).with_fill(FillStyle::new(Color::RED)));
+    batch.rebuild();
+    let small = batch.vertices().len();
+
+    // a 100 times bigger circle needs more segments for the same error in world units
+    batch.set_transform(circle, Transform::scale(100.0, 100.0));
+    batch.rebuild();
+    assert_eq!(batch.tessellations(), 2);
+    assert!(batch.vertices().len() > small);
+    let big = batch.vertices().len();
+
+    // moving it keeps the tessellation
+    batch.set_transform(
+        circle,
+        Transform::scale(100.0, 100.0).then_translate([50.0, 0.0].into()),
+    );
+    batch.rebuild();
+    assert_eq!(batch.tessellations(), 2);
+
+    batch.set_tolerance(DEFAULT_TOLERANCE / 10.0);
+    assert!(batch.rebuild());
+    assert_eq!(batch.tessellations(), 3);
+    assert!(batch.vertices().len() > big);
+}
+
+#[test]
+fn batch_skips_failed_paths() {
+    use super::path::FillStyle;
+
+    let mut batch = PathBatch::default();
+    // an infinite scale leaves no tolerance, the tessellator refuses it
+    let broken = batch.add(
+        VectorPath::rect([20.0, 0.0], [30.0, 10.0])
+            .with_fill(FillStyle::new(Color::RED))
+            .with_transform(Transform::scale(f32::INFINITY, 1.0)),
+    );
+    let square =
+        batch.add(VectorPath::rect([0.0, 0.0], [10.0, 10.0]).with_fill(FillStyle::new(Color::RED)));
+    assert!(batch.rebuild());
+    assert!(!batch.rebuild(), "the batch is complete");
+
+    let errors = batch.errors().collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, broken);
+    assert_eq!(batch.ranges(broken).unwrap().0, 0..0);
+    assert!((area(batch.vertices(), batch.indices()) - 100.0).abs() < 1e-3);
+    assert_eq!(batch.ranges(square).unwrap().1.end, batch.indices().len());
+
+    // the error is cleared once the path is fixed
+    batch.set_transform(broken, Transform::identity());
+    assert!(batch.rebuild());
+    assert_eq!(batch.errors().count(), 0);
+    assert!((area(batch.vertices(), batch.indices()) - 200.0).abs() < 1e-3);
+}
+
+#[test]
+fn batch_u32_indices() {
+    use super::path::StrokeStyle;
+
+    // a long polyline has more vertices than `u16` indices can address
+    let points = (0..40_000)
+        .map(|i| [i as f32, (i % 2) as f32 * 10.0])
+        .collect::<Vec<_>>();
+    let mut batch = PathBatch::default();
+    batch.add(VectorPath::polygon(&points, false).with_stroke(StrokeStyle::new(Color::BLACK, 1.0)));
+    batch.rebuild();
+
+    assert!(batch.vertices().len() > u16::MAX as usize);
+    assert_eq!(
+        *batch.indices().iter().max().unwrap() as usize,
+        batch.vertices().len() - 1
+    );
+}
@@ -0,0 +1,4 @@
+// Vector graphics: paths with fill and stroke styles, tessellated with lyon into one batch
+pub mod batch;
+pub mod path;
+pub mod svg;
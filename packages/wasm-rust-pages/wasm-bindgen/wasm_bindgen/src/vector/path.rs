@@ -0,0 +1,261 @@
+use lyon::{
+    extra::parser::{ParserOptions, PathParser, Source},
+    math::{point, Angle, Box2D, Point, Transform, Vector},
+    path::{builder::BorderRadii, FillRule, LineCap, LineJoin, Path, Polygon, Winding},
+};
+use webgl_common::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillStyle {
+    pub color: Color,
+    pub rule: FillRule,
+}
+
+impl FillStyle {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            rule: FillRule::NonZero,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    pub color: Color,
+    /// In path units, scaled with the transform of the path.
+    pub width: f32,
+    pub line_join: LineJoin,
+    pub start_cap: LineCap,
+    pub end_cap: LineCap,
+    pub miter_limit: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(color: Color, width: f32) -> Self {
+        Self {
+            color,
+            width,
+            line_join: LineJoin::Miter,
+            start_cap: LineCap::Butt,
+            end_cap: LineCap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+
+    pub fn with_join(self, line_join: LineJoin) -> Self {
+        Self { line_join, ..self }
+    }
+
+    pub fn with_cap(self, cap: LineCap) -> Self {
+        Self {
+            start_cap: cap,
+            end_cap: cap,
+            ..self
+        }
+    }
+
+    pub fn with_miter_limit(self, miter_limit: f32) -> Self {
+        Self {
+            miter_limit,
+            ..self
+        }
+    }
+}
+
+/// How a path is drawn, the fill is drawn below the stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Style {
+    pub fill: Option<FillStyle>,
+    pub stroke: Option<StrokeStyle>,
+}
+
+///
+/// # Vector path
+///
+/// A [lyon path](Path) with a [Style] and a transform, what a [super::PathBatch] draws.
+/// Build it from a lyon path, an [SVG path string](https://developer.mozilla.org/en-US/docs/Web/SVG/Attribute/d)
+/// or one of the basic shapes.
+///
+#[derive(Debug, Clone)]
+pub struct VectorPath {
+    pub path: Path,
+    pub style: Style,
+    pub transform: Transform,
+}
+
+impl VectorPath {
+    /// A path that isn't drawn until it gets a fill or a stroke.
+    pub fn new(path: Path) -> Self {
+        Self {
+            path,
+            style: Style::default(),
+            transform: Transform::identity(),
+        }
+    }
+
+    /// Parses the `d` attribute of an SVG `<path>`.
+    pub fn from_svg(d: &str) -> Result<Self, String> {
+        parse_svg_path(d).map(Self::new)
+    }
+
+    pub fn rect(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self::build(|builder| {
+            builder.add_rectangle(&Box2D::new(min.into(), max.into()), Winding::Positive)
+        })
+    }
+
+    pub fn rounded_rect(min: [f32; 2], max: [f32; 2], radius: f32) -> Self {
+        Self::build(|builder| {
+            builder.add_rounded_rectangle(
+                &Box2D::new(min.into(), max.into()),
+                &BorderRadii::new(radius),
+                Winding::Positive,
+            )
+        })
+    }
+
+    pub fn circle(center: [f32; 2], radius: f32) -> Self {
+        Self::build(|builder| builder.add_circle(center.into(), radius, Winding::Positive))
+    }
+
+    /// `rotation` in radians.
+    pub fn ellipse(center: [f32; 2], radii: [f32; 2], rotation: f32) -> Self {
+        Self::build(|builder| {
+            builder.add_ellipse(
+                center.into(),
+                Vector::from(radii),
+                Angle::radians(rotation),
+                Winding::Positive,
+            )
+        })
+    }
+
+    pub fn line(from: [f32; 2], to: [f32; 2]) -> Self {
+        Self::polygon(&[from, to], false)
+    }
+
+    /// A polyline when not `closed`.
+    pub fn polygon(points: &[[f32; 2]], closed: bool) -> Self {
+        let points = points
+            .iter()
+            .map(|p| point(p[0], p[1]))
+            .collect::<Vec<Point>>();
+        Self::build(|builder| {
+            if !points.is_empty() {
+                builder.add_polygon(Polygon {
+                    points: &points,
+                    closed,
+                })
+            }
+        })
+    }
+
+    fn build(build: impl FnOnce(&mut lyon::path::path::Builder)) -> Self {
+        let mut builder = Path::builder();
+        build(&mut builder);
+        Self::new(builder.build())
+    }
+
+    pub fn with_fill(self, fill: FillStyle) -> Self {
+        Self {
+            style: Style {
+                fill: Some(fill),
+                ..self.style
+            },
+            ..self
+        }
+    }
+
+    pub fn with_stroke(self, stroke: StrokeStyle) -> Self {
+        Self {
+            style: Style {
+                stroke: Some(stroke),
+                ..self.style
+            },
+            ..self
+        }
+    }
+
+    pub fn with_style(self, style: Style) -> Self {
+        Self { style, ..self }
+    }
+
+    pub fn with_transform(self, transform: Transform) -> Self {
+        Self { transform, ..self }
+    }
+}
+
+pub fn parse_svg_path(d: &str) -> Result<Path, String> {
+    // the path builder panics on a line before a move
+    if let Some(command) = d.trim_start().chars().next() {
+        if !matches!(command, 'M' | 'm') {
+            return Err(format!("Expected a move-to command, got {:?}", command));
+        }
+    }
+    let mut builder = Path::builder_with_attributes(0);
+    PathParser::new()
+        .parse(
+            &ParserOptions::DEFAULT,
+            &mut Source::new(d.chars()),
+            &mut builder,
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+fn bounds(path: &VectorPath) -> [[f32; 2]; 2] {
+    let bounds = lyon::algorithms::aabb::bounding_box(path.path.iter());
+    [bounds.min.to_array(), bounds.max.to_array()]
+}
+
+#[test]
+fn svg_path_strings() {
+    let path = VectorPath::from_svg("M 0 0 L 10 0 l 0 10 H 0 Z m 20 0 q 5 -5 10 0").unwrap();
+    assert_eq!(bounds(&path), [[0.0, -2.5], [30.0, 10.0]]);
+    assert_eq!(path.style, Style::default());
+
+    assert!(VectorPath::from_svg("M 0 0 L 10").is_err());
+    assert!(VectorPath::from_svg("L 10 10").is_err());
+    assert!(VectorPath::from_svg("")
+        .unwrap()
+        .path
+        .iter()
+        .next()
+        .is_none());
+}
+
+#[test]
+fn basic_shapes() {
+    assert_eq!(
+        bounds(&VectorPath::rect([1.0, 2.0], [3.0, 5.0])),
+        [[1.0, 2.0], [3.0, 5.0]]
+    );
+    assert_eq!(
+        bounds(&VectorPath::rounded_rect([0.0, 0.0], [10.0, 10.0], 2.0)),
+        [[0.0, 0.0], [10.0, 10.0]]
+    );
+    let [min, max] = bounds(&VectorPath::circle([5.0, 5.0], 2.0));
+    assert!((min[0] - 3.0).abs() < 1e-3 && (max[1] - 7.0).abs() < 1e-3);
+    let [min, max] = bounds(&VectorPath::ellipse([0.0, 0.0], [4.0, 1.0], 0.0));
+    assert!((min[0] + 4.0).abs() < 1e-3 && (max[1] - 1.0).abs() < 1e-3);
+    assert_eq!(
+        bounds(&VectorPath::polygon(
+            &[[0.0, 0.0], [4.0, 0.0], [2.0, 3.0]],
+            true
+        )),
+        [[0.0, 0.0], [4.0, 3.0]]
+    );
+    assert_eq!(
+        bounds(&VectorPath::line([0.0, 1.0], [4.0, 1.0])),
+        [[0.0, 1.0], [4.0, 1.0]]
+    );
+    assert!(VectorPath::polygon(&[], false).path.iter().next().is_none());
+
+    let path = VectorPath::circle([0.0, 0.0], 1.0)
+        .with_fill(FillStyle::new(Color::RED))
+        .with_stroke(StrokeStyle::new(Color::BLACK, 2.0).with_cap(LineCap::Round));
+    assert_eq!(path.style.fill.unwrap().color, Color::RED);
+    assert_eq!(path.style.stroke.unwrap().start_cap, LineCap::Round);
+}
@@ -0,0 +1,601 @@
+use std::str::FromStr;
+
+use lyon::{
+    math::{point, vector, Angle, Box2D, Transform},
+    path::{builder::SvgPathBuilder, ArcFlags, FillRule, LineCap, LineJoin, Path},
+};
+use roxmltree::{Document, Node};
+use webgl_common::Color;
+
+use super::{
+    batch::{PathBatch, PathId},
+    path::{parse_svg_path, FillStyle, StrokeStyle, Style, VectorPath},
+};
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+///
+/// # SVG document
+///
+/// The shapes of an [SVG](https://developer.mozilla.org/en-US/docs/Web/SVG) document as
+/// [vector paths](VectorPath), in document order, each with the transform of its groups.
+///
+/// Supports `<path>`, `<rect>`, `<circle>`, `<ellipse>`, `<line>`, `<polyline>`, `<polygon>`,
+/// `<g>` and the fill and stroke presentation attributes, also in a `style` attribute.
+/// Anything else (text, images, `<use>`, gradients, CSS stylesheets...) is skipped with a warning.
+///
+/// Coordinates are SVG user units, y down.
+///
+#[derive(Debug, Clone)]
+pub struct SvgDocument {
+    pub paths: Vec<VectorPath>,
+    /// The `viewBox` of the root, or its `width` and `height`.
+    pub view_box: Option<Box2D>,
+    /// What wasn't imported, with the line and column of the element.
+    pub warnings: Vec<String>,
+}
+
+impl SvgDocument {
+    /// Fails only when the source isn't XML or the root isn't an `<svg>`.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let document = Document::parse(source).map_err(|e| e.to_string())?;
+        let root = document.root_element();
+        if root.tag_name().name() != "svg" {
+            return Err(format!(
+                "Expected an <svg> root element, got <{}>",
+                root.tag_name().name()
+            ));
+        }
+
+        let mut importer = Importer::default();
+        let view_box = importer.view_box(root);
+        importer.element(root, &Inherited::default());
+
+        Ok(Self {
+            paths: importer.paths,
+            view_box,
+            warnings: importer.warnings,
+        })
+    }
+
+    /// Adds every path to the batch, after `transform` (e.g. to flip the document y up).
+    pub fn add_to(&self, batch: &mut PathBatch, transform: &Transform) -> Vec<PathId> {
+        self.paths
+            .iter()
+            .map(|path| {
+                let transform = path.transform.then(transform);
+                batch.add(path.clone().with_transform(transform))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paint {
+    Color(Color),
+    CurrentColor,
+}
+
+/// The properties the children of an element inherit.
+#[derive(Debug, Clone)]
+struct Inherited {
+    transform: Transform,
+    /// `opacity` isn't inherited, but the product of the group opacities is close enough
+    /// as long as the shapes of a group don't overlap.
+    opacity: f32,
+    color: Color,
+    fill: Option<Paint>,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Option<Paint>,
+    stroke_opacity: f32,
+    stroke_width: f32,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    miter_limit: f32,
+    visible: bool,
+}
+
+impl Default for Inherited {
+    fn default() -> Self {
+        Self {
+            transform: Transform::identity(),
+            opacity: 1.0,
+            color: Color::BLACK,
+            fill: Some(Paint::Color(Color::BLACK)),
+            fill_opacity: 1.0,
+            fill_rule: FillRule::NonZero,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            miter_limit: 4.0,
+            visible: true,
+        }
+    }
+}
+
+impl Inherited {
+    fn style(&self) -> Style {
+        let paint = |paint: Option<Paint>, opacity: f32| {
+            let color = match paint? {
+                Paint::Color(color) => color,
+                Paint::CurrentColor => self.color,
+            };
+            let a = color.a * opacity * self.opacity;
+            (a > 0.0).then_some(Color { a, ..color })
+        };
+        Style {
+            fill: paint(self.fill, self.fill_opacity).map(|color| FillStyle {
+                color,
+                rule: self.fill_rule,
+            }),
+            stroke: paint(self.stroke, self.stroke_opacity)
+                .filter(|_| self.stroke_width > 0.0)
+                .map(|color| {
+                    StrokeStyle::new(color, self.stroke_width)
+                        .with_join(self.line_join)
+                        .with_cap(self.line_cap)
+                        .with_miter_limit(self.miter_limit)
+                }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Importer {
+    paths: Vec<VectorPath>,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    fn warn(&mut self, node: Node, message: impl std::fmt::Display) {
+        let position = node.document().text_pos_at(node.range().start);
+        self.warnings.push(format!("{}: {}", position, message));
+    }
+
+    fn view_box(&mut self, root: Node) -> Option<Box2D> {
+        if let Some(value) = root.attribute("viewBox") {
+            match svgtypes::ViewBox::from_str(value) {
+                Ok(view_box) => {
+                    let min = point(view_box.x as f32, view_box.y as f32);
+                    return Some(Box2D::new(
+                        min,
+                        min + vector(view_box.w as f32, view_box.h as f32),
+                    ));
+                }
+                Err(e) => self.warn(root, format_args!("invalid viewBox {:?}: {}", value, e)),
+            }
+        }
+        let width = self.length(root, "width")?;
+        let height = self.length(root, "height")?;
+        Some(Box2D::new(point(0.0, 0.0), point(width, height)))
+    }
+
+    fn element(&mut self, node: Node, parent: &Inherited) {
+        // elements of other namespaces are editor metadata
+        if node
+            .tag_name()
+            .namespace()
+            .is_some_and(|ns| ns != SVG_NAMESPACE)
+        {
+            return;
+        }
+        if self.property(node, "display") == Some("none") {
+            return;
+        }
+
+        let name = node.tag_name().name();
+        match name {
+            "svg" | "g" | "a" => {
+                let mut inherited = self.inherited(node, parent);
+                if name == "svg" && node.parent_element().is_some() {
+                    self.warn(
+                        node,
+                        "a nested <svg> is drawn as a group, without its viewport",
+                    );
+                    let x = self.length(node, "x").unwrap_or(0.0);
+                    let y = self.length(node, "y").unwrap_or(0.0);
+                    inherited.transform = Transform::translation(x, y).then(&inherited.transform);
+                }
+                for child in node.children().filter(Node::is_element) {
+                    self.element(child, &inherited);
+                }
+            }
+            "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {
+                let inherited = self.inherited(node, parent);
+                if let Some(path) = self.shape(node) {
+                    let style = inherited.style();
+                    if inherited.visible && style != Style::default() {
+                        self.paths.push(
+                            VectorPath::new(path)
+                                .with_style(style)
+                                .with_transform(inherited.transform),
+                        );
+                    }
+                }
+            }
+            // not drawn, gradients in them are reported where they are used
+            "defs" | "title" | "desc" | "metadata" => {}
+            _ => self.warn(node, format_args!("unsupported element <{}>", name)),
+        }
+    }
+
+    /// `None` for shapes that aren't drawn, like a zero-sized rectangle.
+    fn shape(&mut self, node: Node) -> Option<Path> {
+        let path = match node.tag_name().name() {
+            "path" => {
+                let d = node.attribute("d")?;
+                match parse_svg_path(d) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        self.warn(node, format_args!("invalid path data: {}", e));
+                        return None;
+                    }
+                }
+            }
+            "rect" => {
+                let [x, y, width, height] = self.lengths(node, ["x", "y", "width", "height"]);
+                if width <= 0.0 || height <= 0.0 {
+                    return None;
+                }
+                // a missing radius is the other one
+                let (rx, ry) = match (self.length(node, "rx"), self.length(node, "ry")) {
+                    (Some(rx), Some(ry)) => (rx, ry),
+                    (Some(r), None) | (None, Some(r)) => (r, r),
+                    (None, None) => (0.0, 0.0),
+                };
+                let (rx, ry) = (rx.clamp(0.0, width / 2.0), ry.clamp(0.0, height / 2.0));
+                rect_path(
+                    Box2D::new(point(x, y), point(x + width, y + height)),
+                    rx,
+                    ry,
+                )
+            }
+            "circle" => {
+                let [cx, cy, r] = self.lengths(node, ["cx", "cy", "r"]);
+                ellipse_path(cx, cy, r, r)?
+            }
+            "ellipse" => {
+                let [cx, cy, rx, ry] = self.lengths(node, ["cx", "cy", "rx", "ry"]);
+                ellipse_path(cx, cy, rx, ry)?
+            }
+            "line" => {
+                let [x1, y1, x2, y2] = self.lengths(node, ["x1", "y1", "x2", "y2"]);
+                VectorPath::line([x1, y1], [x2, y2]).path
+            }
+            name => {
+                let points = svgtypes::PointsParser::from(node.attribute("points")?)
+                    .map(|(x, y)| [x as f32, y as f32])
+                    .collect::<Vec<_>>();
+                if points.len() < 2 {
+                    return None;
+                }
+                VectorPath::polygon(&points, name == "polygon").path
+            }
+        };
+        Some(path)
+    }
+
+    /// The properties of the element on top of the inherited ones, invalid values are ignored.
+    fn inherited(&mut self, node: Node, parent: &Inherited) -> Inherited {
+        let mut inherited = parent.clone();
+
+        if let Some(value) = node.attribute("transform") {
+            match svgtypes::Transform::from_str(value) {
+                Ok(t) => {
+                    let [a, b, c, d, e, f] = [t.a, t.b, t.c, t.d, t.e, t.f].map(|v| v as f32);
+                    inherited.transform = Transform::new(a, b, c, d, e, f).then(&parent.transform);
+                }
+                Err(e) => self.warn(node, format_args!("invalid transform {:?}: {}", value, e)),
+            }
+        }
+
+        if let Some(opacity) = self.number(node, "opacity") {
+            inherited.opacity *= opacity.clamp(0.0, 1.0);
+        }
+        if let Some(value) = self.property(node, "color") {
+            match svgtypes::Color::from_str(value) {
+                Ok(color) => inherited.color = convert_color(color),
+                Err(e) => self.warn(node, format_args!("invalid color {:?}: {}", value, e)),
+            }
+        }
+        if let Some(paint) = self.paint(node, "fill") {
+            inherited.fill = paint;
+        }
+        if let Some(opacity) = self.number(node, "fill-opacity") {
+            inherited.fill_opacity = opacity.clamp(0.0, 1.0);
+        }
+        match self.property(node, "fill-rule") {
+            Some("nonzero") => inherited.fill_rule = FillRule::NonZero,
+            Some("evenodd") => inherited.fill_rule = FillRule::EvenOdd,
+            _ => {}
+        }
+        if let Some(paint) = self.paint(node, "stroke") {
+            inherited.stroke = paint;
+        }
+        if let Some(opacity) = self.number(node, "stroke-opacity") {
+            inherited.stroke_opacity = opacity.clamp(0.0, 1.0);
+        }
+        if let Some(width) = self.length(node, "stroke-width") {
+            inherited.stroke_width = width;
+        }
+        match self.property(node, "stroke-linejoin") {
+            Some("miter") => inherited.line_join = LineJoin::Miter,
+            Some("miter-clip") => inherited.line_join = LineJoin::MiterClip,
+            Some("round") => inherited.line_join = LineJoin::Round,
+            Some("bevel") => inherited.line_join = LineJoin::Bevel,
+            _ => {}
+        }
+        match self.property(node, "stroke-linecap") {
+            Some("butt") => inherited.line_cap = LineCap::Butt,
+            Some("round") => inherited.line_cap = LineCap::Round,
+            Some("square") => inherited.line_cap = LineCap::Square,
+            _ => {}
+        }
+        if let Some(limit) = self.number(node, "stroke-miterlimit") {
+            inherited.miter_limit = limit;
+        }
+        match self.property(node, "visibility") {
+            Some("visible") => inherited.visible = true,
+            Some("hidden" | "collapse") => inherited.visible = false,
+            _ => {}
+        }
+        inherited
+    }
+
+    /// A declaration of the `style` attribute wins over the presentation attribute.
+    fn property<'a>(&self, node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+        let declared = node.attribute("style").and_then(|style| {
+            style.split(';').rev().find_map(|declaration| {
+                let (property, value) = declaration.split_once(':')?;
+                (property.trim() == name).then(|| value.trim())
+            })
+        });
+        declared
+            .or_else(|| node.attribute(name))
+            .filter(|value| *value != "inherit")
+    }
+
+    fn number(&mut self, node: Node, name: &str) -> Option<f32> {
+        let value = self.property(node, name)?;
+        match svgtypes::Number::from_str(value) {
+            Ok(number) => Some(number.0 as f32),
+            Err(e) => {
+                self.warn(node, format_args!("invalid {} {:?}: {}", name, value, e));
+                None
+            }
+        }
+    }
+
+    /// In user units, relative units aren't supported.
+    fn length(&mut self, node: Node, name: &str) -> Option<f32> {
+        use svgtypes::LengthUnit;
+
+        let value = self.property(node, name)?;
+        let length = match svgtypes::Length::from_str(value) {
+            Ok(length) => length,
+            Err(e) => {
+                self.warn(node, format_args!("invalid {} {:?}: {}", name, value, e));
+                return None;
+            }
+        };
+        let scale = match length.unit {
+            LengthUnit::None | LengthUnit::Px => 1.0,
+            LengthUnit::In => 96.0,
+            LengthUnit::Cm => 96.0 / 2.54,
+            LengthUnit::Mm => 96.0 / 25.4,
+            LengthUnit::Pt => 4.0 / 3.0,
+            LengthUnit::Pc => 16.0,
+            LengthUnit::Em | LengthUnit::Ex | LengthUnit::Percent => {
+                self.warn(
+                    node,
+                    format_args!("unsupported relative {} {:?}", name, value),
+                );
+                return None;
+            }
+        };
+        Some((length.number * scale) as f32)
+    }
+
+    /// Missing lengths are 0.
+    fn lengths<const N: usize>(&mut self, node: Node, names: [&str; N]) -> [f32; N] {
+        names.map(|name| self.length(node, name).unwrap_or(0.0))
+    }
+
+    /// `Some(None)` is `none`.
+    fn paint(&mut self, node: Node, name: &str) -> Option<Option<Paint>> {
+        let value = self.property(node, name)?;
+        match svgtypes::Paint::from_str(value) {
+            Ok(svgtypes::Paint::None) => Some(None),
+            Ok(svgtypes::Paint::CurrentColor) => Some(Some(Paint::CurrentColor)),
+            Ok(svgtypes::Paint::Color(color)) => Some(Some(Paint::Color(convert_color(color)))),
+            Ok(svgtypes::Paint::FuncIRI(iri, fallback)) => {
+                self.warn(
+                    node,
+                    format_args!("unsupported {} url(#{}), gradients and patterns", name, iri),
+                );
+                match fallback {
+                    Some(svgtypes::PaintFallback::Color(color)) => {
+                        Some(Some(Paint::Color(convert_color(color))))
+                    }
+                    Some(svgtypes::PaintFallback::CurrentColor) => Some(Some(Paint::CurrentColor)),
+                    Some(svgtypes::PaintFallback::None) | None => Some(None),
+                }
+            }
+            Ok(_) => {
+                self.warn(node, format_args!("unsupported {} {:?}", name, value));
+                None
+            }
+            Err(e) => {
+                self.warn(node, format_args!("invalid {} {:?}: {}", name, value, e));
+                None
+            }
+        }
+    }
+}
+
+fn convert_color(color: svgtypes::Color) -> Color {
+    let [r, g, b, a] = [color.red, color.green, color.blue, color.alpha].map(|c| c as f32 / 255.0);
+    Color::new(r, g, b, a)
+}
+
+fn rect_path(rect: Box2D, rx: f32, ry: f32) -> Path {
+    let mut builder = Path::builder().with_svg();
+    if rx <= 0.0 || ry <= 0.0 {
+        builder.move_to(rect.min);
+        builder.horizontal_line_to(rect.max.x);
+        builder.vertical_line_to(rect.max.y);
+        builder.horizontal_line_to(rect.min.x);
+        builder.close();
+        return builder.build();
+    }
+
+    // clockwise, in y down
+    let Box2D { min, max } = rect;
+    let corner = ArcFlags {
+        large_arc: false,
+        sweep: true,
+    };
+    let radii = vector(rx, ry);
+    builder.move_to(point(min.x + rx, min.y));
+    builder.horizontal_line_to(max.x - rx);
+    builder.arc_to(radii, Angle::zero(), corner, point(max.x, min.y + ry));
+    builder.vertical_line_to(max.y - ry);
+    builder.arc_to(radii, Angle::zero(), corner, point(max.x - rx, max.y));
+    builder.horizontal_line_to(min.x + rx);
+    builder.arc_to(radii, Angle::zero(), corner, point(min.x, max.y - ry));
+    builder.vertical_line_to(min.y + ry);
+    builder.arc_to(radii, Angle::zero(), corner, point(min.x + rx, min.y));
+    builder.close();
+    builder.build()
+}
+
+fn ellipse_path(cx: f32, cy: f32, rx: f32, ry: f32) -> Option<Path> {
+    (rx > 0.0 && ry > 0.0).then(|| VectorPath::ellipse([cx, cy], [rx, ry], 0.0).path)
+}
+
+#[cfg(test)]
+fn bounds(path: &VectorPath) -> [[f32; 2]; 2] {
+    let bounds = lyon::algorithms::aabb::bounding_box(path.path.iter());
+    let bounds = path.transform.outer_transformed_box(&bounds);
+    [bounds.min, bounds.max].map(|p| p.to_array().map(|v| (v * 100.0).round() / 100.0))
+}
+
+#[test]
+fn svg_shapes() {
+    let document = SvgDocument::parse(include_str!("samples/shapes.svg")).unwrap();
+    assert_eq!(document.warnings, Vec::<String>::new());
+    assert_eq!(
+        document.view_box,
+        Some(Box2D::new(point(0.0, 0.0), point(200.0, 100.0)))
+    );
+    assert_eq!(
+        document.paths.iter().map(bounds).collect::<Vec<_>>(),
+        [
+            [[10.0, 10.0], [40.0, 40.0]],
+            [[50.0, 10.0], [80.0, 30.0]],
+            [[95.0, 10.0], [125.0, 40.0]],
+            [[130.0, 15.0], [170.0, 35.0]],
+            [[10.0, 60.0], [60.0, 60.0]],
+            [[70.0, 60.0], [110.0, 80.0]],
+            [[120.0, 60.0], [160.0, 90.0]],
+        ]
+    );
+
+    let [triangle, rect, circle, ellipse, line, polyline, polygon] =
+        <[_; 7]>::try_from(document.paths).unwrap().map(|p| p.style);
+    assert_eq!(triangle.fill, Some(FillStyle::new(Color::RED)));
+    assert_eq!(triangle.stroke, None);
+    assert_eq!(rect.fill.unwrap().color, Color::new(0.0, 0.0, 1.0, 1.0));
+    assert_eq!(rect.stroke, Some(StrokeStyle::new(Color::BLACK, 2.0)));
+    assert_eq!(
+        circle.fill.unwrap().color,
+        Color::new(0.0, 128.0 / 255.0, 0.0, 1.0)
+    );
+    assert_eq!(ellipse.fill, None);
+    assert_eq!(ellipse.stroke.unwrap().line_join, LineJoin::Round);
+    assert_eq!(line.stroke.unwrap().end_cap, LineCap::Round);
+    assert_eq!(polyline.stroke.unwrap().width, 1.0);
+    let fill = polygon.fill.unwrap();
+    assert_eq!((fill.color.a, fill.rule), (0.5, FillRule::EvenOdd));
+
+    let mut batch = PathBatch::default();
+    let flip = Transform::scale(1.0, -1.0);
+    let ids = SvgDocument::parse(include_str!("samples/shapes.svg"))
+        .unwrap()
+        .add_to(&mut batch, &flip);
+    assert_eq!(ids.len(), 7);
+    assert!(batch.rebuild());
+    assert!(batch.vertices().iter().all(|v| v.position[1] <= 0.0));
+}
+
+#[test]
+fn svg_groups_and_styles() {
+    let document = SvgDocument::parse(include_str!("samples/groups.svg")).unwrap();
+    assert_eq!(document.warnings, Vec::<String>::new());
+    assert_eq!(
+        document.paths.iter().map(bounds).collect::<Vec<_>>(),
+        [
+            [[10.0, 20.0], [20.0, 30.0]],
+            [[20.0, 20.0], [40.0, 40.0]],
+            [[50.0, 20.0], [70.0, 40.0]],
+            [[85.0, 15.0], [95.0, 25.0]],
+            [[-10.0, 0.0], [0.0, 10.0]],
+        ]
+    );
+
+    let styles = document.paths.iter().map(|p| p.style).collect::<Vec<_>>();
+    assert_eq!(styles[0].fill.unwrap().color, Color::RED);
+    assert_eq!(styles[0].stroke, Some(StrokeStyle::new(Color::BLACK, 2.0)));
+    // the style of the group and its opacity
+    assert_eq!(
+        styles[1].fill.unwrap().color,
+        Color::new(0.0, 0.0, 1.0, 0.5)
+    );
+    assert_eq!(styles[1].stroke, None);
+    assert_eq!(
+        styles[2].fill.unwrap().color,
+        Color::new(0.0, 128.0 / 255.0, 0.0, 0.5)
+    );
+    assert_eq!(
+        styles[3].fill.unwrap().color,
+        Color::new(1.0, 1.0, 0.0, 1.0)
+    );
+    assert_eq!(styles[4].fill, None);
+    assert!((styles[4].stroke.unwrap().width - 96.0 / 25.4).abs() < 1e-5);
+}
+
+#[test]
+fn svg_warnings() {
+    let document = SvgDocument::parse(include_str!("samples/unsupported.svg")).unwrap();
+    let messages = document
+        .warnings
+        .iter()
+        .map(|w| w.split_once(": ").unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "unsupported fill url(#gradient), gradients and patterns",
+            "unsupported element <text>",
+            "unsupported element <image>",
+            "unsupported element <use>",
+            "invalid path data: Line 0 Column 9: Expected number, got \"\".",
+            "unsupported relative r \"50%\"",
+        ]
+    );
+    assert!(document.warnings[1].starts_with("13:3: "));
+    // the gradient falls back to none, the stroke stays
+    assert_eq!(document.paths.len(), 2);
+    assert_eq!(document.paths[1].style.fill, None);
+    assert_eq!(
+        document.view_box,
+        Some(Box2D::new(point(0.0, 0.0), point(100.0, 100.0)))
+    );
+
+    assert!(SvgDocument::parse("<svg><rect></svg>").is_err());
+    assert!(SvgDocument::parse("<html/>").is_err());
+    assert!(SvgDocument::parse("<svg/>").unwrap().paths.is_empty());
+}
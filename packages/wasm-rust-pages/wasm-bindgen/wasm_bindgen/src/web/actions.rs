@@ -0,0 +1,399 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::input::PointerButton;
+
+/// The bindings of [ActionMap::default].
+pub const DEFAULT_BINDINGS: &str = include_str!("bindings.toml");
+
+pub const PAN_LEFT: &str = "pan_left";
+pub const PAN_RIGHT: &str = "pan_right";
+pub const PAN_UP: &str = "pan_up";
+pub const PAN_DOWN: &str = "pan_down";
+pub const ZOOM_IN: &str = "zoom_in";
+pub const ZOOM_OUT: &str = "zoom_out";
+pub const RESET_VIEW: &str = "reset_view";
+pub const UNDO: &str = "undo";
+pub const REDO: &str = "redo";
+
+/// The modifier keys of a [Chord].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers {
+        ctrl: false,
+        shift: false,
+        alt: false,
+        meta: false,
+    };
+
+    /// The modifier the key with this `KeyboardEvent.code` is, [Modifiers::NONE] for other keys.
+    pub fn of_key(code: &str) -> Self {
+        let mut modifiers = Self::NONE;
+        match code {
+            "ControlLeft" | "ControlRight" => modifiers.ctrl = true,
+            "ShiftLeft" | "ShiftRight" => modifiers.shift = true,
+            "AltLeft" | "AltRight" => modifiers.alt = true,
+            // older browsers call the meta keys `OS`
+            "MetaLeft" | "MetaRight" | "OSLeft" | "OSRight" => modifiers.meta = true,
+            _ => {}
+        }
+        modifiers
+    }
+
+    pub fn union(self, other: Modifiers) -> Self {
+        Self {
+            ctrl: self.ctrl || other.ctrl,
+            shift: self.shift || other.shift,
+            alt: self.alt || other.alt,
+            meta: self.meta || other.meta,
+        }
+    }
+
+    pub fn without(self, other: Modifiers) -> Self {
+        Self {
+            ctrl: self.ctrl && !other.ctrl,
+            shift: self.shift && !other.shift,
+            alt: self.alt && !other.alt,
+            meta: self.meta && !other.meta,
+        }
+    }
+
+    /// Whether every modifier of `other` is held too.
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.union(other) == self
+    }
+}
+
+/// The key or button that triggers a [Chord].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// A [`KeyboardEvent.code`](https://developer.mozilla.org/en-US/docs/Web/API/KeyboardEvent/code).
+    Key(String),
+    Button(PointerButton),
+}
+
+impl Trigger {
+    /// The modifier the trigger is itself, it doesn't count as held for its own chord.
+    pub fn modifiers(&self) -> Modifiers {
+        match self {
+            Trigger::Key(code) => Modifiers::of_key(code),
+            Trigger::Button(_) => Modifiers::NONE,
+        }
+    }
+}
+
+const BUTTON_NAMES: [(PointerButton, &str); 5] = [
+    (PointerButton::Primary, "MousePrimary"),
+    (PointerButton::Secondary, "MouseSecondary"),
+    (PointerButton::Middle, "MouseMiddle"),
+    (PointerButton::Back, "MouseBack"),
+    (PointerButton::Forward, "MouseForward"),
+];
+
+///
+/// # Chord
+///
+/// A key or button pressed while holding exactly the [Modifiers], written like `Ctrl+Shift+KeyZ` or `MouseMiddle`.
+/// Keys are `KeyboardEvent.code`s, the physical keys, so the bindings don't move with the keyboard layout.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub trigger: Trigger,
+}
+
+impl Chord {
+    pub fn key(code: &str) -> Self {
+        Self {
+            modifiers: Modifiers::NONE,
+            trigger: Trigger::Key(code.to_string()),
+        }
+    }
+
+    pub fn button(button: PointerButton) -> Self {
+        Self {
+            modifiers: Modifiers::NONE,
+            trigger: Trigger::Button(button),
+        }
+    }
+
+    pub fn with_modifiers(self, modifiers: Modifiers) -> Self {
+        Self { modifiers, ..self }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(chord: &str) -> Result<Self, Self::Err> {
+        let mut parts = chord.split('+').map(str::trim).collect::<Vec<_>>();
+        let trigger = parts.pop().unwrap_or_default();
+
+        let mut modifiers = Modifiers::NONE;
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                "meta" | "cmd" | "command" | "super" => modifiers.meta = true,
+                _ => return Err(format!("Unknown modifier `{}` in `{}`", part, chord)),
+            }
+        }
+
+        let trigger = match BUTTON_NAMES.iter().find(|(_, name)| *name == trigger) {
+            Some((button, _)) => Trigger::Button(*button),
+            None if !trigger.is_empty() && trigger.chars().all(char::is_alphanumeric) => {
+                Trigger::Key(trigger.to_string())
+            }
+            None => return Err(format!("Invalid key `{}` in `{}`", trigger, chord)),
+        };
+
+        Ok(Self { modifiers, trigger })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl, "Ctrl"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.alt, "Alt"),
+            (self.modifiers.meta, "Meta"),
+        ];
+        for (_, name) in modifiers.iter().filter(|(held, _)| *held) {
+            write!(f, "{}+", name)?;
+        }
+        match &self.trigger {
+            Trigger::Key(code) => write!(f, "{}", code),
+            Trigger::Button(button) => {
+                let (_, name) = BUTTON_NAMES.iter().find(|(b, _)| b == button).unwrap();
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = String;
+
+    fn try_from(chord: String) -> Result<Self, Self::Error> {
+        chord.parse()
+    }
+}
+
+impl From<Chord> for String {
+    fn from(chord: Chord) -> Self {
+        chord.to_string()
+    }
+}
+
+/// The same chord bound to several actions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub chord: Chord,
+    pub actions: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is bound to {}",
+            self.chord,
+            self.actions.join(", ")
+        )
+    }
+}
+
+///
+/// # Action map
+///
+/// Named actions and the [Chord]s bound to them, e.g. `undo = ["Ctrl+KeyZ", "Meta+KeyZ"]`.
+/// Loads and saves as TOML or JSON, a table of action names to lists of chords.
+///
+/// Binding a chord that another action already uses is allowed, [ActionMap::conflicts] lists them.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ActionMap {
+    actions: BTreeMap<String, Vec<Chord>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        Self::from_toml(DEFAULT_BINDINGS).expect("the default bindings are valid")
+    }
+}
+
+impl ActionMap {
+    pub fn from_toml(config: &str) -> Result<Self, String> {
+        toml::from_str(config).map_err(|e| e.to_string())
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(config: &str) -> Result<Self, String> {
+        serde_json::from_str(config).map_err(|e| e.to_string())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// Adds a chord to the action. Returns the other actions the chord is bound to.
+    pub fn bind(&mut self, action: &str, chord: Chord) -> Vec<String> {
+        let others = self
+            .bound_to(&chord)
+            .into_iter()
+            .filter(|other| *other != action)
+            .map(str::to_string)
+            .collect();
+        let chords = self.actions.entry(action.to_string()).or_default();
+        if !chords.contains(&chord) {
+            chords.push(chord);
+        }
+        others
+    }
+
+    /// Returns whether the chord was bound to the action.
+    pub fn unbind(&mut self, action: &str, chord: &Chord) -> bool {
+        let Some(chords) = self.actions.get_mut(action) else {
+            return false;
+        };
+        let len = chords.len();
+        chords.retain(|c| c != chord);
+        len != chords.len()
+    }
+
+    /// Removes all the chords of the action.
+    pub fn clear(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+
+    pub fn chords(&self, action: &str) -> &[Chord] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = (&str, &[Chord])> {
+        self.actions
+            .iter()
+            .map(|(action, chords)| (action.as_str(), chords.as_slice()))
+    }
+
+    /// The actions the chord is bound to.
+    pub fn bound_to(&self, chord: &Chord) -> Vec<&str> {
+        self.actions()
+            .filter(|(_, chords)| chords.contains(chord))
+            .map(|(action, _)| action)
+            .collect()
+    }
+
+    /// The chords bound to more than one action.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts: Vec<Conflict> = Vec::new();
+        for (_, chords) in self.actions() {
+            for chord in chords {
+                if conflicts.iter().any(|conflict| conflict.chord == *chord) {
+                    continue;
+                }
+                let actions = self.bound_to(chord);
+                if actions.len() > 1 {
+                    conflicts.push(Conflict {
+                        chord: chord.clone(),
+                        actions: actions.into_iter().map(str::to_string).collect(),
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+#[test]
+fn chord_parse_and_display() {
+    let chord: Chord = "ctrl + Shift+KeyZ".parse().unwrap();
+    assert_eq!(
+        chord,
+        Chord::key("KeyZ").with_modifiers(Modifiers {
+            ctrl: true,
+            shift: true,
+            ..Modifiers::NONE
+        })
+    );
+    assert_eq!(chord.to_string(), "Ctrl+Shift+KeyZ");
+
+    let chord: Chord = "Alt+MouseMiddle".parse().unwrap();
+    assert_eq!(chord.trigger, Trigger::Button(PointerButton::Middle));
+    assert_eq!(chord.to_string(), "Alt+MouseMiddle");
+
+    assert!("Hyper+KeyA".parse::<Chord>().is_err());
+    assert!("Ctrl+".parse::<Chord>().is_err());
+    assert!("".parse::<Chord>().is_err());
+}
+
+#[test]
+fn action_map_load_and_save() {
+    let actions = ActionMap::default();
+    assert!(actions.conflicts().is_empty());
+    assert_eq!(
+        actions.chords(UNDO),
+        [
+            "Ctrl+KeyZ".parse::<Chord>().unwrap(),
+            "Meta+KeyZ".parse().unwrap(),
+            Chord::button(PointerButton::Back),
+        ]
+    );
+
+    let toml = actions.to_toml().unwrap();
+    assert_eq!(ActionMap::from_toml(&toml).unwrap(), actions);
+    let json = actions.to_json().unwrap();
+    assert_eq!(ActionMap::from_json(&json).unwrap(), actions);
+
+    let actions =
+        ActionMap::from_json(r#"{ "pan_left": ["KeyH"], "pan_right": ["KeyL"] }"#).unwrap();
+    assert_eq!(actions.chords(PAN_LEFT), [Chord::key("KeyH")]);
+    assert!(actions.chords(ZOOM_IN).is_empty());
+
+    let error = ActionMap::from_toml(r#"undo = ["Hyper+KeyZ"]"#).unwrap_err();
+    assert!(error.contains("Unknown modifier `Hyper`"), "{}", error);
+}
+
+#[test]
+fn action_map_conflicts() {
+    let mut actions = ActionMap::default();
+    assert!(actions.bind(PAN_LEFT, Chord::key("KeyH")).is_empty());
+    assert_eq!(actions.bind(ZOOM_IN, Chord::key("KeyA")), [PAN_LEFT]);
+    assert_eq!(
+        actions.bind(RESET_VIEW, Chord::key("KeyA")),
+        [PAN_LEFT, ZOOM_IN]
+    );
+
+    let conflicts = actions.conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].chord, Chord::key("KeyA"));
+    assert_eq!(
+        conflicts[0].to_string(),
+        "`KeyA` is bound to pan_left, reset_view, zoom_in"
+    );
+
+    // the modifiers make a different chord
+    let mut actions = ActionMap::from_toml("").unwrap();
+    actions.bind(UNDO, "Ctrl+KeyZ".parse().unwrap());
+    actions.bind(REDO, "Ctrl+Shift+KeyZ".parse().unwrap());
+    assert!(actions.conflicts().is_empty());
+
+    assert!(actions.unbind(UNDO, &"Ctrl+KeyZ".parse().unwrap()));
+    assert!(!actions.unbind(UNDO, &"Ctrl+KeyZ".parse().unwrap()));
+    assert!(actions.chords(UNDO).is_empty());
+}
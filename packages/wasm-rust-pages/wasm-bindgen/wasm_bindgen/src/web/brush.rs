@@ -0,0 +1,311 @@
+use webgl_common::Color;
+
+use super::camera_2d::Rect;
+
+/// Moves shorter than this, in canvas pixels, wait for the next point.
+pub const MIN_DISTANCE: f32 = 0.5;
+/// The smallest dab, in canvas pixels, the shader needs a pixel to antialias.
+pub const MIN_BRUSH_SIZE: f32 = 1.0;
+
+///
+/// # Brush
+///
+/// How a [Stroke] is painted. Sizes are in canvas pixels.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    /// The diameter at full pressure.
+    pub size: f32,
+    /// `0.0` fades from the center to the edge, `1.0` is a hard edge.
+    pub hardness: f32,
+    /// Of the whole stroke, the segments of a stroke don't add up where they overlap.
+    pub opacity: f32,
+    pub color: Color,
+    /// The longest segment as a fraction of the size, pressure changes are interpolated
+    /// over segments this long.
+    pub spacing: f32,
+    /// The pressure scales the size.
+    pub pressure_size: bool,
+    /// The pressure scales the flow, the coverage of a segment.
+    pub pressure_flow: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            size: 16.0,
+            hardness: 0.8,
+            opacity: 1.0,
+            color: Color::BLACK,
+            spacing: 0.25,
+            pressure_size: true,
+            pressure_flow: false,
+        }
+    }
+}
+
+impl Brush {
+    /// The footprint of the brush at a point of the stroke.
+    pub fn dab(&self, point: StrokePoint) -> Dab {
+        let pressure = point.pressure.clamp(0.0, 1.0);
+        Dab {
+            position: point.position,
+            size: if self.pressure_size {
+                self.size * pressure
+            } else {
+                self.size
+            }
+            .max(MIN_BRUSH_SIZE),
+            flow: if self.pressure_flow { pressure } else { 1.0 },
+        }
+    }
+
+    /// The premultiplied color the coverage of a stroke is multiplied by.
+    pub fn tint(&self) -> Color {
+        let a = self.color.a * self.opacity.clamp(0.0, 1.0);
+        Color::new(self.color.r * a, self.color.g * a, self.color.b * a, a)
+    }
+}
+
+/// A pointer position in canvas pixels and its pressure, from `0.0` to `1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokePoint {
+    pub position: [f32; 2],
+    pub pressure: f32,
+}
+
+impl StrokePoint {
+    pub fn new(position: [f32; 2], pressure: f32) -> Self {
+        Self { position, pressure }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            position: [
+                lerp(self.position[0], other.position[0]),
+                lerp(self.position[1], other.position[1]),
+            ],
+            pressure: lerp(self.pressure, other.pressure),
+        }
+    }
+}
+
+/// The brush at a point: its diameter and its flow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dab {
+    pub position: [f32; 2],
+    pub size: f32,
+    pub flow: f32,
+}
+
+/// What the draw shader paints: a capsule from one dab to the other, a dot when they're the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub from: Dab,
+    pub to: Dab,
+}
+
+impl Segment {
+    /// The pixels the segment can cover.
+    pub fn bounds(&self) -> Rect {
+        // and a pixel for the antialiasing
+        let radius = self.from.size.max(self.to.size) / 2.0 + 1.0;
+        let [from, to] = [self.from.position, self.to.position];
+        Rect::new(
+            [from[0].min(to[0]) - radius, from[1].min(to[1]) - radius],
+            [from[0].max(to[0]) + radius, from[1].max(to[1]) + radius],
+        )
+    }
+}
+
+///
+/// # Stroke
+///
+/// Turns the pointer positions of a stroke into [Segment]s no longer than the brush spacing,
+/// interpolating the pressure, so the size changes smoothly however far apart the events are.
+///
+#[derive(Debug, Clone)]
+pub struct Stroke {
+    brush: Brush,
+    /// The end of the last segment.
+    last: Option<StrokePoint>,
+    /// The latest point too close to `last`, painted by [Stroke::finish].
+    pending: Option<StrokePoint>,
+    /// Not painted yet.
+    segments: Vec<Segment>,
+    bounds: Option<Rect>,
+}
+
+impl Stroke {
+    pub fn new(brush: Brush) -> Self {
+        Self {
+            brush,
+            last: None,
+            pending: None,
+            segments: Vec::new(),
+            bounds: None,
+        }
+    }
+
+    pub fn brush(&self) -> &Brush {
+        &self.brush
+    }
+
+    /// The first point paints a dot.
+    pub fn add(&mut self, point: StrokePoint) {
+        let Some(last) = self.last else {
+            let dab = self.brush.dab(point);
+            self.push(Segment { from: dab, to: dab });
+            self.last = Some(point);
+            return;
+        };
+
+        let length = distance(last.position, point.position);
+        if length < MIN_DISTANCE {
+            self.pending = Some(point);
+            return;
+        }
+        self.pending = None;
+
+        let size = self.brush.dab(last).size.min(self.brush.dab(point).size);
+        let max_length = (size * self.brush.spacing).max(MIN_DISTANCE);
+        let count = (length / max_length).ceil() as usize;
+        let mut from = self.brush.dab(last);
+        for i in 1..=count {
+            let to = self.brush.dab(last.lerp(&point, i as f32 / count as f32));
+            self.push(Segment { from, to });
+            from = to;
+        }
+        self.last = Some(point);
+    }
+
+    /// Paints up to the last point, also when it was too close to the previous one.
+    pub fn finish(&mut self) {
+        if let (Some(last), Some(pending)) = (self.last, self.pending.take()) {
+            self.push(Segment {
+                from: self.brush.dab(last),
+                to: self.brush.dab(pending),
+            });
+            self.last = Some(pending);
+        }
+    }
+
+    /// The segments added since the last call.
+    pub fn take_segments(&mut self) -> Vec<Segment> {
+        std::mem::take(&mut self.segments)
+    }
+
+    /// The pixels the whole stroke can cover, `None` before the first point.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.bounds
+    }
+
+    fn push(&mut self, segment: Segment) {
+        let bounds = segment.bounds();
+        self.bounds = Some(match self.bounds {
+            Some(stroke) => stroke.union(&bounds),
+            None => bounds,
+        });
+        self.segments.push(segment);
+    }
+}
+
+/// Column major, maps canvas pixels (y down) to clip space.
+pub fn pixel_projection(width: f32, height: f32) -> [f32; 16] {
+    #[rustfmt::skip]
+    let matrix = [
+        2.0 / width, 0.0, 0.0, 0.0,
+        0.0, -2.0 / height, 0.0, 0.0,
+        0.0, 0.0, -1.0, 0.0,
+        -1.0, 1.0, 0.0, 1.0,
+    ];
+    matrix
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (b[0] - a[0]).hypot(b[1] - a[1])
+}
+
+#[test]
+fn stroke_spacing() {
+    let brush = Brush {
+        size: 20.0,
+        spacing: 0.25,
+        ..Brush::default()
+    };
+    let mut stroke = Stroke::new(brush);
+    stroke.add(StrokePoint::new([0.0, 0.0], 1.0));
+    let dot = stroke.take_segments();
+    assert_eq!(dot.len(), 1);
+    assert_eq!(dot[0].from, dot[0].to);
+
+    // at most 5 pixels long
+    stroke.add(StrokePoint::new([20.0, 0.0], 1.0));
+    let segments = stroke.take_segments();
+    assert_eq!(segments.len(), 4);
+    assert!(segments
+        .iter()
+        .all(|s| (distance(s.from.position, s.to.position) - 5.0).abs() < 1e-4));
+    assert!(segments.windows(2).all(|s| s[0].to == s[1].from));
+    assert_eq!(segments[3].to.position, [20.0, 0.0]);
+
+    stroke.add(StrokePoint::new([21.0, 0.0], 1.0));
+    assert_eq!(stroke.take_segments().len(), 1);
+
+    // too close, painted when the stroke ends
+    stroke.add(StrokePoint::new([21.2, 0.0], 1.0));
+    assert!(stroke.take_segments().is_empty());
+    stroke.add(StrokePoint::new([21.4, 0.0], 1.0));
+    assert!(stroke.take_segments().is_empty());
+    stroke.finish();
+    let last = stroke.take_segments();
+    assert_eq!(last.len(), 1);
+    assert_eq!(last[0].from.position, [21.0, 0.0]);
+    assert_eq!(last[0].to.position, [21.4, 0.0]);
+    stroke.finish();
+    assert!(stroke.take_segments().is_empty());
+
+    assert_eq!(
+        stroke.bounds(),
+        Some(Rect::new([-11.0, -11.0], [32.4, 11.0]))
+    );
+}
+
+#[test]
+fn stroke_pressure() {
+    let brush = Brush {
+        size: 20.0,
+        spacing: 0.25,
+        pressure_flow: true,
+        ..Brush::default()
+    };
+    let mut stroke = Stroke::new(brush);
+    stroke.add(StrokePoint::new([0.0, 0.0], 0.5));
+    stroke.add(StrokePoint::new([0.0, 20.0], 1.0));
+    let segments = stroke.take_segments();
+    // spaced for the smaller end, 2.5 pixels
+    assert_eq!(segments.len(), 1 + 8);
+    assert_eq!(segments[1].from.size, 10.0);
+    assert_eq!(segments[8].to.size, 20.0);
+    assert!(segments.windows(2).all(|s| s[0].to.size < s[1].to.size));
+    assert!((segments[4].to.flow - 0.75).abs() < 1e-5);
+
+    // too little pressure for a pixel
+    let dab = brush.dab(StrokePoint::new([0.0, 0.0], 0.0));
+    assert_eq!((dab.size, dab.flow), (MIN_BRUSH_SIZE, 0.0));
+
+    let constant = Brush {
+        pressure_size: false,
+        ..brush
+    };
+    assert_eq!(constant.dab(StrokePoint::new([0.0, 0.0], 0.1)).size, 20.0);
+
+    let tint = Brush {
+        color: Color::new(1.0, 0.5, 0.0, 1.0),
+        opacity: 0.5,
+        ..brush
+    }
+    .tint();
+    assert_eq!(tint, Color::new(0.5, 0.25, 0.0, 0.5));
+}
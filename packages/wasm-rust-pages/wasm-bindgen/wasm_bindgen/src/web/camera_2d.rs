@@ -1,15 +1,29 @@
+use webgl_common::{value_as_u8_slice, GpuPod};
+
+///
+/// # 2D camera
+///
+/// Looks at `position` in world space, `zoom` is screen pixels per world unit.
+/// World space is y up, screen space is canvas pixels from the top left corner, y down,
+/// so the world point `position` is drawn at the center of the canvas.
+///
 #[derive(Debug, Clone)]
 pub struct Camera2D {
     pub position: [f32; 2],
     pub zoom: f32,
     pub width: f32,
     pub height: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// When set, the camera never shows anything outside of it.
+    pub bounds: Option<Rect>,
+    pub zoom_curve: ZoomCurve,
     transforms: Transforms,
 }
 
 /// This structure goes straight into the webgl buffer.
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, GpuPod)]
 pub struct Transforms {
     pub projection_matrix: [f32; 16],
     pub view_matrix: [f32; 16],
@@ -17,7 +31,71 @@ pub struct Transforms {
 
 impl Transforms {
     pub fn as_bytes(&self) -> &[u8] {
-        unsafe { any_as_u8_slice(self) }
+        value_as_u8_slice(self)
+    }
+}
+
+/// An axis aligned rectangle in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Rect {
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self { min, max }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max[0] - self.min[0]
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max[1] - self.min[1]
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+        ]
+    }
+
+    /// The smallest rectangle containing both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::new(
+            [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        )
+    }
+}
+
+///
+/// How a wheel delta turns into a zoom factor.
+///
+/// `Exponential` zooms in and out by the same factor for the same delta, whatever the zoom level.
+/// `Linear` is the original `1 + delta * speed`, which zooms out slower than it zooms in.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoomCurve {
+    Exponential { speed: f32 },
+    Linear { speed: f32 },
+}
+
+impl Default for ZoomCurve {
+    fn default() -> Self {
+        ZoomCurve::Exponential { speed: 0.001 }
+    }
+}
+
+impl ZoomCurve {
+    /// The factor the zoom is multiplied by, always positive.
+    pub fn factor(&self, delta: f32) -> f32 {
+        match *self {
+            ZoomCurve::Exponential { speed } => (delta * speed).exp(),
+            ZoomCurve::Linear { speed } => (1.0 + delta * speed).max(f32::EPSILON),
+        }
     }
 }
 
@@ -28,6 +106,10 @@ impl Camera2D {
             zoom: 1.0,
             width,
             height,
+            min_zoom: 0.01,
+            max_zoom: 100.0,
+            bounds: None,
+            zoom_curve: ZoomCurve::default(),
             transforms: Transforms {
                 projection_matrix: [0.0; 16],
                 view_matrix: [0.0; 16],
@@ -46,8 +128,38 @@ impl Camera2D {
         self.update_view_matrix();
     }
 
-    pub fn zoom(&mut self, delta: f32) {
-        self.zoom *= 1.0 + delta * 0.001;
+    /// Moves the world along with a pointer that moved by `dx`, `dy` screen pixels.
+    pub fn drag(&mut self, dx: f32, dy: f32) {
+        self.pan(-dx, dy);
+    }
+
+    /// Zooms so the world point under the screen point `anchor` stays under it, e.g. the pointer.
+    pub fn zoom_at(&mut self, delta: f32, anchor: [f32; 2]) {
+        let zoom = self.zoom * self.zoom_curve.factor(delta);
+        self.set_zoom_at(zoom, anchor);
+    }
+
+    /// Sets the zoom level, keeping the world point under the screen point `anchor` in place.
+    pub fn set_zoom_at(&mut self, zoom: f32, anchor: [f32; 2]) {
+        let world = self.screen_to_world(anchor);
+        self.zoom = self.clamp_zoom(zoom);
+        // the inverse of screen_to_world for the new zoom
+        self.position = [
+            world[0] - (anchor[0] - self.width / 2.0) / self.zoom,
+            world[1] + (anchor[1] - self.height / 2.0) / self.zoom,
+        ];
+        self.update_view_matrix();
+    }
+
+    pub fn set_zoom_limits(&mut self, min_zoom: f32, max_zoom: f32) {
+        self.min_zoom = min_zoom;
+        self.max_zoom = max_zoom.max(min_zoom);
+        self.zoom = self.clamp_zoom(self.zoom);
+        self.update_view_matrix();
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<Rect>) {
+        self.bounds = bounds;
         self.update_view_matrix();
     }
 
@@ -55,10 +167,63 @@ impl Camera2D {
         self.width = width;
         self.height = height;
         self.update_projection_matrix();
+        // a bigger screen may show more than the bounds
+        self.update_view_matrix();
+    }
+
+    /// The world point drawn at the screen point, in canvas pixels.
+    pub fn screen_to_world(&self, screen: [f32; 2]) -> [f32; 2] {
+        [
+            self.position[0] + (screen[0] - self.width / 2.0) / self.zoom,
+            self.position[1] - (screen[1] - self.height / 2.0) / self.zoom,
+        ]
+    }
+
+    /// The screen point, in canvas pixels, the world point is drawn at.
+    pub fn world_to_screen(&self, world: [f32; 2]) -> [f32; 2] {
+        [
+            self.width / 2.0 + (world[0] - self.position[0]) * self.zoom,
+            self.height / 2.0 - (world[1] - self.position[1]) * self.zoom,
+        ]
+    }
+
+    fn clamp_zoom(&self, zoom: f32) -> f32 {
+        zoom.clamp(self.min_zoom, self.max_zoom)
+    }
+
+    /// Keeps the visible rect inside the bounds, centered on them when it is bigger.
+    fn clamp_position(&mut self) {
+        let Some(bounds) = self.bounds else {
+            return;
+        };
+        let clamp = |position: f32, min: f32, max: f32, half_extent: f32| {
+            let (min, max) = (min + half_extent, max - half_extent);
+            if min > max {
+                (min + max) / 2.0
+            } else {
+                position.clamp(min, max)
+            }
+        };
+        self.position = [
+            clamp(
+                self.position[0],
+                bounds.min[0],
+                bounds.max[0],
+                self.width / 2.0 / self.zoom,
+            ),
+            clamp(
+                self.position[1],
+                bounds.min[1],
+                bounds.max[1],
+                self.height / 2.0 / self.zoom,
+            ),
+        ];
     }
 
     #[rustfmt::skip]
     pub fn update_view_matrix(&mut self) {
+        self.clamp_position();
+
         let translation = [
             1.0, 0.0, 0.0, 0.0,
             0.0, 1.0, 0.0, 0.0,
@@ -109,6 +274,121 @@ fn multiply_matrices(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
     result
 }
 
-pub unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-    ::core::slice::from_raw_parts((p as *const T) as *const u8, ::core::mem::size_of::<T>())
+/// Runs a world point through the matrices like the vertex shader, then maps clip space to the canvas.
+#[cfg(test)]
+fn project(camera: &mut Camera2D, world: [f32; 2]) -> [f32; 2] {
+    let (width, height) = (camera.width, camera.height);
+    let transforms = camera.get_transforms();
+    let transform = |matrix: &[f32; 16], v: [f32; 4]| {
+        let mut result = [0.0; 4];
+        for (row, value) in result.iter_mut().enumerate() {
+            *value = (0..4)
+                .map(|column| matrix[column * 4 + row] * v[column])
+                .sum();
+        }
+        result
+    };
+    let view = transform(&transforms.view_matrix, [world[0], world[1], 0.0, 1.0]);
+    let clip = transform(&transforms.projection_matrix, view);
+    [
+        (clip[0] / clip[3] + 1.0) / 2.0 * width,
+        (1.0 - clip[1] / clip[3]) / 2.0 * height,
+    ]
+}
+
+#[cfg(test)]
+fn assert_near(a: [f32; 2], b: [f32; 2]) {
+    assert!(
+        (a[0] - b[0]).abs() < 1e-3 && (a[1] - b[1]).abs() < 1e-3,
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+#[test]
+fn camera_screen_world_matches_matrices() {
+    let mut camera = Camera2D::new(800.0, 600.0);
+    camera.position = [120.0, -40.0];
+    camera.zoom = 2.5;
+    camera.update_view_matrix();
+
+    assert_near(camera.world_to_screen([120.0, -40.0]), [400.0, 300.0]);
+    for world in [[0.0, 0.0], [130.0, -30.0], [-75.5, 210.25]] {
+        assert_near(camera.world_to_screen(world), project(&mut camera, world));
+        assert_near(camera.screen_to_world(camera.world_to_screen(world)), world);
+    }
+    // y is up in the world and down on screen
+    assert!(camera.world_to_screen([120.0, 0.0])[1] < 300.0);
+}
+
+#[test]
+fn camera_zoom_at_keeps_anchor() {
+    let mut camera = Camera2D::new(800.0, 600.0);
+    camera.position = [10.0, 20.0];
+    camera.update_view_matrix();
+
+    let anchor = [650.0, 120.0];
+    let world = camera.screen_to_world(anchor);
+    camera.zoom_at(500.0, anchor);
+    assert!(camera.zoom > 1.0);
+    assert_near(camera.screen_to_world(anchor), world);
+    assert_near(project(&mut camera, world), anchor);
+
+    camera.zoom_at(-1500.0, anchor);
+    assert!(camera.zoom < 1.0);
+    assert_near(camera.screen_to_world(anchor), world);
+
+    // zooming around the center doesn't move the camera
+    let position = camera.position;
+    camera.zoom_at(300.0, [400.0, 300.0]);
+    assert_near(camera.position, position);
+}
+
+#[test]
+fn camera_zoom_limits_and_curve() {
+    let mut camera = Camera2D::new(800.0, 600.0);
+    camera.set_zoom_limits(0.5, 4.0);
+    camera.zoom_at(100_000.0, [400.0, 300.0]);
+    assert_eq!(camera.zoom, 4.0);
+    camera.zoom_at(-100_000.0, [400.0, 300.0]);
+    assert_eq!(camera.zoom, 0.5);
+
+    // exponential zoom is symmetric, linear zoom is not
+    let exponential = ZoomCurve::Exponential { speed: 0.001 };
+    assert!((exponential.factor(200.0) * exponential.factor(-200.0) - 1.0).abs() < 1e-6);
+    let linear = ZoomCurve::Linear { speed: 0.001 };
+    assert_eq!(linear.factor(200.0), 1.2);
+    assert!(linear.factor(-5000.0) > 0.0);
+
+    camera.zoom_curve = linear;
+    camera.set_zoom_limits(0.01, 100.0);
+    camera.zoom = 1.0;
+    camera.zoom_at(200.0, [400.0, 300.0]);
+    assert!((camera.zoom - 1.2).abs() < 1e-6);
+}
+
+#[test]
+fn camera_bounds_clamp_pan() {
+    let mut camera = Camera2D::new(200.0, 100.0);
+    camera.set_bounds(Some(Rect::new([0.0, 0.0], [1000.0, 500.0])));
+    // the visible rect is moved inside the bounds
+    assert_near(camera.position, [100.0, 50.0]);
+
+    camera.pan(10_000.0, 10_000.0);
+    assert_near(camera.position, [900.0, 450.0]);
+    // the top right corner of the screen
+    assert_near(camera.screen_to_world([200.0, 0.0]), [1000.0, 500.0]);
+
+    camera.drag(50.0, 0.0);
+    assert_near(camera.position, [850.0, 450.0]);
+
+    // zoomed out further than the bounds, they are centered
+    camera.set_zoom_limits(0.01, 100.0);
+    camera.set_zoom_at(0.1, [0.0, 0.0]);
+    assert_near(camera.position, [500.0, 250.0]);
+
+    camera.set_bounds(None);
+    camera.pan(-10_000.0, 0.0);
+    assert!(camera.position[0] < 0.0);
 }
@@ -0,0 +1,414 @@
+use wasm_bindgen::prelude::*;
+
+use super::camera_2d::{Camera2D, Rect};
+
+/// How an animation moves from its start to its end, `t` goes from `0.0` to `1.0`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    Linear,
+    EaseOutCubic,
+    #[default]
+    EaseInOutCubic,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pointer {
+    id: i32,
+    position: [f32; 2],
+}
+
+/// A [CameraController::fly_to] in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Flight {
+    from_position: [f32; 2],
+    from_zoom: f32,
+    to_position: [f32; 2],
+    to_zoom: f32,
+    elapsed: f32,
+    duration: f32,
+    easing: Easing,
+}
+
+///
+/// # Camera controller
+///
+/// Moves a [Camera2D] from pointer input: dragging with one pointer keeps coasting after it is released
+/// and slows down with `friction`, two pointers pinch-zoom around their midpoint.
+/// [CameraController::fly_to] animates the camera to show a rectangle.
+///
+/// Input only updates the controller, [CameraController::update] moves the camera once per animation frame
+/// and tells whether it has to be drawn again. The result doesn't depend on how the time is split into frames.
+///
+/// Positions are canvas pixels, times are seconds.
+///
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    pub camera: Camera2D,
+    /// The fraction of the speed left after coasting for one second, between `0` (no coasting)
+    /// and `1` (no friction).
+    pub friction: f32,
+    /// Coasting stops below this speed, in pixels per second.
+    pub min_speed: f32,
+    /// Pointers resting longer than this before they are released don't coast.
+    pub release_delay: f64,
+    velocity: [f32; 2],
+    pointers: Vec<Pointer>,
+    last_move_time: f64,
+    flight: Option<Flight>,
+    changed: bool,
+}
+
+impl CameraController {
+    pub fn new(camera: Camera2D) -> Self {
+        Self {
+            camera,
+            friction: 0.02,
+            min_speed: 10.0,
+            release_delay: 0.1,
+            velocity: [0.0, 0.0],
+            pointers: Vec::new(),
+            last_move_time: 0.0,
+            flight: None,
+            changed: true,
+        }
+    }
+
+    pub fn pointer_down(&mut self, id: i32, position: [f32; 2], time: f64) {
+        self.flight = None;
+        self.velocity = [0.0, 0.0];
+        self.last_move_time = time;
+        self.pointers.retain(|pointer| pointer.id != id);
+        self.pointers.push(Pointer { id, position });
+    }
+
+    pub fn pointer_move(&mut self, id: i32, position: [f32; 2], time: f64) {
+        let Some(index) = self.pointers.iter().position(|pointer| pointer.id == id) else {
+            return;
+        };
+
+        match (self.pointers.len(), index) {
+            (1, _) => {
+                let last = self.pointers[0].position;
+                let delta = [position[0] - last[0], position[1] - last[1]];
+                self.pointers[0].position = position;
+                self.camera.drag(delta[0], delta[1]);
+
+                let dt = (time - self.last_move_time) as f32;
+                if dt > 0.0 {
+                    // smoothed, single moves are noisy
+                    let smooth = |delta: f32, velocity: f32| 0.8 * delta / dt + 0.2 * velocity;
+                    self.velocity = [
+                        smooth(delta[0], self.velocity[0]),
+                        smooth(delta[1], self.velocity[1]),
+                    ];
+                }
+            }
+            // the first two pointers pinch, any others are ignored
+            (_, 0 | 1) => {
+                let (last_center, last_distance) = self.pinch();
+                self.pointers[index].position = position;
+                let (center, distance) = self.pinch();
+
+                if last_distance > 0.0 {
+                    let zoom = self.camera.zoom * distance / last_distance;
+                    self.camera.set_zoom_at(zoom, last_center);
+                }
+                self.camera
+                    .drag(center[0] - last_center[0], center[1] - last_center[1]);
+                self.velocity = [0.0, 0.0];
+            }
+            _ => {
+                self.pointers[index].position = position;
+                return;
+            }
+        }
+        self.last_move_time = time;
+        self.changed = true;
+    }
+
+    pub fn pointer_up(&mut self, id: i32, time: f64) {
+        let count = self.pointers.len();
+        self.pointers.retain(|pointer| pointer.id != id);
+        // a pinch ends without coasting, as does a drag that stopped before the release
+        if count > 1 || time - self.last_move_time > self.release_delay {
+            self.velocity = [0.0, 0.0];
+        }
+        self.last_move_time = time;
+    }
+
+    /// Keeps moving the camera like a drag released at `velocity`, in pixels per second,
+    /// e.g. when the pan keys are released.
+    pub fn coast(&mut self, velocity: [f32; 2]) {
+        self.flight = None;
+        self.velocity = velocity;
+    }
+
+    /// Zooms at once around `anchor`, see [Camera2D::zoom_at].
+    pub fn wheel(&mut self, delta: f32, anchor: [f32; 2]) {
+        self.flight = None;
+        self.camera.zoom_at(delta, anchor);
+        self.changed = true;
+    }
+
+    /// Animates the camera to fit `rect` on screen in `duration` seconds.
+    pub fn fly_to(&mut self, rect: Rect, duration: f32, easing: Easing) {
+        let zoom = (self.camera.width / rect.width()).min(self.camera.height / rect.height());
+        let flight = Flight {
+            from_position: self.camera.position,
+            from_zoom: self.camera.zoom,
+            to_position: rect.center(),
+            to_zoom: zoom.clamp(self.camera.min_zoom, self.camera.max_zoom),
+            elapsed: 0.0,
+            duration: duration.max(0.0),
+            easing,
+        };
+        self.velocity = [0.0, 0.0];
+        self.flight = Some(flight);
+        self.changed = true;
+    }
+
+    /// Whether the camera still moves without input, so [CameraController::update] needs more frames.
+    pub fn is_animating(&self) -> bool {
+        self.flight.is_some() || self.speed() > self.min_speed
+    }
+
+    /// Marks the camera as changed, e.g. after it was resized.
+    pub fn invalidate(&mut self) {
+        self.changed = true;
+    }
+
+    /// Advances the animations by `dt` seconds. Returns whether the camera changed since the last update.
+    pub fn update(&mut self, dt: f32) -> bool {
+        if let Some(flight) = &mut self.flight {
+            flight.elapsed += dt;
+            let t = if flight.duration > 0.0 {
+                (flight.elapsed / flight.duration).min(1.0)
+            } else {
+                1.0
+            };
+            let eased = flight.easing.apply(t);
+            // the zoom is interpolated geometrically, so zooming in and out feel the same
+            let zoom = flight.from_zoom * (flight.to_zoom / flight.from_zoom).powf(eased);
+            let lerp = |from: f32, to: f32| from + (to - from) * eased;
+            self.camera.zoom = zoom.clamp(self.camera.min_zoom, self.camera.max_zoom);
+            self.camera.position = [
+                lerp(flight.from_position[0], flight.to_position[0]),
+                lerp(flight.from_position[1], flight.to_position[1]),
+            ];
+            self.camera.update_view_matrix();
+            if t >= 1.0 {
+                self.flight = None;
+            }
+            self.changed = true;
+        } else if self.pointers.is_empty() && dt > 0.0 {
+            if self.speed() > self.min_speed {
+                // the exact distance covered while the speed decays by `friction` per second,
+                // the formula divides by zero at both ends of the range
+                let friction = self.friction.clamp(0.0, 1.0);
+                let decay = friction.powf(dt);
+                let distance = if friction == 1.0 {
+                    dt
+                } else if friction == 0.0 {
+                    0.0
+                } else {
+                    (decay - 1.0) / friction.ln()
+                };
+                self.camera
+                    .drag(self.velocity[0] * distance, self.velocity[1] * distance);
+                self.velocity = [self.velocity[0] * decay, self.velocity[1] * decay];
+                self.changed = true;
+            } else {
+                self.velocity = [0.0, 0.0];
+            }
+        }
+        std::mem::take(&mut self.changed)
+    }
+
+    fn speed(&self) -> f32 {
+        self.velocity[0].hypot(self.velocity[1])
+    }
+
+    /// The midpoint and distance of the first two pointers.
+    fn pinch(&self) -> ([f32; 2], f32) {
+        let [a, b] = [self.pointers[0].position, self.pointers[1].position];
+        (
+            [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0],
+            (a[0] - b[0]).hypot(a[1] - b[1]),
+        )
+    }
+}
+
+#[cfg(test)]
+fn assert_near(a: [f32; 2], b: [f32; 2]) {
+    assert!(
+        (a[0] - b[0]).abs() < 1e-2 && (a[1] - b[1]).abs() < 1e-2,
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+/// A controller that was flicked to the right at 1000 pixels per second.
+#[cfg(test)]
+fn flicked() -> CameraController {
+    let mut controller = CameraController::new(Camera2D::new(800.0, 600.0));
+    controller.pointer_down(1, [100.0, 100.0], 0.0);
+    for step in 1..=10 {
+        controller.pointer_move(1, [100.0 + 10.0 * step as f32, 100.0], 0.01 * step as f64);
+    }
+    controller.pointer_up(1, 0.1);
+    controller
+}
+
+#[test]
+fn controller_drag_coasts_and_stops() {
+    let mut controller = flicked();
+    assert_near(controller.camera.position, [-100.0, 0.0]);
+    assert!(controller.is_animating());
+    assert!(controller.update(0.1));
+    assert!(controller.camera.position[0] < -100.0);
+
+    // the speed decays to `friction` after one second, so the camera can't coast forever
+    let limit = -100.0 - 1000.0 / -controller.friction.ln();
+    for _ in 0..1000 {
+        controller.update(1.0 / 60.0);
+    }
+    assert!(!controller.is_animating());
+    assert!(controller.camera.position[0] > limit);
+    assert!(!controller.update(1.0 / 60.0));
+
+    // resting before the release doesn't coast
+    let mut controller = CameraController::new(Camera2D::new(800.0, 600.0));
+    controller.pointer_down(1, [0.0, 0.0], 0.0);
+    controller.pointer_move(1, [50.0, 0.0], 0.01);
+    controller.pointer_up(1, 0.5);
+    assert!(!controller.is_animating());
+}
+
+#[test]
+fn controller_friction_limits() {
+    // without friction the camera keeps its speed
+    let mut controller = flicked();
+    controller.friction = 1.0;
+    controller.update(0.5);
+    assert_near(controller.camera.position, [-600.0, 0.0]);
+    assert!(controller.is_animating());
+
+    // out of range values are clamped, not turned into NaN
+    let mut controller = flicked();
+    controller.friction = 2.0;
+    controller.update(0.5);
+    assert_near(controller.camera.position, [-600.0, 0.0]);
+
+    // full friction stops it right away
+    for friction in [0.0, -1.0] {
+        let mut controller = flicked();
+        controller.friction = friction;
+        controller.update(0.5);
+        assert_near(controller.camera.position, [-100.0, 0.0]);
+        assert!(!controller.is_animating());
+    }
+}
+
+#[test]
+fn controller_coast() {
+    let mut controller = CameraController::new(Camera2D::new(800.0, 600.0));
+    controller.fly_to(Rect::new([0.0, 0.0], [10.0, 10.0]), 1.0, Easing::Linear);
+    controller.coast([1000.0, 0.0]);
+    assert!(controller.is_animating());
+    assert!(controller.update(0.1));
+    assert!(controller.camera.position[0] < 0.0);
+    assert_eq!(controller.camera.zoom, 1.0);
+
+    // a pointer stops it like any coasting
+    controller.pointer_down(1, [0.0, 0.0], 0.2);
+    assert!(!controller.is_animating());
+}
+
+#[test]
+fn controller_time_step_independent() {
+    let mut coarse = flicked();
+    let mut fine = flicked();
+    coarse.update(0.5);
+    for _ in 0..50 {
+        fine.update(0.01);
+    }
+    assert_near(coarse.camera.position, fine.camera.position);
+
+    let rect = Rect::new([1000.0, 1000.0], [1400.0, 1300.0]);
+    coarse.fly_to(rect, 1.0, Easing::EaseInOutCubic);
+    fine.fly_to(rect, 1.0, Easing::EaseInOutCubic);
+    coarse.update(0.3);
+    for _ in 0..30 {
+        fine.update(0.01);
+    }
+    assert_near(coarse.camera.position, fine.camera.position);
+    assert!((coarse.camera.zoom - fine.camera.zoom).abs() < 1e-4);
+}
+
+#[test]
+fn controller_fly_to() {
+    let mut controller = CameraController::new(Camera2D::new(800.0, 600.0));
+    controller.fly_to(
+        Rect::new([100.0, 100.0], [300.0, 400.0]),
+        0.5,
+        Easing::EaseOutCubic,
+    );
+    assert!(controller.update(0.25));
+    assert!(controller.is_animating());
+    controller.update(0.25);
+    assert!(!controller.is_animating());
+    assert_near(controller.camera.position, [200.0, 250.0]);
+    // 300 units high on 600 pixels
+    assert!((controller.camera.zoom - 2.0).abs() < 1e-5);
+
+    // input interrupts the flight
+    controller.fly_to(Rect::new([0.0, 0.0], [10.0, 10.0]), 1.0, Easing::Linear);
+    controller.update(0.1);
+    controller.pointer_down(1, [0.0, 0.0], 1.0);
+    assert!(!controller.is_animating());
+
+    assert_eq!(Easing::EaseInOutCubic.apply(0.5), 0.5);
+    assert_eq!(Easing::EaseOutCubic.apply(1.5), 1.0);
+}
+
+#[test]
+fn controller_pinch_zoom() {
+    let mut controller = CameraController::new(Camera2D::new(800.0, 600.0));
+    controller.pointer_down(1, [300.0, 300.0], 0.0);
+    controller.pointer_down(2, [500.0, 300.0], 0.0);
+    let center = controller.camera.screen_to_world([400.0, 300.0]);
+
+    // spreading the fingers to twice the distance doubles the zoom around their midpoint
+    controller.pointer_move(1, [200.0, 300.0], 0.1);
+    controller.pointer_move(2, [600.0, 300.0], 0.1);
+    assert!((controller.camera.zoom - 2.0).abs() < 1e-5);
+    assert_near(controller.camera.screen_to_world([400.0, 300.0]), center);
+
+    // moving both fingers pans
+    controller.pointer_move(1, [200.0, 350.0], 0.2);
+    controller.pointer_move(2, [600.0, 350.0], 0.2);
+    assert_near(controller.camera.screen_to_world([400.0, 350.0]), center);
+
+    controller.pointer_up(1, 0.2);
+    controller.pointer_up(2, 0.2);
+    assert!(!controller.is_animating());
+}
@@ -0,0 +1,12 @@
+#version 300 es
+precision mediump float;
+
+uniform sampler2D layer;
+uniform vec4 tint;  // Premultiplied
+
+in vec2 uv;
+out vec4 out_color;
+
+void main(void) {
+    out_color = texture(layer, uv) * tint;
+}
@@ -0,0 +1,10 @@
+#version 300 es
+layout(location = 0) in vec3 a_pos;
+
+out vec2 uv;
+
+void main(void) {
+	// The unit quad covers the whole target
+	uv = a_pos.xy;
+	gl_Position = vec4(a_pos.xy * 2.0f - 1.0f, 0.0f, 1.0f);
+}
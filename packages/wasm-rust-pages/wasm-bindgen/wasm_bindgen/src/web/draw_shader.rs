@@ -1,24 +1,47 @@
 use std::rc::Rc;
 
-use super::start_webgl::create_program;
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlUniformLocation};
 
-struct DrawShader {
-    gl: Rc<web_sys::WebGl2RenderingContext>,
-    program: web_sys::WebGlProgram,
+use super::{brush::Segment, start_webgl::create_program};
+
+///
+/// # Draw shader
+///
+/// Paints a brush [Segment] as a capsule, the coverage of the brush in every channel.
+/// Draws the unit quad at location `0`, scaled to the bounds of the segment.
+///
+pub struct DrawShader {
+    gl: Rc<WebGl2RenderingContext>,
+    program: WebGlProgram,
+    ortho_loc: Option<WebGlUniformLocation>,
+    bound_loc: Option<WebGlUniformLocation>,
+    segment_loc: Option<WebGlUniformLocation>,
+    brush_size_loc: Option<WebGlUniformLocation>,
+    flow_loc: Option<WebGlUniformLocation>,
+    hardness_loc: Option<WebGlUniformLocation>,
 }
 
 impl DrawShader {
-    pub fn new(gl: Rc<web_sys::WebGl2RenderingContext>) -> Self {
+    pub fn new(gl: Rc<WebGl2RenderingContext>) -> Result<Self, String> {
         let program = create_program(
             &gl,
             (
                 include_str!("draw_shader.vert"),
                 include_str!("draw_shader.frag"),
             ),
-        )
-        .unwrap();
+        )?;
+        let location = |name| gl.get_uniform_location(&program, name);
 
-        Self { program, gl }
+        Ok(Self {
+            ortho_loc: location("ortho"),
+            bound_loc: location("bound"),
+            segment_loc: location("segment"),
+            brush_size_loc: location("brush_size"),
+            flow_loc: location("flow"),
+            hardness_loc: location("hardness"),
+            program,
+            gl,
+        })
     }
 
     pub fn use_program(&self) {
@@ -29,19 +52,57 @@ impl DrawShader {
         self.gl.use_program(None);
     }
 
-    pub fn ortho(&self, ortho: Mat) {
-        self.gl.uniformMatrix4fv(this.orthoLoc, false, ortho);
+    /// Column major, see [super::brush::pixel_projection].
+    pub fn ortho(&self, ortho: &[f32; 16]) {
+        self.gl
+            .uniform_matrix4fv_with_f32_array(self.ortho_loc.as_ref(), false, ortho);
+    }
+
+    /// The diameters at the start and the end of the segment.
+    pub fn brush_size(&self, from: f32, to: f32) {
+        self.gl.uniform2f(self.brush_size_loc.as_ref(), from, to);
+    }
+
+    /// Where the quad is drawn, `[x, y, width, height]`.
+    pub fn bound(&self, bound: &[f32; 4]) {
+        self.gl
+            .uniform4fv_with_f32_array(self.bound_loc.as_ref(), bound);
+    }
+
+    /// `[start x, start y, end x, end y]`.
+    pub fn segment(&self, segment: &[f32; 4]) {
+        self.gl
+            .uniform4fv_with_f32_array(self.segment_loc.as_ref(), segment);
     }
 
-    pub fn brush_size(&self, size: number) {
-        self.gl.uniform1f(this.brushSizeLoc, size);
+    /// The coverage at the start and the end of the segment.
+    pub fn flow(&self, from: f32, to: f32) {
+        self.gl.uniform2f(self.flow_loc.as_ref(), from, to);
     }
 
-    pub fn bound(&self, bound: Float32Array) {
-        self.gl.uniform4fv(this.boundLoc, bound);
+    pub fn hardness(&self, hardness: f32) {
+        self.gl.uniform1f(self.hardness_loc.as_ref(), hardness);
     }
 
-    pub fn segment(&self, segment: Float32Array) {
-        self.gl.uniform4fv(this.segmentLoc, segment);
+    /// Sets the uniforms of the segment and draws the bound quad, the program has to be in use.
+    pub fn draw_segment(&self, segment: &Segment) {
+        let bounds = segment.bounds();
+        self.bound(&[
+            bounds.min[0],
+            bounds.min[1],
+            bounds.width(),
+            bounds.height(),
+        ]);
+        let [from, to] = [segment.from, segment.to];
+        self.segment(&[
+            from.position[0],
+            from.position[1],
+            to.position[0],
+            to.position[1],
+        ]);
+        self.brush_size(from.size, to.size);
+        self.flow(from.flow, to.flow);
+        self.gl
+            .draw_arrays(WebGl2RenderingContext::TRIANGLE_STRIP, 0, 4);
     }
 }
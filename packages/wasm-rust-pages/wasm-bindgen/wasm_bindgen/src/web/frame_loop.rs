@@ -0,0 +1,459 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    rc::{Rc, Weak},
+};
+
+use wasm_bindgen::{prelude::*, JsCast};
+
+use super::event_handle::JsCallback;
+
+/// A source of time in milliseconds, like `performance.now()`.
+pub trait Clock {
+    fn now(&self) -> f64;
+}
+
+impl<F: Fn() -> f64> Clock for F {
+    fn now(&self) -> f64 {
+        self()
+    }
+}
+
+///
+/// [`performance.now()`](https://developer.mozilla.org/en-US/docs/Web/API/Performance/now)
+///
+pub struct PerformanceClock {
+    performance: web_sys::Performance,
+}
+
+impl PerformanceClock {
+    pub fn new() -> Result<Self, JsValue> {
+        let performance = web_sys::window()
+            .and_then(|window| window.performance())
+            .ok_or("`performance` is not available")?;
+        Ok(Self { performance })
+    }
+}
+
+impl Clock for PerformanceClock {
+    fn now(&self) -> f64 {
+        self.performance.now()
+    }
+}
+
+/// How many frames [FrameStats] averages over.
+pub const FRAME_STATS_WINDOW: usize = 120;
+
+/// Frame times of the last [FRAME_STATS_WINDOW] frames, in milliseconds.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameTimeSummary {
+    /// Frames since the loop started.
+    pub frames: u32,
+    pub last: f64,
+    pub average: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Frames per second from the average frame time.
+    pub fps: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    frame_times: VecDeque<f64>,
+    frames: u32,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(FRAME_STATS_WINDOW),
+            frames: 0,
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn record(&mut self, frame_time: f64) {
+        if self.frame_times.len() == FRAME_STATS_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.frames = self.frames.wrapping_add(1);
+    }
+
+    pub fn summary(&self) -> FrameTimeSummary {
+        let Some(&last) = self.frame_times.back() else {
+            return FrameTimeSummary::default();
+        };
+        let average = self.frame_times.iter().sum::<f64>() / self.frame_times.len() as f64;
+        FrameTimeSummary {
+            frames: self.frames,
+            last,
+            average,
+            min: self
+                .frame_times
+                .iter()
+                .copied()
+                .fold(f64::INFINITY, f64::min),
+            max: self.frame_times.iter().copied().fold(0.0, f64::max),
+            fps: if average > 0.0 { 1000.0 / average } else { 0.0 },
+        }
+    }
+}
+
+/// What to do in one animation frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStep {
+    /// How many fixed updates to run before rendering.
+    pub updates: u32,
+    /// How far the render is between the last update and the next one, from `0.0` to `1.0`.
+    pub alpha: f32,
+}
+
+///
+/// # Frame scheduler
+///
+/// The timing of a [FrameLoop]: splits the time between frames into fixed steps,
+/// so the simulation doesn't depend on the frame rate, and leaves the remainder as the interpolation `alpha`.
+///
+/// Times come from a [Clock] in milliseconds, the fixed step is in seconds.
+///
+pub struct FrameScheduler<C> {
+    clock: C,
+    fixed_step: f64,
+    /// Updates in one frame before the rest of the backlog is dropped,
+    /// so slow updates can't make every frame slower.
+    pub max_updates: u32,
+    accumulator: f64,
+    last_time: Option<f64>,
+    paused: bool,
+    stats: FrameStats,
+}
+
+impl<C: Clock> FrameScheduler<C> {
+    pub fn new(clock: C, fixed_step: f64) -> Self {
+        Self {
+            clock,
+            fixed_step,
+            max_updates: 8,
+            accumulator: 0.0,
+            last_time: None,
+            paused: false,
+            stats: FrameStats::default(),
+        }
+    }
+
+    pub fn fixed_step(&self) -> f64 {
+        self.fixed_step
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Stops counting time, a paused scheduler has nothing to do.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Counts time again from now on, the time spent paused is skipped.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.last_time = None;
+        self.accumulator = 0.0;
+    }
+
+    /// Call once per animation frame. The first frame after a resume only renders.
+    pub fn frame(&mut self) -> Option<FrameStep> {
+        if self.paused {
+            return None;
+        }
+
+        let now = self.clock.now();
+        if let Some(last_time) = self.last_time.replace(now) {
+            let frame_time = (now - last_time).max(0.0);
+            self.stats.record(frame_time);
+            self.accumulator += frame_time / 1000.0;
+        }
+
+        let mut updates = 0;
+        while self.accumulator >= self.fixed_step && updates < self.max_updates {
+            self.accumulator -= self.fixed_step;
+            updates += 1;
+        }
+        if updates == self.max_updates {
+            self.accumulator %= self.fixed_step;
+        }
+
+        Some(FrameStep {
+            updates,
+            alpha: (self.accumulator / self.fixed_step) as f32,
+        })
+    }
+}
+
+/// What a [FrameLoop] runs.
+pub trait FrameHandler {
+    /// Called once per frame before the updates, to handle the input of the frame.
+    fn begin_frame(&mut self) {}
+    /// Advances the simulation by one fixed step of `dt` seconds.
+    fn update(&mut self, dt: f32);
+    /// Draws the state `alpha` of the way from the previous update to the last one.
+    fn render(&mut self, alpha: f32);
+}
+
+struct LoopState<C> {
+    window: web_sys::Window,
+    scheduler: FrameScheduler<C>,
+    handler: Rc<RefCell<dyn FrameHandler>>,
+    on_frame: Option<Closure<dyn FnMut()>>,
+    request_id: Option<i32>,
+    running: bool,
+    hidden: bool,
+}
+
+impl<C: Clock> LoopState<C> {
+    fn request_frame(&mut self) {
+        if !self.running || self.hidden || self.request_id.is_some() {
+            return;
+        }
+        if let Some(on_frame) = &self.on_frame {
+            self.request_id = self
+                .window
+                .request_animation_frame(on_frame.as_ref().unchecked_ref())
+                .ok();
+        }
+    }
+
+    fn cancel_frame(&mut self) {
+        if let Some(request_id) = self.request_id.take() {
+            let _ = self.window.cancel_animation_frame(request_id);
+        }
+    }
+
+    fn frame(&mut self) {
+        self.request_id = None;
+        if let Some(step) = self.scheduler.frame() {
+            let dt = self.scheduler.fixed_step() as f32;
+            let mut handler = self.handler.borrow_mut();
+            handler.begin_frame();
+            for _ in 0..step.updates {
+                handler.update(dt);
+            }
+            handler.render(step.alpha);
+        }
+        self.request_frame();
+    }
+
+    fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+        if hidden {
+            self.scheduler.pause();
+            self.cancel_frame();
+        } else if self.running {
+            self.scheduler.resume();
+            self.request_frame();
+        }
+    }
+}
+
+///
+/// # Frame loop
+///
+/// Owns the [`requestAnimationFrame()`](https://developer.mozilla.org/en-US/docs/Web/API/Window/requestAnimationFrame)
+/// callback and runs a [FrameHandler] with a [FrameScheduler]: fixed `update`s, then one `render` per frame.
+///
+/// Pauses while the tab is hidden ([`visibilitychange`](https://developer.mozilla.org/en-US/docs/Web/API/Document/visibilitychange_event)),
+/// the hidden time isn't simulated. Dropping the loop stops it.
+///
+pub struct FrameLoop<C: Clock + 'static = PerformanceClock> {
+    state: Rc<RefCell<LoopState<C>>>,
+    _on_visibility_change: JsCallback<web_sys::Event>,
+}
+
+impl FrameLoop {
+    pub fn new(handler: Rc<RefCell<dyn FrameHandler>>, fixed_step: f64) -> Result<Self, JsValue> {
+        Self::with_clock(PerformanceClock::new()?, handler, fixed_step)
+    }
+}
+
+impl<C: Clock + 'static> FrameLoop<C> {
+    pub fn with_clock(
+        clock: C,
+        handler: Rc<RefCell<dyn FrameHandler>>,
+        fixed_step: f64,
+    ) -> Result<Self, JsValue> {
+        let window = web_sys::window().ok_or("no global `window` exists")?;
+        let document = window
+            .document()
+            .ok_or("should have a document on window")?;
+
+        let state = Rc::new(RefCell::new(LoopState {
+            window,
+            scheduler: FrameScheduler::new(clock, fixed_step),
+            handler,
+            on_frame: None,
+            request_id: None,
+            running: false,
+            hidden: document.hidden(),
+        }));
+
+        // the callbacks only hold weak references, the loop owns them
+        let weak = Rc::downgrade(&state);
+        state.borrow_mut().on_frame = Some(Closure::new(move || {
+            if let Some(state) = Weak::upgrade(&weak) {
+                state.borrow_mut().frame();
+            }
+        }));
+
+        let weak = Rc::downgrade(&state);
+        let on_visibility_change = JsCallback::register(
+            &document.clone(),
+            "visibilitychange",
+            false,
+            move |_event: web_sys::Event| {
+                if let Some(state) = Weak::upgrade(&weak) {
+                    state.borrow_mut().set_hidden(document.hidden());
+                }
+            },
+        );
+
+        Ok(Self {
+            state,
+            _on_visibility_change: on_visibility_change,
+        })
+    }
+
+    pub fn start(&self) {
+        let mut state = self.state.borrow_mut();
+        if !state.running {
+            state.running = true;
+            state.scheduler.resume();
+            state.request_frame();
+        }
+    }
+
+    pub fn stop(&self) {
+        let mut state = self.state.borrow_mut();
+        state.running = false;
+        state.scheduler.pause();
+        state.cancel_frame();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state.borrow().running
+    }
+
+    pub fn stats(&self) -> FrameTimeSummary {
+        self.state.borrow().scheduler.stats().summary()
+    }
+}
+
+impl<C: Clock + 'static> Drop for FrameLoop<C> {
+    fn drop(&mut self) {
+        self.state.borrow_mut().cancel_frame();
+    }
+}
+
+#[cfg(test)]
+fn manual_clock() -> (Rc<std::cell::Cell<f64>>, impl Clock) {
+    let time = Rc::new(std::cell::Cell::new(0.0));
+    let clock = {
+        let time = time.clone();
+        move || time.get()
+    };
+    (time, clock)
+}
+
+#[test]
+fn scheduler_fixed_steps_and_alpha() {
+    let (time, clock) = manual_clock();
+    let mut scheduler = FrameScheduler::new(clock, 0.01);
+
+    // the first frame has no previous time
+    assert_eq!(
+        scheduler.frame(),
+        Some(FrameStep {
+            updates: 0,
+            alpha: 0.0
+        })
+    );
+
+    time.set(25.0);
+    let step = scheduler.frame().unwrap();
+    assert_eq!(step.updates, 2);
+    assert!((step.alpha - 0.5).abs() < 1e-4);
+
+    // the remainder carries over to the next frame
+    time.set(30.0);
+    let step = scheduler.frame().unwrap();
+    assert_eq!(step.updates, 1);
+    assert!(step.alpha.abs() < 1e-4);
+}
+
+#[test]
+fn scheduler_drops_backlog() {
+    let (time, clock) = manual_clock();
+    let mut scheduler = FrameScheduler::new(clock, 0.01);
+    scheduler.max_updates = 4;
+    scheduler.frame();
+
+    time.set(1005.0);
+    let step = scheduler.frame().unwrap();
+    assert_eq!(step.updates, 4);
+    assert!((step.alpha - 0.5).abs() < 1e-4);
+
+    time.set(1015.0);
+    assert_eq!(scheduler.frame().unwrap().updates, 1);
+}
+
+#[test]
+fn scheduler_pause_skips_hidden_time() {
+    let (time, clock) = manual_clock();
+    let mut scheduler = FrameScheduler::new(clock, 0.01);
+    scheduler.frame();
+    time.set(15.0);
+    assert_eq!(scheduler.frame().unwrap().updates, 1);
+
+    scheduler.pause();
+    time.set(5000.0);
+    assert_eq!(scheduler.frame(), None);
+
+    scheduler.resume();
+    assert_eq!(scheduler.frame().unwrap().updates, 0);
+    time.set(5010.0);
+    let step = scheduler.frame().unwrap();
+    assert_eq!(step.updates, 1);
+    assert!(step.alpha.abs() < 1e-4);
+}
+
+#[test]
+fn frame_stats_summary() {
+    let (time, clock) = manual_clock();
+    let mut scheduler = FrameScheduler::new(clock, 1.0 / 60.0);
+    assert_eq!(scheduler.stats().summary(), FrameTimeSummary::default());
+
+    for frame_time in [10.0, 20.0, 30.0] {
+        scheduler.frame();
+        time.set(time.get() + frame_time);
+    }
+    scheduler.frame();
+
+    let summary = scheduler.stats().summary();
+    assert_eq!(summary.frames, 3);
+    assert_eq!(summary.last, 30.0);
+    assert_eq!(summary.average, 20.0);
+    assert_eq!(summary.min, 10.0);
+    assert_eq!(summary.max, 30.0);
+    assert_eq!(summary.fps, 50.0);
+
+    let mut stats = FrameStats::default();
+    for _ in 0..FRAME_STATS_WINDOW {
+        stats.record(100.0);
+    }
+    stats.record(10.0);
+    assert_eq!(stats.summary().max, 100.0);
+    assert_eq!(stats.summary().min, 10.0);
+    assert_eq!(stats.summary().frames, FRAME_STATS_WINDOW as u32 + 1);
+}
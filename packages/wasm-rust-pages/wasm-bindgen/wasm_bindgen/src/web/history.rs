@@ -0,0 +1,369 @@
+use std::collections::VecDeque;
+
+use super::camera_2d::Rect;
+
+/// The side of a history tile, in canvas pixels.
+pub const TILE_SIZE: u32 = 64;
+/// Of pixels, before and after, for the undo and the redo entries together.
+pub const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// A tile of the canvas, in pixels from the top left corner, smaller at the right and bottom edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileRect {
+    /// Of its RGBA8 pixels.
+    pub fn bytes(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+}
+
+/// The tiles of a `width` by `height` canvas the pixel rectangle touches.
+pub fn tiles_in(bounds: &Rect, width: u32, height: u32) -> Vec<TileRect> {
+    let tile_range = |min: f32, max: f32, size: u32| {
+        let first = (min.max(0.0) as u32) / TILE_SIZE;
+        let last = (max.ceil().clamp(0.0, size as f32) as u32).div_ceil(TILE_SIZE);
+        // an empty range when the bounds miss the canvas
+        let last = if max <= 0.0 || min >= size as f32 {
+            first
+        } else {
+            last
+        };
+        first..last
+    };
+
+    let mut tiles = Vec::new();
+    for row in tile_range(bounds.min[1], bounds.max[1], height) {
+        for column in tile_range(bounds.min[0], bounds.max[0], width) {
+            let [x, y] = [column * TILE_SIZE, row * TILE_SIZE];
+            tiles.push(TileRect {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// The RGBA8 pixels of a tile before and after an edit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub rect: TileRect,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+impl Tile {
+    pub fn bytes(&self) -> usize {
+        self.before.len() + self.after.len()
+    }
+
+    fn is_changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// One undo step, the tiles an edit changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    tiles: Vec<Tile>,
+}
+
+impl HistoryEntry {
+    /// Keeps the tiles whose pixels changed, `None` when none did.
+    pub fn diff(tiles: impl IntoIterator<Item = Tile>) -> Option<Self> {
+        let tiles = tiles
+            .into_iter()
+            .filter(Tile::is_changed)
+            .collect::<Vec<_>>();
+        (!tiles.is_empty()).then_some(Self { tiles })
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.tiles.iter().map(Tile::bytes).sum()
+    }
+
+    /// Appends a later edit: the tiles keep their first `before` and take the last `after`.
+    fn merge(&mut self, later: HistoryEntry) {
+        for tile in later.tiles {
+            match self.tiles.iter_mut().find(|t| t.rect == tile.rect) {
+                Some(existing) => existing.after = tile.after,
+                None => self.tiles.push(tile),
+            }
+        }
+        // an edit that was taken back by a later one
+        self.tiles.retain(Tile::is_changed);
+    }
+}
+
+///
+/// # History
+///
+/// Undo and redo of the canvas edits as [tile snapshots](HistoryEntry), within `max_bytes`:
+/// when the entries need more, the oldest undo entries go first, then the furthest redo ones.
+///
+/// The edits between [History::begin_merge] and [History::end_merge] become one entry.
+///
+#[derive(Debug, Clone)]
+pub struct History {
+    undo: VecDeque<HistoryEntry>,
+    /// The next redo is the last.
+    redo: Vec<HistoryEntry>,
+    max_bytes: usize,
+    bytes: usize,
+    merging: bool,
+    /// The last undo entry was pushed in the current merge, the next pushes go into it.
+    merge_into_last: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BYTES)
+    }
+}
+
+impl History {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_bytes,
+            bytes: 0,
+            merging: false,
+            merge_into_last: false,
+        }
+    }
+
+    /// Adds an edit, the redo entries are dropped.
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.bytes -= self.redo.drain(..).map(|e| e.bytes()).sum::<usize>();
+
+        match self.undo.back_mut() {
+            Some(last) if self.merge_into_last => {
+                self.bytes -= last.bytes();
+                last.merge(entry);
+                self.bytes += last.bytes();
+                if last.tiles.is_empty() {
+                    self.undo.pop_back();
+                    self.merge_into_last = false;
+                }
+            }
+            _ => {
+                self.bytes += entry.bytes();
+                self.undo.push_back(entry);
+                self.merge_into_last = self.merging;
+            }
+        }
+        self.evict();
+    }
+
+    /// The next edits, until [History::end_merge], become one entry.
+    pub fn begin_merge(&mut self) {
+        self.merging = true;
+        self.merge_into_last = false;
+    }
+
+    pub fn end_merge(&mut self) {
+        self.merging = false;
+        self.merge_into_last = false;
+    }
+
+    /// The entry to take back, write its `before` pixels.
+    pub fn undo(&mut self) -> Option<&HistoryEntry> {
+        let entry = self.undo.pop_back()?;
+        // an undo ends the entry of the merge
+        self.merge_into_last = false;
+        self.redo.push(entry);
+        self.redo.last()
+    }
+
+    /// The entry to apply again, write its `after` pixels.
+    pub fn redo(&mut self) -> Option<&HistoryEntry> {
+        let entry = self.redo.pop()?;
+        self.merge_into_last = false;
+        self.undo.push_back(entry);
+        self.undo.back()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Of the undo and the redo entries.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+        self.merge_into_last = false;
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.max_bytes {
+            let entry = if let Some(entry) = self.undo.pop_front() {
+                if self.undo.is_empty() {
+                    self.merge_into_last = false;
+                }
+                entry
+            } else if !self.redo.is_empty() {
+                self.redo.remove(0)
+            } else {
+                break;
+            };
+            self.bytes -= entry.bytes();
+        }
+    }
+}
+
+#[cfg(test)]
+fn tile(x: u32, before: u8, after: u8) -> Tile {
+    let rect = TileRect {
+        x,
+        y: 0,
+        width: 2,
+        height: 2,
+    };
+    Tile {
+        rect,
+        before: vec![before; rect.bytes()],
+        after: vec![after; rect.bytes()],
+    }
+}
+
+#[test]
+fn history_tiles_in() {
+    let rect = |x, y, width, height| TileRect {
+        x,
+        y,
+        width,
+        height,
+    };
+    // a 100 by 70 canvas, the edge tiles are smaller
+    assert_eq!(
+        tiles_in(&Rect::new([-5.0, -5.0], [70.0, 10.0]), 100, 70),
+        [rect(0, 0, 64, 64), rect(64, 0, 36, 64)]
+    );
+    assert_eq!(
+        tiles_in(&Rect::new([10.0, 60.0], [20.0, 90.0]), 100, 70),
+        [rect(0, 0, 64, 64), rect(0, 64, 64, 6)]
+    );
+    assert_eq!(
+        tiles_in(&Rect::new([64.0, 64.0], [64.5, 64.5]), 100, 70),
+        [rect(64, 64, 36, 6)]
+    );
+    assert_eq!(rect(64, 64, 36, 6).bytes(), 36 * 6 * 4);
+    // outside
+    assert!(tiles_in(&Rect::new([-20.0, 0.0], [-1.0, 10.0]), 100, 70).is_empty());
+    assert!(tiles_in(&Rect::new([0.0, 70.0], [10.0, 80.0]), 100, 70).is_empty());
+}
+
+#[test]
+fn history_diff_and_merge() {
+    assert_eq!(HistoryEntry::diff([tile(0, 1, 1)]), None);
+    let entry = HistoryEntry::diff([tile(0, 1, 1), tile(2, 1, 2)]).unwrap();
+    assert_eq!(entry.tiles(), [tile(2, 1, 2)]);
+    assert_eq!(entry.bytes(), 2 * 16);
+
+    let mut history = History::default();
+    history.begin_merge();
+    history.push(entry);
+    history.push(HistoryEntry::diff([tile(2, 2, 3), tile(4, 0, 5)]).unwrap());
+    history.end_merge();
+    history.push(HistoryEntry::diff([tile(4, 5, 6)]).unwrap());
+
+    assert_eq!(history.undo().unwrap().tiles(), [tile(4, 5, 6)]);
+    // the first before and the last after
+    assert_eq!(
+        history.undo().unwrap().tiles(),
+        [tile(2, 1, 3), tile(4, 0, 5)]
+    );
+    assert!(!history.can_undo());
+    assert_eq!(history.redo().unwrap().tiles().len(), 2);
+    assert!(history.can_redo());
+
+    // a merge that ends where it started leaves nothing
+    let mut history = History::default();
+    history.begin_merge();
+    history.push(HistoryEntry::diff([tile(0, 1, 2)]).unwrap());
+    history.push(HistoryEntry::diff([tile(0, 2, 1)]).unwrap());
+    assert!(!history.can_undo());
+    assert_eq!(history.bytes(), 0);
+    history.push(HistoryEntry::diff([tile(0, 1, 3)]).unwrap());
+    // an undo ends the merged entry
+    history.undo();
+    history.redo();
+    history.push(HistoryEntry::diff([tile(0, 3, 4)]).unwrap());
+    assert_eq!(history.undo().unwrap().tiles(), [tile(0, 3, 4)]);
+}
+
+#[test]
+fn history_memory() {
+    // 32 bytes an entry
+    let entry = |x| HistoryEntry::diff([tile(x, 0, 1)]).unwrap();
+    let mut history = History::new(100);
+    for x in 0..3 {
+        history.push(entry(x));
+    }
+    assert_eq!(history.bytes(), 96);
+
+    // the oldest goes
+    history.push(entry(3));
+    assert_eq!(history.bytes(), 96);
+    for x in [3, 2, 1] {
+        assert_eq!(history.undo().unwrap().tiles()[0].rect.x, x);
+    }
+    assert!(history.undo().is_none());
+    assert_eq!(history.bytes(), 96);
+
+    // an edit drops the redo entries
+    history.redo();
+    history.push(entry(4));
+    assert_eq!(history.bytes(), 64);
+    assert!(!history.can_redo());
+
+    // the furthest redo entries go after all the undo ones
+    history.undo();
+    history.set_max_bytes(40);
+    assert!(!history.can_undo());
+    assert_eq!(history.redo().unwrap().tiles()[0].rect.x, 4);
+    assert_eq!(history.bytes(), 32);
+
+    // an entry bigger than everything isn't kept
+    history.set_max_bytes(16);
+    assert_eq!(history.bytes(), 0);
+    history.push(entry(5));
+    assert!(!history.can_undo());
+
+    history.set_max_bytes(1000);
+    history.push(entry(6));
+    history.undo();
+    history.clear();
+    assert_eq!((history.bytes(), history.can_redo()), (0, false));
+}
@@ -0,0 +1,641 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlCanvasElement, KeyboardEvent, MouseEvent, PointerEvent, WheelEvent};
+
+use super::{
+    actions::{ActionMap, Chord, Modifiers, Trigger},
+    event_handle::JsCallback,
+};
+
+/// [`PointerEvent.pointerType`](https://developer.mozilla.org/en-US/docs/Web/API/PointerEvent/pointerType)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerKind {
+    Mouse,
+    Touch,
+    Pen,
+}
+
+impl PointerKind {
+    pub fn from_pointer_type(pointer_type: &str) -> Self {
+        match pointer_type {
+            "touch" => PointerKind::Touch,
+            "pen" => PointerKind::Pen,
+            _ => PointerKind::Mouse,
+        }
+    }
+}
+
+/// Touches and pens in contact press [PointerButton::Primary].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerButton {
+    Primary,
+    Secondary,
+    Middle,
+    Back,
+    Forward,
+}
+
+impl PointerButton {
+    pub const ALL: [PointerButton; 5] = [
+        PointerButton::Primary,
+        PointerButton::Secondary,
+        PointerButton::Middle,
+        PointerButton::Back,
+        PointerButton::Forward,
+    ];
+
+    /// The bit of the button in [`MouseEvent.buttons`](https://developer.mozilla.org/en-US/docs/Web/API/MouseEvent/buttons).
+    pub fn bit(&self) -> u16 {
+        match self {
+            PointerButton::Primary => 1,
+            PointerButton::Secondary => 2,
+            PointerButton::Middle => 4,
+            PointerButton::Back => 8,
+            PointerButton::Forward => 16,
+        }
+    }
+}
+
+/// A pointer event of any pointer kind. Positions are canvas pixels, times are seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerInput {
+    pub id: i32,
+    pub kind: PointerKind,
+    pub position: [f32; 2],
+    /// The pressed buttons after the event, [PointerButton::bit]s.
+    pub buttons: u16,
+    /// From `0.0` to `1.0`, a mouse presses with `1.0`.
+    pub pressure: f32,
+    pub time: f64,
+}
+
+/// The events [InputState] understands, the browser events are converted to them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    PointerDown(PointerInput),
+    PointerMove(PointerInput),
+    PointerUp(PointerInput),
+    /// Also when the pointer is captured by something else, releases its buttons.
+    PointerCancel {
+        id: i32,
+        time: f64,
+    },
+    Wheel {
+        delta: [f32; 2],
+        position: [f32; 2],
+    },
+    /// [`KeyboardEvent.code`](https://developer.mozilla.org/en-US/docs/Web/API/KeyboardEvent/code),
+    /// the physical key, so bindings don't change with the keyboard layout.
+    KeyDown {
+        code: String,
+        repeat: bool,
+    },
+    KeyUp {
+        code: String,
+    },
+    /// The canvas lost focus, the releases won't arrive, so everything is released.
+    FocusLost,
+}
+
+/// The buttons a pointer holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PointerState {
+    kind: PointerKind,
+    buttons: u16,
+}
+
+///
+/// # Input state
+///
+/// Keys and buttons from [InputEvent]s. Besides what is held down it keeps
+/// what was pressed and released since the last [InputState::end_frame], so a press
+/// and release between two frames isn't lost.
+///
+/// Actions are bound to [Chord]s by an [ActionMap], an action is down when any of its chords is.
+///
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<String>,
+    /// With the modifiers held when pressed.
+    keys_pressed: HashMap<String, Modifiers>,
+    keys_released: HashSet<String>,
+    buttons_pressed: HashMap<PointerButton, Modifiers>,
+    buttons_released: HashSet<PointerButton>,
+    pointers: HashMap<i32, PointerState>,
+    events: Vec<InputEvent>,
+    actions: ActionMap,
+}
+
+impl InputState {
+    pub fn handle(&mut self, event: InputEvent) {
+        match &event {
+            InputEvent::PointerDown(pointer) | InputEvent::PointerMove(pointer) => {
+                self.update_pointer(pointer);
+            }
+            InputEvent::PointerUp(pointer) => {
+                self.update_pointer(pointer);
+                // a mouse still hovers, a touch or pen is gone
+                if pointer.kind != PointerKind::Mouse {
+                    self.pointers.remove(&pointer.id);
+                }
+            }
+            InputEvent::PointerCancel { id, .. } => {
+                if let Some(pointer) = self.pointers.remove(id) {
+                    self.release_buttons(pointer.buttons, 0);
+                }
+            }
+            InputEvent::Wheel { .. } => {}
+            InputEvent::KeyDown { code, repeat } => {
+                if self.keys_down.insert(code.clone()) || !repeat {
+                    let modifiers = self.modifiers();
+                    self.keys_pressed.insert(code.clone(), modifiers);
+                }
+            }
+            InputEvent::KeyUp { code } => {
+                if self.keys_down.remove(code) {
+                    self.keys_released.insert(code.clone());
+                }
+            }
+            InputEvent::FocusLost => {
+                self.keys_released.extend(self.keys_down.drain());
+                for pointer in self.pointers.values_mut() {
+                    let buttons = std::mem::take(&mut pointer.buttons);
+                    for button in PointerButton::ALL {
+                        if buttons & button.bit() != 0 {
+                            self.buttons_released.insert(button);
+                        }
+                    }
+                }
+                self.pointers
+                    .retain(|_, pointer| pointer.kind == PointerKind::Mouse);
+            }
+        }
+        self.events.push(event);
+    }
+
+    fn update_pointer(&mut self, input: &PointerInput) {
+        let previous_buttons = self
+            .pointers
+            .insert(
+                input.id,
+                PointerState {
+                    kind: input.kind,
+                    buttons: input.buttons,
+                },
+            )
+            .map_or(0, |pointer| pointer.buttons);
+        let modifiers = self.modifiers();
+        for button in PointerButton::ALL {
+            if input.buttons & !previous_buttons & button.bit() != 0 {
+                self.buttons_pressed.insert(button, modifiers);
+            }
+        }
+        self.release_buttons(previous_buttons, input.buttons);
+    }
+
+    fn release_buttons(&mut self, previous_buttons: u16, buttons: u16) {
+        for button in PointerButton::ALL {
+            if previous_buttons & !buttons & button.bit() != 0 {
+                self.buttons_released.insert(button);
+            }
+        }
+    }
+
+    /// Forgets the presses, releases and events of the frame.
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.events.clear();
+    }
+
+    /// The events of this frame, in order.
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    /// Takes the events of this frame, the pressed and released state stays until [InputState::end_frame].
+    pub fn take_events(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn is_key_down(&self, code: &str) -> bool {
+        self.keys_down.contains(code)
+    }
+
+    pub fn key_released(&self, code: &str) -> bool {
+        self.keys_released.contains(code)
+    }
+
+    /// Held by any pointer.
+    pub fn is_button_down(&self, button: PointerButton) -> bool {
+        self.pointers
+            .values()
+            .any(|pointer| pointer.buttons & button.bit() != 0)
+    }
+
+    pub fn button_released(&self, button: PointerButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
+    }
+
+    pub fn set_actions(&mut self, actions: ActionMap) {
+        self.actions = actions;
+    }
+
+    /// The modifier keys held down.
+    pub fn modifiers(&self) -> Modifiers {
+        self.keys_down
+            .iter()
+            .fold(Modifiers::NONE, |modifiers, code| {
+                modifiers.union(Modifiers::of_key(code))
+            })
+    }
+
+    /// The trigger is held with at least the modifiers of the chord,
+    /// so pressing another modifier doesn't stop e.g. panning.
+    pub fn chord_down(&self, chord: &Chord) -> bool {
+        let down = match &chord.trigger {
+            Trigger::Key(code) => self.is_key_down(code),
+            Trigger::Button(button) => self.is_button_down(*button),
+        };
+        down && self
+            .modifiers()
+            .without(chord.trigger.modifiers())
+            .contains(chord.modifiers)
+    }
+
+    /// The trigger was pressed with exactly the modifiers of the chord, so `KeyZ` doesn't fire with `Ctrl+KeyZ`.
+    pub fn chord_pressed(&self, chord: &Chord) -> bool {
+        let modifiers = match &chord.trigger {
+            Trigger::Key(code) => self.keys_pressed.get(code),
+            Trigger::Button(button) => self.buttons_pressed.get(button),
+        };
+        modifiers.is_some_and(|modifiers| {
+            modifiers.without(chord.trigger.modifiers()) == chord.modifiers
+        })
+    }
+
+    /// The trigger was released, whatever the modifiers.
+    pub fn chord_released(&self, chord: &Chord) -> bool {
+        match &chord.trigger {
+            Trigger::Key(code) => self.key_released(code),
+            Trigger::Button(button) => self.button_released(*button),
+        }
+    }
+
+    pub fn action_down(&self, action: &str) -> bool {
+        self.actions
+            .chords(action)
+            .iter()
+            .any(|chord| self.chord_down(chord))
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions
+            .chords(action)
+            .iter()
+            .any(|chord| self.chord_pressed(chord))
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.actions
+            .chords(action)
+            .iter()
+            .any(|chord| self.chord_released(chord))
+    }
+}
+
+/// Canvas pixels per CSS pixel, pointer events are in CSS pixels.
+fn canvas_scale(canvas: &HtmlCanvasElement) -> [f32; 2] {
+    let scale = |pixels: u32, css_pixels: i32| match css_pixels {
+        0 => 1.0,
+        css_pixels => pixels as f32 / css_pixels as f32,
+    };
+    [
+        scale(canvas.width(), canvas.client_width()),
+        scale(canvas.height(), canvas.client_height()),
+    ]
+}
+
+/// The pointer position in canvas pixels.
+fn canvas_position(canvas: &HtmlCanvasElement, event: &MouseEvent) -> [f32; 2] {
+    let scale = canvas_scale(canvas);
+    [
+        event.offset_x() as f32 * scale[0],
+        event.offset_y() as f32 * scale[1],
+    ]
+}
+
+fn pointer_input(canvas: &HtmlCanvasElement, event: &PointerEvent) -> PointerInput {
+    let kind = PointerKind::from_pointer_type(&event.pointer_type());
+    let buttons = event.buttons();
+    // a mouse reports 0.5 while pressed
+    let pressure = match kind {
+        PointerKind::Mouse if buttons != 0 => 1.0,
+        PointerKind::Mouse => 0.0,
+        _ => event.pressure(),
+    };
+    PointerInput {
+        id: event.pointer_id(),
+        kind,
+        position: canvas_position(canvas, event),
+        buttons,
+        pressure,
+        time: event.time_stamp() / 1000.0,
+    }
+}
+
+/// The browser event as an [InputEvent], `None` for the events [InputState] doesn't use.
+fn input_event(canvas: &HtmlCanvasElement, event: &web_sys::Event) -> Option<InputEvent> {
+    if let Some(event) = event.dyn_ref::<PointerEvent>() {
+        let pointer = pointer_input(canvas, event);
+        return match event.type_().as_str() {
+            "pointerdown" => Some(InputEvent::PointerDown(pointer)),
+            "pointermove" => Some(InputEvent::PointerMove(pointer)),
+            "pointerup" => Some(InputEvent::PointerUp(pointer)),
+            "pointercancel" => Some(InputEvent::PointerCancel {
+                id: pointer.id,
+                time: pointer.time,
+            }),
+            _ => None,
+        };
+    }
+    if let Some(event) = event.dyn_ref::<WheelEvent>() {
+        return Some(InputEvent::Wheel {
+            delta: [event.delta_x() as f32, event.delta_y() as f32],
+            position: canvas_position(canvas, event),
+        });
+    }
+    if let Some(event) = event.dyn_ref::<KeyboardEvent>() {
+        return match event.type_().as_str() {
+            "keydown" => Some(InputEvent::KeyDown {
+                code: event.code(),
+                repeat: event.repeat(),
+            }),
+            "keyup" => Some(InputEvent::KeyUp { code: event.code() }),
+            _ => None,
+        };
+    }
+    match event.type_().as_str() {
+        "blur" => Some(InputEvent::FocusLost),
+        _ => None,
+    }
+}
+
+const CANVAS_EVENTS: [&str; 8] = [
+    "pointerdown",
+    "pointermove",
+    "pointerup",
+    "pointercancel",
+    "wheel",
+    "keydown",
+    "keyup",
+    "blur",
+];
+
+///
+/// # Input
+///
+/// Listens to the pointer, wheel and keyboard events of a canvas for as long as it lives
+/// and keeps them in an [InputState]. The canvas needs a `tabIndex` to get the key events,
+/// it is focused when it is clicked or touched and keeps the focus until the page moves it elsewhere.
+///
+pub struct Input {
+    state: Rc<RefCell<InputState>>,
+    _listeners: Vec<JsCallback<web_sys::Event>>,
+}
+
+impl Input {
+    pub fn new(canvas: &HtmlCanvasElement) -> Self {
+        let state = Rc::new(RefCell::new(InputState::default()));
+
+        let listeners = CANVAS_EVENTS
+            .into_iter()
+            .map(|name| {
+                let state = state.clone();
+                let canvas = canvas.clone();
+                JsCallback::register(
+                    &canvas.clone(),
+                    name,
+                    false,
+                    move |event: web_sys::Event| {
+                        match name {
+                            "pointerdown" => {
+                                let _ = canvas.focus();
+                                // keep getting the moves of this pointer when it leaves the canvas
+                                if let Some(event) = event.dyn_ref::<PointerEvent>() {
+                                    let _ = canvas.set_pointer_capture(event.pointer_id());
+                                }
+                            }
+                            // don't scroll the page
+                            "wheel" => event.prevent_default(),
+                            _ => {}
+                        }
+                        if let Some(input_event) = input_event(&canvas, &event) {
+                            state.borrow_mut().handle(input_event);
+                        }
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            state,
+            _listeners: listeners,
+        }
+    }
+
+    pub fn state(&self) -> std::cell::Ref<'_, InputState> {
+        self.state.borrow()
+    }
+
+    pub fn state_mut(&self) -> std::cell::RefMut<'_, InputState> {
+        self.state.borrow_mut()
+    }
+}
+
+#[cfg(test)]
+fn mouse(id: i32, position: [f32; 2], buttons: u16) -> PointerInput {
+    PointerInput {
+        id,
+        kind: PointerKind::Mouse,
+        position,
+        buttons,
+        pressure: if buttons != 0 { 1.0 } else { 0.0 },
+        time: 0.0,
+    }
+}
+
+#[cfg(test)]
+fn key_down(code: &str) -> InputEvent {
+    InputEvent::KeyDown {
+        code: code.to_string(),
+        repeat: false,
+    }
+}
+
+#[test]
+fn input_keys_per_frame() {
+    let mut input = InputState::default();
+    input.handle(key_down("KeyW"));
+    assert!(input.is_key_down("KeyW"));
+    assert!(input.chord_pressed(&Chord::key("KeyW")));
+
+    input.end_frame();
+    input.handle(InputEvent::KeyDown {
+        code: "KeyW".to_string(),
+        repeat: true,
+    });
+    assert!(input.is_key_down("KeyW"));
+    assert!(!input.chord_pressed(&Chord::key("KeyW")));
+
+    // pressed and released between two frames
+    input.end_frame();
+    input.handle(InputEvent::KeyUp {
+        code: "KeyW".to_string(),
+    });
+    input.handle(key_down("KeyA"));
+    input.handle(InputEvent::KeyUp {
+        code: "KeyA".to_string(),
+    });
+    assert!(input.key_released("KeyW"));
+    assert!(input.chord_pressed(&Chord::key("KeyA")) && input.key_released("KeyA"));
+    assert!(!input.is_key_down("KeyW") && !input.is_key_down("KeyA"));
+    assert_eq!(input.events().len(), 3);
+
+    input.end_frame();
+    assert!(!input.key_released("KeyW"));
+    assert!(input.events().is_empty());
+}
+
+#[test]
+fn input_buttons_and_pointers() {
+    let mut input = InputState::default();
+    input.handle(InputEvent::PointerMove(mouse(1, [10.0, 10.0], 0)));
+    input.handle(InputEvent::PointerDown(mouse(1, [10.0, 10.0], 1)));
+    assert!(input.chord_pressed(&Chord::button(PointerButton::Primary)));
+
+    // a second button is a move with more buttons
+    input.end_frame();
+    input.handle(InputEvent::PointerMove(mouse(1, [20.0, 10.0], 1 | 2)));
+    assert!(input.chord_pressed(&Chord::button(PointerButton::Secondary)));
+    assert!(!input.chord_pressed(&Chord::button(PointerButton::Primary)));
+    assert!(input.is_button_down(PointerButton::Primary));
+
+    input.end_frame();
+    input.handle(InputEvent::PointerUp(mouse(1, [20.0, 10.0], 0)));
+    assert!(input.button_released(PointerButton::Primary));
+    assert!(input.button_released(PointerButton::Secondary));
+    assert!(!input.is_button_down(PointerButton::Primary));
+    // the mouse still hovers
+    assert!(input.pointers.contains_key(&1));
+
+    // touches are normalized to primary presses and leave on release
+    let touch = PointerInput {
+        kind: PointerKind::Touch,
+        pressure: 0.4,
+        ..mouse(2, [5.0, 5.0], 1)
+    };
+    input.handle(InputEvent::PointerDown(touch));
+    assert!(input.is_button_down(PointerButton::Primary));
+    input.handle(InputEvent::PointerCancel { id: 2, time: 0.0 });
+    assert!(!input.pointers.contains_key(&2));
+    assert!(!input.is_button_down(PointerButton::Primary));
+}
+
+#[test]
+fn input_focus_lost_releases_everything() {
+    let mut input = InputState::default();
+    input.handle(key_down("ShiftLeft"));
+    input.handle(InputEvent::PointerDown(mouse(1, [0.0, 0.0], 4)));
+    input.end_frame();
+
+    input.handle(InputEvent::FocusLost);
+    assert!(input.key_released("ShiftLeft"));
+    assert!(input.button_released(PointerButton::Middle));
+    assert!(!input.is_key_down("ShiftLeft"));
+    assert!(!input.is_button_down(PointerButton::Middle));
+}
+
+#[test]
+fn input_actions() {
+    let mut input = InputState::default();
+    input.set_actions(ActionMap::from_toml("").unwrap());
+    input
+        .actions_mut()
+        .bind("pan", Chord::button(PointerButton::Middle));
+    input.actions_mut().bind("pan", Chord::key("Space"));
+
+    input.handle(key_down("Space"));
+    assert!(input.action_down("pan") && input.action_pressed("pan"));
+
+    input.end_frame();
+    input.handle(InputEvent::PointerDown(mouse(1, [0.0, 0.0], 4)));
+    input.handle(InputEvent::KeyUp {
+        code: "Space".to_string(),
+    });
+    assert!(input.action_down("pan"));
+    assert!(input.action_pressed("pan") && input.action_released("pan"));
+
+    input.actions_mut().clear("pan");
+    assert!(!input.action_down("pan"));
+    assert!(!input.action_down("unknown"));
+}
+
+#[test]
+fn input_chords_with_modifiers() {
+    use super::actions::{REDO, UNDO, ZOOM_IN};
+
+    let mut input = InputState::default();
+    input.actions_mut().bind(ZOOM_IN, Chord::key("KeyZ"));
+
+    input.handle(key_down("ControlLeft"));
+    input.handle(key_down("KeyZ"));
+    assert!(input.action_pressed(UNDO));
+    assert!(!input.action_pressed(REDO));
+    assert!(!input.action_pressed(ZOOM_IN));
+
+    input.end_frame();
+    input.handle(InputEvent::KeyUp {
+        code: "KeyZ".to_string(),
+    });
+    input.handle(key_down("ShiftRight"));
+    input.handle(key_down("KeyZ"));
+    assert!(input.action_pressed(REDO));
+    assert!(!input.action_pressed(UNDO));
+
+    // releasing a modifier after the press doesn't change what was pressed
+    input.end_frame();
+    input.handle(InputEvent::KeyUp {
+        code: "KeyZ".to_string(),
+    });
+    input.handle(InputEvent::KeyUp {
+        code: "ControlLeft".to_string(),
+    });
+    input.handle(InputEvent::KeyUp {
+        code: "ShiftRight".to_string(),
+    });
+    input.handle(key_down("KeyZ"));
+    input.handle(key_down("ControlLeft"));
+    assert!(input.action_pressed(ZOOM_IN));
+    assert!(!input.action_pressed(UNDO));
+
+    // a held chord stays down with more modifiers
+    input.handle(key_down("ArrowLeft"));
+    assert!(input.action_down(super::actions::PAN_LEFT));
+    assert!(!input.action_pressed(super::actions::PAN_LEFT));
+}
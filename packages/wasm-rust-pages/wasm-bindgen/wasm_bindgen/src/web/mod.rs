@@ -1,4 +1,24 @@
+// the cameras, the frame loop, the input, the brush strokes and the history build natively too,
+// so they can be tested, only the demos that use them are wasm only
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod actions;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod brush;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
 mod camera_2d;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod camera_controller;
+#[cfg(target_arch = "wasm32")]
 mod draw_shader;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
 mod event_handle;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod frame_loop;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod history;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod input;
+#[cfg(target_arch = "wasm32")]
+mod painting;
+#[cfg(target_arch = "wasm32")]
 mod start_webgl;
@@ -0,0 +1,614 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::prelude::*;
+use web_sys::{
+    console, WebGl2RenderingContext as GL, WebGlFramebuffer, WebGlProgram, WebGlTexture,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
+use webgl_common::{slice_as_u8_slice, Color};
+
+use super::{
+    actions::{REDO, UNDO},
+    brush::{pixel_projection, Brush, Stroke, StrokePoint},
+    camera_2d::Rect,
+    draw_shader::DrawShader,
+    frame_loop::{FrameHandler, FrameLoop},
+    history::{tiles_in, History, HistoryEntry, Tile, TileRect},
+    input::{Input, InputEvent, PointerButton},
+    start_webgl::create_program,
+};
+
+/// A texture and the framebuffer that renders into it.
+struct RenderTarget {
+    texture: WebGlTexture,
+    framebuffer: WebGlFramebuffer,
+}
+
+impl RenderTarget {
+    fn new(gl: &GL, width: u32, height: u32) -> Result<Self, String> {
+        let texture = gl.create_texture().ok_or("Failed to create texture")?;
+        gl.bind_texture(GL::TEXTURE_2D, Some(&texture));
+        gl.tex_storage_2d(GL::TEXTURE_2D, 1, GL::RGBA8, width as i32, height as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+        gl.bind_texture(GL::TEXTURE_2D, None);
+
+        let framebuffer = gl
+            .create_framebuffer()
+            .ok_or("Failed to create framebuffer")?;
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+        gl.framebuffer_texture_2d(
+            GL::FRAMEBUFFER,
+            GL::COLOR_ATTACHMENT0,
+            GL::TEXTURE_2D,
+            Some(&texture),
+            0,
+        );
+        gl.clear_color(0.0, 0.0, 0.0, 0.0);
+        gl.clear(GL::COLOR_BUFFER_BIT);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+        Ok(Self {
+            texture,
+            framebuffer,
+        })
+    }
+}
+
+/// Draws a texture over the whole target, multiplied by a tint.
+struct CompositeShader {
+    program: WebGlProgram,
+    layer_loc: Option<WebGlUniformLocation>,
+    tint_loc: Option<WebGlUniformLocation>,
+}
+
+impl CompositeShader {
+    fn new(gl: &GL) -> Result<Self, String> {
+        let program = create_program(
+            gl,
+            (
+                include_str!("composite.vert"),
+                include_str!("composite.frag"),
+            ),
+        )?;
+        Ok(Self {
+            layer_loc: gl.get_uniform_location(&program, "layer"),
+            tint_loc: gl.get_uniform_location(&program, "tint"),
+            program,
+        })
+    }
+
+    /// With the quad bound, `tint` is premultiplied.
+    fn draw(&self, gl: &GL, texture: &WebGlTexture, tint: Color) {
+        gl.use_program(Some(&self.program));
+        gl.active_texture(GL::TEXTURE0);
+        gl.bind_texture(GL::TEXTURE_2D, Some(texture));
+        gl.uniform1i(self.layer_loc.as_ref(), 0);
+        gl.uniform4f(self.tint_loc.as_ref(), tint.r, tint.g, tint.b, tint.a);
+        gl.draw_arrays(GL::TRIANGLE_STRIP, 0, 4);
+        gl.bind_texture(GL::TEXTURE_2D, None);
+    }
+}
+
+///
+/// # Painting
+///
+/// Paints brush strokes into an offscreen canvas texture, premultiplied RGBA in canvas pixels.
+///
+/// A stroke is painted into its own layer first, the segments keep the highest coverage
+/// ([`MAX`](https://developer.mozilla.org/en-US/docs/Web/API/WebGL2RenderingContext/blendEquation)
+/// blending) so they don't add up where they overlap, and the layer is tinted with the color
+/// and the opacity of the brush when it's drawn over the canvas. One pointer paints at a time.
+///
+/// The [History] keeps the tiles a stroke or a clear changed, read back from the canvas before
+/// and after the edit. The read back waits for the GPU, once per edit.
+///
+pub struct Painting {
+    gl: Rc<GL>,
+    draw_shader: DrawShader,
+    composite: CompositeShader,
+    quad: WebGlVertexArrayObject,
+    width: u32,
+    height: u32,
+    canvas: RenderTarget,
+    stroke_layer: RenderTarget,
+    /// The pointer painting and its stroke.
+    stroke: Option<(i32, Stroke)>,
+    /// For the next stroke.
+    pub brush: Brush,
+    history: History,
+}
+
+impl Painting {
+    pub fn new(gl: Rc<GL>, width: u32, height: u32) -> Result<Self, String> {
+        let quad = gl
+            .create_vertex_array()
+            .ok_or("Could not create vertex array object")?;
+        gl.bind_vertex_array(Some(&quad));
+        let buffer = gl.create_buffer().ok_or("Failed to create buffer")?;
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
+        #[rustfmt::skip]
+        let vertices: [f32; 12] = [
+            0.0, 0.0, 0.0,
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            1.0, 1.0, 0.0,
+        ];
+        gl.buffer_data_with_u8_array(
+            GL::ARRAY_BUFFER,
+            slice_as_u8_slice(&vertices),
+            GL::STATIC_DRAW,
+        );
+        gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(0);
+        gl.bind_vertex_array(None);
+
+        Ok(Self {
+            draw_shader: DrawShader::new(gl.clone())?,
+            composite: CompositeShader::new(&gl)?,
+            quad,
+            width,
+            height,
+            canvas: RenderTarget::new(&gl, width, height)?,
+            stroke_layer: RenderTarget::new(&gl, width, height)?,
+            stroke: None,
+            brush: Brush::default(),
+            history: History::default(),
+            gl,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Ignored while another pointer paints.
+    pub fn begin_stroke(&mut self, pointer: i32, point: StrokePoint) {
+        if self.stroke.is_none() {
+            let mut stroke = Stroke::new(self.brush);
+            stroke.add(point);
+            self.stroke = Some((pointer, stroke));
+        }
+    }
+
+    pub fn stroke_to(&mut self, pointer: i32, point: StrokePoint) {
+        if let Some((id, stroke)) = &mut self.stroke {
+            if *id == pointer {
+                stroke.add(point);
+            }
+        }
+    }
+
+    /// Paints the rest of the stroke and draws it over the canvas, one history entry.
+    pub fn end_stroke(&mut self, pointer: i32) -> Result<(), JsValue> {
+        if !matches!(&self.stroke, Some((id, _)) if *id == pointer) {
+            return Ok(());
+        }
+        if let Some((_, stroke)) = &mut self.stroke {
+            stroke.finish();
+        }
+        self.flush();
+        if let Some((_, stroke)) = self.stroke.take() {
+            self.record(stroke.bounds(), |painting| {
+                painting.bind_target(Some(&painting.canvas));
+                painting.gl.enable(GL::BLEND);
+                painting.gl.blend_equation(GL::FUNC_ADD);
+                painting.gl.blend_func(GL::ONE, GL::ONE_MINUS_SRC_ALPHA);
+                painting.composite.draw(
+                    &painting.gl,
+                    &painting.stroke_layer.texture,
+                    stroke.brush().tint(),
+                );
+                painting.unbind();
+            })?;
+
+            self.bind_target(Some(&self.stroke_layer));
+            self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            self.gl.clear(GL::COLOR_BUFFER_BIT);
+            self.unbind();
+        }
+        Ok(())
+    }
+
+    /// Feeds the pointer events of the primary button to the strokes, `scale` maps the event
+    /// positions to canvas pixels. Returns whether anything was painted.
+    pub fn handle(&mut self, event: &InputEvent, scale: [f32; 2]) -> Result<bool, JsValue> {
+        let point = |position: [f32; 2], pressure: f32| {
+            StrokePoint::new([position[0] * scale[0], position[1] * scale[1]], pressure)
+        };
+        match event {
+            InputEvent::PointerDown(pointer)
+                if pointer.buttons & PointerButton::Primary.bit() != 0 =>
+            {
+                self.begin_stroke(pointer.id, point(pointer.position, pointer.pressure));
+            }
+            InputEvent::PointerMove(pointer) if pointer.buttons != 0 => {
+                self.stroke_to(pointer.id, point(pointer.position, pointer.pressure))
+            }
+            InputEvent::PointerUp(pointer) => self.end_stroke(pointer.id)?,
+            InputEvent::PointerCancel { id, .. } => self.end_stroke(*id)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Paints the segments added since the last flush into the stroke layer.
+    pub fn flush(&mut self) {
+        let Some((_, stroke)) = &mut self.stroke else {
+            return;
+        };
+        let segments = stroke.take_segments();
+        if segments.is_empty() {
+            return;
+        }
+        let hardness = stroke.brush().hardness;
+
+        self.bind_target(Some(&self.stroke_layer));
+        self.gl.enable(GL::BLEND);
+        self.gl.blend_equation(GL::MAX);
+        self.gl.blend_func(GL::ONE, GL::ONE);
+        self.draw_shader.use_program();
+        self.draw_shader
+            .ortho(&pixel_projection(self.width as f32, self.height as f32));
+        self.draw_shader.hardness(hardness);
+        for segment in &segments {
+            self.draw_shader.draw_segment(segment);
+        }
+        self.unbind();
+    }
+
+    /// Erases everything, also the stroke being painted, one history entry.
+    pub fn clear(&mut self) -> Result<(), JsValue> {
+        self.stroke = None;
+        let bounds = Rect::new([0.0, 0.0], [self.width as f32, self.height as f32]);
+        self.record(Some(bounds), |painting| {
+            for target in [&painting.canvas, &painting.stroke_layer] {
+                painting.bind_target(Some(target));
+                painting.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+                painting.gl.clear(GL::COLOR_BUFFER_BIT);
+            }
+            painting.unbind();
+        })
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    /// Takes back the last edit, not while a stroke is painted. Returns whether there was one.
+    pub fn undo(&mut self) -> Result<bool, JsValue> {
+        if self.stroke.is_some() {
+            return Ok(false);
+        }
+        match self.history.undo() {
+            Some(entry) => {
+                write_tiles(&self.gl, &self.canvas, self.height, entry.tiles(), |tile| {
+                    &tile.before
+                })?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Applies the last undone edit again. Returns whether there was one.
+    pub fn redo(&mut self) -> Result<bool, JsValue> {
+        if self.stroke.is_some() {
+            return Ok(false);
+        }
+        match self.history.redo() {
+            Some(entry) => {
+                write_tiles(&self.gl, &self.canvas, self.height, entry.tiles(), |tile| {
+                    &tile.after
+                })?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Runs an edit of the canvas within `bounds` and adds the tiles it changed to the history.
+    fn record(&mut self, bounds: Option<Rect>, edit: impl FnOnce(&Self)) -> Result<(), JsValue> {
+        let rects = bounds
+            .map(|bounds| tiles_in(&bounds, self.width, self.height))
+            .unwrap_or_default();
+        let before = read_tiles(&self.gl, &self.canvas, self.height, &rects)?;
+        edit(self);
+        let after = read_tiles(&self.gl, &self.canvas, self.height, &rects)?;
+
+        let tiles =
+            rects
+                .into_iter()
+                .zip(before.into_iter().zip(after))
+                .map(|(rect, (before, after))| Tile {
+                    rect,
+                    before,
+                    after,
+                });
+        if let Some(entry) = HistoryEntry::diff(tiles) {
+            self.history.push(entry);
+        }
+        Ok(())
+    }
+
+    /// Draws the painting and the stroke being painted over the whole default framebuffer.
+    pub fn draw(&mut self, width: i32, height: i32, background: Color) {
+        self.flush();
+
+        self.gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        self.gl.viewport(0, 0, width, height);
+        self.gl
+            .clear_color(background.r, background.g, background.b, background.a);
+        self.gl.clear(GL::COLOR_BUFFER_BIT);
+
+        self.gl.bind_vertex_array(Some(&self.quad));
+        self.gl.enable(GL::BLEND);
+        self.gl.blend_equation(GL::FUNC_ADD);
+        self.gl.blend_func(GL::ONE, GL::ONE_MINUS_SRC_ALPHA);
+        self.composite
+            .draw(&self.gl, &self.canvas.texture, Color::WHITE);
+        if let Some((_, stroke)) = &self.stroke {
+            self.composite
+                .draw(&self.gl, &self.stroke_layer.texture, stroke.brush().tint());
+        }
+        self.unbind();
+    }
+
+    fn bind_target(&self, target: Option<&RenderTarget>) {
+        self.gl
+            .bind_framebuffer(GL::FRAMEBUFFER, target.map(|target| &target.framebuffer));
+        self.gl
+            .viewport(0, 0, self.width as i32, self.height as i32);
+        self.gl.bind_vertex_array(Some(&self.quad));
+    }
+
+    fn unbind(&self) {
+        self.gl.disable(GL::BLEND);
+        self.draw_shader.clear_program();
+        self.gl.bind_vertex_array(None);
+        self.gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+    }
+}
+
+/// The texture rows go up from the bottom, the canvas pixels down from the top.
+fn texture_y(height: u32, rect: &TileRect) -> i32 {
+    (height - rect.y - rect.height) as i32
+}
+
+/// The RGBA8 pixels of the tiles of the target.
+fn read_tiles(
+    gl: &GL,
+    target: &RenderTarget,
+    height: u32,
+    rects: &[TileRect],
+) -> Result<Vec<Vec<u8>>, JsValue> {
+    gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&target.framebuffer));
+    let tiles = rects
+        .iter()
+        .map(|rect| {
+            let mut pixels = vec![0; rect.bytes()];
+            gl.read_pixels_with_opt_u8_array(
+                rect.x as i32,
+                texture_y(height, rect),
+                rect.width as i32,
+                rect.height as i32,
+                GL::RGBA,
+                GL::UNSIGNED_BYTE,
+                Some(&mut pixels),
+            )?;
+            Ok(pixels)
+        })
+        .collect();
+    gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+    tiles
+}
+
+fn write_tiles(
+    gl: &GL,
+    target: &RenderTarget,
+    height: u32,
+    tiles: &[Tile],
+    pixels: impl Fn(&Tile) -> &Vec<u8>,
+) -> Result<(), JsValue> {
+    gl.bind_texture(GL::TEXTURE_2D, Some(&target.texture));
+    for tile in tiles {
+        gl.tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+            GL::TEXTURE_2D,
+            0,
+            tile.rect.x as i32,
+            texture_y(height, &tile.rect),
+            tile.rect.width as i32,
+            tile.rect.height as i32,
+            GL::RGBA,
+            GL::UNSIGNED_BYTE,
+            Some(pixels(tile)),
+        )?;
+    }
+    gl.bind_texture(GL::TEXTURE_2D, None);
+    Ok(())
+}
+
+///
+/// The painting demo: paints with the primary button, the pen pressure scales the brush,
+/// the undo and redo actions take the strokes back and forth.
+/// The painting has the size the canvas has when the app is created.
+///
+#[wasm_bindgen]
+pub struct PaintApp {
+    state: Rc<RefCell<PaintState>>,
+    frame_loop: Option<FrameLoop>,
+}
+
+struct PaintState {
+    canvas: web_sys::HtmlCanvasElement,
+    input: Input,
+    painting: Painting,
+    needs_render: bool,
+}
+
+#[wasm_bindgen]
+impl PaintApp {
+    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Result<PaintApp, JsValue> {
+        let gl = canvas
+            .get_context("webgl2")?
+            .ok_or("WebGL 2 isn't supported")?
+            .dyn_into::<GL>()?;
+        let painting = Painting::new(Rc::new(gl), canvas.width(), canvas.height())?;
+
+        let state = PaintState {
+            input: Input::new(&canvas),
+            canvas,
+            painting,
+            needs_render: true,
+        };
+        Ok(Self {
+            state: Rc::new(RefCell::new(state)),
+            frame_loop: None,
+        })
+    }
+
+    pub fn start(&mut self) -> Result<(), JsValue> {
+        if self.frame_loop.is_none() {
+            self.frame_loop = Some(FrameLoop::new(self.state.clone(), 1.0 / 60.0)?);
+        }
+        if let Some(frame_loop) = &self.frame_loop {
+            frame_loop.start();
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(frame_loop) = &self.frame_loop {
+            frame_loop.stop();
+        }
+    }
+
+    /// The diameter in canvas pixels at full pressure.
+    pub fn set_brush_size(&mut self, size: f32) {
+        self.state.borrow_mut().painting.brush.size = size.max(0.0);
+    }
+
+    /// From `0.0`, a soft edge, to `1.0`, a hard edge.
+    pub fn set_hardness(&mut self, hardness: f32) {
+        self.state.borrow_mut().painting.brush.hardness = hardness.clamp(0.0, 1.0);
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.state.borrow_mut().painting.brush.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    /// Components from `0.0` to `1.0`.
+    pub fn set_color(&mut self, r: f32, g: f32, b: f32) {
+        self.state.borrow_mut().painting.brush.color = Color::new(r, g, b, 1.0);
+    }
+
+    pub fn clear(&mut self) -> Result<(), JsValue> {
+        let mut state = self.state.borrow_mut();
+        state.painting.clear()?;
+        state.needs_render = true;
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> Result<bool, JsValue> {
+        let mut state = self.state.borrow_mut();
+        let undone = state.painting.undo()?;
+        state.needs_render |= undone;
+        Ok(undone)
+    }
+
+    pub fn redo(&mut self) -> Result<bool, JsValue> {
+        let mut state = self.state.borrow_mut();
+        let redone = state.painting.redo()?;
+        state.needs_render |= redone;
+        Ok(redone)
+    }
+
+    /// The strokes until [PaintApp::end_merge] are undone together.
+    pub fn begin_merge(&mut self) {
+        self.state.borrow_mut().painting.history_mut().begin_merge();
+    }
+
+    pub fn end_merge(&mut self) {
+        self.state.borrow_mut().painting.history_mut().end_merge();
+    }
+
+    /// The memory the history may use, the oldest edits are forgotten first.
+    pub fn set_history_limit(&mut self, bytes: usize) {
+        self.state
+            .borrow_mut()
+            .painting
+            .history_mut()
+            .set_max_bytes(bytes);
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.state.borrow().painting.history().max_bytes()
+    }
+
+    /// The memory the history uses.
+    pub fn history_bytes(&self) -> usize {
+        self.state.borrow().painting.history().bytes()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.state.borrow().painting.history().can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.state.borrow().painting.history().can_redo()
+    }
+
+    /// Forgets the undo and redo entries, the painting stays as it is.
+    pub fn clear_history(&mut self) {
+        self.state.borrow_mut().painting.history_mut().clear();
+    }
+}
+
+impl FrameHandler for PaintState {
+    fn begin_frame(&mut self) {
+        let scale = [
+            self.painting.width() as f32 / self.canvas.width().max(1) as f32,
+            self.painting.height() as f32 / self.canvas.height().max(1) as f32,
+        ];
+        let events = self.input.state_mut().take_events();
+        let undo = self.input.state().action_pressed(UNDO);
+        let redo = self.input.state().action_pressed(REDO);
+        let result = (|| {
+            for event in &events {
+                self.needs_render |= self.painting.handle(event, scale)?;
+            }
+            if undo {
+                self.needs_render |= self.painting.undo()?;
+            }
+            if redo {
+                self.needs_render |= self.painting.redo()?;
+            }
+            Ok::<_, JsValue>(())
+        })();
+        if let Err(e) = result {
+            console::error_1(&e);
+        }
+    }
+
+    fn update(&mut self, _dt: f32) {}
+
+    fn render(&mut self, _alpha: f32) {
+        if self.needs_render {
+            self.needs_render = false;
+            let [width, height] = [self.canvas.width(), self.canvas.height()];
+            self.painting
+                .draw(width as i32, height as i32, Color::WHITE);
+        }
+        self.input.state_mut().end_frame();
+    }
+}
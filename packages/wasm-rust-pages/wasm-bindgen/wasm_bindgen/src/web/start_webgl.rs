@@ -1,31 +1,62 @@
-use lyon::tessellation::VertexBuffers;
+use std::{cell::RefCell, rc::Rc};
+
+use lyon::{
+    algorithms::aabb::bounding_box,
+    extra::rust_logo::build_logo_path,
+    math::Transform,
+    path::{LineCap, LineJoin, Path},
+};
 use wasm_bindgen::prelude::*;
 use web_sys::{console, WebGl2RenderingContext, WebGlProgram, WebGlShader};
+use webgl_common::{slice_as_u8_slice, Color};
 use wgsl_to_glsl_macro::make_naga_module;
 
 use crate::{
-    test_lyon::{test_geometry, MyVertex},
+    vector::{
+        batch::{PathBatch, PathId, Vertex, DEFAULT_TOLERANCE},
+        path::{FillStyle, StrokeStyle, Style, VectorPath},
+        svg::SvgDocument,
+    },
     web::{
-        camera_2d::{any_as_u8_slice, Camera2D},
-        event_handle::JsCallback,
+        actions::{
+            ActionMap, Chord, PAN_DOWN, PAN_LEFT, PAN_RIGHT, PAN_UP, RESET_VIEW, ZOOM_IN, ZOOM_OUT,
+        },
+        camera_2d::{Camera2D, Rect, ZoomCurve},
+        camera_controller::{CameraController, Easing},
+        frame_loop::{FrameHandler, FrameLoop, FrameTimeSummary},
+        input::{Input, InputEvent},
     },
 };
 
-#[derive(Debug)]
-enum BindingRegister {
-    UniformBuffers,
-    StorageBuffers,
-    Textures,
-    Images,
-}
+/// Seconds of one camera update.
+const FIXED_STEP: f64 = 1.0 / 60.0;
+/// Screen pixels per second of the pan actions.
+const KEY_PAN_SPEED: f32 = 600.0;
+/// The wheel delta of one zoom action.
+const KEY_ZOOM_STEP: f32 = 200.0;
+
+///
+/// The demo app: [Input] listens to the canvas, the [FrameLoop] started by [AppWebGL::start]
+/// feeds the input to the camera controller, updates it and renders.
+///
 #[wasm_bindgen]
 pub struct AppWebGL {
+    state: Rc<RefCell<AppState>>,
+    frame_loop: Option<FrameLoop>,
+}
+
+struct AppState {
     canvas: web_sys::HtmlCanvasElement,
     gl: WebGl2RenderingContext,
-    camera: Camera2D,
-    is_panning: bool,
-    last_pointer_position: Option<(f32, f32)>,
-    geometry: Option<VertexBuffers<MyVertex, u16>>,
+    input: Input,
+    controller: CameraController,
+    /// The direction of the held pan keys, the camera coasts that way when they are released.
+    key_pan: [f32; 2],
+    /// Set by the updates that moved the camera, cleared by the next render.
+    needs_render: bool,
+    paths: PathBatch,
+    vertex_buffer: Option<web_sys::WebGlBuffer>,
+    index_buffer: Option<web_sys::WebGlBuffer>,
     camera_uniform_buffer: Option<web_sys::WebGlBuffer>,
 }
 
@@ -37,29 +68,377 @@ impl AppWebGL {
             .unwrap()
             .dyn_into::<WebGl2RenderingContext>()?;
 
-        Ok(Self {
+        let state = AppState {
             canvas: canvas.clone(),
             gl,
-            camera: Camera2D::new(canvas.width() as f32, canvas.height() as f32),
-            is_panning: false,
-            last_pointer_position: None,
-            geometry: None,
+            input: Input::new(&canvas),
+            controller: CameraController::new(Camera2D::new(
+                canvas.width() as f32,
+                canvas.height() as f32,
+            )),
+            key_pan: [0.0, 0.0],
+            needs_render: true,
+            paths: PathBatch::new(DEFAULT_TOLERANCE),
+            vertex_buffer: None,
+            index_buffer: None,
             camera_uniform_buffer: None,
+        };
+
+        Ok(Self {
+            state: Rc::new(RefCell::new(state)),
+            frame_loop: None,
         })
     }
 
     pub fn init(&mut self) {
-        let callback = JsCallback::register(
-            &self.canvas.clone(),
-            "click",
-            false,
-            |_event: web_sys::MouseEvent| {
-                console::log_1(&"Click".into());
-            },
+        self.state.borrow_mut().init();
+    }
+
+    /// Starts updating and rendering every animation frame, paused while the tab is hidden.
+    pub fn start(&mut self) -> Result<(), JsValue> {
+        if self.frame_loop.is_none() {
+            self.frame_loop = Some(FrameLoop::new(self.state.clone(), FIXED_STEP)?);
+        }
+        if let Some(frame_loop) = &self.frame_loop {
+            frame_loop.start();
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(frame_loop) = &self.frame_loop {
+            frame_loop.stop();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.frame_loop.as_ref().is_some_and(FrameLoop::is_running)
+    }
+
+    /// Frame times of the recent frames, all zero before the loop has run.
+    pub fn frame_stats(&self) -> FrameTimeSummary {
+        self.frame_loop
+            .as_ref()
+            .map(FrameLoop::stats)
+            .unwrap_or_default()
+    }
+
+    pub fn update_camera(&mut self) {
+        self.state.borrow_mut().update_camera();
+    }
+
+    /// Animates the camera to show the world rectangle.
+    pub fn fly_to(
+        &mut self,
+        min_x: f32,
+        min_y: f32,
+        max_x: f32,
+        max_y: f32,
+        duration: f32,
+        easing: Easing,
+    ) {
+        self.state.borrow_mut().controller.fly_to(
+            Rect::new([min_x, min_y], [max_x, max_y]),
+            duration,
+            easing,
         );
+    }
+
+    /// Whether the camera still moves on its own, after a flick or during [AppWebGL::fly_to].
+    pub fn is_animating(&self) -> bool {
+        self.state.borrow().controller.is_animating()
+    }
+
+    /// Screen pixels per world unit.
+    pub fn set_zoom_limits(&mut self, min_zoom: f32, max_zoom: f32) {
+        let mut state = self.state.borrow_mut();
+        state.controller.camera.set_zoom_limits(min_zoom, max_zoom);
+        state.controller.invalidate();
+    }
 
-        std::mem::forget(callback);
+    /// `linear` zooms by `1 + delta * speed` for a wheel delta, instead of `exp(delta * speed)`.
+    pub fn set_zoom_curve(&mut self, speed: f32, linear: bool) {
+        self.state.borrow_mut().controller.camera.zoom_curve = if linear {
+            ZoomCurve::Linear { speed }
+        } else {
+            ZoomCurve::Exponential { speed }
+        };
+    }
+
+    /// Keeps the camera from showing anything outside of the world rectangle.
+    pub fn set_bounds(&mut self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) {
+        let mut state = self.state.borrow_mut();
+        state
+            .controller
+            .camera
+            .set_bounds(Some(Rect::new([min_x, min_y], [max_x, max_y])));
+        state.controller.invalidate();
+    }
+
+    pub fn clear_bounds(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.controller.camera.set_bounds(None);
+        state.controller.invalidate();
+    }
+
+    /// The world point drawn at the canvas pixel, e.g. under the pointer.
+    pub fn screen_to_world(&self, x: f32, y: f32) -> Vec<f32> {
+        self.state
+            .borrow()
+            .controller
+            .camera
+            .screen_to_world([x, y])
+            .to_vec()
+    }
+
+    /// The canvas pixel the world point is drawn at.
+    pub fn world_to_screen(&self, x: f32, y: f32) -> Vec<f32> {
+        self.state
+            .borrow()
+            .controller
+            .camera
+            .world_to_screen([x, y])
+            .to_vec()
+    }
+
+    pub fn render(&mut self) {
+        self.state.borrow_mut().render();
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.state.borrow_mut().resize(width, height);
+    }
+
+    pub fn canvas(&self) -> web_sys::HtmlCanvasElement {
+        self.state.borrow().canvas.clone()
+    }
 
+    pub fn context(&self) -> WebGl2RenderingContext {
+        self.state.borrow().gl.clone()
+    }
+
+    /// Replaces the scene with the shapes of an SVG document, centered at the origin.
+    /// Returns the warnings about what couldn't be imported.
+    pub fn load_svg(&mut self, source: &str) -> Result<Vec<String>, JsValue> {
+        let document = SvgDocument::parse(source)?;
+        for warning in &document.warnings {
+            console::warn_1(&warning.into());
+        }
+        let center = document
+            .view_box
+            .map(|view_box| view_box.center())
+            .unwrap_or_default();
+        // the document is y down
+        let transform = Transform::translation(-center.x, -center.y).then_scale(1.0, -1.0);
+
+        let mut state = self.state.borrow_mut();
+        state.paths.clear();
+        document.add_to(&mut state.paths, &transform);
+        state.needs_render = true;
+        Ok(document.warnings)
+    }
+
+    /// Adds a filled SVG path (`d` attribute), in world units with y up. Returns its id.
+    pub fn add_path(&mut self, svg: &str, r: f32, g: f32, b: f32, a: f32) -> Result<u32, JsValue> {
+        let path = VectorPath::from_svg(svg)?.with_fill(FillStyle::new(Color::new(r, g, b, a)));
+        let mut state = self.state.borrow_mut();
+        let id = state.paths.add(path);
+        state.needs_render = true;
+        Ok(id.into())
+    }
+
+    /// Replaces the shape of the path, keeping its style and transform.
+    /// Returns false when there is no such path, e.g. after [AppWebGL::load_svg].
+    pub fn set_path(&mut self, id: u32, svg: &str) -> Result<bool, JsValue> {
+        let mut state = self.state.borrow_mut();
+        let Some((style, transform)) = state
+            .paths
+            .get(id.into())
+            .map(|old| (old.style, old.transform))
+        else {
+            return Ok(false);
+        };
+        let path = VectorPath::from_svg(svg)?
+            .with_style(style)
+            .with_transform(transform);
+        state.paths.set(id.into(), path);
+        state.needs_render = true;
+        Ok(true)
+    }
+
+    /// Fills the path with the color, without a stroke.
+    pub fn set_path_fill(&mut self, id: u32, r: f32, g: f32, b: f32, a: f32) -> bool {
+        let style = Style {
+            fill: Some(FillStyle::new(Color::new(r, g, b, a))),
+            stroke: None,
+        };
+        let mut state = self.state.borrow_mut();
+        let found = state.paths.set_style(PathId::from(id), style);
+        state.needs_render |= found;
+        found
+    }
+
+    /// `transform` is the 2D affine transform `[a, b, c, d, e, f]`, like the canvas `setTransform()`.
+    pub fn set_path_transform(&mut self, id: u32, transform: &[f32]) -> Result<bool, JsValue> {
+        let [a, b, c, d, e, f] = <[f32; 6]>::try_from(transform)
+            .map_err(|_| format!("Expected 6 transform values, got {}", transform.len()))?;
+        let mut state = self.state.borrow_mut();
+        let found = state
+            .paths
+            .set_transform(id.into(), Transform::new(a, b, c, d, e, f));
+        state.needs_render |= found;
+        Ok(found)
+    }
+
+    /// How many times paths were tessellated, moving a path doesn't tessellate it again.
+    pub fn tessellations(&self) -> usize {
+        self.state.borrow().paths.tessellations()
+    }
+
+    /// The tessellation tolerance in world units, smaller is smoother and has more vertices.
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        let mut state = self.state.borrow_mut();
+        state.paths.set_tolerance(tolerance);
+        state.needs_render = true;
+    }
+
+    /// The action bindings as JSON, see `bindings.toml` for the format.
+    pub fn bindings(&self) -> Result<String, JsValue> {
+        Ok(self.state.borrow().input.state().actions().to_json()?)
+    }
+
+    /// The action bindings in the `bindings.toml` format.
+    pub fn bindings_toml(&self) -> Result<String, JsValue> {
+        Ok(self.state.borrow().input.state().actions().to_toml()?)
+    }
+
+    /// Binds a chord like `Ctrl+KeyZ` to the action. Returns the other actions the chord is bound to.
+    pub fn bind(&mut self, action: &str, chord: &str) -> Result<Vec<String>, JsValue> {
+        let chord = chord.parse::<Chord>()?;
+        let state = self.state.borrow();
+        let others = state.input.state_mut().actions_mut().bind(action, chord);
+        Ok(others)
+    }
+
+    /// Returns whether the chord was bound to the action.
+    pub fn unbind(&mut self, action: &str, chord: &str) -> Result<bool, JsValue> {
+        let chord = chord.parse::<Chord>()?;
+        let state = self.state.borrow();
+        let found = state.input.state_mut().actions_mut().unbind(action, &chord);
+        Ok(found)
+    }
+
+    pub fn clear_bindings(&mut self, action: &str) {
+        self.state
+            .borrow()
+            .input
+            .state_mut()
+            .actions_mut()
+            .clear(action);
+    }
+
+    /// Replaces the action bindings with JSON ones. Returns the conflicts, chords bound to several actions.
+    pub fn set_bindings(&mut self, json: &str) -> Result<Vec<String>, JsValue> {
+        let actions = ActionMap::from_json(json)?;
+        let conflicts = actions
+            .conflicts()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        for conflict in &conflicts {
+            console::warn_1(&conflict.into());
+        }
+        self.state.borrow().input.state_mut().set_actions(actions);
+        Ok(conflicts)
+    }
+}
+
+impl FrameHandler for AppState {
+    fn begin_frame(&mut self) {
+        let input = self.input.state();
+        for event in input.events() {
+            match event {
+                InputEvent::PointerDown(pointer) => {
+                    self.controller
+                        .pointer_down(pointer.id, pointer.position, pointer.time)
+                }
+                InputEvent::PointerMove(pointer) => {
+                    self.controller
+                        .pointer_move(pointer.id, pointer.position, pointer.time)
+                }
+                InputEvent::PointerUp(pointer) => {
+                    self.controller.pointer_up(pointer.id, pointer.time)
+                }
+                InputEvent::PointerCancel { id, time } => self.controller.pointer_up(*id, *time),
+                InputEvent::Wheel { delta, position } => {
+                    self.controller.wheel(-delta[1], *position)
+                }
+                _ => {}
+            }
+        }
+
+        let center = [
+            self.controller.camera.width / 2.0,
+            self.controller.camera.height / 2.0,
+        ];
+        if input.action_pressed(ZOOM_IN) {
+            self.controller.wheel(KEY_ZOOM_STEP, center);
+        }
+        if input.action_pressed(ZOOM_OUT) {
+            self.controller.wheel(-KEY_ZOOM_STEP, center);
+        }
+        if input.action_pressed(RESET_VIEW) {
+            let [width, height] = [self.controller.camera.width, self.controller.camera.height];
+            self.controller.fly_to(
+                Rect::new([-width / 2.0, -height / 2.0], [width / 2.0, height / 2.0]),
+                0.5,
+                Easing::EaseInOutCubic,
+            );
+        }
+
+        let axis = |negative: &str, positive: &str| {
+            input.action_down(positive) as i32 as f32 - input.action_down(negative) as i32 as f32
+        };
+        let direction = [axis(PAN_LEFT, PAN_RIGHT), axis(PAN_DOWN, PAN_UP)];
+        let released = [PAN_LEFT, PAN_RIGHT, PAN_DOWN, PAN_UP]
+            .iter()
+            .any(|action| input.action_released(action));
+        if direction == [0.0, 0.0] && released {
+            // panning moves the camera, dragging moves the world
+            self.controller.coast([
+                -self.key_pan[0] * KEY_PAN_SPEED,
+                self.key_pan[1] * KEY_PAN_SPEED,
+            ]);
+        }
+        self.key_pan = direction;
+    }
+
+    fn update(&mut self, dt: f32) {
+        let direction = self.key_pan;
+        if direction != [0.0, 0.0] {
+            let distance = KEY_PAN_SPEED * dt;
+            self.controller
+                .camera
+                .pan(direction[0] * distance, direction[1] * distance);
+            self.controller.invalidate();
+        }
+
+        if self.controller.update(dt) {
+            self.needs_render = true;
+        }
+    }
+
+    // nothing is interpolated, the camera moves in small steps
+    fn render(&mut self, _alpha: f32) {
+        if self.needs_render {
+            self.render();
+        }
+        self.input.state_mut().end_frame();
+    }
+}
+
+impl AppState {
+    fn init(&mut self) {
         let data = make_naga_module!("src/web/start_webgl.wgsl");
 
         let (vert_shader, frag_shader) =
@@ -90,7 +469,7 @@ impl AppWebGL {
 
             self.gl.buffer_data_with_u8_array(
                 WebGl2RenderingContext::UNIFORM_BUFFER,
-                self.camera.get_transforms().as_bytes(),
+                self.controller.camera.get_transforms().as_bytes(),
                 WebGl2RenderingContext::DYNAMIC_DRAW,
             );
             self.gl.bind_buffer_base(
@@ -101,64 +480,8 @@ impl AppWebGL {
 
             self.camera_uniform_buffer = Some(uniform_buffer);
         }
-        {
-            let uniform_block_index = self
-                .gl
-                .get_uniform_block_index(&program, data.color_uniform_block_name);
-
-            self.gl.uniform_block_binding(
-                &program,
-                uniform_block_index,
-                data.color_uniform_binding,
-            );
-            let uniform_buffer = self.gl.create_buffer().unwrap();
-            self.gl.bind_buffer(
-                WebGl2RenderingContext::UNIFORM_BUFFER,
-                Some(&uniform_buffer),
-            );
-
-            #[repr(C)]
-            struct Color {
-                r: f32,
-                g: f32,
-                b: f32,
-                a: f32,
-            }
-
-            impl Color {
-                pub fn as_bytes(&self) -> &[u8] {
-                    unsafe { any_as_u8_slice(self) }
-                }
-            }
-
-            self.gl.buffer_data_with_u8_array(
-                WebGl2RenderingContext::UNIFORM_BUFFER,
-                Color {
-                    r: 0.7,
-                    g: 0.7,
-                    b: 1.0,
-                    a: 1.0,
-                }
-                .as_bytes(),
-                WebGl2RenderingContext::DYNAMIC_DRAW,
-            );
-            self.gl.bind_buffer_base(
-                WebGl2RenderingContext::UNIFORM_BUFFER,
-                data.color_uniform_binding,
-                Some(&uniform_buffer),
-            );
-        }
-
         // ! Vertex Array Object
-        let geometry = {
-            let geometry = test_geometry();
-
-            let vertices = geometry
-                .vertices
-                .iter()
-                .flat_map(|vertex| vertex.position)
-                .collect::<Vec<f32>>();
-
+        {
             let vao = self
                 .gl
                 .create_vertex_array()
@@ -166,63 +489,51 @@ impl AppWebGL {
                 .unwrap();
             self.gl.bind_vertex_array(Some(&vao));
 
-            {
-                let buffer = self.gl.create_buffer().unwrap();
-                self.gl
-                    .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
-
-                // Note that `Float32Array::view` is somewhat dangerous (hence the
-                // `unsafe`!). This is creating a raw view into our module's
-                // `WebAssembly.Memory` buffer, but if we allocate more pages for ourself
-                // (aka do a memory allocation in Rust) it'll cause the buffer to change,
-                // causing the `Float32Array` to be invalid.
-                //
-                // As a result, after `Float32Array::view` we have to be very careful not to
-                // do any memory allocations before it's dropped.
-
-                self.gl.buffer_data_with_u8_array(
-                    WebGl2RenderingContext::ARRAY_BUFFER,
-                    to_u8(&vertices),
-                    WebGl2RenderingContext::STATIC_DRAW,
-                );
-            }
+            let vertex_buffer = self.gl.create_buffer().unwrap();
+            self.gl
+                .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
 
+            let stride = std::mem::size_of::<Vertex>() as i32;
             self.gl.vertex_attrib_pointer_with_i32(
                 data.position_attribute_location,
                 2,
                 WebGl2RenderingContext::FLOAT,
                 false,
-                0,
+                stride,
                 0,
             );
             self.gl
                 .enable_vertex_attrib_array(data.position_attribute_location);
+            self.gl.vertex_attrib_pointer_with_i32(
+                data.color_attribute_location,
+                4,
+                WebGl2RenderingContext::FLOAT,
+                false,
+                stride,
+                std::mem::size_of::<[f32; 2]>() as i32,
+            );
+            self.gl
+                .enable_vertex_attrib_array(data.color_attribute_location);
 
-            {
-                let index_buffer = self
-                    .gl
-                    .create_buffer()
-                    .ok_or("Failed to create buffer")
-                    .unwrap();
-                self.gl.bind_buffer(
-                    WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
-                    Some(&index_buffer),
-                );
-
-                self.gl.buffer_data_with_u8_array(
-                    WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
-                    to_u8(&geometry.indices),
-                    WebGl2RenderingContext::STATIC_DRAW,
-                );
-            };
-
-            geometry
-        };
+            // the element array binding is part of the vertex array object
+            let index_buffer = self
+                .gl
+                .create_buffer()
+                .ok_or("Failed to create buffer")
+                .unwrap();
+            self.gl.bind_buffer(
+                WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+                Some(&index_buffer),
+            );
 
-        self.geometry = Some(geometry);
+            self.vertex_buffer = Some(vertex_buffer);
+            self.index_buffer = Some(index_buffer);
+        }
+
+        add_demo_paths(&mut self.paths);
     }
 
-    pub fn update_camera(&mut self) {
+    fn update_camera(&mut self) {
         if let Some(camera_uniform_buffer) = &self.camera_uniform_buffer {
             self.gl.bind_buffer(
                 WebGl2RenderingContext::UNIFORM_BUFFER,
@@ -231,13 +542,14 @@ impl AppWebGL {
 
             self.gl.buffer_data_with_u8_array(
                 WebGl2RenderingContext::UNIFORM_BUFFER,
-                self.camera.get_transforms().as_bytes(),
+                self.controller.camera.get_transforms().as_bytes(),
                 WebGl2RenderingContext::DYNAMIC_DRAW,
             );
         }
     }
 
-    pub fn render(&mut self) -> Result<(), JsValue> {
+    fn render(&mut self) {
+        self.needs_render = false;
         self.gl.viewport(
             0,
             0,
@@ -250,76 +562,104 @@ impl AppWebGL {
 
         self.update_camera();
 
-        if let Some(geometry) = &self.geometry {
-            self.gl.draw_elements_with_i32(
-                WebGl2RenderingContext::TRIANGLES,
-                geometry.indices.len() as i32,
-                WebGl2RenderingContext::UNSIGNED_SHORT,
-                0,
-            );
-        }
-
-        Ok(())
-    }
-
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.canvas.set_width(width);
-        self.canvas.set_height(height);
-        self.camera.resize(width as f32, height as f32);
-        self.render().unwrap();
-    }
-
-    pub fn canvas(&self) -> web_sys::HtmlCanvasElement {
-        self.canvas.clone()
-    }
-
-    pub fn context(&self) -> WebGl2RenderingContext {
-        self.gl.clone()
-    }
-
-    pub fn on_pointer_down(&mut self, event: web_sys::PointerEvent) {
-        // console::log_1(&format!("Pointer down {:?}", event.type_()).into());
-        self.is_panning = true;
-        self.last_pointer_position = Some((event.client_x() as f32, event.client_y() as f32));
-        self.render().unwrap();
+        self.upload_paths();
+        self.gl.draw_elements_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            self.paths.indices().len() as i32,
+            WebGl2RenderingContext::UNSIGNED_INT,
+            0,
+        );
     }
 
-    pub fn on_pointer_move(&mut self, event: web_sys::PointerEvent) {
-        if self.is_panning {
-            if let Some((last_x, last_y)) = self.last_pointer_position {
-                let dx = event.client_x() as f32 - last_x;
-                let dy = event.client_y() as f32 - last_y;
-                self.camera.pan(-dx, -dy);
-                self.render().unwrap();
-                self.last_pointer_position =
-                    Some((event.client_x() as f32, event.client_y() as f32));
-            }
+    /// Re-tessellates the changed paths and uploads the batch when anything changed.
+    /// The paths that can't be tessellated are logged and left out.
+    fn upload_paths(&mut self) {
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        else {
+            // not initialized yet
+            return;
+        };
+        if !self.paths.rebuild() {
+            return;
         }
+        for (id, error) in self.paths.errors() {
+            console::error_1(&format!("Skipped path {:?}: {}", id, error).into());
+        }
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(vertex_buffer));
+        self.gl.buffer_data_with_u8_array(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            slice_as_u8_slice(self.paths.vertices()),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        self.gl.bind_buffer(
+            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            Some(index_buffer),
+        );
+        self.gl.buffer_data_with_u8_array(
+            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            slice_as_u8_slice(self.paths.indices()),
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
     }
 
-    pub fn on_pointer_up(&mut self, _event: web_sys::PointerEvent) {
-        self.is_panning = false;
-        self.last_pointer_position = None;
-        self.render().unwrap();
-    }
-
-    pub fn on_pointer_enter(&mut self, _event: web_sys::PointerEvent) {
-        self.canvas.focus().unwrap();
-    }
-
-    pub fn on_pointer_leave(&mut self, _event: web_sys::PointerEvent) {
-        self.canvas.blur().unwrap();
-    }
-
-    pub fn on_keydown(&mut self, event: web_sys::KeyboardEvent) {
-        console::log_1(&format!("Key down {:?}", event.key()).into());
+    fn resize(&mut self, width: u32, height: u32) {
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+        self.controller.camera.resize(width as f32, height as f32);
+        // resizing clears the canvas, draw it again right away
+        self.render();
     }
+}
 
-    pub fn on_wheel(&mut self, event: web_sys::WheelEvent) {
-        let delta = -event.delta_y() as f32;
-        self.camera.zoom(delta);
-        self.render().unwrap();
-    }
+/// The Rust logo and a few shapes around it, in world units (y up).
+fn add_demo_paths(paths: &mut PathBatch) {
+    let mut builder = Path::builder().with_svg();
+    build_logo_path(&mut builder);
+    let logo = builder.build();
+    // the logo is drawn y down, about 145 units wide
+    let center = bounding_box(logo.iter()).center();
+    let logo_transform = Transform::translation(-center.x, -center.y).then_scale(2.0, -2.0);
+    paths.add(
+        VectorPath::new(logo)
+            .with_fill(FillStyle::new(Color::new(0.7, 0.7, 1.0, 1.0)))
+            .with_stroke(
+                StrokeStyle::new(Color::new(0.2, 0.2, 0.5, 1.0), 1.0).with_join(LineJoin::Round),
+            )
+            .with_transform(logo_transform),
+    );
+
+    paths.add(
+        VectorPath::rounded_rect([-220.0, -220.0], [220.0, 220.0], 24.0)
+            .with_stroke(StrokeStyle::new(Color::new(0.5, 0.5, 0.5, 1.0), 4.0)),
+    );
+    paths.add(
+        VectorPath::circle([-300.0, 0.0], 40.0)
+            .with_fill(FillStyle::new(Color::new(1.0, 0.4, 0.3, 1.0))),
+    );
+    paths.add(
+        VectorPath::rect([240.0, -280.0], [360.0, -220.0])
+            .with_fill(FillStyle::new(Color::new(0.3, 0.5, 0.9, 1.0))),
+    );
+    paths.add(
+        VectorPath::ellipse([300.0, 0.0], [60.0, 30.0], std::f32::consts::FRAC_PI_6)
+            .with_fill(FillStyle::new(Color::new(0.3, 0.8, 0.4, 1.0))),
+    );
+    paths.add(
+        VectorPath::polygon(&[[-60.0, -300.0], [0.0, -260.0], [60.0, -300.0]], false).with_stroke(
+            StrokeStyle::new(Color::new(1.0, 0.8, 0.2, 1.0), 8.0).with_cap(LineCap::Round),
+        ),
+    );
+    // an SVG heart, y down
+    let heart = VectorPath::from_svg(
+        "M 0 30 C 0 0 -40 0 -40 30 C -40 60 0 70 0 90 C 0 70 40 60 40 30 C 40 0 0 0 0 30 Z",
+    )
+    .unwrap();
+    paths.add(
+        heart
+            .with_fill(FillStyle::new(Color::new(0.9, 0.2, 0.5, 1.0)))
+            .with_transform(Transform::scale(1.0, -1.0).then_translate([0.0, 360.0].into())),
+    );
 }
 
 pub fn create_program(
@@ -392,12 +732,3 @@ pub fn link_program(
             .unwrap_or_else(|| String::from("Unknown error creating program object")))
     }
 }
-
-fn to_u8<T>(vec: &[T]) -> &[u8] {
-    unsafe {
-        std::slice::from_raw_parts(
-            vec.as_ptr() as *const u8,
-            vec.len() * std::mem::size_of::<f32>(),
-        )
-    }
-}
@@ -3,15 +3,22 @@ struct TransformMatrices {
     view_matrix: mat4x4f,
 };
 
+struct VertexOutput {
+    @builtin(position) position: vec4f,
+    @location(0) color: vec4f,
+};
+
 @binding(1) @group(0) var<uniform> transforms : TransformMatrices;
-@binding(0) @group(0) var<uniform> color : vec4f;
 
 @vertex
-fn vs_main(@location(0) position: vec4f) -> @builtin(position) vec4f {
-    return transforms.projection_matrix * transforms.view_matrix * position;
+fn vs_main(@location(0) position: vec4f, @location(1) color: vec4f) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = transforms.projection_matrix * transforms.view_matrix * position;
+    out.color = color;
+    return out;
 }
 
 @fragment
-fn fs_main() -> @location(0) vec4f {
-    return color;
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    return in.color;
 }
\ No newline at end of file